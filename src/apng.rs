@@ -0,0 +1,384 @@
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    png::{Png, PngError},
+};
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+};
+use thiserror::Error;
+
+// Animated PNG support, following the APNG specification.
+//
+// An animation is described by an acTL chunk before the image data, then each frame
+// is an fcTL chunk followed by its image data. The first frame may use the IDAT chunks,
+// in which case the default image is part of the animation, the rest use fdAT chunks.
+// fdAT chunks are IDAT chunks with a 4 byte sequence number in front.
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum ApngError {
+    #[error("The image is not animated, it has no `acTL` chunk")]
+    NotAnimated,
+    #[error("`{chunk_type}` chunk should be {expected} bytes, but found `{found}` bytes")]
+    WrongLength {
+        chunk_type: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    #[error("`fdAT` chunk is truncated, found `{0}` bytes")]
+    TruncatedFrameData(usize),
+    #[error("`fdAT` chunk was found before any `fcTL` chunk")]
+    DataWithoutFrameControl,
+    #[error("Frame `{0}` doesn't exist, the animation has `{1}` frames")]
+    FrameOutOfRange(usize, usize),
+    #[error("Frame `{0}` has no image data")]
+    NoFrameData(usize),
+    #[error("The image header is invalid")]
+    InvalidHeader,
+    #[error("Failed to build the frame image")]
+    Png(#[from] PngError),
+}
+
+pub type ApngResult<T> = Result<T, ApngError>;
+
+// The acTL chunk
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    // 0 means the animation loops forever
+    pub num_plays: u32,
+}
+
+impl AnimationControl {
+    const LENGTH: usize = 8;
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data = self
+            .num_frames
+            .to_be_bytes()
+            .iter()
+            .chain(self.num_plays.to_be_bytes().iter())
+            .copied()
+            .collect();
+
+        Chunk::new(ChunkType::from_known(*b"acTL"), data)
+    }
+}
+
+impl TryFrom<&Chunk> for AnimationControl {
+    type Error = ApngError;
+
+    fn try_from(chunk: &Chunk) -> ApngResult<AnimationControl> {
+        let data = expect_length(chunk, "acTL", AnimationControl::LENGTH)?;
+
+        Ok(AnimationControl {
+            num_frames: read_u32(&data[0..4]),
+            num_plays: read_u32(&data[4..8]),
+        })
+    }
+}
+
+// The fcTL chunk, the region of the canvas a frame covers and how long it is shown
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: u8,
+    pub blend_op: u8,
+}
+
+impl FrameControl {
+    const LENGTH: usize = 26;
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(FrameControl::LENGTH);
+        for value in &[
+            self.sequence_number,
+            self.width,
+            self.height,
+            self.x_offset,
+            self.y_offset,
+        ] {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        data.extend_from_slice(&self.delay_num.to_be_bytes());
+        data.extend_from_slice(&self.delay_den.to_be_bytes());
+        data.extend_from_slice(&[self.dispose_op, self.blend_op]);
+
+        Chunk::new(ChunkType::from_known(*b"fcTL"), data)
+    }
+
+    // A denominator of 0 is treated as 100, i.e the numerator is in hundredths of a second
+    pub fn delay_seconds(&self) -> f64 {
+        let den = if self.delay_den == 0 {
+            100
+        } else {
+            self.delay_den
+        };
+
+        f64::from(self.delay_num) / f64::from(den)
+    }
+}
+
+impl TryFrom<&Chunk> for FrameControl {
+    type Error = ApngError;
+
+    fn try_from(chunk: &Chunk) -> ApngResult<FrameControl> {
+        let data = expect_length(chunk, "fcTL", FrameControl::LENGTH)?;
+
+        Ok(FrameControl {
+            sequence_number: read_u32(&data[0..4]),
+            width: read_u32(&data[4..8]),
+            height: read_u32(&data[8..12]),
+            x_offset: read_u32(&data[12..16]),
+            y_offset: read_u32(&data[16..20]),
+            delay_num: u16::from_be_bytes([data[20], data[21]]),
+            delay_den: u16::from_be_bytes([data[22], data[23]]),
+            dispose_op: data[24],
+            blend_op: data[25],
+        })
+    }
+}
+
+impl Display for FrameControl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let dispose = match self.dispose_op {
+            0 => "none",
+            1 => "background",
+            2 => "previous",
+            _ => "unknown",
+        };
+        let blend = match self.blend_op {
+            0 => "source",
+            1 => "over",
+            _ => "unknown",
+        };
+        write!(
+            f,
+            "{}x{} at {},{}, shown for {}s, dispose {}, blend {}",
+            self.width,
+            self.height,
+            self.x_offset,
+            self.y_offset,
+            self.delay_seconds(),
+            dispose,
+            blend
+        )
+    }
+}
+
+// A frame and its compressed image data, split over one or more chunks
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub control: FrameControl,
+    pub data: Vec<Vec<u8>>,
+    // Whether the frame is the default image, i.e stored in IDAT chunks
+    pub is_default_image: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub control: AnimationControl,
+    pub frames: Vec<Frame>,
+}
+
+impl Animation {
+    pub fn from_png(png: &Png) -> ApngResult<Animation> {
+        let control = png
+            .chunk_by_type(&ChunkType::from_known(*b"acTL"))
+            .ok_or(ApngError::NotAnimated)?;
+        let control = AnimationControl::try_from(control)?;
+
+        let mut frames: Vec<Frame> = vec![];
+        for chunk in png.chunks() {
+            match chunk.chunk_type().to_string().as_str() {
+                "fcTL" => frames.push(Frame {
+                    control: FrameControl::try_from(chunk)?,
+                    data: vec![],
+                    is_default_image: false,
+                }),
+                // The default image is only a frame if its fcTL came first
+                "IDAT" => {
+                    if let Some(frame) = frames.last_mut() {
+                        frame.data.push(chunk.data().to_vec());
+                        frame.is_default_image = true;
+                    }
+                }
+                "fdAT" => {
+                    if chunk.data().len() < 4 {
+                        return Err(ApngError::TruncatedFrameData(chunk.data().len()));
+                    }
+                    match frames.last_mut() {
+                        Some(frame) => frame.data.push(chunk.data()[4..].to_vec()),
+                        None => return Err(ApngError::DataWithoutFrameControl),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Animation { control, frames })
+    }
+
+    pub fn total_seconds(&self) -> f64 {
+        self.frames.iter().map(|f| f.control.delay_seconds()).sum()
+    }
+}
+
+// Build a standalone PNG holding only the pixels of one frame.
+// Frames are drawn onto the canvas at an offset and may be blended with earlier frames,
+// the PNG holds the frame's own region as stored rather than the composited canvas.
+pub fn extract_frame(png: &Png, index: usize) -> ApngResult<Png> {
+    let animation = Animation::from_png(png)?;
+    let frame = animation
+        .frames
+        .get(index)
+        .ok_or(ApngError::FrameOutOfRange(index, animation.frames.len()))?;
+    if frame.data.is_empty() {
+        return Err(ApngError::NoFrameData(index));
+    }
+
+    let header = &png.chunks()[0];
+    if header.data().len() < 8 {
+        return Err(ApngError::InvalidHeader);
+    }
+    let mut header_data = header.data().to_vec();
+    header_data[0..4].copy_from_slice(&frame.control.width.to_be_bytes());
+    header_data[4..8].copy_from_slice(&frame.control.height.to_be_bytes());
+
+    let mut chunks = vec![Chunk::new(*header.chunk_type(), header_data)];
+    // Chunks before the image data, eg PLTE and colour management, apply to every frame
+    for chunk in png.chunks()[1..].iter() {
+        let chunk_type = chunk.chunk_type().to_string();
+        if chunk_type == "IDAT" || chunk_type == "fdAT" || chunk_type == "fcTL" {
+            break;
+        }
+        if chunk_type != "acTL" {
+            chunks.push(Chunk::new(*chunk.chunk_type(), chunk.data().to_vec()));
+        }
+    }
+    for data in frame.data.iter() {
+        chunks.push(Chunk::new(ChunkType::from_known(*b"IDAT"), data.clone()));
+    }
+    chunks.push(Chunk::new(ChunkType::from_known(*b"IEND"), vec![]));
+
+    Ok(Png::from_chunks(chunks)?)
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_be_bytes([data[0], data[1], data[2], data[3]])
+}
+
+fn expect_length<'a>(
+    chunk: &'a Chunk,
+    chunk_type: &'static str,
+    expected: usize,
+) -> ApngResult<&'a [u8]> {
+    let data = chunk.data();
+    if data.len() != expected {
+        return Err(ApngError::WrongLength {
+            chunk_type,
+            expected,
+            found: data.len(),
+        });
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    fn fctl(sequence_number: u32, width: u32, delay_num: u16) -> Chunk {
+        let control = [sequence_number, width, 2, 0, 0]
+            .iter()
+            .flat_map(|v| v.to_be_bytes().to_vec())
+            .chain(delay_num.to_be_bytes().iter().copied())
+            .chain(vec![0, 10, 0, 0])
+            .collect();
+        chunk("fcTL", control)
+    }
+
+    fn fdat(sequence_number: u32, data: &[u8]) -> Chunk {
+        let mut body = sequence_number.to_be_bytes().to_vec();
+        body.extend(data);
+        chunk("fdAT", body)
+    }
+
+    fn testing_png() -> Png {
+        let header = vec![0, 0, 0, 4, 0, 0, 0, 2, 8, 6, 0, 0, 0];
+        let control = AnimationControl {
+            num_frames: 2,
+            num_plays: 0,
+        };
+        Png::from_chunks(vec![
+            chunk("IHDR", header),
+            control.to_chunk(),
+            chunk("gAMA", vec![0, 0, 177, 143]),
+            fctl(0, 4, 5),
+            chunk("IDAT", vec![1, 2, 3]),
+            fctl(1, 3, 15),
+            fdat(2, &[4, 5]),
+            fdat(3, &[6]),
+            chunk("IEND", vec![]),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_animation_from_png() {
+        let animation = Animation::from_png(&testing_png()).unwrap();
+        assert_eq!(animation.control.num_frames, 2);
+        assert_eq!(animation.control.num_plays, 0);
+        assert_eq!(animation.frames.len(), 2);
+        assert!(animation.frames[0].is_default_image);
+        assert_eq!(animation.frames[1].data, vec![vec![4, 5], vec![6]]);
+        assert_eq!(animation.frames[1].control.delay_seconds(), 1.5);
+        assert_eq!(animation.total_seconds(), 2.0);
+    }
+
+    #[test]
+    fn test_frame_control_to_chunk() {
+        let chunk = fctl(1, 3, 15);
+        let control = FrameControl::try_from(&chunk).unwrap();
+        assert_eq!(control.to_chunk().as_bytes(), chunk.as_bytes());
+    }
+
+    #[test]
+    fn test_not_animated() {
+        let png =
+            Png::from_chunks(vec![chunk("IHDR", vec![0; 13]), chunk("IEND", vec![])]).unwrap();
+        assert!(Animation::from_png(&png).is_err());
+    }
+
+    #[test]
+    fn test_extract_frame() {
+        let frame = extract_frame(&testing_png(), 1).unwrap();
+        let types: Vec<String> = frame
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "gAMA", "IDAT", "IDAT", "IEND"]);
+        assert_eq!(&frame.chunks()[0].data()[0..4], &[0, 0, 0, 3]);
+        assert_eq!(frame.chunks()[2].data(), &[4, 5]);
+    }
+
+    #[test]
+    fn test_extract_frame_out_of_range() {
+        assert!(extract_frame(&testing_png(), 2).is_err());
+    }
+}
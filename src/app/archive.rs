@@ -0,0 +1,113 @@
+use flate2::read::GzDecoder;
+use lib_pngme::inflate::{self, InflateResult};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+// PNG files inside .zip, .tar and .tar.gz archives, so a release bundle can be searched
+// without unpacking it. Each file is named `archive.zip!inner/path.png`, the path of the
+// archive and the path within it. Archives inside archives aren't opened.
+//
+// Compressed files are inflated within the inflate limits, for .tar.gz archives the ratio
+// is to the size of the whole archive as its files aren't compressed separately.
+
+enum Kind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn kind(path: &Path) -> Option<Kind> {
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+    if name.ends_with(".zip") {
+        Some(Kind::Zip)
+    } else if name.ends_with(".tar") {
+        Some(Kind::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(Kind::TarGz)
+    } else {
+        None
+    }
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    kind(path).is_some()
+}
+
+// The name a file within the archive is reported by
+pub fn inner_path(archive: &Path, inner: &str) -> PathBuf {
+    PathBuf::from(format!("{}!{}", archive.display(), inner))
+}
+
+fn has_png_extension(inner: &str) -> bool {
+    inner.to_ascii_lowercase().ends_with(".png")
+}
+
+// Call `f` with the name and contents of every PNG file in the archive, in the order they
+// are stored. The contents are read out of the archive, no file is unpacked to disk.
+pub fn for_each_png<F>(archive: &Path, mut f: F) -> io::Result<()>
+where
+    F: FnMut(PathBuf, InflateResult<Vec<u8>>),
+{
+    let file = File::open(archive)?;
+    let archive_size = file.metadata()?.len() as usize;
+    let compressed = matches!(kind(archive), Some(Kind::TarGz));
+    let reader: Box<dyn Read> = match kind(archive) {
+        Some(Kind::Zip) => return for_each_zip_png(archive, file, f),
+        Some(Kind::Tar) => Box::new(file),
+        Some(Kind::TarGz) => Box::new(GzDecoder::new(file)),
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Not a .zip, .tar or .tar.gz file",
+            ))
+        }
+    };
+
+    let mut tar = tar::Archive::new(reader);
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let inner = entry.path()?.to_string_lossy().into_owned();
+        if !has_png_extension(&inner) {
+            continue;
+        }
+        let bytes = if compressed {
+            inflate::inflate(&mut entry, archive_size, &inflate::limits())
+        } else {
+            let mut bytes = vec![];
+            entry
+                .read_to_end(&mut bytes)
+                .map(|_| bytes)
+                .map_err(Into::into)
+        };
+        f(inner_path(archive, &inner), bytes);
+    }
+
+    Ok(())
+}
+
+fn for_each_zip_png<F>(archive: &Path, file: File, mut f: F) -> io::Result<()>
+where
+    F: FnMut(PathBuf, InflateResult<Vec<u8>>),
+{
+    let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+
+    let mut zip = zip::ZipArchive::new(file).map_err(invalid)?;
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index).map_err(invalid)?;
+        if !entry.is_file() {
+            continue;
+        }
+        let inner = entry.name().to_string();
+        if has_png_extension(&inner) {
+            let compressed_size = entry.compressed_size() as usize;
+            let bytes = inflate::inflate(&mut entry, compressed_size, &inflate::limits());
+            f(inner_path(archive, &inner), bytes);
+        }
+    }
+
+    Ok(())
+}
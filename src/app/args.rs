@@ -1,78 +1,1158 @@
+use crate::units;
+use clap::{ArgGroup, Args, Parser, Subcommand};
 use lib_pngme::chunk_type::ChunkType;
+use lib_pngme::payload::{Payload, PayloadEncoding};
+use lib_pngme::pipeline;
+use lib_pngme::selector::{Selector, TypePattern};
+use lib_pngme::shamir::Scheme;
+use lib_pngme::standard::Rgb;
+use lib_pngme::text::TextKind;
+use std::fmt::{self, Display, Formatter};
 use std::path::PathBuf;
-use structopt::StructOpt;
+use std::str::FromStr;
+use std::time::Duration;
 
-#[derive(StructOpt, Debug)]
-#[structopt(name = "pngme")]
+#[derive(Parser, Debug)]
+#[command(name = "pngme", author, version, about)]
 pub struct ApplicationArguments {
-    #[structopt(subcommand)]
+    #[command(subcommand)]
     pub command: Command,
+    /// The most compressed data, eg a zTXt chunk or compressed message, may inflate to, eg 64MB or 512K
+    #[arg(long, global = true, default_value = "64MB", value_parser = units::parse_size)]
+    pub max_inflate_size: usize,
+    /// The most times larger than its compressed size data may inflate to, beyond the first 1MB
+    #[arg(long, global = true, default_value = "100")]
+    pub max_inflate_ratio: usize,
+    /// Don't send long output, eg from print, through $PAGER when writing to a terminal
+    #[arg(long, global = true)]
+    pub no_pager: bool,
+    /// Fail rather than stop to ask a question, eg in CI. Also set by PNGME_NON_INTERACTIVE=1
+    #[arg(long, global = true)]
+    pub non_interactive: bool,
+    /// Read files whose PNG signature is damaged, or PNGs inside other data such as a memory dump,
+    /// starting from the first intact IHDR chunk and stopping after IEND
+    #[arg(long, global = true)]
+    pub assume_png: bool,
+    /// Stop the command after this long, eg 30s or 5m, finishing its output so far as Ctrl-C does
+    #[arg(long, global = true, value_name = "DURATION", value_parser = units::parse_duration)]
+    pub time_limit: Option<Duration>,
+    /// Output json, one record per line, the same as `--format json` for any command which takes --format
+    #[arg(long, global = true)]
+    pub json: bool,
 }
 
-#[derive(StructOpt, Debug)]
+#[derive(Subcommand, Debug)]
 pub enum Command {
     /// Add a message to a specified PNG file
-    #[structopt(name = "encode")]
+    #[command(name = "encode")]
     Encode(Encode),
     /// Read a message from a specified PNG file
-    #[structopt(name = "decode")]
+    #[command(name = "decode")]
     Decode(Decode),
+    /// Print every message in a PNG file, from whichever chunks hold one, without knowing their types
+    #[command(name = "decode-all")]
+    DecodeAll(DecodeAll),
     /// Remove a message from a specified PNG file
-    #[structopt(name = "remove")]
+    #[command(name = "remove")]
     Remove(Remove),
+    /// Overwrite a chunk's data in place, and its earlier versions, so it can't be recovered from the file
+    #[command(name = "redact")]
+    Redact(Redact),
+    /// Remove every ancillary chunk from a specified PNG file, leaving only what is needed to display it
+    #[command(name = "strip")]
+    Strip(Strip),
+    /// Remove ancillary chunks which are byte-for-byte copies of another chunk in the same file
+    #[command(name = "dedupe")]
+    Dedupe(Dedupe),
+    /// Set or clear the background colour (bKGD) of a specified PNG file
+    #[command(name = "background", subcommand)]
+    Background(Background),
+    /// Clear the transparency (tRNS) of a specified PNG file
+    #[command(name = "transparency", subcommand)]
+    Transparency(Transparency),
+    /// Inspect and edit animated PNGs
+    #[command(name = "apng", subcommand)]
+    Apng(Apng),
+    /// Show or restore the earlier messages kept by `encode --upsert --keep-history`
+    #[command(name = "history", subcommand)]
+    History(History),
+    /// Work with the tEXt, zTXt and iTXt text chunks of a specified PNG file
+    #[command(name = "text", subcommand)]
+    Text(Text),
     /// Identify the chunks which have pure text in them
-    #[structopt(name = "identify-text")]
+    #[command(name = "identify-text")]
     IdentifyText(IdentifyText),
     /// Display some information about the PNG and it's chunks
-    #[structopt(name = "print")]
+    #[command(name = "print")]
     Print(Print),
+    /// List every chunk in a table, with where it is in the file, its length, CRC and the properties its type's letters give it
+    #[command(name = "list")]
+    List(List),
+    /// List every chunk of every PNG file in a directory, a row per chunk, eg with `--format csv` for a spreadsheet
+    #[command(name = "inventory")]
+    Inventory(Inventory),
+    /// Build an SQLite index of the chunks in a directory of PNG files, and search it. Requires `--features index`
+    #[command(name = "index", subcommand)]
+    Index(Index),
+    /// Draw where each chunk is in a PNG file and how big it is, as an SVG or a Graphviz dot graph
+    #[command(name = "layout")]
+    Layout(Layout),
+    /// Show which chunks make up the difference in size between two versions of a PNG file
+    #[command(name = "size-diff")]
+    SizeDiff(SizeDiff),
+    /// Check staged PNG files against the project's policy before each commit, with a git pre-commit hook
+    #[command(name = "hook", subcommand)]
+    Hook(Hook),
+    /// Record the SHA-256 of the source asset a PNG file was exported from, eg a PSD, in the PNG
+    #[command(name = "stamp-source")]
+    StampSource(StampSource),
+    /// Show the source asset a PNG file was exported from, and find it with --search
+    #[command(name = "trace")]
+    Trace(Trace),
+    /// Check the standard chunks of a specified PNG file are consistent with each other
+    #[command(name = "verify")]
+    Verify(Verify),
+    /// Find everything wrong with a PNG file in one go, and fix what can be fixed safely with --fix
+    #[command(name = "doctor")]
+    Doctor(Doctor),
+    /// Find the PNGs inside any file, eg a memory dump or disk image, and extract them, salvaging damaged ones
+    #[command(name = "carve")]
+    Carve(Carve),
+    /// Append a payload to the end of any file, eg an executable or a PDF, where the file's own readers ignore it
+    #[command(name = "append-to")]
+    AppendTo(AppendTo),
+    /// Read the payload appended to a file by append-to
+    #[command(name = "extract-from")]
+    ExtractFrom(ExtractFrom),
+    /// Check the case of each chunk type's letters matches what the chunk is, eg private chunks aren't critical
+    #[command(name = "lint")]
+    Lint(Lint),
+    /// Run pngcheck, exiftool and ImageMagick's identify against a file, whichever are installed
+    #[command(name = "interop-check")]
+    InteropCheck(InteropCheck),
+    /// Check two PNG files decode to exactly the same pixels, eg before and after stripping metadata. Requires `--features image-check`
+    #[command(name = "assert-pixels-equal")]
+    AssertPixelsEqual(AssertPixelsEqual),
+    /// Compare how pngme and the png crate read a file, to debug parser disagreements. Requires `--features xcheck`
+    #[command(name = "xcheck")]
+    Xcheck(Xcheck),
+    /// Explain what a chunk type's upper-case and lower-case letters mean
+    #[command(name = "explain")]
+    Explain(Explain),
+    /// Describe the formats pngme writes to chunks, so other tools can read and write them
+    #[command(name = "format-spec")]
+    FormatSpec(FormatSpec),
+    /// List the optional features, payload versions, encodings and transforms this build supports
+    #[command(name = "capabilities")]
+    Capabilities(Capabilities),
+    /// Write a labelled corpus of small PNGs, valid and broken, for testing software which reads PNGs
+    #[command(name = "gen-fixtures")]
+    GenFixtures(GenFixtures),
+    /// Measure how quickly this build parses, checks and writes a synthetic PNG
+    #[command(name = "bench")]
+    Bench(Bench),
+    /// Show the local usage statistics, once enabled with `--enable`. Nothing is ever sent anywhere
+    #[command(name = "stats")]
+    Stats(Stats),
+    /// Remove the temporary files left in a directory by runs of pngme which were killed while writing
+    #[command(name = "gc")]
+    Gc(Gc),
+    /// Step through building a command interactively, with explanations along the way
+    #[command(name = "wizard")]
+    Wizard,
 }
 
-#[derive(StructOpt, Debug)]
+impl Command {
+    // The name the command is run with, including any subcommand
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Encode(_) => "encode",
+            Command::Decode(_) => "decode",
+            Command::DecodeAll(_) => "decode-all",
+            Command::Remove(_) => "remove",
+            Command::Redact(_) => "redact",
+            Command::Strip(_) => "strip",
+            Command::Dedupe(_) => "dedupe",
+            Command::Background(Background::Set(_)) => "background set",
+            Command::Background(Background::Clear(_)) => "background clear",
+            Command::Transparency(Transparency::Clear(_)) => "transparency clear",
+            Command::Apng(Apng::Info(_)) => "apng info",
+            Command::Apng(Apng::ExtractFrame(_)) => "apng extract-frame",
+            Command::Apng(Apng::SetPlays(_)) => "apng set-plays",
+            Command::History(History::Show(_)) => "history show",
+            Command::History(History::Restore(_)) => "history restore",
+            Command::Text(Text::List(_)) => "text list",
+            Command::Text(Text::Get(_)) => "text get",
+            Command::Text(Text::Set(_)) => "text set",
+            Command::Text(Text::Remove(_)) => "text remove",
+            Command::Text(Text::Convert(_)) => "text convert",
+            Command::IdentifyText(_) => "identify-text",
+            Command::Print(_) => "print",
+            Command::List(_) => "list",
+            Command::Verify(_) => "verify",
+            Command::Doctor(_) => "doctor",
+            Command::Lint(_) => "lint",
+            Command::InteropCheck(_) => "interop-check",
+            Command::Xcheck(_) => "xcheck",
+            Command::Inventory(_) => "inventory",
+            Command::Index(Index::Build(_)) => "index build",
+            Command::Index(Index::Query(_)) => "index query",
+            Command::Layout(_) => "layout",
+            Command::SizeDiff(_) => "size-diff",
+            Command::Hook(Hook::Install(_)) => "hook install",
+            Command::Hook(Hook::Run) => "hook run",
+            Command::StampSource(_) => "stamp-source",
+            Command::Trace(_) => "trace",
+            Command::AssertPixelsEqual(_) => "assert-pixels-equal",
+            Command::Explain(_) => "explain",
+            Command::FormatSpec(_) => "format-spec",
+            Command::Capabilities(_) => "capabilities",
+            Command::GenFixtures(_) => "gen-fixtures",
+            Command::Bench(_) => "bench",
+            Command::Stats(_) => "stats",
+            Command::Gc(_) => "gc",
+            Command::Carve(_) => "carve",
+            Command::AppendTo(_) => "append-to",
+            Command::ExtractFrom(_) => "extract-from",
+            Command::Wizard => "wizard",
+        }
+    }
+
+    // The file the command reads, for commands which read one
+    pub fn file_path(&self) -> Option<&PathBuf> {
+        match self {
+            Command::Encode(args) => Some(&args.file_path),
+            Command::Decode(args) => Some(&args.file_path),
+            Command::DecodeAll(args) => Some(&args.file_path),
+            Command::Remove(args) => Some(&args.file_path),
+            Command::Redact(args) => Some(&args.file_path),
+            Command::Strip(args) => Some(&args.file_path),
+            Command::Dedupe(args) => Some(&args.file_path),
+            Command::Background(Background::Set(args)) => Some(&args.file_path),
+            Command::Background(Background::Clear(args)) => Some(&args.file_path),
+            Command::Transparency(Transparency::Clear(args)) => Some(&args.file_path),
+            Command::Apng(Apng::Info(args)) => Some(&args.file_path),
+            Command::Apng(Apng::ExtractFrame(args)) => Some(&args.file_path),
+            Command::Apng(Apng::SetPlays(args)) => Some(&args.file_path),
+            Command::History(History::Show(args)) => Some(&args.file_path),
+            Command::History(History::Restore(args)) => Some(&args.file_path),
+            Command::Text(Text::List(args)) => Some(&args.file_path),
+            Command::Text(Text::Get(args)) => Some(&args.file_path),
+            Command::Text(Text::Set(args)) => Some(&args.file_path),
+            Command::Text(Text::Remove(args)) => Some(&args.file_path),
+            Command::Text(Text::Convert(args)) => Some(&args.file_path),
+            Command::IdentifyText(args) => Some(&args.file_path),
+            Command::Print(args) => Some(&args.file_path),
+            Command::List(args) => Some(&args.file_path),
+            Command::Verify(args) => Some(&args.file_path),
+            Command::Doctor(args) => Some(&args.file_path),
+            Command::Lint(args) => Some(&args.file_path),
+            Command::InteropCheck(args) => Some(&args.file_path),
+            Command::Xcheck(args) => Some(&args.file_path),
+            Command::Inventory(args) => Some(&args.file_path),
+            Command::Index(Index::Build(args)) => Some(&args.directory),
+            Command::Index(Index::Query(args)) => Some(&args.db),
+            Command::Layout(args) => Some(&args.file_path),
+            Command::SizeDiff(args) => Some(&args.file_path),
+            Command::StampSource(args) => Some(&args.file_path),
+            Command::Trace(args) => Some(&args.file_path),
+            Command::AssertPixelsEqual(args) => Some(&args.file_path),
+            Command::Gc(args) => Some(&args.directory),
+            Command::Carve(args) => Some(&args.file_path),
+            Command::AppendTo(args) => Some(&args.file_path),
+            Command::ExtractFrom(args) => Some(&args.file_path),
+            Command::Explain(_)
+            | Command::Hook(_)
+            | Command::FormatSpec(_)
+            | Command::Capabilities(_)
+            | Command::GenFixtures(_)
+            | Command::Bench(_)
+            | Command::Stats(_)
+            | Command::Wizard => None,
+        }
+    }
+
+    // The --format of commands which take one, for --json to set
+    pub fn format_mut(&mut self) -> Option<&mut OutputFormat> {
+        match self {
+            Command::Decode(args) => Some(&mut args.format),
+            Command::DecodeAll(args) => Some(&mut args.format),
+            Command::IdentifyText(args) => Some(&mut args.format),
+            Command::Print(args) => Some(&mut args.format),
+            Command::List(args) => Some(&mut args.format),
+            Command::Doctor(args) => Some(&mut args.format),
+            Command::InteropCheck(args) => Some(&mut args.format),
+            Command::Xcheck(args) => Some(&mut args.format),
+            Command::Inventory(args) => Some(&mut args.format),
+            Command::Index(Index::Query(args)) => Some(&mut args.format),
+            Command::SizeDiff(args) => Some(&mut args.format),
+            Command::FormatSpec(args) => Some(&mut args.format),
+            Command::Capabilities(args) => Some(&mut args.format),
+            Command::Bench(args) => Some(&mut args.format),
+            Command::Stats(args) => Some(&mut args.format),
+            Command::Carve(args) => Some(&mut args.format),
+            _ => None,
+        }
+    }
+}
+
+// How a command outputs its results, rendered by `report` for everything but human
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OutputFormat {
+    Human,
+    Json,
+    Yaml,
+    Csv,
+    Table,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OutputFormat, String> {
+        match s {
+            "human" | "text" => Ok(OutputFormat::Human),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "csv" => Ok(OutputFormat::Csv),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(format!(
+                "Unknown format `{}`, expected human, json, yaml, csv or table",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OutputFormat::Human => "human",
+            OutputFormat::Json => "json",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Table => "table",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+// How `layout` draws a file
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GraphFormat {
+    Svg,
+    Dot,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<GraphFormat, String> {
+        match s {
+            "svg" => Ok(GraphFormat::Svg),
+            "dot" => Ok(GraphFormat::Dot),
+            _ => Err(format!("Unknown format `{}`, expected svg or dot", s)),
+        }
+    }
+}
+
+impl Display for GraphFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            GraphFormat::Svg => "svg",
+            GraphFormat::Dot => "dot",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Args, Debug)]
 pub struct Encode {
     /// The input PNG file
-    #[structopt(parse(from_os_str))]
     pub file_path: PathBuf,
     /// The 4 letter chunk type to use, eg teSt
     pub chunk_type: ChunkType,
-    /// The message to encode
-    pub message: String,
+    /// The message to encode. Leave it out with --input-url, the output file then follows the chunk type
+    #[arg(required_unless_present = "input_url")]
+    pub message: Option<String>,
     /// Where to write the updated PNG to. If not provided, will overwrite the input PNG
-    #[structopt(parse(from_os_str))]
     pub output_file: Option<PathBuf>,
+    /// How to store the message, one of utf8, json, cbor, msgpack or protobuf.
+    /// cbor and msgpack convert a JSON message, protobuf expects the message to be the path to an encoded message
+    #[arg(long = "as")]
+    pub encoding: Option<PayloadEncoding>,
+    /// Record the MIME type of the message, eg application/json or image/png, so decode knows how to output it.
+    /// Needs a version 3 payload
+    #[arg(long, value_name = "TYPE")]
+    pub mime: Option<String>,
+    /// Compress the message with zlib, the same as `--transform compress`
+    #[arg(long)]
+    pub compress: bool,
+    /// Transforms to apply to the message, any of compress or base64.
+    /// They always run in the same order, eg compression before anything which makes the data look random
+    #[arg(long, num_args = 1.., value_delimiter = ',', value_parser = parse_transform)]
+    pub transform: Vec<String>,
+    /// Don't compress messages which look random, eg already compressed or encrypted data, as they won't get smaller
+    #[arg(long)]
+    pub skip_high_entropy: bool,
+    /// Replace the first chunk of this type instead of adding another, it is added if there isn't one
+    #[arg(long)]
+    pub upsert: bool,
+    /// Show a diff of the old and new message when upserting text. By default it is shown when writing to a terminal
+    #[arg(long, requires = "upsert", conflicts_with = "no_diff")]
+    pub show_diff: bool,
+    /// Don't show a diff of the old and new message when upserting text
+    #[arg(long, requires = "upsert")]
+    pub no_diff: bool,
+    /// Keep this many of the most recent earlier messages when upserting, see `pngme history`
+    #[arg(long, value_name = "N", requires = "upsert")]
+    pub keep_history: Option<usize>,
+    /// Insert the chunk at this position in the file as numbered by `pngme print`, 1 puts it straight after IHDR.
+    /// By default it goes last, before IEND. Ignored when --upsert replaces a chunk
+    #[arg(long, value_name = "INDEX", conflicts_with = "split_secret")]
+    pub position: Option<usize>,
+    /// Split the message into shares with Shamir's Secret Sharing, each in its own chunk.
+    /// 3-of-5 makes 5 shares, any 3 of them rebuild the message with `decode --combine` and fewer reveal nothing
+    #[arg(long, value_name = "SCHEME", conflicts_with = "upsert")]
+    pub split_secret: Option<Scheme>,
+    /// Spread the shares across these PNG files as well as the input, giving each file a share in turn.
+    /// These files are updated in place
+    #[arg(long, num_args = 1.., requires = "split_secret")]
+    pub share_across: Vec<PathBuf>,
+    /// Make the output depend only on the inputs, so encoding twice gives identical files.
+    /// Secret shares are derived from the message rather than random, only use it for messages which can't be guessed
+    #[arg(long)]
+    pub deterministic: bool,
+    /// Only use features which this payload version can hold, so older releases of pngme can read the message.
+    /// Version 1 is a plain UTF-8 message with no header, as written by pngme before structured messages
+    #[arg(long, value_name = "VERSION", value_parser = parse_payload_version)]
+    pub compat_version: Option<u8>,
+    /// Download the message from this URL instead, eg a manifest published by CI.
+    /// Needs pngme built with `--features http`
+    #[arg(long, value_name = "URL")]
+    pub input_url: Option<String>,
+    /// The largest download to accept with --input-url, eg 10MiB, 512K or a number of bytes
+    #[cfg(feature = "http")]
+    #[arg(long, value_name = "SIZE", default_value = "10MB", value_parser = units::parse_size)]
+    pub max_download_size: usize,
+    /// Give up on the download after this long, eg 30s or 2m
+    #[cfg(feature = "http")]
+    #[arg(long, value_name = "DURATION", default_value = "30s", value_parser = units::parse_duration)]
+    pub timeout: Duration,
+    /// Only encode the download if it has this SHA-256, given in hex
+    #[arg(long, value_name = "HEX", requires = "input_url", value_parser = parse_sha256)]
+    pub expect_sha256: Option<String>,
+}
+
+fn parse_sha256(s: &str) -> Result<String, String> {
+    if s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(s.to_ascii_lowercase())
+    } else {
+        Err(format!(
+            "Invalid SHA-256 `{}`, expected 64 hexadecimal digits",
+            s
+        ))
+    }
+}
+
+// The name of a transform pngme knows, resolved to the transform when encoding
+fn parse_transform(s: &str) -> Result<String, String> {
+    pipeline::transform(s)
+        .map(|transform| transform.name().to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn parse_payload_version(s: &str) -> Result<u8, String> {
+    match s.parse::<u8>() {
+        Ok(version) if (Payload::LEGACY_VERSION..=Payload::CURRENT_VERSION).contains(&version) => {
+            Ok(version)
+        }
+        _ => Err(format!(
+            "Unknown payload version `{}`, expected {} to {}",
+            s,
+            Payload::LEGACY_VERSION,
+            Payload::CURRENT_VERSION
+        )),
+    }
 }
 
-#[derive(StructOpt, Debug)]
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("chunk").required(true).args(["chunk_type", "type", "type_glob"])))]
 pub struct Decode {
-    /// The input PNG file
-    #[structopt(parse(from_os_str))]
+    /// The input PNG file, or a directory of PNG files
     pub file_path: PathBuf,
     /// The 4 letter chunk type to search for, eg teSt
-    pub chunk_type: ChunkType,
+    pub chunk_type: Option<ChunkType>,
+    /// The 4 letter chunk type to search for, an alternative to giving it after the file
+    #[arg(id = "type", long = "type")]
+    pub type_option: Option<ChunkType>,
+    /// Decode every chunk whose type matches the pattern, where ? matches any letter and * any letters, eg 'ru??'
+    #[arg(long)]
+    pub type_glob: Option<TypePattern>,
+    /// Match the chunk type ignoring case, so rust also matches ruSt and RUST
+    #[arg(long)]
+    pub ignore_case: bool,
+    /// Decode every chunk of the type rather than only the first, eg all the tEXt chunks.
+    /// Each is labelled with its type
+    #[arg(long, conflicts_with_all = ["qr", "qr_png", "export_env", "output", "raw", "combine"])]
+    pub all: bool,
+    /// Search sub-directories when the input is a directory
+    #[arg(short, long)]
+    pub recursive: bool,
+    /// Also decode the PNG files inside .zip, .tar and .tar.gz archives, reported as `archive.zip!inner/path.png`
+    #[arg(long, conflicts_with = "combine")]
+    pub archives: bool,
+    /// How to output the results, one of human, json, yaml, csv or table.
+    /// json outputs one record per file, per line
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
+    /// How to display the message, one of utf8 or json. Structured messages are pretty printed with json
+    #[arg(long = "as")]
+    pub display_as: Option<PayloadEncoding>,
+    /// A protobuf descriptor set used to display protobuf messages
+    #[arg(long, requires = "message_type")]
+    pub descriptor: Option<PathBuf>,
+    /// The fully qualified protobuf message type within the descriptor set, eg my.Build
+    #[arg(long, requires = "descriptor")]
+    pub message_type: Option<String>,
+    /// Display the message as a QR code in the terminal instead of as text
+    #[arg(long)]
+    pub qr: bool,
+    /// Write the message as a QR code to this PNG file
+    #[arg(long)]
+    pub qr_png: Option<PathBuf>,
+    /// Output each key of a JSON object message as `PREFIX_KEY=value`, ready for `eval` or `$GITHUB_ENV`
+    #[arg(long, value_name = "PREFIX")]
+    pub export_env: Option<String>,
+    /// Rebuild a message split with `encode --split-secret` from the shares in the file,
+    /// or in every PNG file when the input is a directory
+    #[arg(long)]
+    pub combine: bool,
+    /// Don't try to unwrap messages which aren't text, by default zlib, gzip, zstd and base64 wrappers are removed
+    #[arg(long)]
+    pub no_sniff: bool,
+    /// Report the version of each payload read on stderr, 1 is a plain message with no header
+    #[arg(long)]
+    pub show_version: bool,
+    /// Write the message to this file instead of printing it. Binary messages are written exactly as stored
+    #[arg(long, conflicts_with_all = ["qr", "qr_png", "export_env"])]
+    pub output: Option<PathBuf>,
+    /// Output the message exactly as stored, without unwrapping, converting or pretty printing it,
+    /// even when it is binary and the output is a terminal
+    #[arg(long, alias = "force-binary", conflicts_with_all = ["type_glob", "display_as", "export_env"])]
+    pub raw: bool,
 }
 
-#[derive(StructOpt, Debug)]
+#[derive(Args, Debug)]
+pub struct DecodeAll {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// Only decode ancillary chunks
+    #[arg(long)]
+    pub ancillary: bool,
+    /// Only decode private chunks
+    #[arg(long)]
+    pub private: bool,
+    /// Only decode the chunks the selector expression picks, eg 'private and len>1024'.
+    /// Terms are type=PATTERN, index=N, len<N, len>N, critical, ancillary, public and private,
+    /// combined with and, or, not and parentheses
+    #[arg(long)]
+    pub select: Option<Selector>,
+    /// Don't try to unwrap messages which aren't text, by default zlib, gzip, zstd and base64 wrappers are removed
+    #[arg(long)]
+    pub no_sniff: bool,
+    /// How to output the messages, one of human, json, yaml, csv or table
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("chunk").required(true).args(["chunk_type", "type_glob", "select"])))]
 pub struct Remove {
     /// The input PNG file
-    #[structopt(parse(from_os_str))]
     pub file_path: PathBuf,
-    /// The 4 letter chunk type to remove, eg teSt. Will only remove the first chunk of this type found
+    /// The 4 letter chunk type to remove, eg teSt. Will only remove the first chunk of this type found, unless --all is given
+    pub chunk_type: Option<ChunkType>,
+    /// Where to write the updated PNG to. If not provided, will overwrite the input PNG
+    pub output_file: Option<PathBuf>,
+    /// Remove every chunk whose type matches the pattern, where ? matches any letter and * any letters, eg 'ru??'
+    #[arg(long)]
+    pub type_glob: Option<TypePattern>,
+    /// Remove every chunk the selector expression picks, eg 'private and len>1024'.
+    /// Terms are type=PATTERN, index=N, len<N, len>N, critical, ancillary, public and private,
+    /// combined with and, or, not and parentheses
+    #[arg(long, conflicts_with = "ignore_case")]
+    pub select: Option<Selector>,
+    /// Match the chunk type ignoring case and remove every matching chunk, so rust also removes ruSt and RUST
+    #[arg(long)]
+    pub ignore_case: bool,
+    /// Remove every chunk of the type rather than only the first
+    #[arg(long, conflicts_with = "select")]
+    pub all: bool,
+    /// Where to write the updated PNG to, an alternative to giving it after the chunk type
+    #[arg(id = "output", long = "output", conflicts_with = "output_file")]
+    pub output_option: Option<PathBuf>,
+    /// Remove the chunk even though other chunks depend on it, leaving an image which may not display.
+    /// Also set by PNGME_FORCE=1
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct Redact {
+    /// The PNG file, it is overwritten in place
+    pub file_path: PathBuf,
+    /// The 4 letter chunk type to redact, eg ruSt. Every chunk of this type is overwritten
+    #[arg(id = "type", long = "type")]
     pub chunk_type: ChunkType,
+    /// Overwrite with random bytes rather than zeros
+    #[arg(long)]
+    pub random: bool,
+    /// Remove the chunks once they have been overwritten
+    #[arg(long)]
+    pub remove: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct Strip {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// Where to write the updated PNG to. If not provided, will overwrite the input PNG
+    pub output_file: Option<PathBuf>,
+    /// Keep the chunks which affect how colours are rendered, iCCP, sRGB, gAMA, cHRM, cICP, mDCv and cLLi,
+    /// so the stripped image looks the same
+    #[arg(long)]
+    pub keep_color_management: bool,
+    /// Only strip the ancillary chunks the selector expression picks, eg 'private or len>1024'.
+    /// Terms are type=PATTERN, index=N, len<N, len>N, critical, ancillary, public and private,
+    /// combined with and, or, not and parentheses
+    #[arg(long)]
+    pub select: Option<Selector>,
+}
+
+#[derive(Args, Debug)]
+pub struct Dedupe {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// Where to write the updated PNG to. If not provided, will overwrite the input PNG
+    pub output_file: Option<PathBuf>,
+    /// Report the duplicates without removing them
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Background {
+    /// Write a bKGD chunk for the colour, in the form the image's colour type needs
+    #[command(name = "set")]
+    Set(SetBackground),
+    /// Remove the bKGD chunk
+    #[command(name = "clear")]
+    Clear(ClearChunk),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Transparency {
+    /// Remove the tRNS chunk, making every pixel opaque
+    #[command(name = "clear")]
+    Clear(ClearChunk),
+}
+
+#[derive(Args, Debug)]
+pub struct SetBackground {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// The background colour as hex, eg '#ffffff'. Indexed images need a colour from their palette
+    pub color: Rgb,
+    /// Where to write the updated PNG to. If not provided, will overwrite the input PNG
+    pub output_file: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ClearChunk {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// Where to write the updated PNG to. If not provided, will overwrite the input PNG
+    pub output_file: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Apng {
+    /// Show the number of frames, plays and the delay of each frame
+    #[command(name = "info")]
+    Info(ApngInfo),
+    /// Write a single frame out as a standalone PNG
+    #[command(name = "extract-frame")]
+    ExtractFrame(ExtractFrame),
+    /// Set how many times the animation plays, 0 to loop forever
+    #[command(name = "set-plays")]
+    SetPlays(SetPlays),
+}
+
+#[derive(Args, Debug)]
+pub struct ApngInfo {
+    /// The input PNG file
+    pub file_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct ExtractFrame {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// The frame to extract, starting from 0
+    pub frame: usize,
+    /// Where to write the frame to
+    pub output_file: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct SetPlays {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// How many times to play the animation, 0 to loop forever
+    pub plays: u32,
+    /// Where to write the updated PNG to. If not provided, will overwrite the input PNG
+    pub output_file: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum History {
+    /// Show the current message and each earlier version, most recent first
+    #[command(name = "show")]
+    Show(ShowHistory),
+    /// Put an earlier version of the message back, the current message is kept as a version
+    #[command(name = "restore")]
+    Restore(RestoreHistory),
+}
+
+#[derive(Args, Debug)]
+pub struct ShowHistory {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// The 4 letter chunk type holding the message, eg teSt
+    pub chunk_type: ChunkType,
+}
+
+#[derive(Args, Debug)]
+pub struct RestoreHistory {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// The 4 letter chunk type holding the message, eg teSt
+    pub chunk_type: ChunkType,
+    /// The version to restore, as shown by `pngme history show`
+    pub version: u32,
+    /// Where to write the updated PNG to. If not provided, will overwrite the input PNG
+    pub output_file: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Text {
+    /// List the keyword and text of every text chunk
+    #[command(name = "list")]
+    List(ListText),
+    /// Display the text for a keyword
+    #[command(name = "get")]
+    Get(GetText),
+    /// Set the text for a keyword, replacing any existing text
+    #[command(name = "set")]
+    Set(SetText),
+    /// Remove every text chunk with a keyword
+    #[command(name = "remove")]
+    Remove(RemoveText),
+    /// Convert every text chunk of one type to another, keeping the keywords and text
+    #[command(name = "convert")]
+    Convert(ConvertText),
+}
+
+#[derive(Args, Debug)]
+pub struct ListText {
+    /// The input PNG file
+    pub file_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct GetText {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// The keyword of the text, eg Comment. Keywords are case sensitive
+    #[arg(long)]
+    pub keyword: String,
+}
+
+#[derive(Args, Debug)]
+pub struct SetText {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// The text to store
+    pub text: String,
+    /// The keyword of the text, eg Comment. 1 to 79 Latin-1 characters, without leading or trailing spaces
+    #[arg(long)]
+    pub keyword: String,
+    /// The type of text chunk to store the text in, one of tEXt, zTXt or iTXt.
+    /// Defaults to the type of the existing text, or tEXt for a new keyword
+    #[arg(long = "type")]
+    pub kind: Option<TextKind>,
+    /// Where to write the updated PNG to. If not provided, will overwrite the input PNG
+    pub output_file: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct RemoveText {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// The keyword of the text to remove, eg Comment
+    #[arg(long)]
+    pub keyword: String,
+    /// Where to write the updated PNG to. If not provided, will overwrite the input PNG
+    pub output_file: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ConvertText {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// The type of text chunk to convert, one of tEXt, zTXt or iTXt
+    #[arg(long)]
+    pub from: TextKind,
+    /// The type of text chunk to convert to, one of tEXt, zTXt or iTXt.
+    /// tEXt and zTXt only hold Latin-1 text, so converting to them fails for other characters
+    #[arg(long)]
+    pub to: TextKind,
+    /// Compress the text when converting to iTXt
+    #[arg(long)]
+    pub compress: bool,
+    /// Only convert the text with this keyword
+    #[arg(long)]
+    pub keyword: Option<String>,
     /// Where to write the updated PNG to. If not provided, will overwrite the input PNG
-    #[structopt(parse(from_os_str))]
     pub output_file: Option<PathBuf>,
 }
 
-#[derive(StructOpt, Debug)]
+#[derive(Args, Debug)]
 pub struct IdentifyText {
     /// The input PNG file
-    #[structopt(parse(from_os_str))]
     pub file_path: PathBuf,
+    /// How to output the text found, one of human, json, yaml, csv or table
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
 }
 
-#[derive(StructOpt, Debug)]
+#[derive(Args, Debug)]
 pub struct Print {
     /// The input PNG file
-    #[structopt(parse(from_os_str))]
     pub file_path: PathBuf,
+    /// Only list the type, length and position of each chunk. Chunk data isn't read or checked, so large files are listed quickly
+    #[arg(long)]
+    pub headers_only: bool,
+    /// Only print the SHA-256 of the file's canonical form, which is the same for files that
+    /// only differ in how IDAT is split or the order of ancillary chunks
+    #[arg(long, conflicts_with = "headers_only")]
+    pub digest: bool,
+    /// How to output the chunks, one of human, json, yaml, csv or table. Chunk data is base64 encoded
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct List {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// How to output the chunks, one of human, json, yaml, csv or table
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct Layout {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// How to draw the layout, svg for a bar of the file or dot for Graphviz, eg piped to `dot -Tpng`
+    #[arg(long, default_value = "svg")]
+    pub format: GraphFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct Verify {
+    /// The input PNG file
+    pub file_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct Doctor {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// Where to write the fixed PNG to with --fix. If not provided, will overwrite the input PNG
+    pub output_file: Option<PathBuf>,
+    /// Correct the CRCs, remove anything after the IEND chunk and fix the case of chunk types
+    #[arg(long)]
+    pub fix: bool,
+    /// How to output the problems, one of human, json, yaml, csv or table.
+    /// The json has an id, severity, byte range and fix for each problem.
+    /// With --fix the problems before and after fixing are output one after the other
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct Lint {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// Where to write the fixed PNG to with --fix. If not provided, will overwrite the input PNG
+    pub output_file: Option<PathBuf>,
+    /// Rename the chunks to the suggested chunk types, which also updates their CRCs
+    #[arg(long)]
+    pub fix: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct InteropCheck {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// How to output the verdicts, one of human, json, yaml, csv or table
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct AssertPixelsEqual {
+    /// The first PNG file
+    pub file_path: PathBuf,
+    /// The PNG file to compare it with
+    pub other_file_path: PathBuf,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Hook {
+    /// Install a pre-commit hook in the current git repository which runs `pngme hook run`
+    #[command(name = "install")]
+    Install(HookInstall),
+    /// Check the staged PNG files against .pngme-policy.json, failing if any break it
+    #[command(name = "run")]
+    Run,
+}
+
+#[derive(Args, Debug)]
+pub struct HookInstall {
+    /// Replace an existing pre-commit hook which pngme didn't install. Also set by PNGME_FORCE=1
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct StampSource {
+    /// The exported PNG file
+    pub file_path: PathBuf,
+    /// The source asset it was exported from
+    pub source: PathBuf,
+    /// Where to write the updated PNG to. If not provided, will overwrite the input PNG
+    pub output_file: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct Trace {
+    /// The PNG file
+    pub file_path: PathBuf,
+    /// Look for the source in this directory and its sub-directories, including Git LFS pointers
+    /// and git-annex links to it
+    #[arg(long)]
+    pub search: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct SizeDiff {
+    /// The earlier version of the PNG file
+    pub file_path: PathBuf,
+    /// The later version of the PNG file
+    pub other_file_path: PathBuf,
+    /// Also list the chunk types whose size hasn't changed
+    #[arg(short, long)]
+    pub all: bool,
+    /// How to output the differences, one of human, json, yaml, csv or table
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct Inventory {
+    /// The PNG file, or a directory of PNG files
+    pub file_path: PathBuf,
+    /// Search sub-directories when the input is a directory
+    #[arg(short, long)]
+    pub recursive: bool,
+    /// Also list the chunks of the PNG files inside .zip, .tar and .tar.gz archives
+    #[arg(long)]
+    pub archives: bool,
+    /// How to output the chunks, one of human, json, yaml, csv or table. Every format has a record per chunk
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Index {
+    /// Index every PNG file in a directory, only reading the files changed since it was last built
+    #[command(name = "build")]
+    Build(IndexBuild),
+    /// List the indexed chunks matching every filter given, or all of them
+    #[command(name = "query")]
+    Query(IndexQuery),
+}
+
+#[derive(Args, Debug)]
+pub struct IndexBuild {
+    /// The directory of PNG files
+    pub directory: PathBuf,
+    /// The SQLite database holding the index, created if it doesn't exist
+    #[arg(long, default_value = "pngme.db")]
+    pub db: PathBuf,
+    /// Search sub-directories
+    #[arg(short, long)]
+    pub recursive: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct IndexQuery {
+    /// The SQLite database holding the index
+    #[arg(long, default_value = "pngme.db")]
+    pub db: PathBuf,
+    /// Only chunks whose type matches the pattern, where ? matches any letter and * any letters, eg 'tEX?'
+    #[cfg(feature = "index")]
+    #[arg(id = "type", long = "type")]
+    pub chunk_type: Option<TypePattern>,
+    /// Only files whose path matches the glob, eg '*/icons/*'
+    #[cfg(feature = "index")]
+    #[arg(long)]
+    pub path: Option<String>,
+    /// Only chunks whose data has this SHA-256, in hex
+    #[cfg(feature = "index")]
+    #[arg(long, value_name = "HEX", value_parser = parse_sha256)]
+    pub sha256: Option<String>,
+    /// List each matching file once, rather than every matching chunk
+    #[cfg(feature = "index")]
+    #[arg(long)]
+    pub files_only: bool,
+    /// How to output the results, one of human, json, yaml, csv or table
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct Xcheck {
+    /// The input PNG file
+    pub file_path: PathBuf,
+    /// How to output the comparison, one of human, json, yaml, csv or table
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct Explain {
+    /// The 4 letter chunk type to explain, eg ruSt
+    pub chunk_type: ChunkType,
+}
+
+#[derive(Args, Debug)]
+pub struct FormatSpec {
+    /// How to output the formats, one of human, json, yaml, csv or table
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct Capabilities {
+    /// How to output the capabilities, one of human, json, yaml, csv or table
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct GenFixtures {
+    /// The directory to write the PNG files and their manifest.json to, created if it doesn't exist
+    pub directory: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct Bench {
+    /// How much image data the synthetic PNG holds, eg 100MB, 512K or a number of bytes
+    #[arg(long, default_value = "16MB", value_parser = units::parse_size)]
+    pub size: usize,
+    /// How to output the results, one of human, json, yaml, csv or table
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct Stats {
+    /// Show how this installation of pngme has been used
+    #[arg(long = "self", required_unless_present_any = ["enable", "disable"])]
+    pub show: bool,
+    /// Start recording usage statistics, in `~/.pngme-stats.json` or the file named by PNGME_STATS_FILE
+    #[arg(long, conflicts_with = "disable")]
+    pub enable: bool,
+    /// Stop recording usage statistics and delete the statistics recorded so far
+    #[arg(long)]
+    pub disable: bool,
+    /// How to output the statistics, one of human, json, yaml, csv or table
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct Gc {
+    /// The directory to clean up
+    pub directory: PathBuf,
+    /// Search sub-directories
+    #[arg(short, long)]
+    pub recursive: bool,
+    /// Only remove temporary files at least this old, eg 30m or 2h, so writes still going on are left alone
+    #[arg(long, value_name = "DURATION", default_value = "1h", value_parser = units::parse_duration)]
+    pub min_age: Duration,
+    /// List the files which would be removed without removing them
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct Carve {
+    /// The file to search, of any kind
+    pub file_path: PathBuf,
+    /// The directory to write the PNGs to, created if it doesn't exist. Each is named after the file and where it was found
+    #[arg(long, default_value = "carved")]
+    pub out_dir: PathBuf,
+    /// Skip PNGs which had to be repaired, eg because they were cut short
+    #[arg(long)]
+    pub complete_only: bool,
+    /// List the PNGs found without writing them
+    #[arg(long)]
+    pub dry_run: bool,
+    /// How to output the PNGs found, one of human, json, yaml, csv or table
+    #[arg(long, default_value = "human", env = "PNGME_FORMAT")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct AppendTo {
+    /// The file to append to, of any kind. A payload it already has is replaced
+    pub file_path: PathBuf,
+    /// The file to append
+    pub payload: PathBuf,
+    /// Where to write the file with the payload. If not provided, will overwrite the input file
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ExtractFrom {
+    /// The file the payload was appended to
+    pub file_path: PathBuf,
+    /// Write the payload to this file instead of printing it
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+    /// Print the payload even when the output is a terminal
+    #[arg(long)]
+    pub raw: bool,
+    /// Also remove the payload from the file, leaving it as it was before append-to
+    #[arg(long)]
+    pub remove: bool,
 }
@@ -0,0 +1,176 @@
+use anyhow::Context;
+use lib_pngme::chunk::Chunk;
+use lib_pngme::chunk_type::ChunkType;
+use lib_pngme::png::Png;
+use lib_pngme::view::PngView;
+use std::convert::TryFrom;
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::process;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+// Throughput of the main operations on a synthetic PNG built in memory, so builds and
+// features can be compared on the same machine without setting up a benchmark harness
+
+// Image data is split into chunks of this size, as encoders commonly do
+const IDAT_LENGTH: usize = 64 * 1024;
+const SMALL_CHUNK_LENGTH: usize = 16;
+// Small chunks are slow to build, so only this much data is split into them
+const SMALL_CHUNKS_LENGTH: usize = 4 * 1024 * 1024;
+
+pub struct Measurement {
+    pub name: &'static str,
+    pub bytes: usize,
+    pub elapsed: Duration,
+}
+
+impl Measurement {
+    pub fn megabytes_per_second(&self) -> f64 {
+        let megabytes = self.bytes as f64 / (1024.0 * 1024.0);
+        megabytes / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+fn time<T, F: FnOnce() -> T>(f: F) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+// Pseudo-random bytes from xorshift, so the data doesn't compress and every run builds the same image
+fn image_data(length: usize) -> Vec<u8> {
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    (0..length)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+// A PNG of chunks the size of typical ancillary chunks, eg pHYs or tIME
+fn small_chunks_png(data: &[u8]) -> anyhow::Result<Png> {
+    let small = ChunkType::from_str("smAl")?;
+    let mut chunks = vec![Chunk::new(
+        ChunkType::from_str("IHDR")?,
+        vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0],
+    )];
+    chunks.extend(
+        data.chunks(SMALL_CHUNK_LENGTH)
+            .map(|block| Chunk::new(small, block.to_vec())),
+    );
+    chunks.push(Chunk::new(ChunkType::from_str("IEND")?, vec![]));
+
+    Ok(Png::from_chunks(chunks)?)
+}
+
+pub fn run(size: usize) -> anyhow::Result<Vec<Measurement>> {
+    let idat = ChunkType::from_str("IDAT")?;
+    let data = image_data(size);
+    let mut measurements = vec![];
+
+    // Building the chunks computes the CRC of every one
+    let (chunks, elapsed) = time(|| {
+        let mut chunks = vec![Chunk::new(
+            ChunkType::from_str("IHDR").unwrap(),
+            vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0],
+        )];
+        chunks.extend(
+            data.chunks(IDAT_LENGTH)
+                .map(|block| Chunk::new(idat, block.to_vec())),
+        );
+        chunks.push(Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]));
+        chunks
+    });
+    measurements.push(Measurement {
+        name: "crc",
+        bytes: size,
+        elapsed,
+    });
+
+    let mut png = Png::from_chunks(chunks)?;
+    let (bytes, elapsed) = time(|| png.as_bytes());
+    measurements.push(Measurement {
+        name: "serialise",
+        bytes: bytes.len(),
+        elapsed,
+    });
+
+    let (parsed, elapsed) = time(|| Png::try_from(&bytes[..]));
+    parsed.context("Failed to parse the synthetic PNG")?;
+    measurements.push(Measurement {
+        name: "parse",
+        bytes: bytes.len(),
+        elapsed,
+    });
+
+    // Typical files are mostly small ancillary chunks, so parse one made of them too
+    let small_png = small_chunks_png(&data[..size.min(SMALL_CHUNKS_LENGTH)])?;
+    let small = small_png.as_bytes();
+    let (parsed, elapsed) = time(|| Png::try_from(&small[..]));
+    parsed.context("Failed to parse the synthetic PNG")?;
+    measurements.push(Measurement {
+        name: "parse-small",
+        bytes: small.len(),
+        elapsed,
+    });
+
+    // Parsing as batch decoding does, borrowing the chunk data rather than copying it
+    let (parsed, elapsed) = time(|| PngView::parse(&bytes).map(|view| view.chunks().len()));
+    parsed.context("Failed to parse the synthetic PNG")?;
+    measurements.push(Measurement {
+        name: "scan",
+        bytes: bytes.len(),
+        elapsed,
+    });
+
+    let (encoded, elapsed) = time(|| {
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"This is where your secret message will be!".to_vec(),
+        ));
+        png.as_bytes()
+    });
+    measurements.push(Measurement {
+        name: "encode",
+        bytes: encoded.len(),
+        elapsed,
+    });
+
+    let path = env::temp_dir().join(format!("pngme-bench-{}.png", process::id()));
+    let write = |f: &dyn Fn(&mut File) -> std::io::Result<()>| {
+        let (written, elapsed) = time(|| -> std::io::Result<()> {
+            let mut file = File::create(&path)?;
+            f(&mut file)?;
+            file.sync_all()
+        });
+        let _ = fs::remove_file(&path);
+        written
+            .map(|_| elapsed)
+            .with_context(|| format!("Failed to write the synthetic PNG to {:?}", path))
+    };
+    measurements.push(Measurement {
+        name: "write",
+        bytes: encoded.len(),
+        elapsed: write(&|file| file.write_all(&encoded))?,
+    });
+
+    // Serialising a file of small chunks into one buffer copies every chunk, writing the
+    // chunks as slices copies nothing and batches many chunks into each syscall
+    measurements.push(Measurement {
+        name: "write-small-copied",
+        bytes: small.len(),
+        elapsed: write(&|file| file.write_all(&small_png.as_bytes()))?,
+    });
+    measurements.push(Measurement {
+        name: "write-small-vectored",
+        bytes: small.len(),
+        elapsed: write(&|file| small_png.write_to(file))?,
+    });
+
+    Ok(measurements)
+}
@@ -0,0 +1,73 @@
+use lib_pngme::payload::{Payload, PayloadEncoding};
+use lib_pngme::pipeline;
+use serde_json::{json, Value};
+
+// What this build of pngme supports, so scripts can check before relying on it
+
+// Optional cargo features, and whether they were compiled in
+fn features() -> Vec<(&'static str, bool)> {
+    vec![
+        ("cloud", cfg!(feature = "cloud")),
+        ("encoding", cfg!(feature = "encoding")),
+        ("http", cfg!(feature = "http")),
+        ("image-check", cfg!(feature = "image-check")),
+        ("index", cfg!(feature = "index")),
+        ("protobuf", cfg!(feature = "protobuf")),
+        ("qr", cfg!(feature = "qr")),
+        ("simd-crc", cfg!(feature = "simd-crc")),
+        ("xcheck", cfg!(feature = "xcheck")),
+        ("zstd", cfg!(feature = "zstd")),
+    ]
+}
+
+fn payload_versions() -> Vec<u8> {
+    (Payload::LEGACY_VERSION..=Payload::CURRENT_VERSION).collect()
+}
+
+fn encodings() -> Vec<String> {
+    PayloadEncoding::ALL.iter().map(|e| e.to_string()).collect()
+}
+
+fn transforms() -> Vec<&'static str> {
+    pipeline::transforms().iter().map(|t| t.name()).collect()
+}
+
+pub fn json() -> Value {
+    let mut compiled = serde_json::Map::new();
+    for (name, enabled) in features() {
+        compiled.insert(String::from(name), Value::from(enabled));
+    }
+
+    json!({
+        "pngme": env!("CARGO_PKG_VERSION"),
+        "features": compiled,
+        "payload_versions": payload_versions(),
+        "encodings": encodings(),
+        "transforms": transforms(),
+    })
+}
+
+pub fn text() -> String {
+    let enabled: Vec<&str> = features()
+        .into_iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| name)
+        .collect();
+    let versions: Vec<String> = payload_versions().iter().map(|v| v.to_string()).collect();
+
+    [
+        format!("pngme {}", env!("CARGO_PKG_VERSION")),
+        format!(
+            "Features: {}",
+            if enabled.is_empty() {
+                String::from("none")
+            } else {
+                enabled.join(", ")
+            }
+        ),
+        format!("Payload versions: {}", versions.join(", ")),
+        format!("Encodings: {}", encodings().join(", ")),
+        format!("Transforms: {}", transforms().join(", ")),
+    ]
+    .join("\n")
+}
@@ -0,0 +1,40 @@
+// Text found in a legacy charset, converted to UTF-8
+pub struct Detected {
+    pub text: String,
+    // The name of the detected charset, eg windows-1252
+    pub charset: &'static str,
+}
+
+// tEXt is meant to be Latin-1, but tools write whatever charset they use internally.
+// Guess the charset of data which isn't UTF-8 and convert it, as long as the result looks like text.
+#[cfg(feature = "encoding")]
+pub fn detect(data: &[u8]) -> Option<Detected> {
+    use chardetng::EncodingDetector;
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(data, true);
+    let encoding = detector.guess(None, true);
+
+    let (text, had_errors) = encoding.decode_without_bom_handling(data);
+    if had_errors || !looks_like_text(&text) {
+        return None;
+    }
+
+    Some(Detected {
+        text: text.into_owned(),
+        charset: encoding.name(),
+    })
+}
+
+#[cfg(not(feature = "encoding"))]
+pub fn detect(_data: &[u8]) -> Option<Detected> {
+    None
+}
+
+// Any data can be decoded with a single byte charset, so reject anything with control characters.
+// Null is allowed as text chunks separate the keyword from the text with one.
+#[cfg(feature = "encoding")]
+fn looks_like_text(text: &str) -> bool {
+    text.chars()
+        .all(|c| !c.is_control() || c.is_whitespace() || c == '\0')
+}
@@ -0,0 +1,169 @@
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+
+// Objects in S3 or Google Cloud Storage, given as s3://bucket/key or gs://bucket/key in place
+// of a file path, so asset pipelines don't have to copy them to disk first.
+//
+// Google Cloud Storage is reached through its S3 compatible API, with HMAC keys in place of
+// the AWS access keys. Credentials come from the usual AWS environment variables or profile.
+
+#[cfg(feature = "cloud")]
+const GCS_ENDPOINT: &str = "https://storage.googleapis.com";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Service {
+    S3,
+    Gcs,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ObjectUrl {
+    pub service: Service,
+    pub bucket: String,
+    pub key: String,
+}
+
+impl ObjectUrl {
+    // None for anything which isn't an object URL, which is then an ordinary path
+    pub fn parse(path: &Path) -> Option<ObjectUrl> {
+        let path = path.to_str()?;
+        let (service, rest) = if let Some(rest) = path.strip_prefix("s3://") {
+            (Service::S3, rest)
+        } else if let Some(rest) = path.strip_prefix("gs://") {
+            (Service::Gcs, rest)
+        } else {
+            return None;
+        };
+
+        let (bucket, key) = rest.split_once('/')?;
+        if bucket.is_empty() || key.is_empty() {
+            return None;
+        }
+        Some(ObjectUrl {
+            service,
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        })
+    }
+}
+
+impl Display for ObjectUrl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let scheme = match self.service {
+            Service::S3 => "s3",
+            Service::Gcs => "gs",
+        };
+        write!(f, "{}://{}/{}", scheme, self.bucket, self.key)
+    }
+}
+
+#[cfg(feature = "cloud")]
+fn bucket(url: &ObjectUrl) -> anyhow::Result<s3::Bucket> {
+    use anyhow::Context;
+    use s3::creds::Credentials;
+    use s3::Region;
+    use std::env;
+
+    let region = match url.service {
+        Service::S3 => env::var("AWS_REGION")
+            .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+            .unwrap_or_else(|_| String::from("us-east-1"))
+            .parse::<Region>()
+            .context("Invalid AWS region")?,
+        Service::Gcs => Region::Custom {
+            region: String::from("auto"),
+            endpoint: String::from(GCS_ENDPOINT),
+        },
+    };
+    let credentials = Credentials::default().context("Failed to find cloud storage credentials")?;
+
+    s3::Bucket::new(&url.bucket, region, credentials)
+        .with_context(|| format!("Failed to open the bucket of {}", url))
+}
+
+#[cfg(feature = "cloud")]
+pub fn read(url: &ObjectUrl) -> anyhow::Result<Vec<u8>> {
+    use anyhow::Context;
+
+    let response = bucket(url)?
+        .get_object(&url.key)
+        .with_context(|| format!("Failed to download {}", url))?;
+    if response.status_code() != 200 {
+        anyhow::bail!(
+            "Failed to download {}, the server responded {}",
+            url,
+            response.status_code()
+        )
+    }
+
+    Ok(response.bytes().to_vec())
+}
+
+// Uploaded in parts, so large files don't need one request holding all of it
+#[cfg(feature = "cloud")]
+pub fn write(url: &ObjectUrl, bytes: &[u8]) -> anyhow::Result<()> {
+    use anyhow::Context;
+
+    let status = bucket(url)?
+        .put_object_stream(&mut &bytes[..], &url.key)
+        .with_context(|| format!("Failed to upload {}", url))?;
+    if status != 200 {
+        anyhow::bail!("Failed to upload {}, the server responded {}", url, status)
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "cloud"))]
+pub fn read(_url: &ObjectUrl) -> anyhow::Result<Vec<u8>> {
+    anyhow::bail!("pngme was built without cloud storage support, rebuild with `--features cloud`")
+}
+
+#[cfg(not(feature = "cloud"))]
+pub fn write(_url: &ObjectUrl, _bytes: &[u8]) -> anyhow::Result<()> {
+    anyhow::bail!("pngme was built without cloud storage support, rebuild with `--features cloud`")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(path: &str) -> Option<ObjectUrl> {
+        ObjectUrl::parse(Path::new(path))
+    }
+
+    #[test]
+    fn test_parse_object_urls() {
+        assert_eq!(
+            parse("s3://b/k"),
+            Some(ObjectUrl {
+                service: Service::S3,
+                bucket: String::from("b"),
+                key: String::from("k"),
+            })
+        );
+        assert_eq!(
+            parse("gs://b/dir/k.png"),
+            Some(ObjectUrl {
+                service: Service::Gcs,
+                bucket: String::from("b"),
+                key: String::from("dir/k.png"),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_not_object_urls() {
+        assert_eq!(parse("s3:///k"), None);
+        assert_eq!(parse("s3://b/"), None);
+        assert_eq!(parse("s3://b"), None);
+        assert_eq!(parse("images/s3/k.png"), None);
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for url in ["s3://b/k", "gs://b/dir/k.png"] {
+            assert_eq!(parse(url).unwrap().to_string(), url);
+        }
+    }
+}
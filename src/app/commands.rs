@@ -1,95 +1,2465 @@
-use crate::args::{Decode, Encode, Remove};
-use crate::args::{IdentifyText, Print};
+use crate::archive;
+use crate::args::{Apng, ApngInfo, AssertPixelsEqual, ExtractFrame, SetPlays};
+use crate::args::{AppendTo, ExtractFrom};
+use crate::args::{
+    Background, Bench, Capabilities, Carve, ClearChunk, SetBackground, Transparency,
+};
+use crate::args::{ConvertText, GetText, ListText, RemoveText, SetText, Text};
+use crate::args::{Decode, DecodeAll, Dedupe, Doctor, Encode, OutputFormat, Redact, Remove, Strip};
+use crate::args::{Explain, FormatSpec, GenFixtures, IdentifyText, InteropCheck, Lint, Print};
+use crate::args::{Gc, History, Hook, RestoreHistory, ShowHistory};
+use crate::args::{Index, IndexBuild, IndexQuery, Inventory, Layout, List, SizeDiff, Stats};
+use crate::args::{StampSource, Trace, Verify, Xcheck};
+use crate::bench;
+use crate::capabilities;
+use crate::charset;
+use crate::cloud::{self, ObjectUrl};
+use crate::diff;
+use crate::download::{self, Limits};
+use crate::errors::{self, CommandError};
+use crate::explain;
+use crate::hook;
+use crate::index::{self, Filters};
+use crate::interactive;
+use crate::interop::{self, Verdict};
+use crate::interrupt;
+use crate::layout;
+use crate::output::{self, Output};
+use crate::qr;
+use crate::report::{self, Records};
+use crate::sniff;
+use crate::spec;
+use crate::stats;
+use crate::structured::{self, ProtobufSchema};
+use crate::units;
+use crate::walk;
 use anyhow::Context;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use lib_pngme::apng::{self, Animation};
+use lib_pngme::carve;
 use lib_pngme::chunk::Chunk;
-use lib_pngme::png::Png;
+use lib_pngme::chunk_type::ChunkType;
+use lib_pngme::constraints::{self, RemovalConflict};
+use lib_pngme::dedupe;
+use lib_pngme::doctor::{self, Diagnosis};
+use lib_pngme::extensions;
+use lib_pngme::fixtures;
+use lib_pngme::history;
+use lib_pngme::lint;
+use lib_pngme::payload::{self, Payload, PayloadEncoding};
+use lib_pngme::pipeline::{self, Compress, Pipeline, Transform};
+use lib_pngme::png::{Png, PngError};
+use lib_pngme::policy;
+use lib_pngme::provenance::{self, Source};
+use lib_pngme::redact;
+use lib_pngme::registry;
+use lib_pngme::secret::SecretPayload;
+use lib_pngme::selector::TypePattern;
+use lib_pngme::shamir::{self, Scheme, Share};
+use lib_pngme::size_diff;
+use lib_pngme::standard;
+use lib_pngme::temporary;
+use lib_pngme::text::{self, TextChunk, TextKind};
+use lib_pngme::trailer;
+use lib_pngme::view::{ChunkView, CrcCheck, PngBuffer, PngView};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use zeroize::Zeroizing;
+
+// Paths can also be objects in cloud storage, s3://bucket/key or gs://bucket/key
+// With --assume-png files are read from their first intact IHDR chunk, whatever comes before
+static ASSUME_PNG: AtomicBool = AtomicBool::new(false);
+
+pub fn set_assume_png(assume_png: bool) {
+    ASSUME_PNG.store(assume_png, Ordering::Relaxed);
+}
+
+fn load_png(path: &Path) -> anyhow::Result<Png> {
+    let png = match ObjectUrl::parse(path) {
+        Some(url) if ASSUME_PNG.load(Ordering::Relaxed) => Png::recover(&cloud::read(&url)?),
+        Some(url) => Png::try_from(&cloud::read(&url)?[..]),
+        None if ASSUME_PNG.load(Ordering::Relaxed) => fs::read(path)
+            .map_err(PngError::from)
+            .and_then(|bytes| Png::recover(&bytes)),
+        None => Png::from_file_cancellable(path, interrupt::token()),
+    };
+    png.with_context(|| format!("Failed to load PNG file {:?}", path))
+}
+
+fn save_png(png: &Png, path: &Path) -> anyhow::Result<()> {
+    println!("Writing out file to {:?}", path);
+    match ObjectUrl::parse(path) {
+        Some(url) => cloud::write(&url, &png.as_bytes()),
+        None => png
+            .write_file(path)
+            .with_context(|| format!("Failed to write file {:?}", path)),
+    }
+}
 
 pub fn execute_encode(args: Encode) -> anyhow::Result<()> {
-    let mut png = Png::from_file(&args.file_path)
-        .with_context(|| format!("Failed to load PNG file {:?}", args.file_path))?;
+    let mut png = load_png(&args.file_path)?;
 
     let file_path = args.file_path;
-    png.append_chunk(Chunk::new(args.chunk_type, args.message.into_bytes()));
+    let (message, output_file) = match &args.input_url {
+        // There is no message argument, so the output file is given in its place
+        Some(url) => {
+            if args.message.is_some() && args.output_file.is_some() {
+                anyhow::bail!("Give either a message or --input-url, not both");
+            }
+            let limits = Limits {
+                #[cfg(feature = "http")]
+                max_size: args.max_download_size,
+                #[cfg(feature = "http")]
+                timeout: args.timeout,
+                expect_sha256: args.expect_sha256,
+            };
+            let message = String::from_utf8(download::fetch(url, &limits)?)
+                .with_context(|| format!("The download from {} isn't UTF-8 text", url))?;
+            (
+                message,
+                args.message.map(PathBuf::from).or(args.output_file),
+            )
+        }
+        None => (args.message.unwrap_or_default(), args.output_file),
+    };
+    // The message may be a secret to split into shares, so the copy it's read into is wiped.
+    // The payload wipes its own copy.
+    let message = Zeroizing::new(message);
+    let payload = match args.encoding {
+        Some(encoding) => structured::encode_message(&message, encoding)?,
+        // Plain messages are kept headerless so older versions of pngme can still read them
+        None => Payload::legacy(String::clone(&message)),
+    };
+    let payload = match &args.mime {
+        Some(mime) => payload.with_mime(mime)?,
+        None => payload,
+    };
+
+    let mut transforms = args
+        .transform
+        .iter()
+        .map(|name| pipeline::transform(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    if args.compress {
+        transforms.push(Box::new(Compress));
+    }
+    let high_entropy = pipeline::entropy(payload.body()) >= pipeline::HIGH_ENTROPY;
+    let mut pipeline = Pipeline::new();
+    for transform in transforms {
+        if args.skip_high_entropy && high_entropy && transform.flag() == Compress.flag() {
+            eprintln!("Not compressing the message, it looks random so won't get any smaller");
+            continue;
+        }
+        pipeline = pipeline.with(transform);
+    }
+    let stored = pipeline
+        .encode(payload.clone())
+        .context("Failed to process the message")?;
+    if let Some(version) = args.compat_version {
+        check_compat(version, &stored, args.split_secret.is_some())?;
+    }
+
+    if let Some(scheme) = args.split_secret {
+        let output_file = output_file.unwrap_or(file_path);
+        if ObjectUrl::parse(&output_file).is_some() {
+            anyhow::bail!("Secret shares can only be written to local files");
+        }
+        let mut targets = vec![(png, output_file)];
+        for share_file in args.share_across {
+            let png = load_png(&share_file)?;
+            targets.push((png, share_file));
+        }
+        return write_shares(targets, args.chunk_type, stored, scheme, args.deterministic);
+    }
+    let chunk = Chunk::try_new(args.chunk_type, stored.as_bytes())
+        .context("The message is too large for a chunk")?;
+
+    let existing = png
+        .chunks()
+        .iter()
+        .position(|c| c.chunk_type() == chunk.chunk_type());
+    match existing {
+        Some(index) if args.upsert => {
+            if args.show_diff || (!args.no_diff && io::stdout().is_terminal()) {
+                show_payload_diff(&png.chunks()[index], &payload);
+            }
+            if let Some(keep) = args.keep_history {
+                png = history::upsert_with_history(&png, chunk, keep)?;
+            } else {
+                png.replace_chunk(chunk)?;
+            }
+        }
+        _ => match args.position {
+            Some(position) => png.insert_chunk_at(position, chunk)?,
+            None => png.append_chunk(chunk),
+        },
+    }
+
+    save_png(&png, &output_file.unwrap_or(file_path))
+}
+
+// Split the payload into shares, and give each PNG a share in turn
+fn write_shares(
+    mut targets: Vec<(Png, PathBuf)>,
+    chunk_type: ChunkType,
+    payload: Payload,
+    scheme: Scheme,
+    deterministic: bool,
+) -> anyhow::Result<()> {
+    // Dropping the payload wipes its body, leaving the secret's only copy
+    let secret = SecretPayload::from(payload.as_bytes());
+    drop(payload);
+    let shares = if deterministic {
+        shamir::split_deterministic(secret.expose(), scheme)?
+    } else {
+        shamir::split(secret.expose(), scheme)?
+    };
+    let count = targets.len();
+    for (i, share) in shares.iter().enumerate() {
+        let chunk = Chunk::try_new(chunk_type, share.as_bytes())
+            .context("A share of the message is too large for a chunk")?;
+        targets[i % count].0.append_chunk(chunk);
+    }
+    println!(
+        "Split the message into {} shares, any {} of them rebuild it",
+        scheme.shares, scheme.threshold
+    );
 
-    if let Some(output_file) = args.output_file {
+    for (png, output_file) in targets {
         println!("Writing out file to {:?}", output_file);
         png.write_file(&output_file)
-            .with_context(|| format!("Failed to write file {:?}", output_file))
-    } else {
-        println!("Writing out file to {:?}", file_path);
-        png.write_file(&file_path)
-            .with_context(|| format!("Failed to write file {:?}", file_path))
+            .with_context(|| format!("Failed to write file {:?}", output_file))?;
+    }
+
+    Ok(())
+}
+
+// Show how an upsert changes the message, when both the old and new messages are text
+fn show_payload_diff(old: &Chunk, new: &Payload) {
+    let old_text = parse_payload(old.data())
+        .ok()
+        .filter(|payload| payload.encoding() == PayloadEncoding::Utf8)
+        .and_then(|payload| String::from_utf8(payload.into_body()).ok());
+    let new_text = Some(new)
+        .filter(|payload| payload.encoding() == PayloadEncoding::Utf8)
+        .and_then(|payload| std::str::from_utf8(payload.body()).ok());
+
+    match (old_text, new_text) {
+        (Some(old_text), Some(new_text)) => {
+            let label = old.chunk_type().to_string();
+            let color = io::stdout().is_terminal();
+            match diff::unified(&old_text, new_text, &label, color) {
+                Some(diff) => println!("{}", diff),
+                None => println!("Message in {} is unchanged", label),
+            }
+        }
+        _ => println!(
+            "Not showing a diff for {}, the messages aren't both text",
+            old.chunk_type()
+        ),
     }
 }
 
 pub fn execute_decode(args: Decode) -> anyhow::Result<()> {
-    let png = Png::from_file(&args.file_path)
-        .with_context(|| format!("Failed to load PNG file {:?}", args.file_path))?;
+    // One of the three is required when parsing the arguments
+    let pattern = type_pattern(
+        args.chunk_type.or(args.type_option),
+        args.type_glob.clone(),
+        args.ignore_case,
+    )?;
+
+    if args.combine {
+        return execute_combine(&args, &pattern);
+    }
+    if args.file_path.is_dir()
+        || args.format != OutputFormat::Human
+        || (args.archives && archive::is_archive(&args.file_path))
+    {
+        return execute_decode_many(&args, &pattern);
+    }
+
+    let png = load_png(&args.file_path)?;
+
+    let chunks = pattern.matching_chunks(&png);
+    let chunk = match chunks.first() {
+        Some(chunk) => *chunk,
+        None => {
+            // Not finding the chunk isn't a failure, the message goes to stderr and the exit
+            // code stays 0 as it always has
+            let error = anyhow::Error::from(no_matching_chunks(&png, &pattern, args.file_path));
+            eprintln!("{}", error);
+            for hint in errors::hints(&error) {
+                eprintln!("hint: {}", hint);
+            }
+            return Ok(());
+        }
+    };
+    if let Some(prefix) = &args.export_env {
+        let payload = read_payload(chunk)?;
+        for line in structured::export_env(&payload, protobuf_schema(&args).as_ref(), prefix)? {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+
+    // Each matching chunk is labelled with its type, unless only one type could ever match
+    if args.all || (!pattern.is_exact() && !args.qr && args.qr_png.is_none()) {
+        for chunk in chunks {
+            println!("{}: {}", chunk.chunk_type(), decode_chunk(chunk, &args)?);
+        }
+        return Ok(());
+    }
+
+    let payload = read_payload(chunk)?;
+    if args.raw || is_binary(&payload, &args) {
+        if args.qr || args.qr_png.is_some() {
+            anyhow::bail!(
+                "Message in {} is binary ({}), it can't be shown as a QR code",
+                chunk.chunk_type(),
+                output::mime_type(&payload)
+            );
+        }
+        return output::write_binary(
+            chunk.chunk_type(),
+            &payload,
+            args.output.as_deref(),
+            args.raw,
+        );
+    }
+    let data = decode_chunk(chunk, &args)?;
+
+    if let Some(qr_png) = &args.qr_png {
+        println!("Writing QR code to {:?}", qr_png);
+        qr::write_png(&data, qr_png)?;
+    }
+    if args.qr {
+        println!("{}", qr::render_terminal(&data)?);
+    } else if let Some(output) = &args.output {
+        println!("Writing message to {:?}", output);
+        fs::write(output, data).with_context(|| format!("Failed to write file {:?}", output))?;
+    } else if args.qr_png.is_none() {
+        println!("{}", data);
+    }
+
+    Ok(())
+}
+
+// A message is binary when its MIME type says so, or when it has none and isn't text even
+// after removing any wrappers and trying other charsets
+fn is_binary(payload: &Payload, args: &Decode) -> bool {
+    match payload.mime() {
+        Some(mime) => !payload::mime_is_text(mime),
+        None => {
+            let body = payload.body();
+            payload.encoding() == PayloadEncoding::Utf8
+                && std::str::from_utf8(body).is_err()
+                && (args.no_sniff || sniff::sniff(body).is_none())
+                && charset::detect(body).is_none()
+        }
+    }
+}
 
-    let chunk = png.chunk_by_type(&args.chunk_type);
+// Rebuild a split message from the shares in the file, or in every PNG in the directory
+fn execute_combine(args: &Decode, pattern: &TypePattern) -> anyhow::Result<()> {
+    let files = walk::png_files(&args.file_path, args.recursive, false)
+        .with_context(|| format!("Failed to search {:?} for PNG files", args.file_path))?;
 
-    match chunk {
-        Some(chunk) => {
-            let data = chunk.data_as_string().with_context(|| {
+    let mut chunk_type = None;
+    let mut shares = vec![];
+    for file_path in files {
+        interrupt::check()?;
+        let png = load_png(&file_path)?;
+        for chunk in pattern.matching_chunks(&png) {
+            let share = Share::from_bytes(chunk.data()).with_context(|| {
                 format!(
-                    "Failed to decode message from {} as string",
-                    args.chunk_type
+                    "Failed to read the share in {} of {:?}",
+                    chunk.chunk_type(),
+                    file_path
                 )
             })?;
-            println!("{}", data);
-        }
-        None => {
-            eprintln!("Failed to find a chunk of type {}", args.chunk_type)
+            chunk_type = chunk_type.or_else(|| Some(*chunk.chunk_type()));
+            shares.push(share);
         }
+    }
+    let chunk_type = match chunk_type {
+        Some(chunk_type) => chunk_type,
+        None => anyhow::bail!(
+            "Failed to find any {} shares in {:?}",
+            pattern,
+            args.file_path
+        ),
     };
 
+    eprintln!("Found {} shares", shares.len());
+    // Decoded straight from the rebuilt secret, as a chunk would keep a copy which isn't wiped.
+    // The payload wipes its body when dropped, and the text is wiped once printed.
+    let secret = shamir::combine(&shares).context("Failed to rebuild the message")?;
+    let payload = parse_payload(secret.expose())
+        .with_context(|| format!("Failed to read the payload in the {} shares", chunk_type))?;
+    let message = SecretPayload::from(decode_payload(&chunk_type, &payload, args)?.into_bytes());
+    println!("{}", String::from_utf8_lossy(message.expose()));
+
     Ok(())
 }
 
-pub fn execute_remove(args: Remove) -> anyhow::Result<()> {
-    let mut png = Png::from_file(&args.file_path)
-        .with_context(|| format!("Failed to load PNG file {:?}", args.file_path))?;
+// Decode the message from every PNG found, with one record per file.
+// Failures are recorded against the file rather than stopping the whole run.
+fn execute_decode_many(args: &Decode, pattern: &TypePattern) -> anyhow::Result<()> {
+    let files = walk::png_files(&args.file_path, args.recursive, args.archives)
+        .with_context(|| format!("Failed to search {:?} for PNG files", args.file_path))?;
+
+    // Only the matching chunk is copied out of each file, and the file's bytes are read into
+    // the same buffer every time, so scanning many files doesn't allocate for every chunk
+    let mut buffer = PngBuffer::new();
+    let mut records = Records::new(args.format);
+    for file_path in files {
+        // Stopped, the records so far are still finished off
+        if interrupt::is_cancelled() {
+            break;
+        }
+        if args.archives && archive::is_archive(&file_path) {
+            let result = archive::for_each_png(&file_path, |inner_path, bytes| {
+                let read = match bytes {
+                    Ok(bytes) => buffer
+                        .read_from(&bytes[..], CrcCheck::Eager)
+                        .map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                }
+                .map_err(|e| format!("Failed to load PNG file: {}", e));
+                print_record(
+                    &decode_record(inner_path, read, args, pattern),
+                    &mut records,
+                );
+            });
+            // Files found before the archive turned out to be unreadable have been reported
+            if let Err(e) = result {
+                let read = Err(format!("Failed to read archive: {}", e));
+                print_record(&decode_record(file_path, read, args, pattern), &mut records);
+            }
+            continue;
+        }
 
-    png.remove_chunk(&args.chunk_type)?;
+        let read = match ObjectUrl::parse(&file_path) {
+            Some(url) => match cloud::read(&url) {
+                Ok(bytes) => buffer
+                    .read_from(&bytes[..], CrcCheck::Eager)
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(format!("{:#}", e)),
+            },
+            None => buffer.read(&file_path).map_err(|e| e.to_string()),
+        }
+        .map_err(|e| format!("Failed to load PNG file: {}", e));
+        print_record(&decode_record(file_path, read, args, pattern), &mut records);
+    }
+    records.finish();
+    interrupt::check()?;
 
-    if let Some(output_file) = args.output_file {
-        println!("Writing out file to {:?}", output_file);
-        png.write_file(&output_file)
-            .with_context(|| format!("Failed to write file {:?}", output_file))
+    Ok(())
+}
+
+// The record of one file, `read` is the file parsed or why it couldn't be
+fn decode_record(
+    file_path: PathBuf,
+    read: Result<PngView<'_>, String>,
+    args: &Decode,
+    pattern: &TypePattern,
+) -> DecodeRecord {
+    let mut record = DecodeRecord {
+        file_path,
+        chunk_type: pattern.to_string(),
+        found: false,
+        payload_version: None,
+        mime: None,
+        message: None,
+        length: None,
+        crc: None,
+        data: None,
+        sha256: None,
+        error: None,
+    };
+
+    let png = match read {
+        Ok(png) => png,
+        Err(e) => {
+            record.error = Some(e);
+            return record;
+        }
+    };
+    let found = png
+        .chunks()
+        .iter()
+        .find(|chunk| pattern.matches(chunk.chunk_type()));
+    if let Some(chunk) = found {
+        record.found = true;
+        record.chunk_type = chunk.chunk_type().to_string();
+        record.length = Some(chunk.data().len());
+        record.crc = Some(chunk.crc());
+        record.data = Some(STANDARD.encode(chunk.data()));
+        record.sha256 = Some(format!("{:x}", Sha256::digest(chunk.data())));
+        let payload = Payload::try_from(chunk.data()).ok();
+        record.payload_version = payload.as_ref().map(|payload| payload.version());
+        record.mime = payload.and_then(|payload| payload.mime().map(String::from));
+        match decode_chunk(&chunk.to_chunk(), args) {
+            Ok(message) => record.message = Some(message),
+            Err(e) => record.error = Some(format!("{:#}", e)),
+        }
+    }
+
+    record
+}
+
+fn print_record(record: &DecodeRecord, records: &mut Records) {
+    match records.format() {
+        OutputFormat::Human => println!("{}", record.to_human()),
+        _ => records.push(record.to_json()),
+    }
+}
+
+struct DecodeRecord {
+    file_path: PathBuf,
+    // The type of the chunk found, or the type searched for when nothing was found
+    chunk_type: String,
+    found: bool,
+    payload_version: Option<u8>,
+    mime: Option<String>,
+    message: Option<String>,
+    length: Option<usize>,
+    crc: Option<u32>,
+    // The raw chunk data, base64 encoded
+    data: Option<String>,
+    // Hash of the raw chunk data
+    sha256: Option<String>,
+    error: Option<String>,
+}
+
+impl DecodeRecord {
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "path": self.file_path.display().to_string(),
+            "chunk_type": self.chunk_type.clone(),
+            "found": self.found,
+            "payload_version": self.payload_version,
+            "mime": self.mime.clone(),
+            "payload": self.message.clone(),
+            "length": self.length,
+            "crc": self.crc.map(|crc| format!("{:08x}", crc)),
+            "data": self.data.clone(),
+            "payload_sha256": self.sha256.clone(),
+            "error": self.error.clone(),
+        })
+    }
+
+    fn to_human(&self) -> String {
+        let path = self.file_path.display();
+        match (&self.message, &self.error) {
+            (_, Some(error)) => format!("{}: error: {}", path, error),
+            (Some(message), None) => format!("{}: {}", path, message),
+            (None, None) => format!("{}: no {} chunk", path, self.chunk_type),
+        }
+    }
+}
+
+// Read the payload within the chunk and turn it into text for display
+fn decode_chunk(chunk: &Chunk, args: &Decode) -> anyhow::Result<String> {
+    let payload = read_payload(chunk)?;
+    decode_payload(chunk.chunk_type(), &payload, args)
+}
+
+fn decode_payload(
+    chunk_type: &ChunkType,
+    payload: &Payload,
+    args: &Decode,
+) -> anyhow::Result<String> {
+    if args.show_version {
+        eprintln!(
+            "Message in {} is a version {} payload",
+            chunk_type,
+            payload.version()
+        );
+    }
+
+    display_message(
+        chunk_type,
+        payload,
+        args.display_as,
+        args.no_sniff,
+        protobuf_schema(args).as_ref(),
+    )
+}
+
+// Turn the payload of a chunk into text for display, unwrapping or converting it if need be
+fn display_message(
+    chunk_type: &ChunkType,
+    payload: &Payload,
+    display_as: Option<PayloadEncoding>,
+    no_sniff: bool,
+    schema: Option<&ProtobufSchema<'_>>,
+) -> anyhow::Result<String> {
+    // The MIME type recorded at encoding picks how to display the message, unless --as is given
+    let display_as = match payload.mime() {
+        Some(mime) if !payload::mime_is_text(mime) => anyhow::bail!(
+            "Message in {} is binary ({}), decode the file on its own to write it out",
+            chunk_type,
+            mime
+        ),
+        Some(mime) if payload::mime_is_json(mime) => display_as.or(Some(PayloadEncoding::Json)),
+        _ => display_as,
+    };
+    let plain_text = payload.encoding() == PayloadEncoding::Utf8
+        && display_as.unwrap_or(PayloadEncoding::Utf8) == PayloadEncoding::Utf8;
+    if plain_text && !no_sniff {
+        if let Some(sniffed) = sniff::sniff(payload.body()) {
+            eprintln!(
+                "Message in {} was wrapped, unwrapped it with {}",
+                chunk_type,
+                sniffed.steps.join(" then ")
+            );
+            return Ok(sniffed.text);
+        }
+    }
+    if plain_text && std::str::from_utf8(payload.body()).is_err() {
+        if let Some(detected) = charset::detect(payload.body()) {
+            eprintln!(
+                "Message in {} isn't UTF-8, converted it from {}",
+                chunk_type, detected.charset
+            );
+            return Ok(detected.text);
+        }
+    }
+
+    structured::display_payload(payload, display_as, schema)
+        .with_context(|| format!("Failed to decode message from {} as string", chunk_type))
+}
+
+fn read_payload(chunk: &Chunk) -> anyhow::Result<Payload> {
+    parse_payload(chunk.data())
+        .with_context(|| format!("Failed to read the payload in {}", chunk.chunk_type()))
+}
+
+// Releases reading version 2 payloads were the first to combine secret shares
+const SHARES_VERSION: u8 = Payload::HEADER_VERSION;
+
+// Check older releases of pngme which only read payloads up to the version can read the message
+fn check_compat(version: u8, payload: &Payload, split: bool) -> Result<(), CommandError> {
+    let newer = payload.version() > version;
+    let feature = if payload.mime().is_some() && version < Payload::MIME_VERSION {
+        String::from("MIME types")
+    } else if newer && payload.encoding().is_structured() {
+        format!("{} messages", payload.encoding())
+    } else if newer && payload.flags() != 0 {
+        String::from("transformed messages, eg compressed")
+    } else if newer {
+        String::from("messages with a header")
+    } else if split && version < SHARES_VERSION {
+        String::from("secret shares")
     } else {
-        println!("Writing out file to {:?}", args.file_path);
-        png.write_file(&args.file_path)
-            .with_context(|| format!("Failed to write file {:?}", args.file_path))
+        return Ok(());
+    };
+
+    Err(CommandError::IncompatiblePayload { version, feature })
+}
+
+// Read the payload header and undo the steps it records, eg compression
+fn parse_payload(data: &[u8]) -> anyhow::Result<Payload> {
+    let payload = Payload::try_from(data).context("Failed to read payload header")?;
+    Ok(pipeline::decode(payload)?)
+}
+
+fn protobuf_schema(args: &Decode) -> Option<ProtobufSchema<'_>> {
+    match (&args.descriptor, &args.message_type) {
+        (Some(descriptor), Some(message_type)) => Some(ProtobufSchema {
+            descriptor,
+            message_type,
+        }),
+        _ => None,
     }
 }
 
-pub fn execute_identify_text(args: IdentifyText) -> anyhow::Result<()> {
-    let png = Png::from_file(&args.file_path)
-        .with_context(|| format!("Failed to load PNG file {:?}", args.file_path))?;
+// Every message in the file, from any chunk, for when the chunk types holding them aren't known.
+// Standard chunks other than text chunks hold image data rather than messages, so are skipped,
+// as are chunks which can't be decoded.
+pub fn execute_decode_all(args: DecodeAll) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
 
+    let mut found = 0;
+    let mut records = Records::new(args.format);
     for (index, chunk) in png.chunks().iter().enumerate() {
-        match chunk.data_as_string() {
-            Ok(data) => {
-                if data.len() > 0 {
-                    println!("{} - {} - {}", index, chunk.chunk_type(), data);
-                }
+        let chunk_type = chunk.chunk_type();
+        if (args.ancillary && chunk_type.is_critical())
+            || (args.private && chunk_type.is_public())
+            || args
+                .select
+                .as_ref()
+                .is_some_and(|selector| !selector.matches(index, chunk))
+        {
+            continue;
+        }
+
+        let (keyword, message) = if TextKind::from_str(&chunk_type.to_string()).is_ok() {
+            match TextChunk::try_from(chunk) {
+                Ok(text) => (Some(text.keyword), text.text),
+                Err(_) => continue,
             }
-            Err(_) => {}
+        } else if registry::lookup(chunk_type).is_some() {
+            continue;
+        } else {
+            let payload = match parse_payload(chunk.data()) {
+                Ok(payload) => payload,
+                Err(_) => continue,
+            };
+            let message = match payload.mime() {
+                Some(mime) if !payload::mime_is_text(mime) => output::summary(chunk_type, &payload),
+                _ => match display_message(chunk_type, &payload, None, args.no_sniff, None) {
+                    Ok(message) => message,
+                    Err(_) => continue,
+                },
+            };
+            (None, message)
+        };
+
+        found += 1;
+        match args.format {
+            OutputFormat::Human => match &keyword {
+                Some(keyword) => println!("{} {} {}: {}", index, chunk_type, keyword, message),
+                None => println!("{} {}: {}", index, chunk_type, message),
+            },
+            _ => records.push(json!({
+                "index": index,
+                "chunk_type": chunk_type.to_string(),
+                "length": chunk.length(),
+                "crc": format!("{:08x}", chunk.crc()),
+                "data": STANDARD.encode(chunk.data()),
+                "keyword": keyword,
+                "message": message,
+            })),
         }
     }
+    records.finish();
+
+    if found == 0 && args.format == OutputFormat::Human {
+        println!("No messages found in {:?}", args.file_path);
+    }
 
     Ok(())
 }
 
-pub fn execute_print(args: Print) -> anyhow::Result<()> {
-    let png = Png::from_file(&args.file_path)
-        .with_context(|| format!("Failed to load PNG file {:?}", args.file_path))?;
+pub fn execute_remove(args: Remove) -> anyhow::Result<()> {
+    let mut png = load_png(&args.file_path)?;
+    let output_file = args.output_file.or(args.output_option);
 
-    println!("There are {} chunks within this png", png.chunks().len());
+    if let Some(selector) = &args.select {
+        let selected = selector.select(&png);
+        if selected.is_empty() {
+            anyhow::bail!("No chunks in {:?} match the selector", args.file_path);
+        }
+        let mut chunk_types: Vec<ChunkType> = vec![];
+        for index in selected.iter() {
+            let chunk_type = *png.chunks()[*index].chunk_type();
+            if !chunk_types.contains(&chunk_type) {
+                chunk_types.push(chunk_type);
+            }
+        }
+        check_removal(&png, &chunk_types, interactive::force(args.force))?;
 
-    for (index, chunk) in png.chunks().iter().enumerate() {
-        println!("{} - {}", index, chunk);
+        println!("Removed {} chunks", selected.len());
+        let png = Png::from_chunks(without_chunks(&png, &selected))?;
+        return write_output(&png, output_file, args.file_path);
+    }
+
+    let pattern = type_pattern(args.chunk_type, args.type_glob.clone(), args.ignore_case)?;
+    let chunk_types = pattern.matching_types(&png);
+    if chunk_types.is_empty() {
+        return Err(no_matching_chunks(&png, &pattern, args.file_path).into());
+    }
+    check_removal(&png, &chunk_types, interactive::force(args.force))?;
+
+    // An exact chunk type keeps the original behaviour of removing only the first chunk
+    if pattern.is_exact() && !args.all {
+        png.remove_chunk(&chunk_types[0])?;
+    } else {
+        let mut removed = 0;
+        for chunk_type in chunk_types.iter() {
+            removed += remove_all(&mut png, chunk_type)?;
+        }
+        println!("Removed {} chunks", removed);
+    }
+
+    write_output(&png, output_file, args.file_path)
+}
+
+// Refuse to remove chunks the image depends on unless forced, and warn about the rest
+fn check_removal(png: &Png, chunk_types: &[ChunkType], force: bool) -> anyhow::Result<()> {
+    let mut warnings = vec![];
+    for chunk_type in chunk_types.iter() {
+        let (fatal, rest): (Vec<RemovalConflict>, Vec<RemovalConflict>) =
+            constraints::removal_conflicts(png, chunk_type)
+                .into_iter()
+                .partition(|conflict| conflict.is_fatal());
+        if !fatal.is_empty() && !force {
+            return Err(CommandError::RemovalRefused {
+                chunk_type: *chunk_type,
+                conflicts: fatal,
+            }
+            .into());
+        }
+        warnings.extend(fatal);
+        warnings.extend(rest);
+    }
+    for conflict in warnings.iter() {
+        eprintln!("warning: {}", conflict);
+    }
+
+    Ok(())
+}
+
+// Copies of every chunk except those at the given positions
+fn without_chunks(png: &Png, indices: &[usize]) -> Vec<Chunk> {
+    png.chunks()
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !indices.contains(index))
+        .map(|(_, chunk)| chunk.clone())
+        .collect()
+}
+
+pub fn execute_redact(args: Redact) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+    // Overwriting in place only hits the chunk's bytes if the file is exactly the PNG read
+    // from it, not eg a PNG recovered with --assume-png or followed by appended data
+    let on_disk = fs::read(&args.file_path)
+        .with_context(|| format!("Failed to read {:?}", args.file_path))?;
+    if on_disk != png.as_bytes() {
+        anyhow::bail!(
+            "{:?} holds more than the PNG read from it, eg data before the signature or after IEND, \
+             so its chunks can't be overwritten in place",
+            args.file_path
+        );
+    }
+
+    let fill = if args.random {
+        redact::Fill::Random
+    } else {
+        redact::Fill::Zeros
+    };
+    let (redacted, count) = redact::redact(&png, &args.chunk_type, fill)?;
+    if count == 0 {
+        return Err(chunk_not_found(&png, args.chunk_type, args.file_path).into());
+    }
+    // Filling keeps every chunk the same length, so the layout still matches the file
+    if redacted.as_bytes().len() != on_disk.len() {
+        anyhow::bail!(
+            "Redacting {:?} would move its chunks, so it can't be done in place",
+            args.file_path
+        );
+    }
+    overwrite_in_place(&args.file_path, &redacted)?;
+    println!("Overwrote {} chunks in {:?}", count, args.file_path);
+
+    if args.remove {
+        overwrite_in_place(
+            &args.file_path,
+            &redact::remove(&redacted, &args.chunk_type)?,
+        )?;
+        println!("Removed {} chunks", count);
+    }
+
+    Ok(())
+}
+
+// Write over the existing bytes of the file rather than replacing it with a new file,
+// so the old data doesn't stay behind in blocks the filesystem has let go of
+fn overwrite_in_place(file_path: &Path, png: &Png) -> anyhow::Result<()> {
+    let bytes = png.as_bytes();
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(file_path)
+        .with_context(|| format!("Failed to open {:?} for writing", file_path))?;
+    file.write_all(&bytes)
+        .and_then(|_| file.set_len(bytes.len() as u64))
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("Failed to write file {:?}", file_path))
+}
+
+pub fn execute_strip(args: Strip) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+
+    let to_remove: Vec<usize> = png
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| !chunk.chunk_type().is_critical())
+        .filter(|(_, chunk)| {
+            !(args.keep_color_management && registry::is_color_management(chunk.chunk_type()))
+        })
+        .filter(|(index, chunk)| match &args.select {
+            Some(selector) => selector.matches(*index, chunk),
+            None => true,
+        })
+        .map(|(index, _)| index)
+        .collect();
+    println!("Removed {} chunks", to_remove.len());
+
+    let png = Png::from_chunks(without_chunks(&png, &to_remove))?;
+    write_output(&png, args.output_file, args.file_path)
+}
+
+pub fn execute_dedupe(args: Dedupe) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+
+    let duplicates = dedupe::find_duplicates(&png);
+    if duplicates.is_empty() {
+        println!("No duplicate chunks found");
+        return Ok(());
+    }
+    for duplicate in duplicates.iter() {
+        println!(
+            "{} - {} - same as chunk {}, {} bytes",
+            duplicate.index,
+            png.chunks()[duplicate.index].chunk_type(),
+            duplicate.kept,
+            duplicate.size(&png)
+        );
+    }
+    let saved: usize = duplicates.iter().map(|d| d.size(&png)).sum();
+    let verb = if args.dry_run {
+        "Would remove"
+    } else {
+        "Removed"
+    };
+    println!(
+        "{} {} duplicate chunks, saving {} bytes",
+        verb,
+        duplicates.len(),
+        saved
+    );
+    if args.dry_run {
+        return Ok(());
+    }
+
+    let indices: Vec<usize> = duplicates.iter().map(|d| d.index).collect();
+    let png = Png::from_chunks(without_chunks(&png, &indices))?;
+    write_output(&png, args.output_file, args.file_path)
+}
+
+pub fn execute_background(args: Background) -> anyhow::Result<()> {
+    match args {
+        Background::Set(args) => execute_set_background(args),
+        Background::Clear(args) => execute_clear_chunk(args, "bKGD"),
+    }
+}
+
+pub fn execute_transparency(args: Transparency) -> anyhow::Result<()> {
+    match args {
+        Transparency::Clear(args) => execute_clear_chunk(args, "tRNS"),
+    }
+}
+
+fn execute_set_background(args: SetBackground) -> anyhow::Result<()> {
+    let mut png = load_png(&args.file_path)?;
+
+    let header = standard::ImageHeader::from_png(&png)
+        .with_context(|| format!("Failed to read the image header of {:?}", args.file_path))?;
+    let background = standard::Background::for_color(args.color, &header, standard::palette(&png))
+        .with_context(|| format!("Can't use {} as the background", args.color))?;
+
+    let chunk = background.to_chunk();
+    remove_all(&mut png, chunk.chunk_type())?;
+    png.insert_before_image_data(chunk);
+    println!("{}", background);
+
+    write_output(&png, args.output_file, args.file_path)
+}
+
+fn execute_clear_chunk(args: ClearChunk, chunk_type: &str) -> anyhow::Result<()> {
+    let mut png = load_png(&args.file_path)?;
+
+    let chunk_type = ChunkType::from_str(chunk_type)?;
+    if remove_all(&mut png, &chunk_type)? == 0 {
+        println!("No {} chunk found, nothing to clear", chunk_type);
+        return Ok(());
+    }
+
+    write_output(&png, args.output_file, args.file_path)
+}
+
+pub fn execute_apng(args: Apng) -> anyhow::Result<()> {
+    match args {
+        Apng::Info(args) => execute_apng_info(args),
+        Apng::ExtractFrame(args) => execute_extract_frame(args),
+        Apng::SetPlays(args) => execute_set_plays(args),
+    }
+}
+
+fn execute_apng_info(args: ApngInfo) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+    let animation = Animation::from_png(&png)
+        .with_context(|| format!("Failed to read the animation in {:?}", args.file_path))?;
+
+    if animation.control.num_frames as usize == animation.frames.len() {
+        println!("Frames: {}", animation.frames.len());
+    } else {
+        println!(
+            "Frames: {}, but acTL says there are {}",
+            animation.frames.len(),
+            animation.control.num_frames
+        );
+    }
+    match animation.control.num_plays {
+        0 => println!("Plays: forever"),
+        plays => println!("Plays: {}", plays),
+    }
+    println!("Duration: {}s", animation.total_seconds());
+
+    for (index, frame) in animation.frames.iter().enumerate() {
+        println!(
+            "{} - {}{}",
+            index,
+            frame.control,
+            if frame.is_default_image {
+                ", default image"
+            } else {
+                ""
+            }
+        );
+    }
+
+    Ok(())
+}
+
+fn execute_extract_frame(args: ExtractFrame) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+    let frame = apng::extract_frame(&png, args.frame).with_context(|| {
+        format!(
+            "Failed to extract frame {} from {:?}",
+            args.frame, args.file_path
+        )
+    })?;
+
+    println!("Writing out file to {:?}", args.output_file);
+    frame
+        .write_file(&args.output_file)
+        .with_context(|| format!("Failed to write file {:?}", args.output_file))
+}
+
+fn execute_set_plays(args: SetPlays) -> anyhow::Result<()> {
+    let mut png = load_png(&args.file_path)?;
+    set_plays(&mut png, args.plays)
+        .with_context(|| format!("Failed to read the animation in {:?}", args.file_path))?;
+
+    write_output(&png, args.output_file, args.file_path)
+}
+
+// Rewrites acTL where it is, as it has to stay ahead of the default image's fcTL
+fn set_plays(png: &mut Png, plays: u32) -> anyhow::Result<()> {
+    let mut control = Animation::from_png(png)?.control;
+    control.num_plays = plays;
+    png.replace_chunk(control.to_chunk())?;
+    Ok(())
+}
+
+pub fn execute_history(args: History) -> anyhow::Result<()> {
+    match args {
+        History::Show(args) => execute_show_history(args),
+        History::Restore(args) => execute_restore_history(args),
+    }
+}
+
+fn execute_show_history(args: ShowHistory) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+
+    let chunk = match png.chunk_by_type(&args.chunk_type) {
+        Some(chunk) => chunk,
+        None => return Err(chunk_not_found(&png, args.chunk_type, args.file_path).into()),
+    };
+    println!("Current: {}", display_data(chunk.data())?);
+
+    let versions = history::versions(&png, &args.chunk_type);
+    if versions.is_empty() {
+        println!("No earlier versions of {} are kept", args.chunk_type);
+    }
+    for (_, version) in versions {
+        println!(
+            "Version {}: {}",
+            version.version,
+            display_data(&version.data)?
+        );
     }
 
     Ok(())
 }
+
+fn execute_restore_history(args: RestoreHistory) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+
+    let png = history::restore(&png, &args.chunk_type, args.version).with_context(|| {
+        format!(
+            "Failed to restore version {} of {}",
+            args.version, args.chunk_type
+        )
+    })?;
+    println!("Restored version {} of {}", args.version, args.chunk_type);
+
+    write_output(&png, args.output_file, args.file_path)
+}
+
+// The message held in chunk data, as decode shows it by default
+fn display_data(data: &[u8]) -> anyhow::Result<String> {
+    structured::display_payload(&parse_payload(data)?, None, None)
+}
+
+pub fn execute_text(args: Text) -> anyhow::Result<()> {
+    match args {
+        Text::List(args) => execute_list_text(args),
+        Text::Get(args) => execute_get_text(args),
+        Text::Set(args) => execute_set_text(args),
+        Text::Remove(args) => execute_remove_text(args),
+        Text::Convert(args) => execute_convert_text(args),
+    }
+}
+
+fn execute_list_text(args: ListText) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+
+    let mut out = Output::paged();
+    for (index, text) in text::text_chunks(&png) {
+        match text {
+            Ok(text) => writeln!(
+                out,
+                "{} - {} - {} - {}",
+                index, text.kind, text.keyword, text.text
+            )?,
+            Err(e) => writeln!(out, "{} - invalid, {}", index, e)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn execute_get_text(args: GetText) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+
+    let found = text::find_keyword(&png, &args.keyword);
+    if found.is_empty() {
+        return Err(keyword_not_found(&png, args.keyword, args.file_path).into());
+    }
+    for (_, text) in found {
+        println!("{}", text.text);
+    }
+
+    Ok(())
+}
+
+fn execute_set_text(args: SetText) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+    text::validate_keyword(&args.keyword)?;
+
+    let existing = text::find_keyword(&png, &args.keyword);
+    let kind = args
+        .kind
+        .or_else(|| existing.first().map(|(_, text)| text.kind))
+        .unwrap_or(TextKind::Text);
+    let text = TextChunk::new(kind, &args.keyword, &args.text);
+    let (png, replaced) = text::upsert(&png, &text)
+        .with_context(|| format!("Failed to store the text for {:?}", args.keyword))?;
+    if replaced {
+        println!("Replaced the text for {:?}", args.keyword);
+    } else {
+        println!("Added the text for {:?}", args.keyword);
+    }
+
+    write_output(&png, args.output_file, args.file_path)
+}
+
+fn execute_remove_text(args: RemoveText) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+
+    let found: Vec<usize> = text::find_keyword(&png, &args.keyword)
+        .iter()
+        .map(|(index, _)| *index)
+        .collect();
+    if found.is_empty() {
+        return Err(keyword_not_found(&png, args.keyword, args.file_path).into());
+    }
+
+    println!(
+        "Removed {} chunks with the keyword {:?}",
+        found.len(),
+        args.keyword
+    );
+
+    let png = Png::from_chunks(without_chunks(&png, &found))?;
+    write_output(&png, args.output_file, args.file_path)
+}
+
+fn execute_convert_text(args: ConvertText) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+
+    let from = args.from.chunk_type();
+    let mut converted = 0;
+    let mut chunks = vec![];
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        if *chunk.chunk_type() != from {
+            chunks.push(chunk.clone());
+            continue;
+        }
+
+        let text = TextChunk::try_from(chunk)
+            .with_context(|| format!("Failed to read the {} chunk at index {}", from, index))?;
+        if args.keyword.iter().any(|k| *k != text.keyword) {
+            chunks.push(chunk.clone());
+            continue;
+        }
+        let chunk = text
+            .convert(args.to, args.compress)
+            .to_chunk()
+            .with_context(|| {
+                format!(
+                    "Failed to convert the {} keyword to {}",
+                    text.keyword, args.to
+                )
+            })?;
+        chunks.push(chunk);
+        converted += 1;
+    }
+
+    if converted == 0 {
+        println!("No {} chunks found, nothing to convert", from);
+        return Ok(());
+    }
+    println!(
+        "Converted {} chunks from {} to {}",
+        converted, from, args.to
+    );
+
+    let png = Png::from_chunks(chunks)?;
+    write_output(&png, args.output_file, args.file_path)
+}
+
+// Remove every chunk of the type, returning how many were removed
+fn remove_all(png: &mut Png, chunk_type: &ChunkType) -> anyhow::Result<usize> {
+    Ok(png.remove_chunks_by_type(chunk_type)?.len())
+}
+
+// Write the PNG to the output file if given, otherwise overwrite the input
+fn write_output(png: &Png, output_file: Option<PathBuf>, file_path: PathBuf) -> anyhow::Result<()> {
+    let output_file = output_file.unwrap_or(file_path);
+    println!("Writing out file to {:?}", output_file);
+    png.write_file(&output_file)
+        .with_context(|| format!("Failed to write file {:?}", output_file))
+}
+
+pub fn execute_identify_text(args: IdentifyText) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+
+    if args.format != OutputFormat::Human {
+        let mut records = Records::new(args.format);
+        for (index, chunk) in png.chunks().iter().enumerate() {
+            let (text, charset) = match chunk.data_as_string() {
+                Ok(data) if data.is_empty() => continue,
+                Ok(data) => (data, None),
+                Err(_) => match charset::detect(chunk.data()) {
+                    Some(detected) => (detected.text, Some(detected.charset.to_string())),
+                    None => continue,
+                },
+            };
+            records.push(json!({
+                "index": index,
+                "type": chunk.chunk_type().to_string(),
+                "length": chunk.length(),
+                "crc": format!("{:08x}", chunk.crc()),
+                "data": STANDARD.encode(chunk.data()),
+                "text": text,
+                "converted_from": charset,
+            }));
+        }
+        records.finish();
+        return Ok(());
+    }
+
+    let mut out = Output::paged();
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        match chunk.data_as_string() {
+            Ok(data) => {
+                if !data.is_empty() {
+                    writeln!(out, "{} - {} - {}", index, chunk.chunk_type(), data)?;
+                }
+            }
+            Err(_) => {
+                if let Some(detected) = charset::detect(chunk.data()) {
+                    writeln!(
+                        out,
+                        "{} - {} - {} (converted from {})",
+                        index,
+                        chunk.chunk_type(),
+                        detected.text,
+                        detected.charset
+                    )?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn execute_print(args: Print) -> anyhow::Result<()> {
+    if args.headers_only {
+        return print_headers(&args.file_path, args.format);
+    }
+
+    let png = load_png(&args.file_path)?;
+
+    if args.digest {
+        let digest = format!("{:x}", Sha256::digest(png.canonical_bytes()));
+        if args.format == OutputFormat::Human {
+            println!("{}", digest);
+        } else {
+            let mut records = Records::new(args.format);
+            records.push(json!({ "digest": digest }));
+            records.finish();
+        }
+        return Ok(());
+    }
+
+    if args.format != OutputFormat::Human {
+        let mut records = Records::new(args.format);
+        for (index, chunk) in png.chunks().iter().enumerate() {
+            let (description, invalid) = match describe_chunk(&png, chunk) {
+                Some(Ok(description)) => (Some(description), None),
+                Some(Err(e)) => (None, Some(e)),
+                None => (None, None),
+            };
+            records.push(json!({
+                "index": index,
+                "type": chunk.chunk_type().to_string(),
+                "length": chunk.length(),
+                "crc": format!("{:08x}", chunk.crc()),
+                "data": STANDARD.encode(chunk.data()),
+                "contents": description,
+                "invalid": invalid,
+            }));
+        }
+        records.finish();
+        return Ok(());
+    }
+
+    let mut out = Output::paged();
+    writeln!(
+        out,
+        "There are {} chunks within this png",
+        png.chunks().len()
+    )?;
+
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        write!(out, "{} - {}", index, chunk)?;
+        match describe_chunk(&png, chunk) {
+            Some(Ok(description)) => writeln!(out, "    Contents: {}", description)?,
+            Some(Err(e)) => writeln!(out, "    Contents: invalid, {}", e)?,
+            None => {}
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+// What a standard or known extension chunk holds, or why it is invalid
+fn describe_chunk(png: &Png, chunk: &Chunk) -> Option<Result<String, String>> {
+    standard::describe(png, chunk)
+        .map(|d| d.map_err(|e| e.to_string()))
+        .or_else(|| extensions::describe(chunk).map(|d| d.map_err(|e| e.to_string())))
+}
+
+// A row for every chunk of the file. CRCs are only checked to report whether they are right,
+// so a file with a damaged chunk can still be inspected.
+pub fn execute_list(args: List) -> anyhow::Result<()> {
+    let bytes = fs::read(&args.file_path)
+        .with_context(|| format!("Failed to read {:?}", args.file_path))?;
+    let png = PngView::parse_with(&bytes, CrcCheck::Lazy)
+        .with_context(|| format!("Failed to load PNG file {:?}", args.file_path))?;
+
+    let mut records = Records::new(args.format);
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        let chunk_type = chunk.chunk_type();
+        let mut record = json!({
+            "index": index,
+            "type": chunk_type.to_string(),
+            "offset": chunk.offset(),
+            "length": chunk.data().len(),
+            "crc": format!("{:08x}", chunk.crc()),
+            "crc_valid": chunk.verify().is_ok(),
+            "critical": chunk_type.is_critical(),
+            "public": chunk_type.is_public(),
+            "safe_to_copy": chunk_type.is_safe_to_copy(),
+        });
+        // The data would swamp the table, so only the other formats have it
+        if args.format != OutputFormat::Human {
+            record["data"] = json!(STANDARD.encode(chunk.data()));
+        }
+        records.push(record);
+    }
+    records.finish();
+
+    Ok(())
+}
+
+// A record for every chunk of every PNG found, for pivoting in a spreadsheet. CRCs are only
+// checked to report whether they are right, a bad one doesn't stop the file being listed.
+pub fn execute_inventory(args: Inventory) -> anyhow::Result<()> {
+    let files = walk::png_files(&args.file_path, args.recursive, args.archives)
+        .with_context(|| format!("Failed to search {:?} for PNG files", args.file_path))?;
+
+    let mut records = Records::new(args.format);
+    let mut buffer = PngBuffer::new();
+    for file_path in files {
+        if interrupt::is_cancelled() {
+            break;
+        }
+        if args.archives && archive::is_archive(&file_path) {
+            let result = archive::for_each_png(&file_path, |inner_path, bytes| {
+                let read = match bytes {
+                    Ok(bytes) => buffer
+                        .read_from(&bytes[..], CrcCheck::Lazy)
+                        .map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                }
+                .map_err(|e| format!("Failed to load PNG file: {}", e));
+                push_inventory(&inner_path, read, &mut records);
+            });
+            if let Err(e) = result {
+                let read = Err(format!("Failed to read archive: {}", e));
+                push_inventory(&file_path, read, &mut records);
+            }
+            continue;
+        }
+
+        let read = buffer
+            .read_with(&file_path, CrcCheck::Lazy)
+            .map_err(|e| format!("Failed to load PNG file: {}", e));
+        push_inventory(&file_path, read, &mut records);
+    }
+    records.finish();
+    interrupt::check()?;
+
+    Ok(())
+}
+
+// Chunks with a wrong CRC are still drawn, they're what the drawing is for
+pub fn execute_layout(args: Layout) -> anyhow::Result<()> {
+    let mut buffer = PngBuffer::new();
+    let png = buffer
+        .read_with(&args.file_path, CrcCheck::Lazy)
+        .with_context(|| format!("Failed to load PNG file {:?}", args.file_path))?;
+    print!("{}", layout::render(&png, args.format));
+
+    Ok(())
+}
+
+pub fn execute_size_diff(args: SizeDiff) -> anyhow::Result<()> {
+    let (old, new) = (load_png(&args.file_path)?, load_png(&args.other_file_path)?);
+    let (old_size, new_size) = (old.as_bytes().len() as i64, new.as_bytes().len() as i64);
+    let changes: Vec<_> = size_diff::size_diff(&old, &new)
+        .into_iter()
+        .filter(|change| args.all || change.is_changed())
+        .collect();
+    // Whether the image itself changed size, or only its metadata
+    let delta = |critical: bool| -> i64 {
+        changes
+            .iter()
+            .filter(|change| change.chunk_type.is_critical() == critical)
+            .map(|change| change.delta())
+            .sum()
+    };
+
+    if args.format == OutputFormat::Human {
+        println!(
+            "{:?} is {} bytes and {:?} is {} bytes, a difference of {:+}",
+            args.file_path,
+            old_size,
+            args.other_file_path,
+            new_size,
+            new_size - old_size
+        );
+        for change in changes.iter() {
+            println!("  {}", change);
+        }
+        if !changes.is_empty() {
+            println!(
+                "Critical chunks changed by {:+} bytes and ancillary chunks by {:+}",
+                delta(true),
+                delta(false)
+            );
+        }
+        return Ok(());
+    }
+
+    let json = json!({
+        "old": { "path": args.file_path.display().to_string(), "size": old_size },
+        "new": { "path": args.other_file_path.display().to_string(), "size": new_size },
+        "delta": new_size - old_size,
+        "critical_delta": delta(true),
+        "ancillary_delta": delta(false),
+        "chunks": changes
+            .iter()
+            .map(|change| json!({
+                "chunk_type": change.chunk_type.to_string(),
+                "critical": change.chunk_type.is_critical(),
+                "old_count": change.old.count,
+                "old_bytes": change.old.bytes,
+                "new_count": change.new.count,
+                "new_bytes": change.new.bytes,
+                "delta": change.delta(),
+            }))
+            .collect::<Vec<_>>(),
+    });
+    println!("{}", report::document(&json, "/chunks", args.format));
+
+    Ok(())
+}
+
+pub fn execute_hook(args: Hook) -> anyhow::Result<()> {
+    hook::enter_repository()?;
+    match args {
+        Hook::Install(args) => {
+            let hook = hook::install(interactive::force(args.force))?;
+            println!("Installed the pre-commit hook {:?}", hook);
+            if !Path::new(hook::POLICY_FILE).exists() {
+                println!(
+                    "Add a {} to the top of the repository for it to check against",
+                    hook::POLICY_FILE
+                );
+            }
+            Ok(())
+        }
+        Hook::Run => hook_run(),
+    }
+}
+
+// Check every staged PNG against the staged policy, reading both from git's index
+fn hook_run() -> anyhow::Result<()> {
+    let policy = match hook::staged_policy()? {
+        Some(policy) => policy,
+        // Nothing to enforce, a repository without a policy commits as normal
+        None => return Ok(()),
+    };
+
+    let mut failed = 0;
+    for path in hook::staged_pngs()? {
+        let bytes = hook::staged_blob(&path)?;
+        let problems: Vec<String> = match Png::try_from(&bytes[..]) {
+            Ok(png) => policy::check(&png, &policy)
+                .iter()
+                .map(|violation| violation.to_string())
+                .collect(),
+            Err(e) => vec![format!("It isn't a valid PNG file: {}", e)],
+        };
+        if !problems.is_empty() {
+            failed += 1;
+            eprintln!("{}", path);
+            for problem in problems {
+                eprintln!("  {}", problem);
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(CommandError::PolicyViolated {
+            files: failed,
+            policy_file: String::from(hook::POLICY_FILE),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+pub fn execute_stamp_source(args: StampSource) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+    let content = fs::read(&args.source)
+        .with_context(|| format!("Failed to read the source {:?}", args.source))?;
+
+    let source = Source::new(&content, &args.source.display().to_string());
+    println!(
+        "Stamping the source {:?}, SHA-256 {}",
+        args.source,
+        source.sha256_hex()
+    );
+    let png = provenance::stamp(&png, &source)?;
+    write_output(&png, args.output_file, args.file_path)
+}
+
+pub fn execute_trace(args: Trace) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+    let source = match provenance::source(&png) {
+        Some(source) => source?,
+        None => anyhow::bail!(
+            "{:?} wasn't stamped with its source, use `pngme stamp-source` when exporting it",
+            args.file_path
+        ),
+    };
+    println!("Source: {}", source.path);
+    println!("SHA-256: {}", source.sha256_hex());
+    println!("Size: {} bytes", source.size);
+
+    let directory = match args.search {
+        Some(directory) => directory,
+        None => return Ok(()),
+    };
+    let files =
+        walk::all_files(&directory).with_context(|| format!("Failed to search {:?}", directory))?;
+    let mut found = 0;
+    for file_path in files {
+        interrupt::check()?;
+        if let Some(how) = source_match(&source, &file_path) {
+            println!("{:?} {}", file_path, how);
+            found += 1;
+        }
+    }
+    if found == 0 {
+        anyhow::bail!("Failed to find the source in {:?}", directory);
+    }
+
+    Ok(())
+}
+
+// How the file is the source, if it is. Repositories using Git LFS or git-annex may only
+// have a pointer to the source checked out, which names it by the same hash.
+fn source_match(source: &Source, file_path: &Path) -> Option<&'static str> {
+    if let Ok(target) = fs::read_link(file_path) {
+        if source.matches_annex_key(&target.to_string_lossy()) {
+            return Some("is a git-annex link to the source");
+        }
+    }
+
+    // Pointer files are well under a kilobyte, and the source can only be its own size
+    let size = fs::metadata(file_path).ok()?.len();
+    if size >= 1024 && size != source.size {
+        return None;
+    }
+    let content = fs::read(file_path).ok()?;
+    if source.matches(&content) {
+        Some("is the source")
+    } else if source.matches_lfs_pointer(&content) {
+        Some("is a Git LFS pointer to the source")
+    } else {
+        None
+    }
+}
+
+pub fn execute_index(args: Index) -> anyhow::Result<()> {
+    match args {
+        Index::Build(args) => index_build(args),
+        Index::Query(args) => index_query(args),
+    }
+}
+
+fn index_build(args: IndexBuild) -> anyhow::Result<()> {
+    let files = walk::png_files(&args.directory, args.recursive, false)
+        .with_context(|| format!("Failed to search {:?} for PNG files", args.directory))?;
+    let summary = index::build(&args.db, &files)?;
+
+    println!(
+        "Indexed {} files in {:?}, {} unchanged and {} removed",
+        summary.indexed, args.db, summary.unchanged, summary.removed
+    );
+    Ok(())
+}
+
+fn index_query(args: IndexQuery) -> anyhow::Result<()> {
+    let filters = Filters {
+        #[cfg(feature = "index")]
+        chunk_type: args.chunk_type,
+        #[cfg(feature = "index")]
+        path: args.path,
+        #[cfg(feature = "index")]
+        sha256: args.sha256,
+        #[cfg(feature = "index")]
+        files_only: args.files_only,
+    };
+
+    let mut records = Records::new(args.format);
+    for record in index::query(&args.db, &filters)? {
+        records.push(record);
+    }
+    records.finish();
+
+    Ok(())
+}
+
+// Every record has the same fields, so a file which couldn't be read is a row with only
+// the path and the error
+fn push_inventory(file_path: &Path, read: Result<PngView<'_>, String>, records: &mut Records) {
+    let record = |chunk: Option<(usize, &ChunkView)>, error: Option<String>| {
+        let chunk_type = chunk.map(|(_, chunk)| *chunk.chunk_type());
+        json!({
+            "path": file_path.display().to_string(),
+            "index": chunk.map(|(index, _)| index),
+            "chunk_type": chunk_type.map(|chunk_type| chunk_type.to_string()),
+            "offset": chunk.map(|(_, chunk)| chunk.offset()),
+            "data_offset": chunk.map(|(_, chunk)| chunk.offset() + 8),
+            "length": chunk.map(|(_, chunk)| chunk.data().len()),
+            "crc": chunk.map(|(_, chunk)| format!("{:08x}", chunk.crc())),
+            "crc_valid": chunk.map(|(_, chunk)| chunk.verify().is_ok()),
+            "critical": chunk_type.map(|chunk_type| chunk_type.is_critical()),
+            "public": chunk_type.map(|chunk_type| chunk_type.is_public()),
+            "reserved_valid": chunk_type.map(|chunk_type| chunk_type.is_reserved_bit_valid()),
+            "safe_to_copy": chunk_type.map(|chunk_type| chunk_type.is_safe_to_copy()),
+            "error": error,
+        })
+    };
+
+    match read {
+        Ok(png) => {
+            for (index, chunk) in png.chunks().iter().enumerate() {
+                records.push(record(Some((index, chunk)), None));
+            }
+        }
+        Err(e) => records.push(record(None, Some(e))),
+    }
+}
+
+// List the chunks without reading their data, seeking over it instead
+fn print_headers(file_path: &Path, format: OutputFormat) -> anyhow::Result<()> {
+    let headers = File::open(file_path)
+        .map_err(PngError::from)
+        .and_then(|mut file| Png::scan_headers(&mut file))
+        .with_context(|| format!("Failed to load PNG file {:?}", file_path))?;
+
+    if format != OutputFormat::Human {
+        let mut records = Records::new(format);
+        for (index, header) in headers.iter().enumerate() {
+            records.push(json!({
+                "index": index,
+                "type": header.chunk_type.to_string(),
+                "length": header.length,
+                "offset": header.offset,
+            }));
+        }
+        records.finish();
+        return Ok(());
+    }
+
+    let mut out = Output::paged();
+    writeln!(out, "There are {} chunks within this png", headers.len())?;
+    for (index, header) in headers.iter().enumerate() {
+        writeln!(
+            out,
+            "{} - {} {} bytes at byte {}",
+            index, header.chunk_type, header.length, header.offset
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn execute_verify(args: Verify) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+
+    let problems = standard::check(&png);
+    if problems.is_empty() {
+        println!("No problems found in {:?}", args.file_path);
+        return Ok(());
+    }
+
+    for problem in problems.iter() {
+        println!("{}", problem);
+    }
+    anyhow::bail!("Found {} problems in {:?}", problems.len(), args.file_path)
+}
+
+fn print_diagnosis(when: &str, size: usize, diagnosis: &Diagnosis) {
+    println!(
+        "{}: {} bytes, {} problems, {} can be fixed",
+        when,
+        size,
+        diagnosis.problems.len(),
+        diagnosis.fixable()
+    );
+    for problem in diagnosis.problems.iter() {
+        let label = if problem.is_fixable() {
+            "fixable"
+        } else {
+            "manual"
+        };
+        println!("  {:<7}  {}", label, problem);
+    }
+}
+
+pub fn execute_doctor(args: Doctor) -> anyhow::Result<()> {
+    let bytes = fs::read(&args.file_path)
+        .with_context(|| format!("Failed to read file {:?}", args.file_path))?;
+    let diagnosis = doctor::diagnose(&bytes)
+        .with_context(|| format!("Failed to read the chunks of {:?}", args.file_path))?;
+    if args.format != OutputFormat::Human {
+        return report_doctor(args, &diagnosis);
+    }
+
+    print_diagnosis("Before", bytes.len(), &diagnosis);
+    if diagnosis.is_healthy() {
+        println!("No problems found in {:?}", args.file_path);
+        return Ok(());
+    }
+    if !args.fix {
+        if diagnosis.fixable() > 0 {
+            println!(
+                "Run again with --fix to fix {} problems",
+                diagnosis.fixable()
+            );
+        }
+        anyhow::bail!(
+            "Found {} problems in {:?}",
+            diagnosis.problems.len(),
+            args.file_path
+        )
+    }
+
+    let fixed = diagnosis.fix()?;
+    let fixed_bytes = fixed.as_bytes();
+    let after = doctor::diagnose(&fixed_bytes)?;
+    print_diagnosis("After", fixed_bytes.len(), &after);
+    write_output(&fixed, args.output_file, args.file_path)
+}
+
+// The diagnosis in a format other than human. With --fix the problems before and after fixing
+// are separate documents, told apart by their stage, and the fixed file is reported on stderr
+// so stdout only has the documents.
+fn report_doctor(args: Doctor, diagnosis: &Diagnosis) -> anyhow::Result<()> {
+    let mut before = diagnosis.diagnostics().json();
+    if !args.fix {
+        println!("{}", report::document(&before, "/diagnostics", args.format));
+        return Ok(());
+    }
+
+    before["stage"] = json!("before");
+    println!("{}", report::document(&before, "/diagnostics", args.format));
+    if diagnosis.is_healthy() {
+        return Ok(());
+    }
+
+    let fixed = diagnosis.fix()?;
+    let mut after = doctor::diagnose(&fixed.as_bytes())?.diagnostics().json();
+    after["stage"] = json!("after");
+    println!("{}", report::document(&after, "/diagnostics", args.format));
+
+    let output_file = args.output_file.unwrap_or(args.file_path);
+    eprintln!("Writing out file to {:?}", output_file);
+    fixed
+        .write_file(&output_file)
+        .with_context(|| format!("Failed to write file {:?}", output_file))
+}
+
+pub fn execute_lint(args: Lint) -> anyhow::Result<()> {
+    let png = load_png(&args.file_path)?;
+
+    let findings = lint::lint(&png);
+    if findings.is_empty() {
+        println!("No problems found in {:?}", args.file_path);
+        return Ok(());
+    }
+
+    for finding in findings.iter() {
+        for problem in finding.lints.iter() {
+            println!("{} - {}", finding.index, problem);
+        }
+        println!(
+            "{} - rename {} to {}",
+            finding.index,
+            png.chunks()[finding.index].chunk_type(),
+            finding.fixed_type
+        );
+    }
+    if !args.fix {
+        anyhow::bail!(
+            "Found problems with {} chunks in {:?}, fix them with --fix",
+            findings.len(),
+            args.file_path
+        )
+    }
+
+    let png = lint::fix(&png, &findings)?;
+    println!("Renamed {} chunks", findings.len());
+    write_output(&png, args.output_file, args.file_path)
+}
+
+pub fn execute_interop_check(args: InteropCheck) -> anyhow::Result<()> {
+    let checks = interop::check(&args.file_path)
+        .with_context(|| format!("Failed to run the tools against {:?}", args.file_path))?;
+
+    match args.format {
+        OutputFormat::Human => {
+            for check in checks.iter() {
+                println!("{:<9} {}", check.tool, check.verdict);
+            }
+        }
+        format => {
+            let json = interop::json(&args.file_path, &checks);
+            println!("{}", report::document(&json, "/tools", format))
+        }
+    }
+
+    let failed: Vec<&str> = checks
+        .iter()
+        .filter(|check| matches!(check.verdict, Verdict::Failed(_)))
+        .map(|check| check.tool)
+        .collect();
+    if checks.iter().all(|c| c.verdict == Verdict::NotInstalled) {
+        anyhow::bail!("None of pngcheck, exiftool or ImageMagick's identify are installed")
+    }
+    if !failed.is_empty() {
+        anyhow::bail!("{} rejected {:?}", failed.join(", "), args.file_path)
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "image-check")]
+pub fn execute_assert_pixels_equal(args: AssertPixelsEqual) -> anyhow::Result<()> {
+    use lib_pngme::pixels;
+
+    let read =
+        |path: &PathBuf| fs::read(path).with_context(|| format!("Failed to read {:?}", path));
+    pixels::assert_pixels_equal(&read(&args.file_path)?, &read(&args.other_file_path)?)
+        .with_context(|| {
+            format!(
+                "{:?} and {:?} don't show the same pixels",
+                args.file_path, args.other_file_path
+            )
+        })?;
+    println!(
+        "{:?} and {:?} show the same pixels",
+        args.file_path, args.other_file_path
+    );
+
+    Ok(())
+}
+
+#[cfg(not(feature = "image-check"))]
+pub fn execute_assert_pixels_equal(_args: AssertPixelsEqual) -> anyhow::Result<()> {
+    anyhow::bail!("pngme was built without an image decoder, rebuild with `--features image-check`")
+}
+
+#[cfg(feature = "xcheck")]
+pub fn execute_xcheck(args: Xcheck) -> anyhow::Result<()> {
+    use lib_pngme::xcheck;
+
+    let bytes = fs::read(&args.file_path)
+        .with_context(|| format!("Failed to read {:?}", args.file_path))?;
+    let disagreements = xcheck::check(&bytes);
+
+    match args.format {
+        OutputFormat::Human if disagreements.is_empty() => {
+            println!("pngme and the png crate read the file the same way")
+        }
+        OutputFormat::Human => {
+            for disagreement in disagreements.iter() {
+                println!("{}", disagreement);
+            }
+        }
+        format => {
+            let json = json!({
+                "path": args.file_path.display().to_string(),
+                "agree": disagreements.is_empty(),
+                "disagreements": disagreements.iter().map(|d| d.json()).collect::<Vec<_>>(),
+            });
+            println!("{}", report::document(&json, "/disagreements", format))
+        }
+    }
+
+    if !disagreements.is_empty() {
+        anyhow::bail!(
+            "pngme and the png crate disagree about {:?} in {} ways",
+            args.file_path,
+            disagreements.len()
+        )
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "xcheck"))]
+pub fn execute_xcheck(_args: Xcheck) -> anyhow::Result<()> {
+    anyhow::bail!(
+        "pngme was built without the png crate to compare with, rebuild with `--features xcheck`"
+    )
+}
+
+pub fn execute_explain(args: Explain) -> anyhow::Result<()> {
+    println!("{}", explain::explain(&args.chunk_type));
+
+    Ok(())
+}
+
+pub fn execute_format_spec(args: FormatSpec) -> anyhow::Result<()> {
+    match args.format {
+        OutputFormat::Human => println!("{}", spec::text()),
+        format => println!(
+            "{}",
+            report::document(&spec::json(), "/payload/header", format)
+        ),
+    }
+
+    Ok(())
+}
+
+pub fn execute_capabilities(args: Capabilities) -> anyhow::Result<()> {
+    match args.format {
+        OutputFormat::Human => println!("{}", capabilities::text()),
+        format => println!(
+            "{}",
+            report::document(&capabilities::json(), "/features", format)
+        ),
+    }
+
+    Ok(())
+}
+
+pub fn execute_gen_fixtures(args: GenFixtures) -> anyhow::Result<()> {
+    fs::create_dir_all(&args.directory)
+        .with_context(|| format!("Failed to create the directory {:?}", args.directory))?;
+
+    let fixtures = fixtures::generate();
+    for fixture in fixtures.iter() {
+        let path = args.directory.join(fixture.file_name());
+        fs::write(&path, &fixture.bytes)
+            .with_context(|| format!("Failed to write fixture {:?}", path))?;
+    }
+    let path = args.directory.join("manifest.json");
+    let manifest = serde_json::to_string_pretty(&fixtures::manifest(&fixtures))?;
+    fs::write(&path, manifest).with_context(|| format!("Failed to write {:?}", path))?;
+
+    let broken = fixtures.iter().filter(|fixture| !fixture.valid).count();
+    println!(
+        "Wrote {} PNG files to {:?}, {} of them broken on purpose, described in manifest.json",
+        fixtures.len(),
+        args.directory,
+        broken
+    );
+
+    Ok(())
+}
+
+pub fn execute_bench(args: Bench) -> anyhow::Result<()> {
+    if args.format == OutputFormat::Human {
+        println!("Benchmarking with {} bytes of image data", args.size);
+    }
+    let measurements = bench::run(args.size)?;
+
+    let mut records = Records::new(args.format);
+    for measurement in measurements.iter() {
+        match args.format {
+            OutputFormat::Human => println!(
+                "  {:<10} {:>10.1} MB/s  ({:.3}s)",
+                measurement.name,
+                measurement.megabytes_per_second(),
+                measurement.elapsed.as_secs_f64()
+            ),
+            _ => records.push(json!({
+                "operation": measurement.name,
+                "bytes": measurement.bytes,
+                "seconds": measurement.elapsed.as_secs_f64(),
+                "megabytes_per_second": measurement.megabytes_per_second(),
+            })),
+        }
+    }
+    records.finish();
+
+    Ok(())
+}
+
+// Remove the temporary files of writes which never finished, eg because pngme was killed.
+// Recent ones may belong to a run which is still writing, so they're left alone.
+pub fn execute_gc(args: Gc) -> anyhow::Result<()> {
+    let files = walk::temporary_files(&args.directory, args.recursive)
+        .with_context(|| format!("Failed to search {:?}", args.directory))?;
+
+    let (mut removed, mut bytes, mut recent) = (0, 0, 0);
+    for file_path in files {
+        interrupt::check()?;
+        let metadata = fs::symlink_metadata(&file_path)
+            .with_context(|| format!("Failed to read the metadata of {:?}", file_path))?;
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .unwrap_or_default();
+        if age < args.min_age {
+            recent += 1;
+            continue;
+        }
+
+        if args.dry_run {
+            println!("Would remove {:?}", file_path);
+        } else {
+            fs::remove_file(&file_path)
+                .with_context(|| format!("Failed to remove {:?}", file_path))?;
+            println!("Removed {:?}", file_path);
+        }
+        removed += 1;
+        bytes += metadata.len();
+    }
+
+    let verb = if args.dry_run {
+        "Would remove"
+    } else {
+        "Removed"
+    };
+    println!("{} {} temporary files, {} bytes", verb, removed, bytes);
+    if recent > 0 {
+        println!(
+            "Left {} temporary files newer than {}, they may still be being written",
+            recent,
+            units::format_duration(args.min_age)
+        );
+    }
+
+    Ok(())
+}
+
+// Extract the PNGs inside any file. Each is named after where it was found, so carving the
+// same file again gives the same names.
+pub fn execute_carve(args: Carve) -> anyhow::Result<()> {
+    let bytes = fs::read(&args.file_path)
+        .with_context(|| format!("Failed to read {:?}", args.file_path))?;
+    if !args.dry_run {
+        fs::create_dir_all(&args.out_dir)
+            .with_context(|| format!("Failed to create the directory {:?}", args.out_dir))?;
+    }
+    let stem = args
+        .file_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("carved"));
+
+    let (mut found, mut salvaged) = (0, 0);
+    let mut records = Records::new(args.format);
+    for carved in carve::carve(&bytes) {
+        interrupt::check()?;
+        if args.complete_only && !carved.is_complete() {
+            continue;
+        }
+
+        let path = args
+            .out_dir
+            .join(format!("{}-{:08x}.png", stem, carved.offset));
+        if !args.dry_run {
+            carved
+                .png
+                .write_file(&path)
+                .with_context(|| format!("Failed to write {:?}", path))?;
+        }
+
+        let repairs: Vec<String> = carved.repairs.iter().map(|r| r.to_string()).collect();
+        match args.format {
+            OutputFormat::Human if repairs.is_empty() => println!(
+                "{:#010x}  {:>10} bytes  {:?}",
+                carved.offset, carved.length, path
+            ),
+            OutputFormat::Human => println!(
+                "{:#010x}  {:>10} bytes  {:?}, salvaged: {}",
+                carved.offset,
+                carved.length,
+                path,
+                repairs.join(", ")
+            ),
+            _ => records.push(json!({
+                "offset": carved.offset,
+                "length": carved.length,
+                "file": path.display().to_string(),
+                "complete": carved.is_complete(),
+                "repairs": repairs,
+            })),
+        }
+        found += 1;
+        if !carved.is_complete() {
+            salvaged += 1;
+        }
+    }
+    records.finish();
+
+    if args.format == OutputFormat::Human {
+        let verb = if args.dry_run { "Found" } else { "Extracted" };
+        println!(
+            "{} {} PNGs from {:?}, {} of them salvaged",
+            verb, found, args.file_path, salvaged
+        );
+    }
+
+    Ok(())
+}
+
+pub fn execute_append_to(args: AppendTo) -> anyhow::Result<()> {
+    let bytes = fs::read(&args.file_path)
+        .with_context(|| format!("Failed to read {:?}", args.file_path))?;
+    let payload =
+        fs::read(&args.payload).with_context(|| format!("Failed to read {:?}", args.payload))?;
+    if trailer::has_trailer(&bytes) {
+        println!(
+            "Replacing the payload already appended to {:?}",
+            args.file_path
+        );
+    }
+    let appended = trailer::append(&bytes, &payload).with_context(|| {
+        format!(
+            "Failed to read the payload appended to {:?}",
+            args.file_path
+        )
+    })?;
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.file_path.clone());
+    println!(
+        "Appending {} bytes from {:?}, writing out file to {:?}",
+        payload.len(),
+        args.payload,
+        output
+    );
+    temporary::write_atomically(&output, |file| file.write_all(&appended))
+        .with_context(|| format!("Failed to write file {:?}", output))?;
+    // A new output file is given the host's permissions, so an executable host stays executable
+    if output != args.file_path {
+        let permissions = fs::metadata(&args.file_path)
+            .with_context(|| format!("Failed to read {:?}", args.file_path))?
+            .permissions();
+        fs::set_permissions(&output, permissions)
+            .with_context(|| format!("Failed to set the permissions of {:?}", output))?;
+    }
+
+    Ok(())
+}
+
+pub fn execute_extract_from(args: ExtractFrom) -> anyhow::Result<()> {
+    extract_from(args, io::stdout().is_terminal())
+}
+
+fn extract_from(args: ExtractFrom, to_terminal: bool) -> anyhow::Result<()> {
+    let bytes = fs::read(&args.file_path)
+        .with_context(|| format!("Failed to read {:?}", args.file_path))?;
+    let found = trailer::find(&bytes).with_context(|| {
+        format!(
+            "Failed to read the payload appended to {:?}",
+            args.file_path
+        )
+    })?;
+
+    match &args.output {
+        Some(output) => {
+            println!("Writing {} bytes to {:?}", found.payload.len(), output);
+            fs::write(output, found.payload)
+                .with_context(|| format!("Failed to write file {:?}", output))?;
+        }
+        None if to_terminal && !args.raw => {
+            // Only the summary is printed, so removing the payload would lose it
+            if args.remove {
+                anyhow::bail!("Refusing to remove a payload which isn't written anywhere, use --output or --raw");
+            }
+            println!(
+                "{:?} has a payload of {} bytes appended",
+                args.file_path,
+                found.payload.len()
+            );
+            eprintln!("hint: Write it to a file with --output, or use --raw to print it anyway");
+        }
+        None => {
+            let mut stdout = io::stdout();
+            stdout
+                .write_all(found.payload)
+                .and_then(|_| stdout.flush())
+                .context("Failed to write the payload")?;
+        }
+    }
+
+    if args.remove {
+        temporary::write_atomically(&args.file_path, |file| file.write_all(found.host))
+            .with_context(|| format!("Failed to write file {:?}", args.file_path))?;
+        eprintln!("Removed the payload from {:?}", args.file_path);
+    }
+
+    Ok(())
+}
+
+pub fn execute_stats(args: Stats) -> anyhow::Result<()> {
+    if args.enable {
+        let path = stats::enable()?;
+        println!("Recording usage statistics in {:?}", path);
+        return Ok(());
+    }
+    if args.disable {
+        match stats::disable()? {
+            Some(path) => println!("Stopped recording usage statistics, removed {:?}", path),
+            None => println!("Usage statistics aren't being recorded"),
+        }
+        return Ok(());
+    }
+
+    let path = match stats::path() {
+        Some(path) if path.is_file() => path,
+        _ => anyhow::bail!(
+            "Usage statistics aren't being recorded, use `pngme stats --enable` to start"
+        ),
+    };
+    let usage = stats::load(&path)?;
+    match args.format {
+        OutputFormat::Human => {
+            println!("Usage statistics from {:?}", path);
+            if usage.is_empty() {
+                println!("Nothing recorded yet");
+            }
+            for (command, usage) in usage.iter() {
+                match usage.average_file_size() {
+                    Some(size) => println!(
+                        "  {:<20} {} runs, average file size {} bytes",
+                        command, usage.runs, size
+                    ),
+                    None => println!("  {:<20} {} runs", command, usage.runs),
+                }
+            }
+        }
+        format => {
+            let commands: Vec<serde_json::Value> = usage
+                .iter()
+                .map(|(command, usage)| {
+                    json!({
+                        "command": command.clone(),
+                        "runs": usage.runs,
+                        "average_file_size": usage.average_file_size(),
+                    })
+                })
+                .collect();
+            let json = json!({ "path": path.display().to_string(), "commands": commands });
+            println!("{}", report::document(&json, "/commands", format));
+        }
+    }
+
+    Ok(())
+}
+
+fn keyword_not_found(png: &Png, keyword: String, file_path: PathBuf) -> CommandError {
+    let mut available: Vec<String> = vec![];
+    for (_, text) in text::text_chunks(png) {
+        if let Ok(text) = text {
+            if !available.contains(&text.keyword) {
+                available.push(text.keyword);
+            }
+        }
+    }
+
+    CommandError::KeywordNotFound {
+        keyword,
+        file_path,
+        available,
+    }
+}
+
+// The chunk types to work on, given either exactly or as a --type-glob pattern
+fn type_pattern(
+    chunk_type: Option<ChunkType>,
+    type_glob: Option<TypePattern>,
+    ignore_case: bool,
+) -> anyhow::Result<TypePattern> {
+    let pattern = match (chunk_type, type_glob) {
+        (Some(chunk_type), _) => TypePattern::exact(&chunk_type),
+        (None, Some(pattern)) => pattern,
+        (None, None) => anyhow::bail!("A chunk type must be provided"),
+    };
+
+    Ok(pattern.ignore_case(ignore_case))
+}
+
+// Exact chunk types get suggestions for similar types, patterns get the types in the file
+fn no_matching_chunks(png: &Png, pattern: &TypePattern, file_path: PathBuf) -> CommandError {
+    match pattern.as_chunk_type() {
+        Some(chunk_type) => chunk_not_found(png, chunk_type, file_path),
+        None => CommandError::NoMatchingChunks {
+            pattern: pattern.clone(),
+            file_path,
+            available: png.chunk_types(),
+        },
+    }
+}
+
+fn chunk_not_found(png: &Png, chunk_type: ChunkType, file_path: PathBuf) -> CommandError {
+    CommandError::ChunkNotFound {
+        chunk_type,
+        file_path,
+        available: png.chunk_types(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib_pngme::{fixtures, payload::PayloadEncoding};
+    use std::{env, process};
+
+    #[test]
+    fn test_set_plays_keeps_chunk_order() {
+        let fixture = fixtures::generate()
+            .into_iter()
+            .find(|fixture| fixture.name == "animated")
+            .unwrap();
+        let mut png = Png::try_from(&fixture.bytes[..]).unwrap();
+        let order = |png: &Png| -> Vec<ChunkType> {
+            png.chunks()
+                .iter()
+                .map(|chunk| *chunk.chunk_type())
+                .collect()
+        };
+        let before = order(&png);
+
+        set_plays(&mut png, 3).unwrap();
+
+        assert_eq!(order(&png), before);
+        assert_eq!(Animation::from_png(&png).unwrap().control.num_plays, 3);
+    }
+
+    #[test]
+    fn test_extract_from_remove_keeps_payload_when_not_written() {
+        let path = env::temp_dir().join(format!("pngme-extract-from-{}", process::id()));
+        let appended = trailer::append(b"host", b"payload").unwrap();
+        fs::write(&path, &appended).unwrap();
+
+        let args = ExtractFrom {
+            file_path: path.clone(),
+            output: None,
+            raw: false,
+            remove: true,
+        };
+        let result = extract_from(args, true);
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(bytes, appended);
+    }
+
+    #[test]
+    fn test_check_compat_secret_shares() {
+        let message = Payload::legacy(String::from("message"));
+        assert!(check_compat(Payload::CURRENT_VERSION, &message, true).is_ok());
+        assert!(check_compat(Payload::HEADER_VERSION, &message, true).is_ok());
+        match check_compat(Payload::LEGACY_VERSION, &message, true) {
+            Err(CommandError::IncompatiblePayload { feature, .. }) => {
+                assert_eq!(feature, "secret shares")
+            }
+            result => panic!("Expected secret shares to be refused, got {:?}", result),
+        }
+
+        let json = Payload::new(PayloadEncoding::Json, b"{}".to_vec());
+        assert!(check_compat(Payload::CURRENT_VERSION, &json, true).is_ok());
+        match check_compat(Payload::LEGACY_VERSION, &json, true) {
+            Err(CommandError::IncompatiblePayload { feature, .. }) => {
+                assert_eq!(feature, "json messages")
+            }
+            result => panic!("Expected json messages to be refused, got {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,140 @@
+// A line by line diff of two messages, shown in the unified format `git diff` uses
+
+// Lines of unchanged text shown around each change
+const CONTEXT: usize = 3;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+impl Line<'_> {
+    fn is_change(&self) -> bool {
+        !matches!(self, Line::Same(_))
+    }
+}
+
+// The unified diff turning `old` into `new`, or None when they are the same.
+// Removed lines are red and added lines green when `color` is set.
+pub fn unified(old: &str, new: &str, label: &str, color: bool) -> Option<String> {
+    let lines = diff_lines(old, new);
+    let changes: Vec<usize> = (0..lines.len()).filter(|&i| lines[i].is_change()).collect();
+    if changes.is_empty() {
+        return None;
+    }
+
+    let mut output = vec![
+        paint(&format!("--- a/{}", label), "1", color),
+        paint(&format!("+++ b/{}", label), "1", color),
+    ];
+    for (start, end) in hunks(&changes, lines.len()) {
+        // Line numbers are 1 based, and count the lines before the hunk on each side
+        let old_before = lines[..start]
+            .iter()
+            .filter(|l| !matches!(l, Line::Added(_)))
+            .count();
+        let new_before = lines[..start]
+            .iter()
+            .filter(|l| !matches!(l, Line::Removed(_)))
+            .count();
+        let old_len = lines[start..end]
+            .iter()
+            .filter(|l| !matches!(l, Line::Added(_)))
+            .count();
+        let new_len = lines[start..end]
+            .iter()
+            .filter(|l| !matches!(l, Line::Removed(_)))
+            .count();
+        output.push(paint(
+            &format!(
+                "@@ -{} +{} @@",
+                range(old_before, old_len),
+                range(new_before, new_len)
+            ),
+            "36",
+            color,
+        ));
+
+        for line in lines[start..end].iter() {
+            output.push(match line {
+                Line::Same(text) => format!(" {}", text),
+                Line::Removed(text) => paint(&format!("-{}", text), "31", color),
+                Line::Added(text) => paint(&format!("+{}", text), "32", color),
+            });
+        }
+    }
+
+    Some(output.join("\n"))
+}
+
+// Line by line edits, from the longest common subsequence of the two texts
+fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|l| Line::Removed(l)));
+    lines.extend(new[j..].iter().map(|l| Line::Added(l)));
+
+    lines
+}
+
+// The ranges of lines shown for each hunk, changes close enough to share context are merged
+fn hunks(changes: &[usize], total: usize) -> Vec<(usize, usize)> {
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for &change in changes {
+        let start = change.saturating_sub(CONTEXT);
+        let end = (change + 1 + CONTEXT).min(total);
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    hunks
+}
+
+fn range(before: usize, len: usize) -> String {
+    match len {
+        // An empty range refers to the line before it, as `git diff` does
+        0 => format!("{},0", before),
+        1 => format!("{}", before + 1),
+        _ => format!("{},{}", before + 1, len),
+    }
+}
+
+fn paint(text: &str, code: &str, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
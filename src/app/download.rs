@@ -0,0 +1,78 @@
+use sha2::{Digest, Sha256};
+#[cfg(feature = "http")]
+use std::time::Duration;
+
+// Fetching a message to encode from a URL, eg a build manifest published by CI.
+// Downloads are limited in size and time, so a wrong URL can't fill the memory or hang a pipeline.
+
+// Only the hash can be checked without `--features http`, as nothing is downloaded
+pub struct Limits {
+    #[cfg(feature = "http")]
+    pub max_size: usize,
+    #[cfg(feature = "http")]
+    pub timeout: Duration,
+    // The hex SHA-256 the downloaded bytes must have
+    pub expect_sha256: Option<String>,
+}
+
+pub fn fetch(url: &str, limits: &Limits) -> anyhow::Result<Vec<u8>> {
+    let data = get(url, limits)?;
+
+    if let Some(expected) = &limits.expect_sha256 {
+        let actual = format!("{:x}", Sha256::digest(&data));
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            anyhow::bail!(
+                "The download from {} has the SHA-256 {}, but {} was expected",
+                url,
+                actual,
+                expected
+            )
+        }
+    }
+
+    Ok(data)
+}
+
+#[cfg(feature = "http")]
+fn get(url: &str, limits: &Limits) -> anyhow::Result<Vec<u8>> {
+    use anyhow::Context;
+    use std::io::Read;
+
+    let agent = ureq::AgentBuilder::new().timeout(limits.timeout).build();
+    let response = agent
+        .get(url)
+        .call()
+        .with_context(|| format!("Failed to download {}", url))?;
+
+    let too_large = || {
+        anyhow::anyhow!(
+            "The download from {} is larger than the limit of {} bytes, raise it with --max-download-size",
+            url,
+            limits.max_size
+        )
+    };
+    let length = response
+        .header("Content-Length")
+        .and_then(|length| length.parse::<usize>().ok());
+    if matches!(length, Some(length) if length > limits.max_size) {
+        return Err(too_large());
+    }
+
+    // The length header can't be trusted, so read one byte past the limit to tell if it's over
+    let mut data = vec![];
+    response
+        .into_reader()
+        .take(limits.max_size as u64 + 1)
+        .read_to_end(&mut data)
+        .with_context(|| format!("Failed to download {}", url))?;
+    if data.len() > limits.max_size {
+        return Err(too_large());
+    }
+
+    Ok(data)
+}
+
+#[cfg(not(feature = "http"))]
+fn get(_url: &str, _limits: &Limits) -> anyhow::Result<Vec<u8>> {
+    anyhow::bail!("pngme was built without support for URLs, rebuild with `--features http`")
+}
@@ -0,0 +1,262 @@
+use crate::fuzzy;
+use lib_pngme::cancel::CancelError;
+use lib_pngme::chunk::ChunkError;
+use lib_pngme::chunk_type::ChunkType;
+use lib_pngme::constraints::RemovalConflict;
+use lib_pngme::inflate::InflateError;
+use lib_pngme::png::PngError;
+use lib_pngme::selector::TypePattern;
+use lib_pngme::trailer::TrailerError;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::string::FromUtf8Error;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CommandError {
+    #[error("Failed to find a chunk of type {chunk_type} in {file_path:?}")]
+    ChunkNotFound {
+        chunk_type: ChunkType,
+        file_path: PathBuf,
+        // The distinct chunk types which are in the file
+        available: Vec<ChunkType>,
+    },
+    #[error("Failed to find a chunk with a type matching {pattern} in {file_path:?}")]
+    NoMatchingChunks {
+        pattern: TypePattern,
+        file_path: PathBuf,
+        // The distinct chunk types which are in the file
+        available: Vec<ChunkType>,
+    },
+    #[error("Failed to find text with the keyword {keyword:?} in {file_path:?}")]
+    KeywordNotFound {
+        keyword: String,
+        file_path: PathBuf,
+        // The distinct keywords which are in the file
+        available: Vec<String>,
+    },
+    #[error("Version {version} payloads can't hold {feature}")]
+    IncompatiblePayload { version: u8, feature: String },
+    #[error("Refusing to remove the {chunk_type} chunk, the image depends on it")]
+    RemovalRefused {
+        chunk_type: ChunkType,
+        conflicts: Vec<RemovalConflict>,
+    },
+    #[error("{files} staged PNG files break the policy in {policy_file}, stopping the commit")]
+    PolicyViolated { files: usize, policy_file: String },
+}
+
+// Suggestions on how to recover from an error, shown after the error itself.
+// The first error in the chain which we know something about decides the hints.
+pub fn hints(error: &anyhow::Error) -> Vec<String> {
+    for cause in error.chain() {
+        if let Some(e) = cause.downcast_ref::<CommandError>() {
+            return command_error_hints(e);
+        }
+        if let Some(e) = cause.downcast_ref::<PngError>() {
+            return png_error_hints(e);
+        }
+        if cause.downcast_ref::<FromUtf8Error>().is_some() {
+            let mut hints = vec![String::from(
+                "The chunk doesn't hold text, it may be image data rather than a message",
+            )];
+            if !cfg!(feature = "encoding") {
+                hints.push(String::from(
+                    "If it is text in a legacy charset such as Latin-1, rebuild with `--features encoding` to convert it",
+                ));
+            }
+            return hints;
+        }
+        if let Some(CancelError::TimedOut) = cause.downcast_ref::<CancelError>() {
+            return vec![String::from("Allow the command longer with --time-limit")];
+        }
+        if let Some(e) = cause.downcast_ref::<InflateError>() {
+            return inflate_error_hints(e);
+        }
+        if let Some(TrailerError::NotFound) = cause.downcast_ref::<TrailerError>() {
+            return vec![String::from(
+                "Only payloads appended by `pngme append-to` can be extracted",
+            )];
+        }
+        if let Some(e) = cause.downcast_ref::<io::Error>() {
+            return io_error_hints(e);
+        }
+    }
+
+    vec![]
+}
+
+fn inflate_error_hints(error: &InflateError) -> Vec<String> {
+    match error {
+        InflateError::TooLarge(_) => vec![String::from(
+            "If the file is trusted, raise the limit with --max-inflate-size",
+        )],
+        InflateError::TooCompressed(_) => vec![String::from(
+            "If the file is trusted, raise the limit with --max-inflate-ratio",
+        )],
+        _ => vec![],
+    }
+}
+
+fn command_error_hints(error: &CommandError) -> Vec<String> {
+    match error {
+        CommandError::ChunkNotFound {
+            chunk_type,
+            file_path,
+            available,
+        } => {
+            let mut hints = vec![];
+            let closest = fuzzy::closest_chunk_types(chunk_type, available);
+            if !closest.is_empty() {
+                let names: Vec<String> = closest.iter().map(highlight).collect();
+                hints.push(format!("Did you mean {}?", names.join(" or ")));
+            }
+
+            let present: Vec<String> = available
+                .iter()
+                .map(|c| {
+                    if closest.contains(c) {
+                        highlight(c)
+                    } else {
+                        c.to_string()
+                    }
+                })
+                .collect();
+            hints.push(format!("Chunk types in the file: {}", present.join(", ")));
+            hints.push(String::from(
+                "Chunk types are case sensitive, `ruSt` and `rust` are different chunks",
+            ));
+            hints.push(format!(
+                "Run `pngme print {}` to see every chunk in the file",
+                file_path.display()
+            ));
+            hints
+        }
+        CommandError::NoMatchingChunks {
+            pattern,
+            file_path,
+            available,
+        } => {
+            let names: Vec<String> = available.iter().map(|c| c.to_string()).collect();
+            let mut hints = vec![format!("Chunk types in the file: {}", names.join(", "))];
+            // Nothing matched as given, so anything matching now differs only in case
+            let loose = pattern.clone().ignore_case(true);
+            let close: Vec<String> = available
+                .iter()
+                .filter(|c| loose.matches(c))
+                .map(highlight)
+                .collect();
+            if !close.is_empty() {
+                hints.push(format!(
+                    "Chunk types are case sensitive, use --ignore-case to also match {}",
+                    close.join(", ")
+                ));
+            }
+            hints.push(format!(
+                "Run `pngme print {}` to see every chunk in the file",
+                file_path.display()
+            ));
+            hints
+        }
+        CommandError::KeywordNotFound { available, .. } => {
+            if available.is_empty() {
+                vec![String::from("The file has no text chunks")]
+            } else {
+                vec![
+                    format!("Keywords in the file: {}", available.join(", ")),
+                    String::from(
+                        "Keywords are case sensitive, `Comment` and `comment` are different keywords",
+                    ),
+                ]
+            }
+        }
+        CommandError::IncompatiblePayload { version, .. } => vec![
+            format!(
+                "Releases of pngme which only read version {} payloads won't understand the message",
+                version
+            ),
+            String::from("Drop the option, or --compat-version if every reader has been upgraded"),
+        ],
+        CommandError::RemovalRefused {
+            chunk_type,
+            conflicts,
+        } => {
+            let mut hints: Vec<String> = conflicts.iter().map(|c| c.to_string()).collect();
+            hints.push(format!(
+                "Use --force to remove the {} chunk anyway, the image may no longer display",
+                chunk_type
+            ));
+            hints
+        }
+        CommandError::PolicyViolated { .. } => vec![
+            String::from("Fix the files and stage them again, the problems are listed above"),
+            String::from("To commit anyway, use `git commit --no-verify`"),
+        ],
+    }
+}
+
+// Make close matches stand out, using colour when writing to a terminal
+fn highlight(chunk_type: &ChunkType) -> String {
+    if io::stderr().is_terminal() {
+        format!("\x1b[1;33m{}\x1b[0m", chunk_type)
+    } else {
+        format!("`{}`", chunk_type)
+    }
+}
+
+fn png_error_hints(error: &PngError) -> Vec<String> {
+    match error {
+        PngError::NotAValidPNGHeader(_) => vec![String::from(
+            "The file doesn't start with the PNG signature, check it is a PNG and not another image format",
+        ), String::from(
+            "If the signature is damaged, or the PNG is inside other data, --assume-png reads it from its IHDR chunk",
+        )],
+        PngError::NeedAtLeastTwoChunks(_) | PngError::IENDChunkShouldLast(_) => {
+            vec![String::from(
+                "The file looks truncated, it may not have been fully downloaded or written",
+            )]
+        }
+        PngError::InvalidChunk {
+            start_index,
+            source,
+        } => vec![chunk_error_hint(*start_index, source)],
+        PngError::NoImageHeader => vec![String::from(
+            "There is no intact IHDR chunk to start from, so nothing looks like a PNG",
+        )],
+        PngError::InvalidPosition(_, _) => vec![String::from(
+            "`pngme print` numbers the chunks, the new chunk takes the place of the one at that number",
+        )],
+        PngError::Cancelled(CancelError::TimedOut) => {
+            vec![String::from("Allow the command longer with --time-limit")]
+        }
+        _ => vec![],
+    }
+}
+
+fn chunk_error_hint(start_index: usize, error: &ChunkError) -> String {
+    match error {
+        ChunkError::InvalidCRCValue(_, _) => format!(
+            "The chunk at byte {} doesn't match its CRC, the file has been corrupted or edited without updating the CRC",
+            start_index
+        ),
+        ChunkError::LengthTooLarge(_, _) | ChunkError::NotEnoughBytes(_, _) => format!(
+            "The chunk at byte {} runs past the end of the file, the file looks truncated",
+            start_index
+        ),
+        ChunkError::InvalidChunk { .. } => format!(
+            "The chunk at byte {} doesn't have a valid type, chunk types are 4 ASCII letters. The file is likely corrupt",
+            start_index
+        ),
+        _ => format!("The chunk at byte {} is corrupt", start_index),
+    }
+}
+
+fn io_error_hints(error: &io::Error) -> Vec<String> {
+    match error.kind() {
+        io::ErrorKind::NotFound => vec![String::from("Check the file path is correct")],
+        io::ErrorKind::PermissionDenied => vec![String::from(
+            "Check you have permission to read the file, and to write the output file",
+        )],
+        _ => vec![],
+    }
+}
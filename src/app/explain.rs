@@ -0,0 +1,98 @@
+use lib_pngme::chunk_type::ChunkType;
+use lib_pngme::registry;
+
+// Describe what each of the property bits of a chunk type means, following
+// section 3.2 of the PNG specification
+pub fn explain(chunk_type: &ChunkType) -> String {
+    let letters: Vec<char> = chunk_type.to_string().chars().collect();
+    let mut lines = vec![format!("{}", chunk_type)];
+
+    lines.push(format!(
+        "  {} - {}",
+        letters[0],
+        if chunk_type.is_critical() {
+            "critical: needed to display the image correctly"
+        } else {
+            "ancillary: decoders may safely ignore it"
+        }
+    ));
+    lines.push(format!(
+        "  {} - {}",
+        letters[1],
+        if chunk_type.is_public() {
+            "public: part of the PNG specification or registered as an extension"
+        } else {
+            "private: defined by an application for its own use"
+        }
+    ));
+    lines.push(format!(
+        "  {} - {}",
+        letters[2],
+        if chunk_type.is_reserved_bit_valid() {
+            "reserved: upper-case, as required by this version of PNG"
+        } else {
+            "reserved: lower-case, which is not allowed by this version of PNG"
+        }
+    ));
+    lines.push(format!(
+        "  {} - {}",
+        letters[3],
+        if chunk_type.is_safe_to_copy() {
+            "safe to copy: editors may copy it even after modifying the image"
+        } else {
+            "unsafe to copy: editors must drop it if they modify the critical chunks"
+        }
+    ));
+
+    match registry::lookup(chunk_type) {
+        Some(known) => lines.push(format!(
+            "Registered: yes, {} ({})",
+            known.name, known.defined_in
+        )),
+        None => lines.push(String::from(
+            "Registered: no, this is not a standard or registered chunk type",
+        )),
+    }
+
+    if registry::is_color_management(chunk_type) {
+        lines.push(String::from(
+            "Colour management: yes, removing it can shift the image's colours",
+        ));
+    }
+
+    if chunk_type.is_reserved_bit_valid() {
+        lines.push(String::from("Legal: yes"));
+    } else {
+        lines.push(String::from(
+            "Legal: no, the third letter must be upper-case",
+        ));
+    }
+
+    lines.push(decoder_behaviour(chunk_type));
+
+    lines.join("\n")
+}
+
+fn decoder_behaviour(chunk_type: &ChunkType) -> String {
+    if registry::lookup(chunk_type).is_some() {
+        return format!("Decoders which support {} will understand it.", chunk_type);
+    }
+
+    let decoder = if !chunk_type.is_reserved_bit_valid() {
+        "treat it as unrecognised, and may reject the file as it doesn't conform to the specification"
+    } else if chunk_type.is_critical() {
+        "reject the file, as it can't be displayed without understanding this chunk"
+    } else {
+        "ignore it"
+    };
+    let editor = if chunk_type.is_safe_to_copy() {
+        "copy it to the new file"
+    } else {
+        "only copy it to the new file if no critical chunks were changed"
+    };
+
+    format!(
+        "A decoder which doesn't recognise {} will {}. An editor which doesn't recognise it will {}.",
+        chunk_type, decoder, editor
+    )
+}
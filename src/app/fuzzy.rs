@@ -0,0 +1,41 @@
+use lib_pngme::chunk_type::ChunkType;
+
+// The number of single letter insertions, deletions or substitutions needed to turn `a` into `b`
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // Only the previous row of the distance matrix is needed to calculate the next one
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == b_char { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+// Chunk types which are close enough to the wanted one that it was likely a typo,
+// closest first
+pub fn closest_chunk_types(wanted: &ChunkType, candidates: &[ChunkType]) -> Vec<ChunkType> {
+    const MAX_DISTANCE: usize = 2;
+
+    let wanted = wanted.to_string();
+    let mut matches: Vec<(usize, ChunkType)> = candidates
+        .iter()
+        .map(|candidate| (edit_distance(&wanted, &candidate.to_string()), *candidate))
+        .filter(|&(distance, _)| distance <= MAX_DISTANCE)
+        .collect();
+
+    matches.sort_by_key(|&(distance, _)| distance);
+    matches.dedup_by_key(|(_, chunk_type)| *chunk_type);
+
+    matches
+        .into_iter()
+        .map(|(_, chunk_type)| chunk_type)
+        .collect()
+}
@@ -0,0 +1,160 @@
+use anyhow::Context;
+use lib_pngme::policy::Policy;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// A git pre-commit hook checking the PNG files being committed follow the project's policy.
+//
+// The policy is `.pngme-policy.json` at the top of the repository, eg
+//
+//     { "forbid_gps": true, "max_metadata_bytes": 65536, "required_keywords": ["License"] }
+//
+// The hook checks what is staged rather than the files on disk, which may have changes that
+// aren't being committed, so the policy and each file are read from git's index.
+
+pub const POLICY_FILE: &str = ".pngme-policy.json";
+
+// Marks a hook as written by pngme, so installing again can replace it
+const HOOK_MARKER: &str = "# Installed by pngme";
+
+// Run git in the repository and return what it printed
+fn git(args: &[&str]) -> anyhow::Result<Vec<u8>> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .context("Failed to run git, is it installed?")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(output.stdout)
+}
+
+fn git_line(args: &[&str]) -> anyhow::Result<String> {
+    Ok(String::from_utf8_lossy(&git(args)?).trim().to_string())
+}
+
+// Write the pre-commit hook, refusing to replace a hook which pngme didn't write unless forced
+pub fn install(force: bool) -> anyhow::Result<PathBuf> {
+    let hooks = PathBuf::from(git_line(&["rev-parse", "--git-path", "hooks"])?);
+    let hook = hooks.join("pre-commit");
+    if let Ok(existing) = fs::read_to_string(&hook) {
+        if !existing.contains(HOOK_MARKER) && !force {
+            anyhow::bail!(
+                "{:?} already exists and wasn't installed by pngme, use --force to replace it",
+                hook
+            );
+        }
+    }
+
+    fs::create_dir_all(&hooks).with_context(|| format!("Failed to create {:?}", hooks))?;
+    fs::write(
+        &hook,
+        format!("#!/bin/sh\n{}\nexec pngme hook run\n", HOOK_MARKER),
+    )
+    .with_context(|| format!("Failed to write the hook {:?}", hook))?;
+    make_executable(&hook)?;
+
+    Ok(hook)
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("Failed to make {:?} executable", path))
+}
+
+// Git for Windows runs hooks through its own shell, which doesn't need the permission
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> anyhow::Result<()> {
+    Ok(())
+}
+
+// The staged policy, or None when the repository doesn't have one
+pub fn staged_policy() -> anyhow::Result<Option<Policy>> {
+    let staged = git(&["ls-files", "--cached", "--", POLICY_FILE])?;
+    if staged.is_empty() {
+        return Ok(None);
+    }
+
+    let json = git(&["cat-file", "blob", &format!(":{}", POLICY_FILE)])?;
+    let value: Value = serde_json::from_slice(&json)
+        .with_context(|| format!("Failed to parse {}", POLICY_FILE))?;
+    parse_policy(&value)
+        .with_context(|| format!("Failed to read {}", POLICY_FILE))
+        .map(Some)
+}
+
+fn parse_policy(value: &Value) -> anyhow::Result<Policy> {
+    let fields = value.as_object().context("Expected a JSON object")?;
+    let mut policy = Policy::default();
+    for (name, field) in fields.iter() {
+        match name.as_str() {
+            "forbid_gps" => {
+                policy.forbid_gps = field
+                    .as_bool()
+                    .context("forbid_gps must be true or false")?
+            }
+            "max_metadata_bytes" => {
+                let max = field
+                    .as_u64()
+                    .context("max_metadata_bytes must be a number of bytes")?;
+                policy.max_metadata_bytes = Some(max as usize);
+            }
+            "required_keywords" => {
+                policy.required_keywords = field
+                    .as_array()
+                    .and_then(|keywords| {
+                        keywords
+                            .iter()
+                            .map(|keyword| keyword.as_str().map(String::from))
+                            .collect()
+                    })
+                    .context("required_keywords must be a list of keywords")?
+            }
+            // Better to stop than to quietly ignore a rule with a typo in its name
+            _ => anyhow::bail!(
+                "Unknown rule {:?}, expected forbid_gps, max_metadata_bytes or required_keywords",
+                name
+            ),
+        }
+    }
+
+    Ok(policy)
+}
+
+// The PNG files added or changed in the index, relative to the top of the repository
+pub fn staged_pngs() -> anyhow::Result<Vec<String>> {
+    let names = git(&[
+        "diff",
+        "--cached",
+        "--name-only",
+        "-z",
+        "--diff-filter=ACMR",
+    ])?;
+
+    Ok(names
+        .split(|b| *b == 0)
+        .map(|name| String::from_utf8_lossy(name).to_string())
+        .filter(|name| name.to_ascii_lowercase().ends_with(".png"))
+        .collect())
+}
+
+// The staged content of a file, which can differ from the file on disk
+pub fn staged_blob(path: &str) -> anyhow::Result<Vec<u8>> {
+    git(&["cat-file", "blob", &format!(":{}", path)])
+}
+
+// Git runs hooks at the top of the work tree, `hook install` may be run anywhere in it
+pub fn enter_repository() -> anyhow::Result<()> {
+    let top = git_line(&["rev-parse", "--show-toplevel"])?;
+    std::env::set_current_dir(&top).with_context(|| format!("Failed to enter {:?}", top))
+}
@@ -0,0 +1,240 @@
+#[cfg(feature = "index")]
+use lib_pngme::selector::TypePattern;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+// An SQLite index of the chunks in a directory of PNGs, so questions like "which files have
+// a tEXt chunk" are answered from the index instead of reading every file again. Building
+// the index again only reads the files whose size or modification time has changed, and
+// forgets the ones which have gone.
+//
+// Each chunk is stored with a SHA-256 of its data, to find the files holding a given payload.
+
+#[cfg(feature = "index")]
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS files (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL UNIQUE,
+        size INTEGER NOT NULL,
+        modified INTEGER NOT NULL,
+        error TEXT
+    );
+    CREATE TABLE IF NOT EXISTS chunks (
+        file_id INTEGER NOT NULL REFERENCES files (id),
+        idx INTEGER NOT NULL,
+        chunk_type TEXT NOT NULL,
+        offset INTEGER NOT NULL,
+        length INTEGER NOT NULL,
+        crc TEXT NOT NULL,
+        crc_valid INTEGER NOT NULL,
+        sha256 TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS chunks_by_type ON chunks (chunk_type);
+    CREATE INDEX IF NOT EXISTS chunks_by_sha256 ON chunks (sha256);
+    CREATE INDEX IF NOT EXISTS chunks_by_file ON chunks (file_id);
+";
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    // Files read and added to the index, because they are new or have changed
+    pub indexed: usize,
+    pub unchanged: usize,
+    // Files in the index which weren't found again
+    pub removed: usize,
+}
+
+// What to look for in the index, every filter given has to match. There is nothing to
+// filter without `--features index`.
+#[derive(Debug, Default)]
+pub struct Filters {
+    #[cfg(feature = "index")]
+    pub chunk_type: Option<TypePattern>,
+    // A glob matched against the file's path, eg '*/icons/*'
+    #[cfg(feature = "index")]
+    pub path: Option<String>,
+    // Lower case hex
+    #[cfg(feature = "index")]
+    pub sha256: Option<String>,
+    // Only list each matching file once, rather than every matching chunk
+    #[cfg(feature = "index")]
+    pub files_only: bool,
+}
+
+#[cfg(feature = "index")]
+pub fn build(db: &Path, files: &[PathBuf]) -> anyhow::Result<Summary> {
+    use crate::interrupt;
+    use anyhow::Context;
+    use lib_pngme::view::{CrcCheck, PngBuffer};
+    use rusqlite::{params, Connection};
+    use sha2::{Digest, Sha256};
+    use std::collections::{HashMap, HashSet};
+    use std::fs;
+    use std::time::UNIX_EPOCH;
+
+    let mut connection =
+        Connection::open(db).with_context(|| format!("Failed to open the index {:?}", db))?;
+    connection.execute_batch(SCHEMA)?;
+    let transaction = connection.transaction()?;
+
+    // What the index knows of each file, its id, size and modification time
+    let mut known: HashMap<String, (i64, i64, i64)> = HashMap::new();
+    {
+        let mut statement = transaction.prepare("SELECT id, path, size, modified FROM files")?;
+        let rows = statement.query_map(params![], |row| {
+            let path: String = row.get(1)?;
+            let file: (i64, i64, i64) = (row.get(0)?, row.get(2)?, row.get(3)?);
+            Ok((path, file))
+        })?;
+        for row in rows {
+            let (path, file) = row?;
+            known.insert(path, file);
+        }
+    }
+    let forget = |id: i64| -> rusqlite::Result<()> {
+        transaction.execute("DELETE FROM chunks WHERE file_id = ?1", params![id])?;
+        transaction.execute("DELETE FROM files WHERE id = ?1", params![id])?;
+        Ok(())
+    };
+
+    let mut summary = Summary::default();
+    let mut seen = HashSet::new();
+    let mut buffer = PngBuffer::new();
+    for file_path in files {
+        // Stopped, the transaction is rolled back and the index is left as it was
+        interrupt::check()?;
+        let metadata = fs::metadata(file_path)
+            .with_context(|| format!("Failed to read the metadata of {:?}", file_path))?;
+        let size = metadata.len() as i64;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or(0);
+        let path = file_path.display().to_string();
+        seen.insert(path.clone());
+
+        match known.get(&path) {
+            Some(&(_, known_size, known_modified))
+                if known_size == size && known_modified == modified =>
+            {
+                summary.unchanged += 1;
+                continue;
+            }
+            Some(&(id, _, _)) => forget(id)?,
+            None => {}
+        }
+
+        let png = buffer.read_with(file_path, CrcCheck::Lazy);
+        let error = png.as_ref().err().map(|e| e.to_string());
+        transaction.execute(
+            "INSERT INTO files (path, size, modified, error) VALUES (?1, ?2, ?3, ?4)",
+            params![path, size, modified, error],
+        )?;
+        let file_id = transaction.last_insert_rowid();
+        if let Ok(png) = png {
+            for (index, chunk) in png.chunks().iter().enumerate() {
+                transaction.execute(
+                    "INSERT INTO chunks (file_id, idx, chunk_type, offset, length, crc, crc_valid, sha256)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        file_id,
+                        index as i64,
+                        chunk.chunk_type().to_string(),
+                        chunk.offset() as i64,
+                        chunk.data().len() as i64,
+                        format!("{:08x}", chunk.crc()),
+                        chunk.verify().is_ok(),
+                        format!("{:x}", Sha256::digest(chunk.data())),
+                    ],
+                )?;
+            }
+        }
+        summary.indexed += 1;
+    }
+
+    for (path, (id, _, _)) in known.iter() {
+        if !seen.contains(path) {
+            forget(*id)?;
+            summary.removed += 1;
+        }
+    }
+    transaction.commit()?;
+
+    Ok(summary)
+}
+
+#[cfg(not(feature = "index"))]
+pub fn build(_db: &Path, _files: &[PathBuf]) -> anyhow::Result<Summary> {
+    anyhow::bail!("pngme was built without SQLite, rebuild with `--features index`")
+}
+
+// The chunks matching the filters, or the files holding them, as records
+#[cfg(feature = "index")]
+pub fn query(db: &Path, filters: &Filters) -> anyhow::Result<Vec<Value>> {
+    use anyhow::Context;
+    use rusqlite::{params, Connection, OpenFlags};
+    use serde_json::json;
+
+    // Opened read only, so querying a path which isn't an index doesn't create one
+    let connection = Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open the index {:?}", db))?;
+    // GLOB is case sensitive and uses `*` and `?`, the same as chunk type patterns
+    let filter = "WHERE (?1 IS NULL OR chunks.chunk_type GLOB ?1)
+        AND (?2 IS NULL OR files.path GLOB ?2)
+        AND (?3 IS NULL OR chunks.sha256 = ?3)";
+    let chunk_type = filters
+        .chunk_type
+        .as_ref()
+        .map(|pattern| pattern.to_string());
+    let parameters = params![chunk_type, filters.path, filters.sha256];
+
+    let records = if filters.files_only {
+        let mut statement = connection.prepare(&format!(
+            "SELECT DISTINCT files.path FROM chunks JOIN files ON files.id = chunks.file_id {}
+             ORDER BY files.path",
+            filter
+        ))?;
+        let rows = statement.query_map(parameters, |row| {
+            let path: String = row.get(0)?;
+            Ok(json!({ "path": path }))
+        })?;
+        rows.collect::<rusqlite::Result<Vec<Value>>>()?
+    } else {
+        let mut statement = connection.prepare(&format!(
+            "SELECT files.path, chunks.idx, chunks.chunk_type, chunks.offset, chunks.length,
+                    chunks.crc, chunks.crc_valid, chunks.sha256
+             FROM chunks JOIN files ON files.id = chunks.file_id {}
+             ORDER BY files.path, chunks.idx",
+            filter
+        ))?;
+        let rows = statement.query_map(parameters, |row| {
+            let path: String = row.get(0)?;
+            let index: i64 = row.get(1)?;
+            let chunk_type: String = row.get(2)?;
+            let offset: i64 = row.get(3)?;
+            let length: i64 = row.get(4)?;
+            let crc: String = row.get(5)?;
+            let crc_valid: bool = row.get(6)?;
+            let sha256: String = row.get(7)?;
+            Ok(json!({
+                "path": path,
+                "index": index,
+                "chunk_type": chunk_type,
+                "offset": offset,
+                "length": length,
+                "crc": crc,
+                "crc_valid": crc_valid,
+                "sha256": sha256,
+            }))
+        })?;
+        rows.collect::<rusqlite::Result<Vec<Value>>>()?
+    };
+
+    Ok(records)
+}
+
+#[cfg(not(feature = "index"))]
+pub fn query(_db: &Path, _filters: &Filters) -> anyhow::Result<Vec<Value>> {
+    anyhow::bail!("pngme was built without SQLite, rebuild with `--features index`")
+}
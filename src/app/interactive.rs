@@ -0,0 +1,35 @@
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Whether there is someone to answer questions. In CI there isn't, and a question would wait
+// forever, so with --non-interactive or PNGME_NON_INTERACTIVE set anything which would ask
+// fails instead. Long output isn't paged either, as a pager waits for a key press.
+//
+// What would otherwise be given on the command line can be set in the environment instead:
+// PNGME_FORMAT for the --format of commands with human, json, yaml, csv and table output,
+// and PNGME_FORCE for --force.
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_non_interactive(non_interactive: bool) {
+    NON_INTERACTIVE.store(non_interactive, Ordering::Relaxed);
+}
+
+pub fn is_non_interactive() -> bool {
+    NON_INTERACTIVE.load(Ordering::Relaxed)
+}
+
+// Whether a variable turning an option on is set, to anything but nothing, 0, false or no
+pub fn enabled(name: &str) -> bool {
+    match env::var(name) {
+        Ok(value) => !matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "" | "0" | "false" | "no"
+        ),
+        Err(_) => false,
+    }
+}
+
+// Whether to force, given --force or PNGME_FORCE
+pub fn force(flag: bool) -> bool {
+    flag || enabled("PNGME_FORCE")
+}
@@ -0,0 +1,172 @@
+use serde_json::{json, Value};
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::path::Path;
+use std::process::{Command, Output};
+
+// Run whichever well-known PNG tools are installed against a file, so users can check other
+// software still accepts what pngme wrote. None of the tools are required, missing ones are skipped.
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Passed,
+    // Accepted the file, but complained about something
+    Warned(String),
+    Failed(String),
+    NotInstalled,
+}
+
+impl Verdict {
+    fn name(&self) -> &'static str {
+        match self {
+            Verdict::Passed => "passed",
+            Verdict::Warned(_) => "warned",
+            Verdict::Failed(_) => "failed",
+            Verdict::NotInstalled => "not installed",
+        }
+    }
+
+    fn detail(&self) -> Option<&str> {
+        match self {
+            Verdict::Warned(detail) | Verdict::Failed(detail) => Some(detail),
+            Verdict::Passed | Verdict::NotInstalled => None,
+        }
+    }
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.detail() {
+            Some(detail) => write!(f, "{}, {}", self.name(), detail),
+            None => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+pub struct Check {
+    pub tool: &'static str,
+    pub verdict: Verdict,
+}
+
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+// The output of the first of the programs which is installed
+fn run(programs: &[(&str, &[&str])], file_path: &Path) -> io::Result<Option<Output>> {
+    for (program, args) in programs {
+        match Command::new(program).args(*args).arg(file_path).output() {
+            Ok(output) => return Ok(Some(output)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(None)
+}
+
+// pngcheck exits with an error for anything it considers invalid
+fn pngcheck(output: &Output) -> Verdict {
+    if output.status.success() {
+        Verdict::Passed
+    } else {
+        Verdict::Failed(text(&output.stdout))
+    }
+}
+
+// exiftool accepts most files, its verdict is in the Warning and Error tags
+fn exiftool(output: &Output) -> Verdict {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let tag = |name: &str| {
+        stdout
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .filter(|(tag, _)| tag.trim() == name)
+            .map(|(_, value)| value.trim().to_string())
+            .collect::<Vec<_>>()
+            .join("; ")
+    };
+
+    let errors = tag("Error");
+    let warnings = tag("Warning");
+    if !output.status.success() || !errors.is_empty() {
+        Verdict::Failed(if errors.is_empty() {
+            text(&output.stderr)
+        } else {
+            errors
+        })
+    } else if !warnings.is_empty() {
+        Verdict::Warned(warnings)
+    } else {
+        Verdict::Passed
+    }
+}
+
+// ImageMagick fails for files it can't decode, and prints anything it recovered from to stderr
+fn identify(output: &Output) -> Verdict {
+    let stderr = text(&output.stderr);
+    if !output.status.success() {
+        Verdict::Failed(stderr)
+    } else if !stderr.is_empty() {
+        Verdict::Warned(stderr)
+    } else {
+        Verdict::Passed
+    }
+}
+
+type Tool = (
+    &'static str,
+    &'static [(&'static str, &'static [&'static str])],
+    fn(&Output) -> Verdict,
+);
+
+const TOOLS: [Tool; 3] = [
+    ("pngcheck", &[("pngcheck", &[])], pngcheck),
+    (
+        "exiftool",
+        &[("exiftool", &["-s", "-Warning", "-Error"])],
+        exiftool,
+    ),
+    (
+        "identify",
+        // ImageMagick 7 installs `magick`, earlier versions only `identify`
+        &[("magick", &["identify"]), ("identify", &[])],
+        identify,
+    ),
+];
+
+pub fn check(file_path: &Path) -> io::Result<Vec<Check>> {
+    let mut checks = vec![];
+    for (tool, programs, verdict) in TOOLS.iter() {
+        let verdict = match run(programs, file_path)? {
+            Some(output) => verdict(&output),
+            None => Verdict::NotInstalled,
+        };
+        checks.push(Check { tool, verdict });
+    }
+
+    Ok(checks)
+}
+
+pub fn json(file_path: &Path, checks: &[Check]) -> Value {
+    let tools: Vec<Value> = checks
+        .iter()
+        .map(|check| {
+            json!({
+                "tool": check.tool,
+                "verdict": check.verdict.name(),
+                "detail": check.verdict.detail(),
+            })
+        })
+        .collect();
+
+    json!({
+        "file": file_path.display().to_string(),
+        "tools": tools,
+    })
+}
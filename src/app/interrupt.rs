@@ -0,0 +1,47 @@
+use lib_pngme::cancel::{CancelError, CancellationToken};
+use lib_pngme::temporary;
+use std::process;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+// Stopping a command cleanly. The first Ctrl-C, or --time-limit running out, cancels the
+// command where it can stop, eg between the files of a directory, so the output so far is
+// finished off and written files are never left half written. A second Ctrl-C exits at once.
+
+// Exiting because of Ctrl-C, as a shell reports a process killed by SIGINT
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+static TOKEN: OnceLock<CancellationToken> = OnceLock::new();
+
+pub fn install(time_limit: Option<Duration>) {
+    let token = CancellationToken::new();
+    let handled = token.clone();
+    // Without a handler Ctrl-C still stops pngme, just not cleanly
+    let _ = ctrlc::set_handler(move || {
+        if handled.is_cancelled() {
+            // Exiting part way through a write, the file it would have replaced is untouched
+            temporary::remove_in_progress();
+            process::exit(INTERRUPTED_EXIT_CODE);
+        }
+        eprintln!("Stopping, press Ctrl-C again to stop straight away");
+        handled.cancel();
+    });
+
+    let token = match time_limit {
+        Some(time_limit) => token.with_timeout(time_limit),
+        None => token,
+    };
+    let _ = TOKEN.set(token);
+}
+
+pub fn token() -> &'static CancellationToken {
+    TOKEN.get_or_init(CancellationToken::new)
+}
+
+pub fn is_cancelled() -> bool {
+    token().is_cancelled()
+}
+
+pub fn check() -> Result<(), CancelError> {
+    token().check()
+}
@@ -0,0 +1,175 @@
+use crate::args::GraphFormat;
+use lib_pngme::view::PngView;
+
+// Drawing where each chunk sits in a PNG file, to teach the format or to spot something odd
+// at a glance, eg a huge private chunk or text after the image data. Critical chunks are
+// blue, ancillary chunks orange, and a chunk whose CRC is wrong is outlined in red.
+//
+// The SVG is a bar of the whole file, each chunk as wide as the bytes it takes up, above a
+// row per chunk at the same scale, so chunks too small to see in the bar can still be found.
+// The dot output is the chunks in file order for Graphviz, each box's width in proportion to
+// its size.
+
+const SIGNATURE_LENGTH: usize = 8;
+// The length, chunk type and CRC around a chunk's data
+const CHUNK_OVERHEAD: usize = 12;
+
+const SIGNATURE_COLOUR: &str = "#bab0ac";
+const CRITICAL_COLOUR: &str = "#4e79a7";
+const ANCILLARY_COLOUR: &str = "#f28e2b";
+const BAD_CRC_COLOUR: &str = "#e15759";
+
+const LABEL_WIDTH: f64 = 300.0;
+const BAR_WIDTH: f64 = 660.0;
+const BAR_HEIGHT: f64 = 30.0;
+const ROW_HEIGHT: f64 = 18.0;
+
+// The signature or a chunk, with the bytes it takes up in the file
+struct Block {
+    name: String,
+    offset: usize,
+    size: usize,
+    colour: &'static str,
+    crc_valid: bool,
+}
+
+impl Block {
+    fn end(&self) -> usize {
+        self.offset + self.size
+    }
+
+    fn details(&self) -> Vec<String> {
+        let mut details = vec![
+            format!("at {}", self.offset),
+            format!("{} bytes", self.size),
+        ];
+        if !self.crc_valid {
+            details.push(String::from("CRC is wrong"));
+        }
+        details
+    }
+
+    fn describe(&self) -> String {
+        format!("{} {}", self.name, self.details().join(", "))
+    }
+}
+
+fn blocks(png: &PngView) -> Vec<Block> {
+    let signature = Block {
+        name: String::from("signature"),
+        offset: 0,
+        size: SIGNATURE_LENGTH,
+        colour: SIGNATURE_COLOUR,
+        crc_valid: true,
+    };
+    let chunks = png.chunks().iter().map(|chunk| Block {
+        name: chunk.chunk_type().to_string(),
+        offset: chunk.offset(),
+        size: CHUNK_OVERHEAD + chunk.data().len(),
+        colour: if chunk.chunk_type().is_critical() {
+            CRITICAL_COLOUR
+        } else {
+            ANCILLARY_COLOUR
+        },
+        crc_valid: chunk.verify().is_ok(),
+    });
+
+    std::iter::once(signature).chain(chunks).collect()
+}
+
+pub fn render(png: &PngView, format: GraphFormat) -> String {
+    let blocks = blocks(png);
+    match format {
+        GraphFormat::Svg => svg(&blocks),
+        GraphFormat::Dot => dot(&blocks),
+    }
+}
+
+fn svg(blocks: &[Block]) -> String {
+    let file_size = blocks.iter().map(Block::end).max().unwrap_or(0).max(1);
+    let scale = BAR_WIDTH / file_size as f64;
+    let x = |block: &Block| LABEL_WIDTH + block.offset as f64 * scale;
+    // Chunks too small to see at this scale are drawn a pixel wide
+    let width = |block: &Block| (block.size as f64 * scale).max(1.0);
+    let outline = |block: &Block| {
+        if block.crc_valid {
+            String::new()
+        } else {
+            format!(r#" stroke="{}" stroke-width="2""#, BAD_CRC_COLOUR)
+        }
+    };
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" \
+         font-family=\"monospace\" font-size=\"12\">\n",
+        LABEL_WIDTH + BAR_WIDTH + 10.0,
+        BAR_HEIGHT + ROW_HEIGHT * (blocks.len() + 1) as f64
+    );
+    svg.push_str(&format!(
+        "<text x=\"0\" y=\"{:.0}\">{} chunks, {} bytes</text>\n",
+        BAR_HEIGHT / 2.0 + 4.0,
+        blocks.len() - 1,
+        file_size
+    ));
+    for block in blocks.iter() {
+        svg.push_str(&format!(
+            "<rect x=\"{:.2}\" y=\"0\" width=\"{:.2}\" height=\"{:.0}\" fill=\"{}\"{}><title>{}</title></rect>\n",
+            x(block),
+            width(block),
+            BAR_HEIGHT,
+            block.colour,
+            outline(block),
+            block.describe()
+        ));
+    }
+    for (row, block) in blocks.iter().enumerate() {
+        let y = BAR_HEIGHT + ROW_HEIGHT * (row + 1) as f64;
+        svg.push_str(&format!(
+            "<text x=\"0\" y=\"{:.0}\">{}</text>\n",
+            y - 4.0,
+            block.describe()
+        ));
+        svg.push_str(&format!(
+            "<rect x=\"{:.2}\" y=\"{:.0}\" width=\"{:.2}\" height=\"{:.0}\" fill=\"{}\"{}/>\n",
+            x(block),
+            y - ROW_HEIGHT + 4.0,
+            width(block),
+            ROW_HEIGHT - 6.0,
+            block.colour,
+            outline(block)
+        ));
+    }
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+fn dot(blocks: &[Block]) -> String {
+    let file_size = blocks.iter().map(Block::end).max().unwrap_or(0).max(1);
+
+    let mut dot = String::from(
+        "digraph layout {\n    rankdir=LR;\n    node [shape=box, style=filled, fontname=monospace];\n",
+    );
+    for (index, block) in blocks.iter().enumerate() {
+        let outline = if block.crc_valid {
+            String::new()
+        } else {
+            format!(", color=\"{}\", penwidth=3", BAD_CRC_COLOUR)
+        };
+        dot.push_str(&format!(
+            "    block{} [label=\"{}\\n{}\", fillcolor=\"{}\", width={:.2}{}];\n",
+            index,
+            block.name,
+            block.details().join("\\n"),
+            block.colour,
+            0.75 + 6.0 * block.size as f64 / file_size as f64,
+            outline
+        ));
+    }
+    for index in 1..blocks.len() {
+        dot.push_str(&format!("    block{} -> block{};\n", index - 1, index));
+    }
+    dot.push_str("}\n");
+
+    dot
+}
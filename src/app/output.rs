@@ -0,0 +1,180 @@
+use anyhow::Context;
+use lib_pngme::chunk_type::ChunkType;
+use lib_pngme::payload::Payload;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Where command output goes, and how messages which aren't text are written out.
+//
+// Printed to a terminal binary data garbles the screen, so only a summary of it is shown
+// there unless raw output is asked for. Written to a file or piped it is output exactly as
+// stored.
+
+// Well known file signatures, to say what binary data is when it has no MIME type
+const SIGNATURES: [(&[u8], &str); 7] = [
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF8", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"\x28\xb5\x2f\xfd", "application/zstd"),
+];
+
+// How many bytes of the message the summary shows
+const PREVIEW_LENGTH: usize = 16;
+
+// The MIME type recorded with the message, or one guessed from its first bytes
+pub fn mime_type(payload: &Payload) -> &str {
+    payload
+        .mime()
+        .or_else(|| {
+            SIGNATURES
+                .iter()
+                .find(|(signature, _)| payload.body().starts_with(signature))
+                .map(|(_, mime)| *mime)
+        })
+        .unwrap_or("application/octet-stream")
+}
+
+pub fn summary(chunk_type: &ChunkType, payload: &Payload) -> String {
+    let body = payload.body();
+    let preview: Vec<String> = body
+        .iter()
+        .take(PREVIEW_LENGTH)
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    format!(
+        "Message in {} is {} bytes of binary data ({}), starting {}{}",
+        chunk_type,
+        body.len(),
+        mime_type(payload),
+        preview.join(" "),
+        if body.len() > PREVIEW_LENGTH {
+            " ..."
+        } else {
+            ""
+        }
+    )
+}
+
+// Write the message as stored to `output`, or to stdout. A terminal only gets the summary
+// unless `raw` is set.
+pub fn write_binary(
+    chunk_type: &ChunkType,
+    payload: &Payload,
+    output: Option<&Path>,
+    raw: bool,
+) -> anyhow::Result<()> {
+    match output {
+        Some(output) => {
+            println!(
+                "Writing {} bytes of {} to {:?}",
+                payload.body().len(),
+                mime_type(payload),
+                output
+            );
+            fs::write(output, payload.body())
+                .with_context(|| format!("Failed to write file {:?}", output))
+        }
+        None if io::stdout().is_terminal() && !raw => {
+            println!("{}", summary(chunk_type, payload));
+            eprintln!("hint: Write it to a file with --output, or use --raw to print it anyway");
+            Ok(())
+        }
+        None => {
+            let mut stdout = io::stdout();
+            stdout
+                .write_all(payload.body())
+                .and_then(|_| stdout.flush())
+                .context("Failed to write the message")
+        }
+    }
+}
+
+// Long listings, eg `print`, go through a pager when stdout is a terminal, the way git does.
+// The pager is $PAGER, or `less` when it isn't set, and LESS defaults to FRX so output which
+// fits on the screen is printed as normal. Setting PAGER to `cat` or nothing turns it off,
+// as does --no-pager.
+static PAGER: AtomicBool = AtomicBool::new(true);
+
+pub fn set_pager(enabled: bool) {
+    PAGER.store(enabled, Ordering::Relaxed);
+}
+
+// Where a command writes its output, stdout or the stdin of the pager
+pub enum Output {
+    Stdout(io::Stdout),
+    Pager(Child),
+}
+
+impl Output {
+    // The output for a command whose output can be long
+    pub fn paged() -> Output {
+        if !PAGER.load(Ordering::Relaxed) || !io::stdout().is_terminal() {
+            return Output::Stdout(io::stdout());
+        }
+
+        let pager = env::var("PAGER").unwrap_or_else(|_| String::from("less"));
+        let mut words = pager.split_whitespace();
+        let program = match words.next() {
+            Some(program) if program != "cat" => program,
+            _ => return Output::Stdout(io::stdout()),
+        };
+        let mut command = Command::new(program);
+        command.args(words).stdin(Stdio::piped());
+        if env::var_os("LESS").is_none() {
+            command.env("LESS", "FRX");
+        }
+
+        // Without a pager the output is still worth having
+        match command.spawn() {
+            Ok(child) => Output::Pager(child),
+            Err(_) => Output::Stdout(io::stdout()),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(stdout) => stdout.write(buf),
+            Output::Pager(child) => match child.stdin.as_mut() {
+                // Quitting the pager early closes the pipe, the rest of the output isn't wanted
+                Some(stdin) => match stdin.write(buf) {
+                    Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(buf.len()),
+                    result => result,
+                },
+                None => Ok(buf.len()),
+            },
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.flush(),
+            Output::Pager(child) => match child.stdin.as_mut() {
+                Some(stdin) => match stdin.flush() {
+                    Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                    result => result,
+                },
+                None => Ok(()),
+            },
+        }
+    }
+}
+
+// The pager is left to show the output until the user quits it
+impl Drop for Output {
+    fn drop(&mut self) {
+        if let Output::Pager(child) = self {
+            drop(child.stdin.take());
+            let _ = child.wait();
+        }
+    }
+}
@@ -1,20 +1,146 @@
+mod archive;
 mod args;
+mod bench;
+mod capabilities;
+mod charset;
+mod cloud;
 mod commands;
+mod diff;
+mod download;
+mod errors;
+mod explain;
+mod fuzzy;
+mod hook;
+mod index;
+mod interactive;
+mod interop;
+mod interrupt;
+mod layout;
+mod output;
+mod qr;
+mod report;
+mod sniff;
+mod spec;
+mod stats;
+mod structured;
+mod units;
+mod walk;
+mod wizard;
 
-use crate::args::ApplicationArguments;
+use crate::args::{ApplicationArguments, Command, OutputFormat};
+use clap::Parser;
 use commands::{
-    execute_decode, execute_encode, execute_identify_text, execute_print, execute_remove,
+    execute_apng, execute_append_to, execute_assert_pixels_equal, execute_background,
+    execute_bench, execute_capabilities, execute_carve, execute_decode, execute_decode_all,
+    execute_dedupe, execute_doctor, execute_encode, execute_explain, execute_extract_from,
+    execute_format_spec, execute_gc, execute_gen_fixtures, execute_history, execute_hook,
+    execute_identify_text, execute_index, execute_interop_check, execute_inventory, execute_layout,
+    execute_lint, execute_list, execute_print, execute_redact, execute_remove, execute_size_diff,
+    execute_stamp_source, execute_stats, execute_strip, execute_text, execute_trace,
+    execute_transparency, execute_verify, execute_xcheck,
 };
-use structopt::StructOpt;
+use lib_pngme::cancel::CancelError;
+use lib_pngme::inflate::{self, InflateLimits};
+use lib_pngme::png::PngError;
+use std::process;
 
-fn main() -> anyhow::Result<()> {
-    let args = ApplicationArguments::from_args();
+fn main() {
+    let mut args = ApplicationArguments::parse();
+    if args.json {
+        let name = args.command.name();
+        match args.command.format_mut() {
+            Some(format) => *format = OutputFormat::Json,
+            None => {
+                eprintln!("Error: {} has no --format, so can't output json", name);
+                if let Command::Encode(_) = args.command {
+                    eprintln!("hint: Store a JSON message with --as json");
+                }
+                process::exit(1);
+            }
+        }
+    }
+    inflate::set_limits(
+        InflateLimits::default()
+            .max_size(args.max_inflate_size)
+            .max_ratio(args.max_inflate_ratio),
+    );
+    let non_interactive = args.non_interactive || interactive::enabled("PNGME_NON_INTERACTIVE");
+    interactive::set_non_interactive(non_interactive);
+    output::set_pager(!args.no_pager && !non_interactive);
+    interrupt::install(args.time_limit);
+    commands::set_assume_png(args.assume_png);
+
+    // Running the command consumes it, so take what the usage statistics need first
+    let name = args.command.name();
+    let file_path = args.command.file_path().cloned();
+    let result = execute(args.command);
+    stats::record(name, file_path.as_deref());
+
+    if let Err(error) = result {
+        eprintln!("Error: {:?}", error);
+        for hint in errors::hints(&error) {
+            eprintln!("hint: {}", hint);
+        }
+        let cancelled = error.chain().any(|cause| {
+            matches!(
+                cause.downcast_ref::<CancelError>(),
+                Some(CancelError::Cancelled)
+            ) || matches!(
+                cause.downcast_ref::<PngError>(),
+                Some(PngError::Cancelled(CancelError::Cancelled))
+            )
+        });
+        process::exit(if cancelled {
+            interrupt::INTERRUPTED_EXIT_CODE
+        } else {
+            1
+        });
+    }
+}
 
-    match args.command {
-        args::Command::Encode(args) => execute_encode(args),
-        args::Command::Decode(args) => execute_decode(args),
-        args::Command::Remove(args) => execute_remove(args),
-        args::Command::IdentifyText(args) => execute_identify_text(args),
-        args::Command::Print(args) => execute_print(args),
+fn execute(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Encode(args) => execute_encode(args),
+        Command::Decode(args) => execute_decode(args),
+        Command::DecodeAll(args) => execute_decode_all(args),
+        Command::Remove(args) => execute_remove(args),
+        Command::Redact(args) => execute_redact(args),
+        Command::Strip(args) => execute_strip(args),
+        Command::Dedupe(args) => execute_dedupe(args),
+        Command::Background(args) => execute_background(args),
+        Command::Transparency(args) => execute_transparency(args),
+        Command::Apng(args) => execute_apng(args),
+        Command::History(args) => execute_history(args),
+        Command::Text(args) => execute_text(args),
+        Command::IdentifyText(args) => execute_identify_text(args),
+        Command::Print(args) => execute_print(args),
+        Command::List(args) => execute_list(args),
+        Command::Verify(args) => execute_verify(args),
+        Command::Doctor(args) => execute_doctor(args),
+        Command::Lint(args) => execute_lint(args),
+        Command::InteropCheck(args) => execute_interop_check(args),
+        Command::Xcheck(args) => execute_xcheck(args),
+        Command::Inventory(args) => execute_inventory(args),
+        Command::Index(args) => execute_index(args),
+        Command::Layout(args) => execute_layout(args),
+        Command::SizeDiff(args) => execute_size_diff(args),
+        Command::Hook(args) => execute_hook(args),
+        Command::StampSource(args) => execute_stamp_source(args),
+        Command::Trace(args) => execute_trace(args),
+        Command::AssertPixelsEqual(args) => execute_assert_pixels_equal(args),
+        Command::Explain(args) => execute_explain(args),
+        Command::FormatSpec(args) => execute_format_spec(args),
+        Command::Capabilities(args) => execute_capabilities(args),
+        Command::GenFixtures(args) => execute_gen_fixtures(args),
+        Command::Bench(args) => execute_bench(args),
+        Command::Stats(args) => execute_stats(args),
+        Command::Gc(args) => execute_gc(args),
+        Command::Carve(args) => execute_carve(args),
+        Command::AppendTo(args) => execute_append_to(args),
+        Command::ExtractFrom(args) => execute_extract_from(args),
+        Command::Wizard => match wizard::run()? {
+            Some(command) => execute(command),
+            None => Ok(()),
+        },
     }
 }
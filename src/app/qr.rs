@@ -0,0 +1,43 @@
+use std::path::Path;
+
+// Render the message as a QR code made of unicode half blocks, so it can be
+// scanned straight off the terminal
+#[cfg(feature = "qr")]
+pub fn render_terminal(message: &str) -> anyhow::Result<String> {
+    use anyhow::Context;
+    use qrcode::render::unicode::Dense1x2;
+    use qrcode::QrCode;
+
+    let code = QrCode::new(message.as_bytes()).context("Message is too long for a QR code")?;
+
+    // Terminals are usually light text on a dark background, so invert the colours
+    // to keep the quiet zone light, which phone cameras need to find the code
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
+
+#[cfg(feature = "qr")]
+pub fn write_png(message: &str, output_file: &Path) -> anyhow::Result<()> {
+    use anyhow::Context;
+    use image::Luma;
+    use qrcode::QrCode;
+
+    let code = QrCode::new(message.as_bytes()).context("Message is too long for a QR code")?;
+    code.render::<Luma<u8>>()
+        .build()
+        .save(output_file)
+        .with_context(|| format!("Failed to write QR code to {:?}", output_file))
+}
+
+#[cfg(not(feature = "qr"))]
+pub fn render_terminal(_message: &str) -> anyhow::Result<String> {
+    anyhow::bail!("pngme was built without QR code support, rebuild with `--features qr`")
+}
+
+#[cfg(not(feature = "qr"))]
+pub fn write_png(_message: &str, _output_file: &Path) -> anyhow::Result<()> {
+    anyhow::bail!("pngme was built without QR code support, rebuild with `--features qr`")
+}
@@ -0,0 +1,248 @@
+use crate::args::OutputFormat;
+use serde_json::{Map, Value};
+
+// Rendering command results in whichever format was asked for. Commands describe their
+// results as JSON values, and every format is produced from those, so a command gets yaml,
+// csv and table output as soon as it has json. The JSON field names are the schema scripts
+// rely on, so fields should only ever be added.
+//
+// csv and table output are rows and columns. Nested values go in a single cell as JSON.
+
+// Results reported as a stream of records, eg one per file, each with the same fields
+pub struct Records {
+    format: OutputFormat,
+    written: usize,
+    // Tables are only written once every record is known, to line up the columns
+    table: Vec<Value>,
+}
+
+impl Records {
+    pub fn new(format: OutputFormat) -> Records {
+        Records {
+            format,
+            written: 0,
+            table: vec![],
+        }
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    pub fn push(&mut self, record: Value) {
+        match self.format {
+            OutputFormat::Json => println!("{}", record),
+            OutputFormat::Yaml => print!("{}", yaml_item(&record, 0)),
+            OutputFormat::Csv => {
+                let (columns, rows) = columns_and_rows(&[record]);
+                if self.written == 0 {
+                    println!("{}", csv_line(&columns));
+                }
+                for row in rows {
+                    println!("{}", csv_line(&row));
+                }
+            }
+            OutputFormat::Table | OutputFormat::Human => self.table.push(record),
+        }
+        self.written += 1;
+    }
+
+    pub fn finish(self) {
+        if matches!(self.format, OutputFormat::Table | OutputFormat::Human) {
+            print!("{}", table(&self.table));
+        }
+    }
+}
+
+// A single result, eg a report about one file. `rows` is the JSON pointer to the array
+// within it shown as rows in csv and table output, the table shows the other fields above.
+pub fn document(value: &Value, rows: &str, format: OutputFormat) -> String {
+    let records = match value.pointer(rows) {
+        Some(Value::Array(items)) => items.clone(),
+        // An object of named values, eg features, is a row per name
+        Some(Value::Object(fields)) => fields
+            .iter()
+            .map(|(name, value)| {
+                let mut row = Map::new();
+                row.insert(String::from("name"), Value::from(name.clone()));
+                row.insert(String::from("value"), value.clone());
+                Value::Object(row)
+            })
+            .collect(),
+        _ => vec![],
+    };
+
+    match format {
+        OutputFormat::Json => value.to_string(),
+        OutputFormat::Yaml => yaml(value, 0).trim_end().to_string(),
+        OutputFormat::Csv => {
+            let (columns, rows) = columns_and_rows(&records);
+            let mut lines = vec![csv_line(&columns)];
+            lines.extend(rows.iter().map(|row| csv_line(row)));
+            lines.join("\n")
+        }
+        OutputFormat::Table | OutputFormat::Human => {
+            let mut output = String::new();
+            if let Value::Object(fields) = value {
+                for (name, field) in fields.iter() {
+                    if !rows.ends_with(&format!("/{}", name)) {
+                        output.push_str(&format!("{}: {}\n", name, cell(field)));
+                    }
+                }
+            }
+            output.push_str(&table(&records));
+            output.trim_end().to_string()
+        }
+    }
+}
+
+// Every field found in the records as the columns, in the order first seen, and each
+// record's cells in that order
+fn columns_and_rows(records: &[Value]) -> (Vec<String>, Vec<Vec<String>>) {
+    let mut columns: Vec<String> = vec![];
+    for record in records.iter() {
+        match record {
+            Value::Object(fields) => {
+                for name in fields.keys() {
+                    if !columns.contains(name) {
+                        columns.push(name.clone());
+                    }
+                }
+            }
+            _ if columns.is_empty() => columns.push(String::from("value")),
+            _ => {}
+        }
+    }
+
+    let rows = records
+        .iter()
+        .map(|record| match record {
+            Value::Object(fields) => columns
+                .iter()
+                .map(|column| fields.get(column).map(cell).unwrap_or_default())
+                .collect(),
+            value => vec![cell(value)],
+        })
+        .collect();
+
+    (columns, rows)
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn csv_line(cells: &[String]) -> String {
+    cells
+        .iter()
+        .map(|cell| {
+            if cell.contains(&[',', '"', '\n', '\r'][..]) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.clone()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn table(records: &[Value]) -> String {
+    let (columns, rows) = columns_and_rows(records);
+    if columns.is_empty() {
+        return String::new();
+    }
+
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            rows.iter()
+                .map(|row| row[index].chars().count())
+                .chain(std::iter::once(column.chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |cells: &[String]| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        format!("{}\n", padded.join("  ").trim_end())
+    };
+
+    let mut output = line(&columns);
+    let rules: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    output.push_str(&line(&rules));
+    for row in rows.iter() {
+        output.push_str(&line(row));
+    }
+
+    output
+}
+
+// YAML for a JSON value. Strings are always quoted, JSON strings are valid YAML, so values
+// like `no` or `1.0` keep their type.
+fn yaml(value: &Value, indent: usize) -> String {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(fields) if !fields.is_empty() => fields
+            .iter()
+            .map(|(name, field)| {
+                if is_collection(field) {
+                    format!("{}{}:\n{}", pad, yaml_key(name), yaml(field, indent + 2))
+                } else {
+                    format!("{}{}: {}\n", pad, yaml_key(name), yaml_scalar(field))
+                }
+            })
+            .collect(),
+        Value::Array(items) if !items.is_empty() => {
+            items.iter().map(|item| yaml_item(item, indent)).collect()
+        }
+        value => format!("{}{}\n", pad, yaml_scalar(value)),
+    }
+}
+
+// A list item, collections start on the same line as the dash
+fn yaml_item(value: &Value, indent: usize) -> String {
+    if !is_collection(value) {
+        return format!("{}- {}\n", " ".repeat(indent), yaml_scalar(value));
+    }
+
+    let nested = yaml(value, indent + 2);
+    format!("{}- {}", " ".repeat(indent), &nested[indent + 2..])
+}
+
+fn is_collection(value: &Value) -> bool {
+    match value {
+        Value::Object(fields) => !fields.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
+fn yaml_scalar(value: &Value) -> String {
+    match value {
+        Value::Null => String::from("null"),
+        Value::Object(_) => String::from("{}"),
+        Value::Array(_) => String::from("[]"),
+        value => value.to_string(),
+    }
+}
+
+fn yaml_key(name: &str) -> String {
+    let plain = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if plain {
+        name.to_string()
+    } else {
+        Value::from(name).to_string()
+    }
+}
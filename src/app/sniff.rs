@@ -0,0 +1,99 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use lib_pngme::inflate;
+
+// A message which was only readable after unwrapping it
+pub struct Sniffed {
+    pub text: String,
+    // The wrappers which were removed, outermost first
+    pub steps: Vec<&'static str>,
+}
+
+// Wrappers can be nested, eg base64 encoded gzip data, but only so deep
+const MAX_LAYERS: usize = 4;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+// Try to turn unreadable data into text by removing known compression and encoding wrappers.
+// Returns None if the data is already text, or if no wrapper produced text. Wrappers which
+// inflate past the inflate limits, eg decompression bombs, are left as they are.
+pub fn sniff(data: &[u8]) -> Option<Sniffed> {
+    let mut data = data.to_vec();
+    let mut steps = vec![];
+
+    for _ in 0..MAX_LAYERS {
+        match unwrap_layer(&data) {
+            Some((step, unwrapped)) => {
+                steps.push(step);
+                data = unwrapped;
+            }
+            None => break,
+        }
+    }
+
+    if steps.is_empty() {
+        return None;
+    }
+
+    String::from_utf8(data)
+        .ok()
+        .map(|text| Sniffed { text, steps })
+}
+
+fn unwrap_layer(data: &[u8]) -> Option<(&'static str, Vec<u8>)> {
+    if data.starts_with(&GZIP_MAGIC) {
+        return inflate::gzip(data, &inflate::limits())
+            .ok()
+            .map(|d| ("gzip", d));
+    }
+    if is_zlib(data) {
+        return inflate::zlib(data, &inflate::limits())
+            .ok()
+            .map(|d| ("zlib", d));
+    }
+    if data.starts_with(&ZSTD_MAGIC) {
+        return decode_zstd(data).map(|d| ("zstd", d));
+    }
+
+    // Text is only treated as base64 if it hides one of the wrappers above,
+    // otherwise ordinary messages such as `abcd` would be decoded
+    let decoded = decode_base64(data)?;
+    if decoded.starts_with(&GZIP_MAGIC) || is_zlib(&decoded) || decoded.starts_with(&ZSTD_MAGIC) {
+        Some(("base64", decoded))
+    } else {
+        None
+    }
+}
+
+// A zlib stream starts with a 2 byte header, where the compression method is deflate (8)
+// and the header is a multiple of 31
+fn is_zlib(data: &[u8]) -> bool {
+    data.len() >= 2
+        && data[0] & 0x0f == 8
+        && (u16::from(data[0]) << 8 | u16::from(data[1])) % 31 == 0
+}
+
+fn decode_base64(data: &[u8]) -> Option<Vec<u8>> {
+    let text: Vec<u8> = data
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    if text.len() < 8 || !text.len().is_multiple_of(4) {
+        return None;
+    }
+
+    STANDARD.decode(&text).ok()
+}
+
+#[cfg(feature = "zstd")]
+fn decode_zstd(data: &[u8]) -> Option<Vec<u8>> {
+    let decoder = zstd::stream::Decoder::new(data).ok()?;
+    inflate::inflate(decoder, data.len(), &inflate::limits()).ok()
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decode_zstd(_data: &[u8]) -> Option<Vec<u8>> {
+    None
+}
@@ -0,0 +1,197 @@
+use lib_pngme::history::HISTORY_TYPE;
+use lib_pngme::payload::{Payload, PayloadEncoding};
+use lib_pngme::pipeline::{self, Transform};
+use lib_pngme::provenance::SOURCE_TYPE;
+use lib_pngme::trailer;
+use serde_json::{json, Value};
+
+// The on-disk formats pngme writes, built from the constants the code reads and writes
+// them with, so the description can't drift from what pngme actually does
+
+struct Field {
+    name: &'static str,
+    length: usize,
+    description: String,
+}
+
+fn header_fields() -> Vec<Field> {
+    let fields = vec![
+        Field {
+            name: "magic",
+            length: Payload::MAGIC.len(),
+            description: format!("`{}`", String::from_utf8_lossy(&Payload::MAGIC)),
+        },
+        Field {
+            name: "version",
+            length: 1,
+            description: format!(
+                "{}, or {} when a MIME type follows the header",
+                Payload::HEADER_VERSION,
+                Payload::MIME_VERSION
+            ),
+        },
+        Field {
+            name: "encoding",
+            length: 1,
+            description: String::from("how the body is encoded, see encodings"),
+        },
+        Field {
+            name: "flags",
+            length: 1,
+            description: String::from("the transforms applied to the body, see transforms"),
+        },
+    ];
+    debug_assert_eq!(
+        fields.iter().map(|f| f.length).sum::<usize>(),
+        Payload::HEADER_LENGTH
+    );
+
+    fields
+}
+
+// Transforms in the order they are applied when encoding
+fn transforms() -> Vec<Box<dyn Transform>> {
+    let mut transforms = pipeline::transforms();
+    transforms.sort_by_key(|t| t.order());
+    transforms
+}
+
+pub fn json() -> Value {
+    let mut offset = 0;
+    let header: Vec<Value> = header_fields()
+        .into_iter()
+        .map(|field| {
+            let value = json!({
+                "name": field.name,
+                "offset": offset,
+                "length": field.length,
+                "description": field.description,
+            });
+            offset += field.length;
+            value
+        })
+        .collect();
+    let encodings: Vec<Value> = PayloadEncoding::ALL
+        .iter()
+        .map(|encoding| json!({ "name": encoding.to_string(), "value": encoding.to_byte() }))
+        .collect();
+    let transforms: Vec<Value> = transforms()
+        .iter()
+        .map(|t| json!({ "name": t.name(), "flag": t.flag() }))
+        .collect();
+
+    json!({
+        "pngme": env!("CARGO_PKG_VERSION"),
+        "payload": {
+            "legacy_version": Payload::LEGACY_VERSION,
+            "current_version": Payload::CURRENT_VERSION,
+            "header_length": Payload::HEADER_LENGTH,
+            "header": header,
+            "mime": {
+                "version": Payload::MIME_VERSION,
+                "layout": ["mime length, 1 byte", "MIME type, that many bytes of ASCII"],
+            },
+            "encodings": encodings,
+            "transforms": transforms,
+        },
+        "history": {
+            "chunk_type": HISTORY_TYPE,
+            "layout": ["chunk type, 4 bytes", "version, 4 bytes big-endian", "chunk data"],
+        },
+        "source": {
+            "chunk_type": SOURCE_TYPE,
+            "layout": ["SHA-256 of the source, 32 bytes", "size of the source, 8 bytes big-endian", "path of the source, UTF-8"],
+        },
+        "secret_share": {
+            "layout": ["threshold, 1 byte", "x coordinate, 1 byte, never 0", "one byte per secret byte"],
+        },
+        "trailer": {
+            "magic": String::from_utf8_lossy(&trailer::MAGIC).into_owned(),
+            "footer_length": trailer::FOOTER_LENGTH,
+            "layout": ["payload", "payload length, 8 bytes big-endian", "CRC-32 of the payload, 4 bytes big-endian", "magic, 8 bytes"],
+        },
+    })
+}
+
+pub fn text() -> String {
+    let mut lines = vec![
+        format!("pngme {} on-disk formats", env!("CARGO_PKG_VERSION")),
+        String::new(),
+        String::from("Payload"),
+        format!(
+            "  Version {} payloads have no header, the chunk data is the message as UTF-8.",
+            Payload::LEGACY_VERSION
+        ),
+        format!(
+            "  Version {} and {} payloads start with a {} byte header, followed by the body:",
+            Payload::HEADER_VERSION,
+            Payload::MIME_VERSION,
+            Payload::HEADER_LENGTH
+        ),
+    ];
+    let mut offset = 0;
+    for field in header_fields() {
+        lines.push(format!(
+            "    {:>2}  {:<8} {} byte{}, {}",
+            offset,
+            field.name,
+            field.length,
+            if field.length == 1 { "" } else { "s" },
+            field.description
+        ));
+        offset += field.length;
+    }
+
+    lines.push(format!(
+        "  Version {} payloads have the MIME type between the header and the body,",
+        Payload::MIME_VERSION
+    ));
+    lines.push(String::from(
+        "  mime length, 1 byte, then the MIME type, that many bytes of ASCII",
+    ));
+
+    lines.push(String::from("  Encodings:"));
+    for encoding in PayloadEncoding::ALL.iter() {
+        lines.push(format!("    {}  {}", encoding.to_byte(), encoding));
+    }
+    lines.push(String::from(
+        "  Transforms, applied in this order when encoding and undone in reverse:",
+    ));
+    for transform in transforms() {
+        lines.push(format!(
+            "    {:#010b}  {}",
+            transform.flag(),
+            transform.name()
+        ));
+    }
+    lines.push(String::from(
+        "  Flags with no known transform must be rejected.",
+    ));
+
+    lines.push(String::new());
+    lines.push(format!("History ({} chunks)", HISTORY_TYPE));
+    lines.push(String::from(
+        "  chunk type, 4 bytes, then version, 4 bytes big-endian, then the chunk data",
+    ));
+
+    lines.push(String::new());
+    lines.push(format!("Source ({} chunks)", SOURCE_TYPE));
+    lines.push(String::from(
+        "  SHA-256 of the source, 32 bytes, then its size, 8 bytes big-endian, then its path as UTF-8",
+    ));
+
+    lines.push(String::new());
+    lines.push(String::from("Secret shares"));
+    lines.push(String::from(
+        "  threshold, 1 byte, then x coordinate, 1 byte and never 0, then one byte per secret byte",
+    ));
+
+    lines.push(String::new());
+    lines.push(String::from("Trailers (append-to)"));
+    lines.push(format!(
+        "  payload, then its length, 8 bytes big-endian, then its CRC-32, 4 bytes big-endian, then `{}`",
+        String::from_utf8_lossy(&trailer::MAGIC)
+    ));
+
+    lines.join("\n")
+}
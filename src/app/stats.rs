@@ -0,0 +1,129 @@
+use anyhow::Context;
+use lib_pngme::temporary;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Local usage statistics, counts of each command and the sizes of the files they were run on.
+//
+// Nothing is recorded unless the stats file exists, `pngme stats --enable` creates it.
+// The file is only ever read and written locally, it is never sent anywhere.
+
+const STATS_FILE: &str = ".pngme-stats.json";
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Usage {
+    pub runs: u64,
+    // Runs which read a file, and the total size of those files
+    pub files: u64,
+    pub total_bytes: u64,
+}
+
+impl Usage {
+    pub fn average_file_size(&self) -> Option<u64> {
+        self.total_bytes.checked_div(self.files)
+    }
+}
+
+// Where the stats are kept, `PNGME_STATS_FILE` or a file in the home directory
+pub fn path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("PNGME_STATS_FILE") {
+        return Some(PathBuf::from(path));
+    }
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(STATS_FILE))
+}
+
+pub fn enable() -> anyhow::Result<PathBuf> {
+    let path = path().context("Failed to find a home directory for the stats file")?;
+    if !path.is_file() {
+        save(&path, &BTreeMap::new())?;
+    }
+
+    Ok(path)
+}
+
+pub fn disable() -> anyhow::Result<Option<PathBuf>> {
+    match path() {
+        Some(path) if path.is_file() => {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove the stats file {:?}", path))?;
+            Ok(Some(path))
+        }
+        _ => Ok(None),
+    }
+}
+
+// Count a run of the command, if stats are enabled.
+// Stats must never get in the way of the command itself, so failures are ignored.
+pub fn record(command: &str, file_path: Option<&Path>) {
+    let path = match path() {
+        Some(path) if path.is_file() => path,
+        _ => return,
+    };
+    let mut stats = match load(&path) {
+        Ok(stats) => stats,
+        Err(_) => return,
+    };
+
+    let usage = stats.entry(command.to_string()).or_default();
+    usage.runs += 1;
+    if let Some(size) = file_path
+        .and_then(|p| fs::metadata(p).ok())
+        .map(|m| m.len())
+    {
+        usage.files += 1;
+        usage.total_bytes += size;
+    }
+
+    let _ = save(&path, &stats);
+}
+
+pub fn load(path: &Path) -> anyhow::Result<BTreeMap<String, Usage>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the stats file {:?}", path))?;
+    let value: Value = serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse the stats file {:?}", path))?;
+
+    let mut stats = BTreeMap::new();
+    if let Some(commands) = value.get("commands").and_then(|c| c.as_object()) {
+        for (command, usage) in commands {
+            let field = |name: &str| usage.get(name).and_then(|v| v.as_u64()).unwrap_or(0);
+            stats.insert(
+                command.clone(),
+                Usage {
+                    runs: field("runs"),
+                    files: field("files"),
+                    total_bytes: field("total_bytes"),
+                },
+            );
+        }
+    }
+
+    Ok(stats)
+}
+
+// Replaced in one step, so an interrupted write can't lose the existing stats
+fn save(path: &Path, stats: &BTreeMap<String, Usage>) -> anyhow::Result<()> {
+    let mut commands = serde_json::Map::new();
+    for (command, usage) in stats {
+        commands.insert(
+            command.clone(),
+            json!({
+                "runs": usage.runs,
+                "files": usage.files,
+                "total_bytes": usage.total_bytes,
+            }),
+        );
+    }
+
+    let json = json!({ "commands": commands }).to_string();
+    temporary::write_atomically(path, |file| file.write_all(json.as_bytes()))
+        .with_context(|| format!("Failed to write the stats file {:?}", path))?;
+
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use anyhow::{bail, Context};
+use lib_pngme::payload::{Payload, PayloadEncoding};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+// What is needed to turn a protobuf payload back into something readable
+#[cfg_attr(not(feature = "protobuf"), allow(dead_code))]
+pub struct ProtobufSchema<'a> {
+    // A serialized `FileDescriptorSet`, eg as output by `protoc --descriptor_set_out`
+    pub descriptor: &'a Path,
+    // The fully qualified message name, eg my.Build
+    pub message_type: &'a str,
+}
+
+// Convert the message given on the command line into a payload stored with the requested encoding.
+// Protobuf messages are binary, so for those the message is the path to the encoded message.
+pub fn encode_message(message: &str, encoding: PayloadEncoding) -> anyhow::Result<Payload> {
+    match encoding {
+        PayloadEncoding::Protobuf => {
+            let body = fs::read(message)
+                .with_context(|| format!("Failed to read protobuf message {:?}", message))?;
+            Ok(Payload::new(encoding, body))
+        }
+        PayloadEncoding::Utf8 => Ok(Payload::new(encoding, message.as_bytes().to_vec())),
+        _ => encode_json(message, encoding),
+    }
+}
+
+// Parse the JSON input and convert it into a payload stored with the requested encoding
+pub fn encode_json(json: &str, encoding: PayloadEncoding) -> anyhow::Result<Payload> {
+    let value: Value = serde_json::from_str(json).context("Message is not valid JSON")?;
+
+    let body = match encoding {
+        PayloadEncoding::Utf8 | PayloadEncoding::Json => serde_json::to_vec(&value)?,
+        PayloadEncoding::Cbor => {
+            let mut body = Vec::new();
+            ciborium::ser::into_writer(&value, &mut body)
+                .context("Failed to convert JSON to CBOR")?;
+            body
+        }
+        PayloadEncoding::MessagePack => {
+            rmp_serde::to_vec(&value).context("Failed to convert JSON to MessagePack")?
+        }
+        PayloadEncoding::Protobuf => {
+            bail!("JSON can't be converted to protobuf without a schema")
+        }
+        encoding => bail!("JSON can't be converted to {}", encoding),
+    };
+
+    Ok(Payload::new(encoding, body))
+}
+
+// Convert a structured payload back into JSON, regardless of how it was stored
+pub fn decode_to_json(payload: &Payload, schema: Option<&ProtobufSchema>) -> anyhow::Result<Value> {
+    let value = match payload.encoding() {
+        PayloadEncoding::Utf8 | PayloadEncoding::Json => {
+            serde_json::from_slice(payload.body()).context("Message is not valid JSON")?
+        }
+        PayloadEncoding::Cbor => ciborium::de::from_reader(payload.body())
+            .context("Failed to read CBOR payload as JSON")?,
+        PayloadEncoding::MessagePack => rmp_serde::from_slice(payload.body())
+            .context("Failed to read MessagePack payload as JSON")?,
+        PayloadEncoding::Protobuf => match schema {
+            Some(schema) => protobuf_to_json(payload.body(), schema)?,
+            None => bail!(
+                "Message is protobuf encoded, use --descriptor and --message-type to display it"
+            ),
+        },
+        encoding => bail!(
+            "Message is {} encoded, it can't be displayed as JSON",
+            encoding
+        ),
+    };
+
+    Ok(value)
+}
+
+#[cfg(feature = "protobuf")]
+fn protobuf_to_json(body: &[u8], schema: &ProtobufSchema) -> anyhow::Result<Value> {
+    use prost_reflect::{DescriptorPool, DynamicMessage};
+
+    let descriptor = fs::read(schema.descriptor)
+        .with_context(|| format!("Failed to read descriptor set {:?}", schema.descriptor))?;
+    let pool = DescriptorPool::decode(&descriptor[..])
+        .with_context(|| format!("{:?} is not a valid descriptor set", schema.descriptor))?;
+    let message_descriptor = pool
+        .get_message_by_name(schema.message_type)
+        .with_context(|| {
+            format!(
+                "Message type {} was not found in {:?}",
+                schema.message_type, schema.descriptor
+            )
+        })?;
+    let message = DynamicMessage::decode(message_descriptor, body)
+        .with_context(|| format!("Failed to decode message as {}", schema.message_type))?;
+
+    Ok(serde_json::to_value(&message)?)
+}
+
+#[cfg(not(feature = "protobuf"))]
+fn protobuf_to_json(_body: &[u8], _schema: &ProtobufSchema) -> anyhow::Result<Value> {
+    bail!("pngme was built without protobuf support, rebuild with `--features protobuf`")
+}
+
+// Produce the text to display for a payload, pretty printing it as JSON if requested
+pub fn display_payload(
+    payload: &Payload,
+    display_as: Option<PayloadEncoding>,
+    schema: Option<&ProtobufSchema>,
+) -> anyhow::Result<String> {
+    match display_as {
+        Some(PayloadEncoding::Json) => {
+            let value = decode_to_json(payload, schema)?;
+            Ok(serde_json::to_string_pretty(&value)?)
+        }
+        Some(PayloadEncoding::Utf8) | None => match payload.encoding() {
+            PayloadEncoding::Utf8 | PayloadEncoding::Json => {
+                Ok(String::from_utf8(payload.body().to_vec())?)
+            }
+            _ => Ok(serde_json::to_string(&decode_to_json(payload, schema)?)?),
+        },
+        Some(encoding) => bail!(
+            "Can only display a message as utf8 or json, not {}",
+            encoding
+        ),
+    }
+}
+
+// Turn a payload holding a JSON object into `PREFIX_KEY=value` lines for use as environment variables.
+// Values are single quoted when needed so the output is safe to `eval`.
+pub fn export_env(
+    payload: &Payload,
+    schema: Option<&ProtobufSchema>,
+    prefix: &str,
+) -> anyhow::Result<Vec<String>> {
+    let value = decode_to_json(payload, schema)?;
+    let object = match value.as_object() {
+        Some(object) => object,
+        None => bail!("Message must be a JSON object of keys and values to export it"),
+    };
+
+    let mut lines = vec![];
+    for (key, value) in object.iter() {
+        let name = format!("{}{}", prefix, env_name(key)).to_ascii_uppercase();
+        let value = match value {
+            Value::String(s) => s.clone(),
+            Value::Null => String::new(),
+            other => other.to_string(),
+        };
+        if value.contains('\n') {
+            bail!(
+                "Value of {} spans multiple lines, which can't be exported",
+                key
+            );
+        }
+
+        lines.push(format!("{}={}", name, shell_quote(&value)));
+    }
+
+    Ok(lines)
+}
+
+// Environment variable names are letters, digits and underscores
+fn env_name(key: &str) -> String {
+    key.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn shell_quote(value: &str) -> String {
+    let is_plain = value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "_-./:@%+,".contains(c));
+
+    if is_plain {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
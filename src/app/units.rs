@@ -0,0 +1,160 @@
+use std::time::Duration;
+
+// Sizes and durations given on the command line in human units, eg `--max-download-size 10MiB`
+// or `--timeout 30s`. Sizes are in powers of 1024 whether written KB or KiB, as in the rest
+// of pngme. A number on its own is bytes or seconds, as the flags took before units.
+
+const SIZE_UNITS: [(&str, usize); 10] = [
+    ("GIB", 1 << 30),
+    ("MIB", 1 << 20),
+    ("KIB", 1 << 10),
+    ("GB", 1 << 30),
+    ("MB", 1 << 20),
+    ("KB", 1 << 10),
+    ("G", 1 << 30),
+    ("M", 1 << 20),
+    ("K", 1 << 10),
+    ("B", 1),
+];
+
+// In milliseconds
+const DURATION_UNITS: [(&str, u64); 5] = [
+    ("ms", 1),
+    ("s", 1000),
+    ("m", 60 * 1000),
+    ("h", 60 * 60 * 1000),
+    ("d", 24 * 60 * 60 * 1000),
+];
+
+// The number and the multiplier of the first unit it ends with, longest units first so
+// `10MiB` isn't read as `10Mi` bytes
+fn split_unit<T: Copy>(s: &str, units: &[(&str, T)], default: T) -> (String, T) {
+    units
+        .iter()
+        .find_map(|(unit, multiplier)| {
+            s.strip_suffix(unit)
+                .map(|number| (number.trim().to_string(), *multiplier))
+        })
+        .unwrap_or_else(|| (s.to_string(), default))
+}
+
+// Parse a size such as 64K, 10MiB, 1GB or 4096
+pub fn parse_size(s: &str) -> Result<usize, String> {
+    let upper = s.trim().to_ascii_uppercase();
+    let (number, multiplier) = split_unit(&upper, &SIZE_UNITS, 1);
+
+    match number.parse::<usize>().ok().map(|n| n.checked_mul(multiplier)) {
+        Some(Some(0)) => Err(format!("Size `{}` must be greater than 0", s)),
+        Some(Some(size)) => Ok(size),
+        Some(None) => Err(format!("Size `{}` is too large", s)),
+        None => Err(format!(
+            "Invalid size `{}`, expected a number of bytes with an optional unit, eg 4096, 64K, 10MiB or 1GB",
+            s
+        )),
+    }
+}
+
+// Parse a duration such as 500ms, 30s, 5m, 1h, 2d or 30
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let trimmed = s.trim();
+    // `ms` is checked before `s` and `m`, units are lower case only so `M` can't be mistaken
+    let (number, multiplier) = split_unit(trimmed, &DURATION_UNITS, 1000);
+
+    match number.parse::<u64>().ok().map(|n| n.checked_mul(multiplier)) {
+        Some(Some(millis)) => Ok(Duration::from_millis(millis)),
+        Some(None) => Err(format!("Duration `{}` is too long", s)),
+        None => Err(format!(
+            "Invalid duration `{}`, expected a number of seconds with an optional unit, eg 30, 500ms, 30s, 5m or 1h",
+            s
+        )),
+    }
+}
+
+// The duration as it would be given on the command line
+pub fn format_duration(duration: Duration) -> String {
+    if duration.subsec_millis() == 0 {
+        format!("{}s", duration.as_secs())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size_units() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("1B"), Ok(1));
+        assert_eq!(parse_size("64K"), Ok(64 << 10));
+        assert_eq!(parse_size("64KB"), Ok(64 << 10));
+        assert_eq!(parse_size("64KiB"), Ok(64 << 10));
+        assert_eq!(parse_size("10M"), Ok(10 << 20));
+        assert_eq!(parse_size("10MB"), Ok(10 << 20));
+        assert_eq!(parse_size("10MiB"), Ok(10 << 20));
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        assert_eq!(parse_size("2GB"), Ok(2 << 30));
+        assert_eq!(parse_size("2GiB"), Ok(2 << 30));
+        assert_eq!(parse_size(" 10 mib "), Ok(10 << 20));
+    }
+
+    #[test]
+    fn test_parse_size_errors() {
+        assert_eq!(
+            parse_size("0"),
+            Err(String::from("Size `0` must be greater than 0"))
+        );
+        assert_eq!(
+            parse_size("0MB"),
+            Err(String::from("Size `0MB` must be greater than 0"))
+        );
+        let too_large = format!("{}G", usize::MAX);
+        assert_eq!(
+            parse_size(&too_large),
+            Err(format!("Size `{}` is too large", too_large))
+        );
+        for invalid in ["", "MB", "ten", "-1", "1.5MB", "10TB"].iter() {
+            assert!(parse_size(invalid).unwrap_err().starts_with("Invalid size"));
+        }
+    }
+
+    #[test]
+    fn test_parse_duration_units() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(5 * 60)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(60 * 60)));
+        assert_eq!(
+            parse_duration("2d"),
+            Ok(Duration::from_secs(2 * 24 * 60 * 60))
+        );
+        assert_eq!(parse_duration(" 30 s "), Ok(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_parse_duration_errors() {
+        let too_long = format!("{}d", u64::MAX);
+        assert_eq!(
+            parse_duration(&too_long),
+            Err(format!("Duration `{}` is too long", too_long))
+        );
+        for invalid in ["", "s", "soon", "-1s", "1.5s", "5M", "1w"].iter() {
+            assert!(parse_duration(invalid)
+                .unwrap_err()
+                .starts_with("Invalid duration"));
+        }
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(3600)), "3600s");
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1500ms");
+        assert_eq!(format_duration(Duration::from_secs(0)), "0s");
+        for given in ["30s", "500ms", "1h"].iter() {
+            let duration = parse_duration(given).unwrap();
+            assert_eq!(parse_duration(&format_duration(duration)), Ok(duration));
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::archive;
+use lib_pngme::temporary;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// The PNG files at the path. A file is returned as is, for a directory the PNG
+// files within it are found, descending into sub-directories if recursive, along with
+// any .zip, .tar and .tar.gz archives when asked for.
+// Files are sorted so the output is the same on every run.
+pub fn png_files(path: &Path, recursive: bool, archives: bool) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = vec![];
+    collect_png_files(path, recursive, archives, &mut files)?;
+    files.sort();
+
+    Ok(files)
+}
+
+fn collect_png_files(
+    directory: &Path,
+    recursive: bool,
+    archives: bool,
+    files: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                collect_png_files(&path, recursive, archives, files)?;
+            }
+        } else if has_png_extension(&path) || (archives && archive::is_archive(&path)) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+// Every file under the directory, whatever it is, eg to look for the source of an export.
+// Symlinks are returned rather than followed, and .git directories are skipped.
+pub fn all_files(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    collect_all_files(directory, &mut files)?;
+    files.sort();
+
+    Ok(files)
+}
+
+fn collect_all_files(directory: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if entry.file_name() != ".git" {
+                collect_all_files(&path, files)?;
+            }
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+// The temporary files left behind by writes which never finished, see `lib_pngme::temporary`
+pub fn temporary_files(directory: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
+    let mut files = if recursive {
+        all_files(directory)?
+    } else {
+        fs::read_dir(directory)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<PathBuf>>>()?
+    };
+    files.retain(|path| {
+        temporary::is_temporary(path)
+            && fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_file())
+    });
+    files.sort();
+
+    Ok(files)
+}
+
+fn has_png_extension(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.eq_ignore_ascii_case("png"))
+        .unwrap_or(false)
+}
@@ -0,0 +1,729 @@
+use crate::args::{
+    Apng, Background, Command, Decode, Encode, History, Hook, IdentifyText, Index, OutputFormat,
+    Print, Remove, Strip, Text, Transparency, Verify,
+};
+use crate::explain;
+use crate::interactive;
+use crate::units;
+use anyhow::bail;
+use lib_pngme::chunk_type::ChunkType;
+use lib_pngme::png::Png;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+#[cfg(feature = "http")]
+use std::time::Duration;
+
+const OPERATIONS: [(&str, &str); 7] = [
+    ("encode", "Add a message to the PNG"),
+    ("decode", "Read a message from the PNG"),
+    ("remove", "Remove a message from the PNG"),
+    ("strip", "Remove every ancillary chunk from the PNG"),
+    ("identify-text", "List the chunks which contain text"),
+    ("print", "Display information about the PNG and its chunks"),
+    (
+        "verify",
+        "Check the standard chunks of the PNG agree with each other",
+    ),
+];
+
+// Walk the user through building a command, one question at a time.
+// Returns the command to run, or None if the user decided not to run it.
+pub fn run() -> anyhow::Result<Option<Command>> {
+    println!("Welcome to pngme! Answer the questions below to build a command.");
+    println!();
+
+    let file_path = prompt_file_path()?;
+    let operation = prompt_operation()?;
+
+    let command = match operation {
+        "encode" => {
+            let chunk_type = prompt_chunk_type("Which chunk type should hold the message?")?;
+            let message = prompt_until("What message would you like to hide?", |input| {
+                if input.is_empty() {
+                    Err(String::from("The message can't be empty"))
+                } else {
+                    Ok(input.to_string())
+                }
+            })?;
+            Command::Encode(Encode {
+                file_path,
+                chunk_type,
+                message: Some(message),
+                output_file: prompt_output_file()?,
+                encoding: None,
+                mime: None,
+                compress: false,
+                transform: vec![],
+                skip_high_entropy: false,
+                upsert: false,
+                show_diff: false,
+                no_diff: false,
+                keep_history: None,
+                position: None,
+                split_secret: None,
+                share_across: vec![],
+                deterministic: false,
+                compat_version: None,
+                input_url: None,
+                #[cfg(feature = "http")]
+                max_download_size: 10 << 20,
+                #[cfg(feature = "http")]
+                timeout: Duration::from_secs(30),
+                expect_sha256: None,
+            })
+        }
+        "decode" => Command::Decode(Decode {
+            file_path,
+            chunk_type: Some(prompt_chunk_type("Which chunk type holds the message?")?),
+            type_option: None,
+            type_glob: None,
+            ignore_case: false,
+            all: false,
+            recursive: false,
+            archives: false,
+            format: OutputFormat::Human,
+            display_as: None,
+            descriptor: None,
+            message_type: None,
+            qr: false,
+            qr_png: None,
+            export_env: None,
+            combine: false,
+            no_sniff: false,
+            show_version: false,
+            output: None,
+            raw: false,
+        }),
+        "remove" => Command::Remove(Remove {
+            file_path,
+            chunk_type: Some(prompt_chunk_type("Which chunk type should be removed?")?),
+            output_file: prompt_output_file()?,
+            type_glob: None,
+            select: None,
+            ignore_case: false,
+            all: false,
+            output_option: None,
+            force: false,
+        }),
+        "strip" => {
+            let keep_color_management = prompt_until(
+                "Keep the chunks which affect how colours are rendered? [Y/n]",
+                |input| match input.to_ascii_lowercase().as_str() {
+                    "" | "y" | "yes" => Ok(true),
+                    "n" | "no" => Ok(false),
+                    _ => Err(String::from("Please answer y or n")),
+                },
+            )?;
+            Command::Strip(Strip {
+                file_path,
+                output_file: prompt_output_file()?,
+                keep_color_management,
+                select: None,
+            })
+        }
+        "identify-text" => Command::IdentifyText(IdentifyText {
+            file_path,
+            format: OutputFormat::Human,
+        }),
+        "verify" => Command::Verify(Verify { file_path }),
+        _ => Command::Print(Print {
+            file_path,
+            headers_only: false,
+            digest: false,
+            format: OutputFormat::Human,
+        }),
+    };
+
+    println!();
+    println!("The equivalent command, for use in scripts, is");
+    println!();
+    println!("    {}", command_line(&command));
+    println!();
+
+    let run_now = prompt_until("Run it now? [Y/n]", |input| {
+        match input.to_ascii_lowercase().as_str() {
+            "" | "y" | "yes" => Ok(true),
+            "n" | "no" => Ok(false),
+            _ => Err(String::from("Please answer y or n")),
+        }
+    })?;
+
+    if run_now {
+        Ok(Some(command))
+    } else {
+        Ok(None)
+    }
+}
+
+fn prompt_file_path() -> anyhow::Result<PathBuf> {
+    prompt_until("Which PNG file would you like to use?", |input| {
+        let path = PathBuf::from(input);
+        if !path.is_file() {
+            return Err(format!("{:?} isn't a file", path));
+        }
+
+        match Png::from_file(&path) {
+            Ok(png) => {
+                println!("Found a PNG with {} chunks", png.chunks().len());
+                Ok(path)
+            }
+            Err(e) => Err(format!("{:?} couldn't be read as a PNG: {}", path, e)),
+        }
+    })
+}
+
+fn prompt_operation() -> anyhow::Result<&'static str> {
+    println!();
+    for (index, (name, description)) in OPERATIONS.iter().enumerate() {
+        println!("  {}) {:<14} {}", index + 1, name, description);
+    }
+
+    prompt_until("What would you like to do?", |input| {
+        let by_number = input
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| OPERATIONS.get(i.wrapping_sub(1)));
+        let by_name = OPERATIONS.iter().find(|(name, _)| *name == input);
+
+        match by_number.or(by_name) {
+            Some((name, _)) => Ok(*name),
+            None => Err(format!(
+                "Please pick a number between 1 and {}",
+                OPERATIONS.len()
+            )),
+        }
+    })
+}
+
+fn prompt_chunk_type(question: &str) -> anyhow::Result<ChunkType> {
+    println!();
+    println!(
+        "Chunk types are 4 letters, and whether each letter is upper-case or lower-case matters:"
+    );
+    println!("  1st letter - upper-case is critical, lower-case is ancillary (safe for decoders to ignore)");
+    println!("  2nd letter - upper-case is public, lower-case is private (your own chunk)");
+    println!("  3rd letter - reserved, should be upper-case");
+    println!(
+        "  4th letter - upper-case is unsafe to copy, lower-case is safe to copy when editing"
+    );
+    println!("A good choice for hiding a message is something like ruSt");
+
+    prompt_until(question, |input| match ChunkType::from_str(input) {
+        Ok(chunk_type) => {
+            println!("{}", explain::explain(&chunk_type));
+            Ok(chunk_type)
+        }
+        Err(e) => Err(e.to_string()),
+    })
+}
+
+fn prompt_output_file() -> anyhow::Result<Option<PathBuf>> {
+    prompt_until(
+        "Where should the updated PNG be written? Leave empty to overwrite the input",
+        |input| {
+            if input.is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some(PathBuf::from(input)))
+            }
+        },
+    )
+}
+
+// Keep asking the question until the answer passes validation
+fn prompt_until<T, F>(question: &str, mut validate: F) -> anyhow::Result<T>
+where
+    F: FnMut(&str) -> Result<T, String>,
+{
+    if interactive::is_non_interactive() {
+        bail!(
+            "The wizard would ask {:?}, but pngme is running non-interactively, run the command directly instead",
+            question
+        );
+    }
+
+    loop {
+        print!("{} ", question);
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            bail!("No more input, stopping the wizard");
+        }
+
+        match validate(input.trim()) {
+            Ok(value) => return Ok(value),
+            Err(message) => println!("{}", message),
+        }
+    }
+}
+
+// Build the command line which would run the same command non-interactively
+fn command_line(command: &Command) -> String {
+    let mut parts = vec![String::from("pngme")];
+
+    match command {
+        Command::Encode(args) => {
+            parts.push(String::from("encode"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(args.chunk_type.to_string());
+            if let Some(message) = &args.message {
+                parts.push(message.clone());
+            }
+            if let Some(output_file) = &args.output_file {
+                parts.push(output_file.display().to_string());
+            }
+        }
+        Command::Decode(args) => {
+            parts.push(String::from("decode"));
+            parts.push(args.file_path.display().to_string());
+            if let Some(chunk_type) = args.chunk_type {
+                parts.push(chunk_type.to_string());
+            }
+        }
+        Command::DecodeAll(args) => {
+            parts.push(String::from("decode-all"));
+            parts.push(args.file_path.display().to_string());
+            if args.ancillary {
+                parts.push(String::from("--ancillary"));
+            }
+            if args.private {
+                parts.push(String::from("--private"));
+            }
+            if args.no_sniff {
+                parts.push(String::from("--no-sniff"));
+            }
+            if args.format != OutputFormat::Human {
+                parts.push(format!("--format={}", args.format));
+            }
+        }
+        Command::Remove(args) => {
+            parts.push(String::from("remove"));
+            parts.push(args.file_path.display().to_string());
+            if let Some(chunk_type) = args.chunk_type {
+                parts.push(chunk_type.to_string());
+            }
+            if let Some(output_file) = &args.output_file {
+                parts.push(output_file.display().to_string());
+            }
+            if args.all {
+                parts.push(String::from("--all"));
+            }
+            if args.force {
+                parts.push(String::from("--force"));
+            }
+        }
+        Command::Redact(args) => {
+            parts.push(String::from("redact"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(format!("--type={}", args.chunk_type));
+            if args.random {
+                parts.push(String::from("--random"));
+            }
+            if args.remove {
+                parts.push(String::from("--remove"));
+            }
+        }
+        Command::Strip(args) => {
+            parts.push(String::from("strip"));
+            parts.push(args.file_path.display().to_string());
+            if let Some(output_file) = &args.output_file {
+                parts.push(output_file.display().to_string());
+            }
+            if args.keep_color_management {
+                parts.push(String::from("--keep-color-management"));
+            }
+        }
+        Command::Doctor(args) => {
+            parts.push(String::from("doctor"));
+            parts.push(args.file_path.display().to_string());
+            if let Some(output_file) = &args.output_file {
+                parts.push(output_file.display().to_string());
+            }
+            if args.fix {
+                parts.push(String::from("--fix"));
+            }
+            if args.format != OutputFormat::Human {
+                parts.push(format!("--format={}", args.format));
+            }
+        }
+        Command::Lint(args) => {
+            parts.push(String::from("lint"));
+            parts.push(args.file_path.display().to_string());
+            if let Some(output_file) = &args.output_file {
+                parts.push(output_file.display().to_string());
+            }
+            if args.fix {
+                parts.push(String::from("--fix"));
+            }
+        }
+        Command::InteropCheck(args) => {
+            parts.push(String::from("interop-check"));
+            parts.push(args.file_path.display().to_string());
+            if args.format != OutputFormat::Human {
+                parts.push(format!("--format={}", args.format));
+            }
+        }
+        Command::AssertPixelsEqual(args) => {
+            parts.push(String::from("assert-pixels-equal"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(args.other_file_path.display().to_string());
+        }
+        Command::Inventory(args) => {
+            parts.push(String::from("inventory"));
+            parts.push(args.file_path.display().to_string());
+            if args.recursive {
+                parts.push(String::from("--recursive"));
+            }
+            if args.archives {
+                parts.push(String::from("--archives"));
+            }
+            if args.format != OutputFormat::Human {
+                parts.push(format!("--format={}", args.format));
+            }
+        }
+        Command::Hook(Hook::Install(args)) => {
+            parts.push(String::from("hook"));
+            parts.push(String::from("install"));
+            if args.force {
+                parts.push(String::from("--force"));
+            }
+        }
+        Command::Hook(Hook::Run) => {
+            parts.push(String::from("hook"));
+            parts.push(String::from("run"));
+        }
+        Command::StampSource(args) => {
+            parts.push(String::from("stamp-source"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(args.source.display().to_string());
+            if let Some(output_file) = &args.output_file {
+                parts.push(output_file.display().to_string());
+            }
+        }
+        Command::Trace(args) => {
+            parts.push(String::from("trace"));
+            parts.push(args.file_path.display().to_string());
+            if let Some(search) = &args.search {
+                parts.push(format!("--search={}", search.display()));
+            }
+        }
+        Command::SizeDiff(args) => {
+            parts.push(String::from("size-diff"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(args.other_file_path.display().to_string());
+            if args.all {
+                parts.push(String::from("--all"));
+            }
+            if args.format != OutputFormat::Human {
+                parts.push(format!("--format={}", args.format));
+            }
+        }
+        Command::Layout(args) => {
+            parts.push(String::from("layout"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(format!("--format={}", args.format));
+        }
+        Command::Index(Index::Build(args)) => {
+            parts.push(String::from("index"));
+            parts.push(String::from("build"));
+            parts.push(args.directory.display().to_string());
+            parts.push(format!("--db={}", args.db.display()));
+            if args.recursive {
+                parts.push(String::from("--recursive"));
+            }
+        }
+        Command::Index(Index::Query(args)) => {
+            parts.push(String::from("index"));
+            parts.push(String::from("query"));
+            parts.push(format!("--db={}", args.db.display()));
+            #[cfg(feature = "index")]
+            {
+                if let Some(chunk_type) = &args.chunk_type {
+                    parts.push(format!("--type={}", chunk_type));
+                }
+                if let Some(path) = &args.path {
+                    parts.push(format!("--path={}", path));
+                }
+                if let Some(sha256) = &args.sha256 {
+                    parts.push(format!("--sha256={}", sha256));
+                }
+                if args.files_only {
+                    parts.push(String::from("--files-only"));
+                }
+            }
+            if args.format != OutputFormat::Human {
+                parts.push(format!("--format={}", args.format));
+            }
+        }
+        Command::Xcheck(args) => {
+            parts.push(String::from("xcheck"));
+            parts.push(args.file_path.display().to_string());
+            if args.format != OutputFormat::Human {
+                parts.push(format!("--format={}", args.format));
+            }
+        }
+        Command::Dedupe(args) => {
+            parts.push(String::from("dedupe"));
+            parts.push(args.file_path.display().to_string());
+            if let Some(output_file) = &args.output_file {
+                parts.push(output_file.display().to_string());
+            }
+            if args.dry_run {
+                parts.push(String::from("--dry-run"));
+            }
+        }
+        Command::Background(Background::Set(args)) => {
+            parts.push(String::from("background"));
+            parts.push(String::from("set"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(args.color.to_string());
+            if let Some(output_file) = &args.output_file {
+                parts.push(output_file.display().to_string());
+            }
+        }
+        Command::Background(Background::Clear(args))
+        | Command::Transparency(Transparency::Clear(args)) => {
+            parts.push(match command {
+                Command::Background(_) => String::from("background"),
+                _ => String::from("transparency"),
+            });
+            parts.push(String::from("clear"));
+            parts.push(args.file_path.display().to_string());
+            if let Some(output_file) = &args.output_file {
+                parts.push(output_file.display().to_string());
+            }
+        }
+        Command::Apng(Apng::Info(args)) => {
+            parts.push(String::from("apng"));
+            parts.push(String::from("info"));
+            parts.push(args.file_path.display().to_string());
+        }
+        Command::Apng(Apng::ExtractFrame(args)) => {
+            parts.push(String::from("apng"));
+            parts.push(String::from("extract-frame"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(args.frame.to_string());
+            parts.push(args.output_file.display().to_string());
+        }
+        Command::Apng(Apng::SetPlays(args)) => {
+            parts.push(String::from("apng"));
+            parts.push(String::from("set-plays"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(args.plays.to_string());
+            if let Some(output_file) = &args.output_file {
+                parts.push(output_file.display().to_string());
+            }
+        }
+        Command::History(History::Show(args)) => {
+            parts.push(String::from("history"));
+            parts.push(String::from("show"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(args.chunk_type.to_string());
+        }
+        Command::History(History::Restore(args)) => {
+            parts.push(String::from("history"));
+            parts.push(String::from("restore"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(args.chunk_type.to_string());
+            parts.push(args.version.to_string());
+            if let Some(output_file) = &args.output_file {
+                parts.push(output_file.display().to_string());
+            }
+        }
+        Command::Text(Text::List(args)) => {
+            parts.push(String::from("text"));
+            parts.push(String::from("list"));
+            parts.push(args.file_path.display().to_string());
+        }
+        Command::Text(Text::Get(args)) => {
+            parts.push(String::from("text"));
+            parts.push(String::from("get"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(format!("--keyword={}", args.keyword));
+        }
+        Command::Text(Text::Set(args)) => {
+            parts.push(String::from("text"));
+            parts.push(String::from("set"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(args.text.clone());
+            parts.push(format!("--keyword={}", args.keyword));
+            if let Some(kind) = args.kind {
+                parts.push(format!("--type={}", kind));
+            }
+            if let Some(output_file) = &args.output_file {
+                parts.push(output_file.display().to_string());
+            }
+        }
+        Command::Text(Text::Remove(args)) => {
+            parts.push(String::from("text"));
+            parts.push(String::from("remove"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(format!("--keyword={}", args.keyword));
+            if let Some(output_file) = &args.output_file {
+                parts.push(output_file.display().to_string());
+            }
+        }
+        Command::Text(Text::Convert(args)) => {
+            parts.push(String::from("text"));
+            parts.push(String::from("convert"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(format!("--from={}", args.from));
+            parts.push(format!("--to={}", args.to));
+            if args.compress {
+                parts.push(String::from("--compress"));
+            }
+            if let Some(keyword) = &args.keyword {
+                parts.push(format!("--keyword={}", keyword));
+            }
+            if let Some(output_file) = &args.output_file {
+                parts.push(output_file.display().to_string());
+            }
+        }
+        Command::IdentifyText(args) => {
+            parts.push(String::from("identify-text"));
+            parts.push(args.file_path.display().to_string());
+            if args.format != OutputFormat::Human {
+                parts.push(format!("--format={}", args.format));
+            }
+        }
+        Command::List(args) => {
+            parts.push(String::from("list"));
+            parts.push(args.file_path.display().to_string());
+            if args.format != OutputFormat::Human {
+                parts.push(format!("--format={}", args.format));
+            }
+        }
+        Command::Print(args) => {
+            parts.push(String::from("print"));
+            parts.push(args.file_path.display().to_string());
+            if args.headers_only {
+                parts.push(String::from("--headers-only"));
+            }
+            if args.digest {
+                parts.push(String::from("--digest"));
+            }
+            if args.format != OutputFormat::Human {
+                parts.push(format!("--format={}", args.format));
+            }
+        }
+        Command::Verify(args) => {
+            parts.push(String::from("verify"));
+            parts.push(args.file_path.display().to_string());
+        }
+        Command::Explain(args) => {
+            parts.push(String::from("explain"));
+            parts.push(args.chunk_type.to_string());
+        }
+        Command::FormatSpec(args) => {
+            parts.push(String::from("format-spec"));
+            if args.format != OutputFormat::Human {
+                parts.push(format!("--format={}", args.format));
+            }
+        }
+        Command::Capabilities(args) => {
+            parts.push(String::from("capabilities"));
+            if args.format != OutputFormat::Human {
+                parts.push(format!("--format={}", args.format));
+            }
+        }
+        Command::GenFixtures(args) => {
+            parts.push(String::from("gen-fixtures"));
+            parts.push(args.directory.display().to_string());
+        }
+        Command::Bench(args) => {
+            parts.push(String::from("bench"));
+            parts.push(format!("--size={}", args.size));
+            if args.format != OutputFormat::Human {
+                parts.push(format!("--format={}", args.format));
+            }
+        }
+        Command::Gc(args) => {
+            parts.push(String::from("gc"));
+            parts.push(args.directory.display().to_string());
+            if args.recursive {
+                parts.push(String::from("--recursive"));
+            }
+            parts.push(format!(
+                "--min-age={}",
+                units::format_duration(args.min_age)
+            ));
+            if args.dry_run {
+                parts.push(String::from("--dry-run"));
+            }
+        }
+        Command::Carve(args) => {
+            parts.push(String::from("carve"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(format!("--out-dir={}", args.out_dir.display()));
+            if args.complete_only {
+                parts.push(String::from("--complete-only"));
+            }
+            if args.dry_run {
+                parts.push(String::from("--dry-run"));
+            }
+            if args.format != OutputFormat::Human {
+                parts.push(format!("--format={}", args.format));
+            }
+        }
+        Command::AppendTo(args) => {
+            parts.push(String::from("append-to"));
+            parts.push(args.file_path.display().to_string());
+            parts.push(args.payload.display().to_string());
+            if let Some(output) = &args.output {
+                parts.push(format!("--output={}", output.display()));
+            }
+        }
+        Command::ExtractFrom(args) => {
+            parts.push(String::from("extract-from"));
+            parts.push(args.file_path.display().to_string());
+            if let Some(output) = &args.output {
+                parts.push(format!("--output={}", output.display()));
+            }
+            if args.raw {
+                parts.push(String::from("--raw"));
+            }
+            if args.remove {
+                parts.push(String::from("--remove"));
+            }
+        }
+        Command::Stats(args) => {
+            parts.push(String::from("stats"));
+            if args.show {
+                parts.push(String::from("--self"));
+            }
+            if args.enable {
+                parts.push(String::from("--enable"));
+            }
+            if args.disable {
+                parts.push(String::from("--disable"));
+            }
+            if args.format != OutputFormat::Human {
+                parts.push(format!("--format={}", args.format));
+            }
+        }
+        Command::Wizard => {
+            parts.push(String::from("wizard"));
+        }
+    }
+
+    parts
+        .iter()
+        .map(|part| quote_argument(part))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn quote_argument(argument: &str) -> String {
+    let is_plain = !argument.is_empty()
+        && argument
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:\\".contains(c));
+
+    if is_plain {
+        argument.to_string()
+    } else {
+        format!("\"{}\"", argument.replace('"', "\\\""))
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+// Stopping long operations part way, eg parsing a file with hundreds of megabytes of image
+// data or decoding a directory of them, when the user presses Ctrl-C or a time limit passes.
+//
+// Cancellation is cooperative, an operation checks its token where it can stop cleanly and
+// returns `CancelError` from there. Clones of a token share it, so one handed to a Ctrl-C
+// handler cancels the operations holding the others. Anything which reads from a `Read` can
+// be cancelled by reading through `CancellationToken::reader`, which fails the next read once
+// the token is cancelled, with an `io::Error` which `cancellation` recognises.
+
+#[derive(Error, Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum CancelError {
+    #[error("Cancelled")]
+    Cancelled,
+    #[error("Ran out of time")]
+    TimedOut,
+}
+
+pub type CancelResult<T> = Result<T, CancelError>;
+
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    // A token which is also cancelled once the deadline passes, sharing this one's cancellation.
+    // The earlier deadline is kept when both have one.
+    pub fn with_deadline(&self, deadline: Instant) -> CancellationToken {
+        CancellationToken {
+            cancelled: self.cancelled.clone(),
+            deadline: Some(self.deadline.map_or(deadline, |d| d.min(deadline))),
+        }
+    }
+
+    pub fn with_timeout(&self, timeout: Duration) -> CancellationToken {
+        self.with_deadline(Instant::now() + timeout)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn check(&self) -> CancelResult<()> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            Err(CancelError::Cancelled)
+        } else if matches!(self.deadline, Some(deadline) if Instant::now() >= deadline) {
+            Err(CancelError::TimedOut)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.check().is_err()
+    }
+
+    // Read through the token, so the reading stops once it is cancelled
+    pub fn reader<R: Read>(&self, reader: R) -> CancellableReader<R> {
+        CancellableReader {
+            reader,
+            token: self.clone(),
+        }
+    }
+}
+
+pub struct CancellableReader<R> {
+    reader: R,
+    token: CancellationToken,
+}
+
+impl<R> CancellableReader<R> {
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for CancellableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Not `Interrupted`, which readers retry
+        self.token.check().map_err(io::Error::other)?;
+        self.reader.read(buf)
+    }
+}
+
+// Why the read failed, if it was because the token it was read through was cancelled
+pub fn cancellation(error: &io::Error) -> Option<CancelError> {
+    error
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<CancelError>())
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_shared_by_clones() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert_eq!(clone.check(), Ok(()));
+
+        token.cancel();
+        assert_eq!(clone.check(), Err(CancelError::Cancelled));
+        assert!(clone.with_timeout(Duration::from_secs(60)).is_cancelled());
+    }
+
+    #[test]
+    fn test_deadline() {
+        let token = CancellationToken::new();
+        let past = token.with_deadline(Instant::now());
+        assert_eq!(past.check(), Err(CancelError::TimedOut));
+        assert_eq!(token.check(), Ok(()));
+
+        // The earlier deadline wins
+        let later = past.with_timeout(Duration::from_secs(60));
+        assert_eq!(later.check(), Err(CancelError::TimedOut));
+    }
+
+    #[test]
+    fn test_reader() {
+        let token = CancellationToken::new();
+        let mut reader = token.reader(&b"some bytes"[..]);
+        let mut buf = [0; 4];
+        reader.read_exact(&mut buf).unwrap();
+
+        token.cancel();
+        let error = reader.read_exact(&mut buf).unwrap_err();
+        assert_eq!(cancellation(&error), Some(CancelError::Cancelled));
+        assert_eq!(cancellation(&io::Error::from(io::ErrorKind::Other)), None);
+    }
+}
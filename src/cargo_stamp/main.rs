@@ -0,0 +1,291 @@
+use anyhow::Context;
+use clap::{Args, Parser};
+use lib_pngme::{stamp, StampOptions, Stamped};
+use serde_json::Value;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+// `cargo pngme-stamp`, records the package name, version and git commit in the PNGs a package
+// bundles, eg after `cargo build --release` so every shipped asset says which build it came
+// from. The files are listed in the package's Cargo.toml, relative to it:
+//
+//     [package.metadata.pngme-stamp]
+//     files = ["assets/**/*.png", "icons/*.png"]
+//
+// Each value is stamped with `lib_pngme::stamp`, so a file which already holds it isn't
+// touched. Cargo runs `cargo-pngme-stamp pngme-stamp <args>` for `cargo pngme-stamp <args>`,
+// which is why the arguments are parsed as the one subcommand of `cargo`.
+
+const METADATA_KEY: &str = "pngme-stamp";
+
+// Directories which never hold the package's own assets
+const SKIPPED_DIRECTORIES: [&str; 2] = [".git", "target"];
+
+#[derive(Parser, Debug)]
+#[command(bin_name = "cargo")]
+enum Cargo {
+    /// Stamp the package name, version and git commit into the PNG files listed in Cargo.toml
+    #[command(name = "pngme-stamp")]
+    PngmeStamp(StampArguments),
+}
+
+#[derive(Args, Debug)]
+struct StampArguments {
+    /// The Cargo.toml of the package or workspace, by default the one cargo finds
+    #[arg(long)]
+    manifest_path: Option<PathBuf>,
+    /// Only stamp the files of this package in the workspace
+    #[arg(short, long)]
+    package: Option<String>,
+    /// List the files which would be stamped without changing them
+    #[arg(long)]
+    dry_run: bool,
+}
+
+struct Package {
+    name: String,
+    version: String,
+    // The directory holding its Cargo.toml
+    root: PathBuf,
+    // The globs under [package.metadata.pngme-stamp] files
+    patterns: Vec<String>,
+}
+
+fn main() {
+    let Cargo::PngmeStamp(args) = Cargo::parse();
+
+    if let Err(error) = run(args) {
+        eprintln!("Error: {:?}", error);
+        process::exit(1);
+    }
+}
+
+fn run(args: StampArguments) -> anyhow::Result<()> {
+    let mut packages = packages(args.manifest_path.as_deref())?;
+    if let Some(name) = &args.package {
+        packages.retain(|package| &package.name == name);
+        if packages.is_empty() {
+            anyhow::bail!("There is no package {:?} in the workspace", name);
+        }
+    }
+    packages.retain(|package| !package.patterns.is_empty());
+    if packages.is_empty() {
+        anyhow::bail!(
+            "No files to stamp, list them in Cargo.toml under [package.metadata.{}], eg files = [\"assets/**/*.png\"]",
+            METADATA_KEY
+        );
+    }
+
+    for package in packages.iter() {
+        stamp_package(package, args.dry_run)?;
+    }
+
+    Ok(())
+}
+
+// The packages of the workspace, as `cargo metadata` describes them
+fn packages(manifest_path: Option<&Path>) -> anyhow::Result<Vec<Package>> {
+    // Cargo says which cargo ran the subcommand
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo"));
+    let mut command = Command::new(cargo);
+    command.args(["metadata", "--format-version", "1", "--no-deps"]);
+    if let Some(manifest_path) = manifest_path {
+        command.arg("--manifest-path").arg(manifest_path);
+    }
+    let output = command.output().context("Failed to run cargo metadata")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let metadata: Value =
+        serde_json::from_slice(&output.stdout).context("Failed to parse cargo metadata")?;
+    metadata
+        .get("packages")
+        .and_then(Value::as_array)
+        .context("cargo metadata didn't list the packages")?
+        .iter()
+        .map(package)
+        .collect()
+}
+
+fn package(value: &Value) -> anyhow::Result<Package> {
+    let field = |name: &str| {
+        value
+            .get(name)
+            .and_then(Value::as_str)
+            .with_context(|| format!("cargo metadata gave a package without a {}", name))
+    };
+    let name = field("name")?;
+    let manifest_path = Path::new(field("manifest_path")?);
+
+    let patterns = match value.pointer(&format!("/metadata/{}/files", METADATA_KEY)) {
+        None => vec![],
+        Some(files) => files
+            .as_array()
+            .and_then(|files| {
+                files
+                    .iter()
+                    .map(|file| file.as_str().map(String::from))
+                    .collect()
+            })
+            .with_context(|| {
+                format!(
+                    "{}: package.metadata.{}.files must be a list of globs",
+                    name, METADATA_KEY
+                )
+            })?,
+    };
+
+    Ok(Package {
+        name: name.to_string(),
+        version: field("version")?.to_string(),
+        root: manifest_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+        patterns,
+    })
+}
+
+fn stamp_package(package: &Package, dry_run: bool) -> anyhow::Result<()> {
+    let mut values = vec![
+        ("Package", package.name.clone()),
+        ("Version", package.version.clone()),
+    ];
+    match commit(&package.root) {
+        Some(commit) => values.push(("Commit", commit)),
+        None => eprintln!(
+            "warning: {} isn't in a git repository, the commit isn't stamped",
+            package.name
+        ),
+    }
+
+    let files = matching_files(package)?;
+    if files.is_empty() {
+        eprintln!(
+            "warning: no files of {} match {:?}",
+            package.name, package.patterns
+        );
+    }
+
+    let mut stamped = 0;
+    for (file, relative) in files.iter() {
+        if dry_run {
+            println!("Would stamp {}", relative);
+            continue;
+        }
+
+        let mut changed = false;
+        for (key, value) in values.iter() {
+            let result = stamp(file, key, value, StampOptions::default())
+                .with_context(|| format!("Failed to stamp {}", relative))?;
+            changed |= result != Stamped::Unchanged;
+        }
+        if changed {
+            println!("Stamped {}", relative);
+            stamped += 1;
+        }
+    }
+
+    if !dry_run {
+        println!(
+            "{} {}: stamped {} of {} files, the rest were up to date",
+            package.name,
+            package.version,
+            stamped,
+            files.len()
+        );
+    }
+
+    Ok(())
+}
+
+// The commit checked out, or None outside a git repository
+fn commit(root: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir(root)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// The files under the package matching any of its globs, with their path relative to it
+fn matching_files(package: &Package) -> anyhow::Result<Vec<(PathBuf, String)>> {
+    let mut files = vec![];
+    collect_files(&package.root, "", &mut files)
+        .with_context(|| format!("Failed to list the files in {:?}", package.root))?;
+    files.retain(|(_, relative)| {
+        package
+            .patterns
+            .iter()
+            .any(|pattern| path_matches(pattern.trim_start_matches("./"), relative))
+    });
+    files.sort();
+
+    Ok(files)
+}
+
+fn collect_files(
+    directory: &Path,
+    prefix: &str,
+    files: &mut Vec<(PathBuf, String)>,
+) -> std::io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let relative = format!("{}{}", prefix, name);
+        if entry.file_type()?.is_dir() {
+            if !SKIPPED_DIRECTORIES.contains(&name.as_str()) {
+                collect_files(&entry.path(), &format!("{}/", relative), files)?;
+            }
+        } else {
+            files.push((entry.path(), relative));
+        }
+    }
+
+    Ok(())
+}
+
+// Whether a path, with / between its components, matches the glob, where ? matches any
+// character, * any characters within a component and ** any number of components
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+
+    components_match(&pattern, &path)
+}
+
+fn components_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|i| components_match(rest, &path[i..])),
+        Some((first, rest)) => match path.split_first() {
+            Some((component, path)) => {
+                name_matches(first.as_bytes(), component.as_bytes()) && components_match(rest, path)
+            }
+            None => false,
+        },
+    }
+}
+
+fn name_matches(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|i| name_matches(rest, &name[i..])),
+        Some((&p, rest)) => match name.split_first() {
+            Some((&c, name)) => (p == b'?' || p == c) && name_matches(rest, name),
+            None => false,
+        },
+    }
+}
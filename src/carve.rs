@@ -0,0 +1,223 @@
+use crate::{
+    chunk::{Chunk, ChunkDecoder, ChunkEvent},
+    chunk_type::ChunkType,
+    png::Png,
+    view::CrcCheck,
+};
+use std::fmt::{self, Display, Formatter};
+
+// Finding the PNGs inside other data, eg a memory dump or a disk image, to recover them.
+// A PNG is found by its signature, or by an intact IHDR chunk where the signature is damaged,
+// and read chunk by chunk up to its IEND chunk. One which is cut short or has chunks with the
+// wrong CRC is salvaged rather than skipped, and the repairs are listed with it.
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum Repair {
+    // The signature was missing or damaged
+    Signature,
+    // The chunk at the index had the wrong CRC, it has the right one now
+    Crc(usize),
+    // The chunks stopped before IEND, so IEND was added after the intact ones
+    Truncated,
+}
+
+impl Display for Repair {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Repair::Signature => write!(f, "the signature was missing"),
+            Repair::Crc(index) => write!(f, "chunk {} had the wrong CRC", index),
+            Repair::Truncated => write!(f, "it was cut short, IEND was added"),
+        }
+    }
+}
+
+pub struct Carved {
+    // Where the PNG starts in the data, at its IHDR chunk if there was no signature
+    pub offset: usize,
+    // How many bytes of the data it was read from
+    pub length: usize,
+    pub png: Png,
+    pub repairs: Vec<Repair>,
+}
+
+impl Carved {
+    pub fn is_complete(&self) -> bool {
+        self.repairs.is_empty()
+    }
+}
+
+// The PNGs in the data, in the order they appear. PNGs don't overlap, the search carries on
+// after the end of each one found.
+pub fn carve(bytes: &[u8]) -> Carver<'_> {
+    Carver { bytes, position: 0 }
+}
+
+pub struct Carver<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Carver<'a> {
+    // Where the next PNG might start and where its chunks would start, the same place when
+    // it is an IHDR chunk without a signature
+    fn next_candidate(&self) -> Option<(usize, usize)> {
+        let rest = self.bytes.get(self.position..)?;
+        let signature = rest
+            .windows(Png::STANDARD_HEADER.len())
+            .position(|window| window == Png::STANDARD_HEADER);
+        let header = Png::find_image_header(rest);
+
+        let (offset, chunks) = match (signature, header) {
+            (Some(signature), Some(header)) if header < signature => (header, header),
+            (Some(signature), _) => (signature, signature + Png::STANDARD_HEADER.len()),
+            (None, Some(header)) => (header, header),
+            (None, None) => return None,
+        };
+        Some((self.position + offset, self.position + chunks))
+    }
+}
+
+impl<'a> Iterator for Carver<'a> {
+    type Item = Carved;
+
+    fn next(&mut self) -> Option<Carved> {
+        loop {
+            let (offset, chunks) = self.next_candidate()?;
+            match read_png(self.bytes, offset, chunks) {
+                Some(carved) => {
+                    self.position = offset + carved.length;
+                    return Some(carved);
+                }
+                None => self.position = offset + 1,
+            }
+        }
+    }
+}
+
+// Read the PNG whose chunks start at `chunks`, salvaging what there is of it. None unless
+// it starts with an IHDR chunk.
+fn read_png(bytes: &[u8], offset: usize, chunks: usize) -> Option<Carved> {
+    let mut repairs = vec![];
+    if offset == chunks {
+        repairs.push(Repair::Signature);
+    }
+
+    let mut found: Vec<Chunk> = vec![];
+    let mut end = chunks;
+    let mut complete = false;
+    for event in ChunkDecoder::with_crc_check(CrcCheck::Lazy).events(&bytes[chunks..]) {
+        let chunk = match event {
+            ChunkEvent::Chunk { chunk, .. } => chunk,
+            ChunkEvent::WrongCrc { chunk, .. } => {
+                repairs.push(Repair::Crc(found.len()));
+                chunk
+            }
+            ChunkEvent::Error { .. } => break,
+        };
+        if found.is_empty() && chunk.chunk_type().to_string() != "IHDR" {
+            return None;
+        }
+
+        end += chunk.length() as usize + 12;
+        complete = chunk.chunk_type().to_string() == "IEND";
+        found.push(chunk);
+        if complete {
+            break;
+        }
+    }
+    if found.is_empty() {
+        return None;
+    }
+    if !complete {
+        repairs.push(Repair::Truncated);
+        found.push(Chunk::new(ChunkType::from_known(*b"IEND"), vec![]));
+    }
+
+    Some(Carved {
+        offset,
+        length: end - offset,
+        png: Png::from_chunks(found).ok()?,
+        repairs,
+    })
+}
+
+// The tests carve the fixtures
+#[cfg(all(test, feature = "fixtures"))]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn fixture_bytes(name: &str) -> Vec<u8> {
+        fixtures::generate()
+            .into_iter()
+            .find(|fixture| fixture.name == name)
+            .unwrap()
+            .bytes
+    }
+
+    #[test]
+    fn test_carve_embedded_pngs() {
+        let first = fixture_bytes("animated");
+        let second = fixture_bytes("interlaced-grey-1bit");
+        let mut bytes = vec![0xAA; 100];
+        bytes.extend(&first);
+        bytes.extend(vec![0x00; 37]);
+        bytes.extend(&second);
+        bytes.extend(vec![0xFF; 5]);
+
+        let carved: Vec<Carved> = carve(&bytes).collect();
+        assert_eq!(carved.len(), 2);
+        assert_eq!(carved[0].offset, 100);
+        assert_eq!(carved[0].length, first.len());
+        assert!(carved[0].is_complete());
+        assert_eq!(carved[0].png.as_bytes(), first);
+        assert_eq!(carved[1].offset, 100 + first.len() + 37);
+        assert_eq!(carved[1].png.as_bytes(), second);
+    }
+
+    #[test]
+    fn test_carve_truncated_png() {
+        let bytes = fixture_bytes("animated");
+        let carved: Vec<Carved> = carve(&bytes[..bytes.len() - 20]).collect();
+        assert_eq!(carved.len(), 1);
+        assert_eq!(carved[0].repairs, vec![Repair::Truncated]);
+        let png = &carved[0].png;
+        assert_eq!(
+            png.chunks().last().unwrap().chunk_type().to_string(),
+            "IEND"
+        );
+    }
+
+    #[test]
+    fn test_carve_without_signature() {
+        let bytes = fixture_bytes("animated");
+        let mut damaged = vec![0x11; 20];
+        damaged.extend(&bytes);
+        damaged[20] = 0;
+
+        let carved: Vec<Carved> = carve(&damaged).collect();
+        assert_eq!(carved.len(), 1);
+        assert_eq!(carved[0].offset, 28);
+        assert_eq!(carved[0].repairs, vec![Repair::Signature]);
+        assert_eq!(carved[0].png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_carve_wrong_crc() {
+        let mut bytes = fixture_bytes("animated");
+        // The last byte of IHDR's CRC
+        bytes[8 + 8 + 13 + 3] ^= 1;
+
+        let carved: Vec<Carved> = carve(&bytes).collect();
+        assert_eq!(carved.len(), 1);
+        assert_eq!(carved[0].repairs, vec![Repair::Crc(0)]);
+    }
+
+    #[test]
+    fn test_carve_nothing() {
+        assert_eq!(carve(&[]).count(), 0);
+        assert_eq!(carve(&Png::STANDARD_HEADER).count(), 0);
+        assert_eq!(carve(&[0x89; 1000]).count(), 0);
+    }
+}
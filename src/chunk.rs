@@ -2,16 +2,23 @@ use std::convert::TryFrom;
 use std::fmt::Display;
 use std::io;
 use std::io::Read;
+use std::iter;
+use std::ops::Deref;
 use std::str::FromStr;
 use std::string::FromUtf8Error;
+use std::sync::Arc;
 use thiserror::Error;
 
 use crate::chunk_type::ChunkType;
 use crate::chunk_type::ChunkTypeError;
+use crate::crc::Crc32;
+use crate::view::CrcCheck;
+use smallvec::SmallVec;
 
 pub type ChunkResult = Result<Chunk, ChunkError>;
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum ChunkError {
     #[error("Provided CRC value `{0}` does match calculated CRC value `{1}`")]
     InvalidCRCValue(u32, u32),
@@ -38,37 +45,97 @@ pub enum ChunkError {
     },
 }
 
+// Most ancillary chunks are tiny, eg gAMA is 4 bytes, pHYs 9 and cHRM 32, so data up to
+// this length is stored inline rather than in its own heap allocation
+const INLINE_DATA_LENGTH: usize = 32;
+
+// Chunk data is never changed once the chunk is made, so anything too big to store inline is
+// shared between clones. Keeping a copy of a Png, eg to undo an edit, then doesn't copy its image data.
+#[derive(Debug, Clone)]
+enum ChunkData {
+    Inline(SmallVec<[u8; INLINE_DATA_LENGTH]>),
+    Shared(Arc<Vec<u8>>),
+}
+
+impl ChunkData {
+    fn from_vec(data: Vec<u8>) -> ChunkData {
+        if data.len() <= INLINE_DATA_LENGTH {
+            ChunkData::Inline(SmallVec::from_slice(&data))
+        } else {
+            ChunkData::Shared(Arc::new(data))
+        }
+    }
+
+    fn from_slice(data: &[u8]) -> ChunkData {
+        if data.len() <= INLINE_DATA_LENGTH {
+            ChunkData::Inline(SmallVec::from_slice(data))
+        } else {
+            ChunkData::Shared(Arc::new(data.to_vec()))
+        }
+    }
+}
+
+impl Deref for ChunkData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            ChunkData::Inline(data) => data,
+            ChunkData::Shared(data) => data,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Chunk {
     // By the PNG 1.2 specification length must be less than
     // 2^31.
     length: u32,
     chunk_type: ChunkType,
-    chunk_data: Vec<u8>,
+    chunk_data: ChunkData,
     // A 4-byte CRC (Cyclic Redundancy Check)
     crc: u32,
 }
 
 impl Chunk {
-    pub fn new(chunk_type: ChunkType, chunk_data: Vec<u8>) -> Chunk {
-        // TODO how can we provide a single slice with chunk_type and chunk_data
-        // and no new vec creation?
-        let mut crc_data = Vec::with_capacity(4 + chunk_data.len());
-        crc_data.extend(chunk_type.bytes().iter());
-        crc_data.extend(chunk_data.iter());
+    // By the PNG 1.2 specification the length is at most 2^31 - 1, so it fits in 31 bits
+    pub const MAX_LENGTH: usize = (1 << 31) - 1;
 
-        let crc = calculate_crc(&crc_data[..]);
+    // The data must fit in a chunk, `try_new` checks data which might not, eg given by a user.
+    // Data which doesn't fit is given a length over `MAX_LENGTH`, which readers reject, rather
+    // than a truncated one which would misread the chunks after it.
+    pub fn new(chunk_type: ChunkType, chunk_data: Vec<u8>) -> Chunk {
+        debug_assert!(chunk_data.len() <= Chunk::MAX_LENGTH);
+        let crc = calculate_chunk_crc(&chunk_type.bytes(), &chunk_data);
         Chunk {
-            length: chunk_data.len() as u32,
-            chunk_type: chunk_type,
-            chunk_data: chunk_data,
+            length: u32::try_from(chunk_data.len()).unwrap_or(u32::MAX),
+            chunk_type,
+            chunk_data: ChunkData::from_vec(chunk_data),
             crc,
         }
     }
 
+    pub fn try_new(chunk_type: ChunkType, chunk_data: Vec<u8>) -> ChunkResult {
+        if chunk_data.len() > Chunk::MAX_LENGTH {
+            return Err(ChunkError::LengthTooLarge(
+                chunk_data.len(),
+                Chunk::MAX_LENGTH,
+            ));
+        }
+
+        Ok(Chunk::new(chunk_type, chunk_data))
+    }
+
+    // The chunk in bytes which are only wrong in their CRC, with the right CRC
+    fn with_correct_crc(bytes: &[u8]) -> Chunk {
+        let chunk_type = ChunkType::from_known([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        Chunk::new(chunk_type, bytes[8..bytes.len() - 4].to_vec())
+    }
+
     pub fn from_strings(chunk_type: &str, chunk_data: &str) -> ChunkResult {
         let chunk_type = ChunkType::from_str(chunk_type)?;
 
-        Ok(Chunk::new(chunk_type, chunk_data.bytes().collect()))
+        Chunk::try_new(chunk_type, chunk_data.bytes().collect())
     }
 
     pub fn length(&self) -> u32 {
@@ -88,7 +155,7 @@ impl Chunk {
     }
 
     pub fn data_as_string(&self) -> Result<String, ChunkError> {
-        let str = String::from_utf8(self.chunk_data.clone())?;
+        let str = String::from_utf8(self.chunk_data.to_vec())?;
 
         Ok(str)
     }
@@ -103,30 +170,6 @@ impl Chunk {
             .copied()
             .collect()
     }
-
-    pub fn next_chunk(stream: &[u8]) -> Result<&[u8], ChunkError> {
-        if stream.len() < 4 {
-            // Minimum length for a chunk is 12 - 4 for length, 4 for type, 0 for data, 4 for CRC
-            return Err(ChunkError::NotEnoughBytes(stream.len(), 12));
-        }
-
-        let orig_stream = stream;
-        let mut stream = stream;
-
-        let mut length = [0 as u8; 4];
-        stream.read_exact(&mut length)?;
-        let length = u32::from_be_bytes(length);
-
-        // Now we know the data length, we can determine the length of this chunk
-        // 4 bytes for length, 4 bytes for type, length bytes for data, 4 bytes for CRC
-        let chunk_length = (4 + 4 + length + 4) as usize;
-
-        if chunk_length > orig_stream.len() {
-            return Err(ChunkError::LengthTooLarge(chunk_length, orig_stream.len()));
-        }
-
-        Ok(&orig_stream[..chunk_length])
-    }
 }
 
 impl Display for Chunk {
@@ -148,23 +191,30 @@ impl TryFrom<&[u8]> for Chunk {
         let orig_value = value;
         let mut value = value;
 
-        let mut length = [0 as u8; 4];
+        let mut length = [0u8; 4];
         value.read_exact(&mut length)?;
 
         let length = u32::from_be_bytes(length);
 
-        if length > (1 << 31) {
-            return Err(ChunkError::LengthTooLarge(length as usize, 1 << 31));
+        if length as usize > Chunk::MAX_LENGTH {
+            return Err(ChunkError::LengthTooLarge(
+                length as usize,
+                Chunk::MAX_LENGTH,
+            ));
         }
 
-        let mut chunk_type_buf = [0 as u8; 4];
+        let mut chunk_type_buf = [0u8; 4];
         value.read_exact(&mut chunk_type_buf)?;
         let chunk_type = ChunkType::try_from(chunk_type_buf)?;
 
-        let mut chunk_data: Vec<u8> = vec![0; length as usize];
-        value.read_exact(&mut chunk_data)?;
+        // Copied straight from the slice, so small chunks never touch the heap
+        if value.len() < length as usize {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        let chunk_data = ChunkData::from_slice(&value[..length as usize]);
+        value = &value[length as usize..];
 
-        let mut crc = [0 as u8; 4];
+        let mut crc = [0u8; 4];
         value.read_exact(&mut crc)?;
         let crc = u32::from_be_bytes(crc);
 
@@ -188,36 +238,307 @@ impl TryFrom<&[u8]> for Chunk {
     }
 }
 
-fn calculate_crc(value: &[u8]) -> u32 {
-    // Based off the implementation of
-    // http://www.libpng.org/pub/png/spec/1.2/PNG-CRCAppendix.html
-    let mut crc: u32 = 0xffffffff; // All 1's
+// 4 bytes for length, 4 bytes for type, 4 bytes for CRC, as well as the data
+const CHUNK_OVERHEAD: usize = 4 + 4 + 4;
+
+// How much a `ChunkReader` reads at once, at most
+const READ_SIZE: usize = 8 * 1024;
+
+// What a `ChunkDecoder` found in the bytes fed to it. Offsets count from the start of the
+// stream of chunks, so after the PNG signature.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ChunkEvent {
+    // A whole chunk
+    Chunk {
+        offset: u64,
+        chunk: Chunk,
+    },
+    // A whole chunk whose stored CRC is wrong, only with `CrcCheck::Lazy`. The chunk has the
+    // right CRC, so writing it out fixes the CRC.
+    WrongCrc {
+        offset: u64,
+        chunk: Chunk,
+        stored: u32,
+        calculated: u32,
+    },
+    // The chunk starting at the offset is broken, nothing after it is decoded
+    Error {
+        offset: u64,
+        error: ChunkError,
+    },
+}
+
+impl ChunkEvent {
+    pub fn offset(&self) -> u64 {
+        match self {
+            ChunkEvent::Chunk { offset, .. }
+            | ChunkEvent::WrongCrc { offset, .. }
+            | ChunkEvent::Error { offset, .. } => *offset,
+        }
+    }
+
+    // The chunk, or why there isn't one. A wrong CRC is an error.
+    pub fn into_result(self) -> ChunkResult {
+        match self {
+            ChunkEvent::Chunk { chunk, .. } => Ok(chunk),
+            ChunkEvent::WrongCrc {
+                stored, calculated, ..
+            } => Err(ChunkError::InvalidCRCValue(stored, calculated)),
+            ChunkEvent::Error { error, .. } => Err(error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecoderState {
+    // Waiting for the 4 bytes of the next chunk's length
+    Length,
+    // Waiting for the rest of a chunk of this many bytes in all
+    Chunk(usize),
+    // After an error
+    Stopped,
+}
+
+// Decodes chunks from bytes as they arrive, eg from a pipe a few bytes at a time, or from a
+// file read into memory all at once. `feed` takes more bytes and returns the next event once
+// enough have arrived for it, feeding nothing returns the next event from the bytes fed
+// already. `finish` is called at the end of the input, it reports a chunk which was cut off.
+//
+// The input starts at a chunk, so the PNG signature has to be read first. With
+// `CrcCheck::Lazy` a wrong CRC is reported and decoding carries on, eg to diagnose a damaged
+// file, otherwise it is an error. Decoding stops after an error.
+#[derive(Debug)]
+pub struct ChunkDecoder {
+    buffer: Vec<u8>,
+    // How much of the buffer has been decoded
+    start: usize,
+    // Where the next chunk starts in the stream
+    offset: u64,
+    state: DecoderState,
+    crc_check: CrcCheck,
+}
+
+impl ChunkDecoder {
+    pub fn new() -> ChunkDecoder {
+        ChunkDecoder::with_crc_check(CrcCheck::Eager)
+    }
+
+    pub fn with_crc_check(crc_check: CrcCheck) -> ChunkDecoder {
+        ChunkDecoder {
+            buffer: vec![],
+            start: 0,
+            offset: 0,
+            state: DecoderState::Length,
+            crc_check,
+        }
+    }
+
+    // Where the next chunk starts in the stream, or the broken chunk after an error
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    // How many more bytes the next event needs, so a reader can read no further than the
+    // chunk. None once decoding has stopped.
+    pub fn needed(&self) -> Option<usize> {
+        let available = self.buffer.len() - self.start;
+        match self.state {
+            DecoderState::Length => Some(4usize.saturating_sub(available)),
+            DecoderState::Chunk(length) => Some(length.saturating_sub(available)),
+            DecoderState::Stopped => None,
+        }
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) -> Option<ChunkEvent> {
+        if self.state == DecoderState::Stopped {
+            return None;
+        }
+        if !bytes.is_empty() {
+            // Keep only what hasn't been decoded, so the buffer holds at most one chunk
+            self.buffer.drain(..self.start);
+            self.start = 0;
+            self.buffer.extend_from_slice(bytes);
+        }
+
+        loop {
+            let available = &self.buffer[self.start..];
+            match self.state {
+                DecoderState::Length => {
+                    let length = match available {
+                        [a, b, c, d, ..] => u32::from_be_bytes([*a, *b, *c, *d]) as usize,
+                        _ => return None,
+                    };
+                    // Checked before waiting for the data, which may never come
+                    if length > Chunk::MAX_LENGTH {
+                        return Some(
+                            self.stop(ChunkError::LengthTooLarge(length, Chunk::MAX_LENGTH)),
+                        );
+                    }
+                    self.state = DecoderState::Chunk(length + CHUNK_OVERHEAD);
+                }
+                DecoderState::Chunk(length) => {
+                    let bytes = available.get(..length)?;
+                    let offset = self.offset;
+                    let event = match (Chunk::try_from(bytes), self.crc_check) {
+                        (Ok(chunk), _) => ChunkEvent::Chunk { offset, chunk },
+                        (Err(ChunkError::InvalidCRCValue(stored, calculated)), CrcCheck::Lazy) => {
+                            ChunkEvent::WrongCrc {
+                                offset,
+                                chunk: Chunk::with_correct_crc(bytes),
+                                stored,
+                                calculated,
+                            }
+                        }
+                        (Err(error), _) => return Some(self.stop(error)),
+                    };
+                    self.start += length;
+                    self.offset += length as u64;
+                    self.state = DecoderState::Length;
+                    return Some(event);
+                }
+                DecoderState::Stopped => return None,
+            }
+        }
+    }
+
+    // The input has ended. Returns the events left from the bytes fed already, then an
+    // error if they end part way through a chunk.
+    pub fn finish(&mut self) -> Option<ChunkEvent> {
+        if let Some(event) = self.feed(&[]) {
+            return Some(event);
+        }
 
-    let crc_table = create_crc_table();
+        let available = self.buffer.len() - self.start;
+        let error = match self.state {
+            DecoderState::Length if available > 0 => {
+                // Minimum length for a chunk is 12 - 4 for length, 4 for type, 0 for data, 4 for CRC
+                ChunkError::NotEnoughBytes(available, 12)
+            }
+            DecoderState::Chunk(length) => ChunkError::LengthTooLarge(length, available),
+            _ => return None,
+        };
 
-    for v in value.iter() {
-        crc = crc_table[(crc as u8 ^ v) as usize] ^ (crc >> 8);
+        Some(self.stop(error))
+    }
+
+    // Every event of a whole input, eg a file read into memory. The input is fed a chunk at a
+    // time as the events are taken, so stopping early doesn't decode or copy the rest.
+    pub fn events(mut self, bytes: &[u8]) -> impl Iterator<Item = ChunkEvent> + '_ {
+        let mut position = 0;
+        iter::from_fn(move || loop {
+            if let Some(event) = self.feed(&[]) {
+                return Some(event);
+            }
+            let needed = self.needed()?;
+            if position == bytes.len() {
+                return self.finish();
+            }
+
+            let end = bytes.len().min(position + needed.max(1));
+            let event = self.feed(&bytes[position..end]);
+            position = end;
+            if event.is_some() {
+                return event;
+            }
+        })
     }
 
-    crc ^ 0xffffffff
+    fn stop(&mut self, error: ChunkError) -> ChunkEvent {
+        self.state = DecoderState::Stopped;
+        ChunkEvent::Error {
+            offset: self.offset,
+            error,
+        }
+    }
+}
+
+impl Default for ChunkDecoder {
+    fn default() -> ChunkDecoder {
+        ChunkDecoder::new()
+    }
+}
+
+// Reads chunks one at a time from a stream, eg to scan a PNG of hundreds of megabytes of
+// image data without holding it all in memory. The stream starts at a chunk, so the PNG
+// signature has to be read first. Reading stops at the end of the stream, or after an error.
+// Nothing past the last chunk is read, so the reader can carry on from there.
+pub struct ChunkReader<R> {
+    reader: R,
+    decoder: ChunkDecoder,
+    finished: bool,
 }
 
-fn create_crc_table() -> [u32; 256] {
-    let mut crc_table = [0; 256];
+impl<R: Read> ChunkReader<R> {
+    pub fn new(reader: R) -> ChunkReader<R> {
+        ChunkReader {
+            reader,
+            decoder: ChunkDecoder::new(),
+            finished: false,
+        }
+    }
+
+    // How far into the stream the chunks read so far go, so where the next chunk starts
+    pub fn offset(&self) -> u64 {
+        self.decoder.offset()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
 
-    for index in 0..crc_table.len() {
-        let mut c = index as u32;
-        for _ in 0..8 {
-            if c & 1 == 1 {
-                c = 0xedb88320 ^ (c >> 1);
-            } else {
-                c = c >> 1;
+    fn read_chunk(&mut self) -> Option<ChunkResult> {
+        let mut buffer = [0; READ_SIZE];
+        loop {
+            if let Some(event) = self.decoder.feed(&[]) {
+                return Some(event.into_result());
+            }
+
+            // Only as much as the chunk needs, so a corrupt length can't ask for more
+            // memory than the stream has bytes
+            let needed = self.decoder.needed()?.min(buffer.len());
+            match self.reader.read(&mut buffer[..needed]) {
+                Ok(0) => return self.decoder.finish().map(ChunkEvent::into_result),
+                Ok(read) => {
+                    if let Some(event) = self.decoder.feed(&buffer[..read]) {
+                        return Some(event.into_result());
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Some(Err(e.into())),
             }
         }
-        crc_table[index] = c;
     }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = ChunkResult;
+
+    fn next(&mut self) -> Option<ChunkResult> {
+        if self.finished {
+            return None;
+        }
+
+        let chunk = self.read_chunk();
+        if !matches!(chunk, Some(Ok(_))) {
+            self.finished = true;
+        }
+
+        chunk
+    }
+}
+
+// The CRC-32 of the bytes, as stored at the end of a chunk for its type and data
+pub fn calculate_crc(value: &[u8]) -> u32 {
+    Crc32::checksum(value)
+}
 
-    crc_table
+// The CRC of the chunk type followed by the data, without copying them into one buffer
+pub(crate) fn calculate_chunk_crc(chunk_type: &[u8], data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(chunk_type);
+    crc.update(data);
+    crc.finalize()
 }
 
 #[cfg(test)]
@@ -363,4 +684,178 @@ mod tests {
 
         let _chunk_string = format!("{}", chunk);
     }
+
+    #[test]
+    fn test_inline_and_heap_data_round_trip() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        for length in [0, 1, INLINE_DATA_LENGTH, INLINE_DATA_LENGTH + 1, 4096].iter() {
+            let data: Vec<u8> = (0..*length).map(|i| i as u8).collect();
+            let chunk = Chunk::new(chunk_type, data.clone());
+            assert_eq!(chunk.data(), &data[..]);
+
+            let parsed = Chunk::try_from(chunk.as_bytes().as_ref()).unwrap();
+            assert_eq!(parsed.data(), &data[..]);
+            assert_eq!(parsed.crc(), chunk.crc());
+        }
+    }
+
+    #[test]
+    fn test_clone_shares_large_data() {
+        let chunk_type = ChunkType::from_str("IDAT").unwrap();
+        let chunk = Chunk::new(chunk_type, vec![0; 4096]);
+        let clone = chunk.clone();
+        assert_eq!(clone.data().as_ptr(), chunk.data().as_ptr());
+
+        let small = Chunk::new(chunk_type, vec![0; INLINE_DATA_LENGTH]);
+        assert_eq!(small.clone().data(), small.data());
+    }
+
+    #[test]
+    fn test_chunk_reader() {
+        let chunks = [
+            Chunk::from_strings("IHDR", "header").unwrap(),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![7; 4096]),
+            Chunk::from_strings("IEND", "").unwrap(),
+        ];
+        let bytes: Vec<u8> = chunks.iter().flat_map(|chunk| chunk.as_bytes()).collect();
+
+        let mut reader = ChunkReader::new(&bytes[..]);
+        for chunk in chunks.iter() {
+            let read = reader.next().unwrap().unwrap();
+            assert_eq!(read.as_bytes(), chunk.as_bytes());
+        }
+        assert!(reader.next().is_none());
+        assert_eq!(reader.offset(), bytes.len() as u64);
+    }
+
+    #[test]
+    fn test_chunk_reader_stops_at_error() {
+        let mut bytes = Chunk::from_strings("IHDR", "header").unwrap().as_bytes();
+        bytes.extend_from_slice(&[0, 0, 0, 9, b'I', b'D']);
+
+        let mut reader = ChunkReader::new(&bytes[..]);
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(
+            reader.next(),
+            Some(Err(ChunkError::LengthTooLarge(21, 6)))
+        ));
+        assert!(reader.next().is_none());
+        assert_eq!(reader.offset(), 18);
+    }
+
+    #[test]
+    fn test_chunk_decoder_byte_at_a_time() {
+        let chunks = [
+            Chunk::from_strings("IHDR", "header").unwrap(),
+            Chunk::from_strings("IEND", "").unwrap(),
+        ];
+        let bytes: Vec<u8> = chunks.iter().flat_map(|chunk| chunk.as_bytes()).collect();
+
+        let mut decoder = ChunkDecoder::new();
+        let mut events = vec![];
+        for byte in bytes.iter() {
+            events.extend(decoder.feed(&[*byte]));
+        }
+        assert!(decoder.finish().is_none());
+
+        let offsets: Vec<u64> = events.iter().map(ChunkEvent::offset).collect();
+        assert_eq!(offsets, vec![0, 18]);
+        for (event, chunk) in events.into_iter().zip(chunks.iter()) {
+            assert_eq!(event.into_result().unwrap().as_bytes(), chunk.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_chunk_decoder_lazy_crc() {
+        let mut bytes = Chunk::from_strings("tEXt", "text").unwrap().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        bytes.extend(Chunk::from_strings("IEND", "").unwrap().as_bytes());
+
+        let events: Vec<ChunkEvent> = ChunkDecoder::with_crc_check(CrcCheck::Lazy)
+            .events(&bytes)
+            .collect();
+        assert_eq!(events.len(), 2);
+        match &events[0] {
+            ChunkEvent::WrongCrc { chunk, stored, .. } => {
+                assert_eq!(chunk.data_as_string().unwrap(), "text");
+                assert_ne!(chunk.crc(), *stored);
+            }
+            event => panic!("Expected a wrong CRC, got {:?}", event),
+        }
+
+        // Eagerly it is an error, and nothing after it is decoded
+        let events: Vec<ChunkEvent> = ChunkDecoder::new().events(&bytes).collect();
+        assert!(matches!(
+            events[..],
+            [ChunkEvent::Error {
+                offset: 0,
+                error: ChunkError::InvalidCRCValue(_, _)
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_max_length_boundary() {
+        // 2^31 - 1 is allowed, so only the missing data is wrong
+        let mut bytes = vec![0x7f, 0xff, 0xff, 0xff];
+        bytes.extend_from_slice(b"IDAT");
+        assert!(matches!(
+            Chunk::try_from(bytes.as_ref()),
+            Err(ChunkError::Io { .. })
+        ));
+        let mut decoder = ChunkDecoder::new();
+        assert!(decoder.feed(&bytes).is_none());
+        assert_eq!(decoder.needed(), Some(Chunk::MAX_LENGTH + 4));
+
+        // 2^31 is one too many
+        bytes[..4].copy_from_slice(&[0x80, 0, 0, 0]);
+        assert!(matches!(
+            Chunk::try_from(bytes.as_ref()),
+            Err(ChunkError::LengthTooLarge(0x8000_0000, Chunk::MAX_LENGTH))
+        ));
+        assert!(matches!(
+            ChunkDecoder::new().feed(&bytes),
+            Some(ChunkEvent::Error {
+                error: ChunkError::LengthTooLarge(0x8000_0000, Chunk::MAX_LENGTH),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_chunk_decoder_errors() {
+        // A length too large for a chunk is an error without waiting for the data
+        let mut decoder = ChunkDecoder::new();
+        assert!(matches!(
+            decoder.feed(&[0xff, 0xff, 0xff, 0xff]),
+            Some(ChunkEvent::Error {
+                error: ChunkError::LengthTooLarge(_, Chunk::MAX_LENGTH),
+                ..
+            })
+        ));
+        assert_eq!(decoder.needed(), None);
+        assert!(decoder.feed(&[0; 16]).is_none());
+
+        let mut decoder = ChunkDecoder::new();
+        assert!(decoder.feed(&[0, 0, 0, 9, b'I', b'D']).is_none());
+        assert_eq!(decoder.needed(), Some(15));
+        assert!(matches!(
+            decoder.finish(),
+            Some(ChunkEvent::Error {
+                error: ChunkError::LengthTooLarge(21, 6),
+                ..
+            })
+        ));
+
+        let mut decoder = ChunkDecoder::new();
+        assert!(decoder.feed(&[0, 0]).is_none());
+        assert!(matches!(
+            decoder.finish(),
+            Some(ChunkEvent::Error {
+                error: ChunkError::NotEnoughBytes(2, 12),
+                ..
+            })
+        ));
+    }
 }
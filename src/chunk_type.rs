@@ -9,6 +9,7 @@ use thiserror::Error;
 pub type ChunkTypeResult = Result<ChunkType, ChunkTypeError>;
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum ChunkTypeError {
     #[error("Input character length of chunk type was `{0}`, expected it to be exactly 4")]
     InvalidCharacterLength(usize),
@@ -28,8 +29,14 @@ pub struct ChunkType {
 }
 
 impl ChunkType {
+    // For the chunk types pngme names itself, eg `ChunkType::from_known(*b"IEND")`, which are
+    // known to be valid, so there is no error to handle
+    pub(crate) const fn from_known(bytes: [u8; 4]) -> ChunkType {
+        ChunkType { bytes }
+    }
+
     pub fn bytes(&self) -> [u8; 4] {
-        self.bytes.clone()
+        self.bytes
     }
     pub fn is_valid(&self) -> Result<(), ChunkTypeError> {
         // For convenience in description and in examining PNG files,
@@ -39,8 +46,7 @@ impl ChunkType {
             .bytes
             .iter()
             .enumerate()
-            .filter(|(_, &v)| !ChunkType::is_ascii(v))
-            .next();
+            .find(|(_, &v)| !ChunkType::is_ascii(v));
 
         match bad_byte {
             Some((i, &v)) => Err(ChunkTypeError::InvalidCharacter(i, v)),
@@ -49,7 +55,7 @@ impl ChunkType {
     }
 
     fn is_ascii(v: u8) -> bool {
-        (v >= 65 && v <= 90) || (v >= 97 && v <= 122)
+        (65..=90).contains(&v) || (97..=122).contains(&v)
     }
 
     pub fn is_critical(&self) -> bool {
@@ -65,7 +71,7 @@ impl ChunkType {
     pub fn is_reserved_bit_valid(&self) -> bool {
         // Reserved bit: bit 5 of third byte
         // Must be 0 (uppercase) in files conforming to this version of PNG.
-        !is_bit_zero(self.bytes[2], 5)
+        is_bit_zero(self.bytes[2], 5)
     }
     pub fn is_safe_to_copy(&self) -> bool {
         // Safe-to-copy bit: bit 5 of fourth byte
@@ -75,9 +81,9 @@ impl ChunkType {
 }
 
 fn is_bit_zero(input: u8, bit: u8) -> bool {
-    assert!(bit <= 8);
-
-    input & (1 << bit) == 0
+    // There are only 8 bits, the rest are zero
+    1u8.checked_shl(bit.into())
+        .is_none_or(|mask| input & mask == 0)
 }
 
 impl FromStr for ChunkType {
@@ -167,13 +173,13 @@ mod tests {
 
     #[test]
     pub fn test_chunk_type_is_reserved_bit_valid() {
-        let chunk = ChunkType::from_str("Rust").unwrap();
+        let chunk = ChunkType::from_str("RuSt").unwrap();
         assert!(chunk.is_reserved_bit_valid());
     }
 
     #[test]
     pub fn test_chunk_type_is_reserved_bit_invalid() {
-        let chunk = ChunkType::from_str("RuSt").unwrap();
+        let chunk = ChunkType::from_str("Rust").unwrap();
         assert!(!chunk.is_reserved_bit_valid());
     }
 
@@ -217,4 +223,11 @@ mod tests {
         let _chunk_string = format!("{}", chunk_type_1);
         let _are_chunks_equal = chunk_type_1 == chunk_type_2;
     }
+
+    #[test]
+    pub fn test_is_bit_zero() {
+        assert!(is_bit_zero(b'R', 5));
+        assert!(!is_bit_zero(b'u', 5));
+        assert!(is_bit_zero(0xff, 8));
+    }
 }
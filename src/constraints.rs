@@ -0,0 +1,152 @@
+use crate::{
+    chunk_type::ChunkType,
+    png::Png,
+    registry,
+    standard::{ColorType, ImageHeader},
+};
+use thiserror::Error;
+
+// Why removing a chunk would leave the image broken, or looking different.
+// Chunks don't stand alone, some only make sense alongside others, eg hIST counts
+// how often each PLTE entry is used.
+#[derive(Error, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RemovalConflict {
+    #[error("The image uses indexed colour, it can't be displayed without its PLTE palette")]
+    PaletteRequired,
+    #[error("The `{0}` chunk refers to entries in the PLTE palette")]
+    PaletteReferenced(ChunkType),
+    #[error(
+        "The `{0}` chunk is part of an animation, removing it breaks the frame sequence numbers"
+    )]
+    AnimationSequence(ChunkType),
+    #[error("The animation's fcTL frame chunks can't be used without the acTL chunk")]
+    AnimationControlRequired,
+    #[error("The `{0}` chunk describes the image's colour space, removing it may shift the image's colours")]
+    ColorManagement(ChunkType),
+}
+
+impl RemovalConflict {
+    // Fatal conflicts leave an invalid image, the rest only change how it looks
+    pub fn is_fatal(&self) -> bool {
+        !matches!(self, RemovalConflict::ColorManagement(_))
+    }
+}
+
+// Chunks which depend on the palette when it is present
+const PALETTE_DEPENDENTS: [ChunkType; 2] = [
+    ChunkType::from_known(*b"hIST"),
+    ChunkType::from_known(*b"tRNS"),
+];
+
+// Work out what removing the first chunk of the given type would break
+pub fn removal_conflicts(png: &Png, chunk_type: &ChunkType) -> Vec<RemovalConflict> {
+    let mut conflicts = vec![];
+    let name = chunk_type.to_string();
+
+    match name.as_str() {
+        "PLTE" => {
+            let indexed = ImageHeader::from_png(png)
+                .map(|header| header.color_type == ColorType::Indexed)
+                .unwrap_or(false);
+            if indexed {
+                conflicts.push(RemovalConflict::PaletteRequired);
+            }
+            for dependent in PALETTE_DEPENDENTS.iter() {
+                if png.chunk_by_type(dependent).is_some() {
+                    conflicts.push(RemovalConflict::PaletteReferenced(*dependent));
+                }
+            }
+        }
+        "fcTL" | "fdAT" => conflicts.push(RemovalConflict::AnimationSequence(*chunk_type)),
+        "acTL"
+            if png
+                .chunk_by_type(&ChunkType::from_known(*b"fcTL"))
+                .is_some() =>
+        {
+            conflicts.push(RemovalConflict::AnimationControlRequired);
+        }
+        _ => {}
+    }
+
+    if registry::is_color_management(chunk_type) {
+        conflicts.push(RemovalConflict::ColorManagement(*chunk_type));
+    }
+
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use std::str::FromStr;
+
+    fn testing_png(color_type: u8, others: &[&str]) -> Png {
+        let ihdr = vec![0, 0, 0, 1, 0, 0, 0, 1, 8, color_type, 0, 0, 0];
+        let mut chunks = vec![Chunk::new(ChunkType::from_str("IHDR").unwrap(), ihdr)];
+        for other in others {
+            chunks.push(Chunk::new(ChunkType::from_str(other).unwrap(), vec![0]));
+        }
+        chunks.push(Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]));
+
+        Png::from_chunks(chunks).unwrap()
+    }
+
+    fn as_chunk_type(input: &str) -> ChunkType {
+        ChunkType::from_str(input).unwrap()
+    }
+
+    #[test]
+    fn test_palette_required_for_indexed_colour() {
+        let png = testing_png(3, &["PLTE", "IDAT"]);
+        let conflicts = removal_conflicts(&png, &as_chunk_type("PLTE"));
+        assert_eq!(conflicts, vec![RemovalConflict::PaletteRequired]);
+        assert!(conflicts[0].is_fatal());
+    }
+
+    #[test]
+    fn test_palette_optional_for_truecolour() {
+        let png = testing_png(2, &["PLTE", "IDAT"]);
+        assert!(removal_conflicts(&png, &as_chunk_type("PLTE")).is_empty());
+    }
+
+    #[test]
+    fn test_palette_referenced_by_histogram() {
+        let png = testing_png(2, &["PLTE", "hIST", "IDAT"]);
+        assert_eq!(
+            removal_conflicts(&png, &as_chunk_type("PLTE")),
+            vec![RemovalConflict::PaletteReferenced(as_chunk_type("hIST"))]
+        );
+    }
+
+    #[test]
+    fn test_animation_chunks() {
+        let png = testing_png(2, &["acTL", "fcTL", "IDAT", "fcTL", "fdAT"]);
+        assert_eq!(
+            removal_conflicts(&png, &as_chunk_type("acTL")),
+            vec![RemovalConflict::AnimationControlRequired]
+        );
+        assert_eq!(
+            removal_conflicts(&png, &as_chunk_type("fdAT")),
+            vec![RemovalConflict::AnimationSequence(as_chunk_type("fdAT"))]
+        );
+    }
+
+    #[test]
+    fn test_colour_management_is_a_warning() {
+        let png = testing_png(2, &["iCCP", "IDAT"]);
+        let conflicts = removal_conflicts(&png, &as_chunk_type("iCCP"));
+        assert_eq!(
+            conflicts,
+            vec![RemovalConflict::ColorManagement(as_chunk_type("iCCP"))]
+        );
+        assert!(!conflicts[0].is_fatal());
+    }
+
+    #[test]
+    fn test_no_conflicts() {
+        let png = testing_png(2, &["ruSt", "IDAT"]);
+        assert!(removal_conflicts(&png, &as_chunk_type("ruSt")).is_empty());
+    }
+}
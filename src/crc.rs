@@ -0,0 +1,182 @@
+use std::hash::Hasher;
+
+// The CRC-32 used by PNG chunks, also used for anything else which needs a checksum so there
+// is only one implementation.
+// Based off the implementation of
+// http://www.libpng.org/pub/png/spec/1.2/PNG-CRCAppendix.html
+
+// Built at compile time rather than on every call, and static so it isn't copied into each
+// use. CRC_TABLES[0] is the usual table of the CRC of each byte, CRC_TABLES[k] the CRC of
+// each byte followed by k zero bytes, so eight bytes can be folded in at once rather than
+// one at a time ("slicing-by-8").
+#[cfg(any(test, not(feature = "simd-crc")))]
+static CRC_TABLES: [[u32; 256]; 8] = create_crc_tables();
+
+#[cfg(any(test, not(feature = "simd-crc")))]
+const fn create_crc_tables() -> [[u32; 256]; 8] {
+    let mut crc_tables = [[0; 256]; 8];
+
+    let mut index: u32 = 0;
+    while index < 256 {
+        let mut c = index;
+        let mut bit = 0;
+        while bit < 8 {
+            if c & 1 == 1 {
+                c = 0xedb88320 ^ (c >> 1);
+            } else {
+                c >>= 1;
+            }
+            bit += 1;
+        }
+        crc_tables[0][index as usize] = c;
+        index += 1;
+    }
+
+    let mut table = 1;
+    while table < crc_tables.len() {
+        let mut index = 0;
+        while index < 256 {
+            let previous = crc_tables[table - 1][index];
+            crc_tables[table][index] = (previous >> 8) ^ crc_tables[0][(previous & 0xff) as usize];
+            index += 1;
+        }
+        table += 1;
+    }
+
+    crc_tables
+}
+
+// Fold the bytes into a running CRC, eight bytes at a time and then byte by byte
+#[cfg(any(test, not(feature = "simd-crc")))]
+fn update_with_tables(mut crc: u32, bytes: &[u8]) -> u32 {
+    let table = |k: usize, byte: u32| CRC_TABLES[k][(byte & 0xff) as usize];
+
+    let mut words = bytes.chunks_exact(8);
+    for word in &mut words {
+        let low = crc ^ u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+        let high = u32::from_le_bytes([word[4], word[5], word[6], word[7]]);
+        crc = table(7, low)
+            ^ table(6, low >> 8)
+            ^ table(5, low >> 16)
+            ^ table(4, low >> 24)
+            ^ table(3, high)
+            ^ table(2, high >> 8)
+            ^ table(1, high >> 16)
+            ^ table(0, high >> 24);
+    }
+    for v in words.remainder() {
+        crc = table(0, crc ^ *v as u32) ^ (crc >> 8);
+    }
+
+    crc
+}
+
+// A running CRC, for checksumming bytes which aren't in one slice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crc32 {
+    crc: u32,
+}
+
+impl Crc32 {
+    pub fn new() -> Crc32 {
+        Crc32 { crc: 0xffffffff } // All 1's
+    }
+
+    // The CRC of one slice of bytes
+    pub fn checksum(bytes: &[u8]) -> u32 {
+        let mut crc = Crc32::new();
+        crc.update(bytes);
+        crc.finalize()
+    }
+
+    #[cfg(not(feature = "simd-crc"))]
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.crc = update_with_tables(self.crc, bytes);
+    }
+
+    // crc32fast uses the CPU's carry-less multiplication or CRC instructions where it has them,
+    // carrying on from the CRC so far
+    #[cfg(feature = "simd-crc")]
+    pub fn update(&mut self, bytes: &[u8]) {
+        let mut hasher = crc32fast::Hasher::new_with_initial(self.finalize());
+        hasher.update(bytes);
+        self.crc = hasher.finalize() ^ 0xffffffff;
+    }
+
+    // The CRC of every byte so far. More bytes can still be added after this.
+    pub fn finalize(&self) -> u32 {
+        self.crc ^ 0xffffffff
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Crc32 {
+        Crc32::new()
+    }
+}
+
+// So a `Crc32` can be used where a `Hasher` is expected, `finish` is the CRC widened to a u64
+impl Hasher for Crc32 {
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        self.finalize() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum() {
+        // The standard check value for CRC-32
+        assert_eq!(Crc32::checksum(b"123456789"), 0xcbf43926);
+        assert_eq!(Crc32::checksum(b""), 0);
+    }
+
+    #[test]
+    fn test_update_in_parts() {
+        let mut crc = Crc32::new();
+        crc.update(b"RuSt");
+        crc.update(b"This is where your secret message will be!");
+        assert_eq!(crc.finalize(), 2882656334);
+        assert_eq!(
+            crc.finalize(),
+            Crc32::checksum(b"RuStThis is where your secret message will be!")
+        );
+    }
+
+    // The CRC a byte at a time, straight from the specification
+    fn reference_checksum(bytes: &[u8]) -> u32 {
+        let mut crc = 0xffffffff;
+        for v in bytes {
+            crc = CRC_TABLES[0][((crc ^ *v as u32) & 0xff) as usize] ^ (crc >> 8);
+        }
+        crc ^ 0xffffffff
+    }
+
+    #[test]
+    fn test_slices_match_reference() {
+        let bytes: Vec<u8> = (0..100u32).map(|i| (i * 37 + 11) as u8).collect();
+        for start in 0..8 {
+            for end in start..bytes.len() {
+                let slice = &bytes[start..end];
+                assert_eq!(
+                    update_with_tables(0xffffffff, slice) ^ 0xffffffff,
+                    reference_checksum(slice)
+                );
+                assert_eq!(Crc32::checksum(slice), reference_checksum(slice));
+            }
+        }
+    }
+
+    #[test]
+    fn test_hasher() {
+        let mut crc = Crc32::default();
+        crc.write(b"123456789");
+        assert_eq!(crc.finish(), 0xcbf43926);
+    }
+}
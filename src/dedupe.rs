@@ -0,0 +1,156 @@
+use crate::{chunk::Chunk, png::Png};
+use std::collections::HashMap;
+
+// Finding byte-identical copies of ancillary chunks, eg an ICC profile or a comment some
+// generators write twice, which can be removed without changing the image.
+
+// Chunks which are repeated on purpose, identical copies of them are still part of the image
+const REPEATABLE_CHUNKS: [&str; 3] = ["IDAT", "fdAT", "fcTL"];
+// Chunks which are only allowed before PLTE and IDAT
+const BEFORE_PALETTE: [&str; 8] = [
+    "iCCP", "sRGB", "gAMA", "cHRM", "sBIT", "cICP", "mDCv", "cLLi",
+];
+// Chunks which are only allowed after PLTE, when there is one
+const AFTER_PALETTE: [&str; 3] = ["bKGD", "hIST", "tRNS"];
+// Chunks which are only allowed before IDAT
+const BEFORE_IMAGE_DATA: [&str; 7] = ["bKGD", "hIST", "tRNS", "pHYs", "sPLT", "oFFs", "sCAL"];
+
+// A chunk which is a copy of an earlier one, or of a later one in a position the specification allows
+#[derive(Debug, PartialEq, Eq)]
+pub struct Duplicate {
+    pub index: usize,
+    // The index of the copy which is kept
+    pub kept: usize,
+}
+
+impl Duplicate {
+    // How much smaller the file is without the duplicate
+    pub fn size(&self, png: &Png) -> usize {
+        let chunk = &png.chunks()[self.index];
+        chunk.length() as usize + 12
+    }
+}
+
+pub fn can_dedupe(chunk: &Chunk) -> bool {
+    let name = chunk.chunk_type().to_string();
+    !chunk.chunk_type().is_critical() && !REPEATABLE_CHUNKS.contains(&name.as_str())
+}
+
+// Whether the specification allows the chunk at this index, as far as ordering goes
+fn is_legal_position(png: &Png, index: usize) -> bool {
+    let position = |name: &str| {
+        png.chunks()
+            .iter()
+            .position(|c| c.chunk_type().to_string() == name)
+            .unwrap_or(usize::MAX)
+    };
+    let name = png.chunks()[index].chunk_type().to_string();
+    let palette = position("PLTE");
+    if AFTER_PALETTE.contains(&name.as_str()) && palette != usize::MAX && index < palette {
+        return false;
+    }
+    if BEFORE_PALETTE.contains(&name.as_str()) {
+        index < palette.min(position("IDAT"))
+    } else if BEFORE_IMAGE_DATA.contains(&name.as_str()) {
+        index < position("IDAT")
+    } else {
+        true
+    }
+}
+
+// Every duplicate in the file, in the order they appear. Of each set of identical chunks
+// the first one in a legal position is kept, or the first one if none are.
+pub fn find_duplicates(png: &Png) -> Vec<Duplicate> {
+    let mut copies: HashMap<([u8; 4], &[u8]), Vec<usize>> = HashMap::new();
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        if can_dedupe(chunk) {
+            copies
+                .entry((chunk.chunk_type().bytes(), chunk.data()))
+                .or_default()
+                .push(index);
+        }
+    }
+
+    let mut duplicates: Vec<Duplicate> = copies
+        .values()
+        .filter(|indices| indices.len() > 1)
+        .flat_map(|indices| {
+            let kept = indices
+                .iter()
+                .copied()
+                .find(|&index| is_legal_position(png, index))
+                .unwrap_or(indices[0]);
+            indices
+                .iter()
+                .filter(move |&&index| index != kept)
+                .map(move |&index| Duplicate { index, kept })
+        })
+        .collect();
+    duplicates.sort_by_key(|d| d.index);
+
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(chunks: &[(&str, &str)]) -> Png {
+        Png::from_chunks(
+            chunks
+                .iter()
+                .map(|(chunk_type, data)| Chunk::from_strings(chunk_type, data).unwrap())
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let png = png(&[
+            ("IHDR", "header"),
+            ("tEXt", "Comment\0hello"),
+            ("tEXt", "Comment\0other"),
+            ("IDAT", "data"),
+            ("IDAT", "data"),
+            ("tEXt", "Comment\0hello"),
+            ("IEND", ""),
+        ]);
+        let duplicates = find_duplicates(&png);
+        assert_eq!(duplicates, vec![Duplicate { index: 5, kept: 1 }]);
+        assert_eq!(duplicates[0].size(&png), 13 + 12);
+    }
+
+    #[test]
+    fn test_keeps_first_legal_copy() {
+        // tRNS has to come after the palette, so the second copy is the one to keep
+        let png = png(&[
+            ("IHDR", "header"),
+            ("tRNS", "alpha"),
+            ("PLTE", "palette"),
+            ("tRNS", "alpha"),
+            ("IDAT", "data"),
+            ("tRNS", "alpha"),
+            ("IEND", ""),
+        ]);
+        assert_eq!(
+            find_duplicates(&png),
+            vec![
+                Duplicate { index: 1, kept: 3 },
+                Duplicate { index: 5, kept: 3 }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keeps_first_copy_when_none_are_legal() {
+        let png = png(&[
+            ("IHDR", "header"),
+            ("IDAT", "data"),
+            ("gAMA", "gamma"),
+            ("gAMA", "gamma"),
+            ("IEND", ""),
+        ]);
+        assert_eq!(find_duplicates(&png), vec![Duplicate { index: 3, kept: 2 }]);
+    }
+}
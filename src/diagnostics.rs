@@ -0,0 +1,361 @@
+use crate::{
+    chunk::ChunkError,
+    chunk_type::ChunkType,
+    crc::Crc32,
+    doctor::{Diagnosis, Problem},
+    lint::Lint,
+    png::{Png, PngError},
+    standard::StandardChunkError,
+};
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+
+// The problems `doctor` finds as plain data, for programs which show them to people in their
+// own way, eg a GUI. Each diagnostic has an id which doesn't change between releases, the
+// bytes of the file it is about, and a fix which can be applied to the bytes directly.
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    // Decoders may reject the file, or show it wrongly
+    Error,
+    // The file works, but isn't as it should be
+    Warning,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+// A range of bytes in the file
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Span {
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl Span {
+    fn end(&self) -> usize {
+        self.offset + self.length
+    }
+
+    fn json(&self) -> Value {
+        json!({ "offset": self.offset, "length": self.length })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum Fix {
+    // Write the CRC calculated from the chunk's type and data
+    RecalculateCrc { chunk: Span },
+    // Cut the file off at the offset
+    Truncate { offset: usize },
+    // Give the chunk a new type, and the CRC to match
+    RenameChunk { chunk: Span, to: ChunkType },
+}
+
+impl Fix {
+    pub fn id(&self) -> &'static str {
+        match self {
+            Fix::RecalculateCrc { .. } => "recalculate-crc",
+            Fix::Truncate { .. } => "truncate",
+            Fix::RenameChunk { .. } => "rename-chunk",
+        }
+    }
+
+    // Change the bytes of the file the diagnostics were made from
+    pub fn apply(&self, bytes: &mut Vec<u8>) -> Result<(), PngError> {
+        let chunk = match self {
+            Fix::Truncate { offset } => {
+                bytes.truncate(*offset);
+                return Ok(());
+            }
+            Fix::RecalculateCrc { chunk } | Fix::RenameChunk { chunk, .. } => chunk,
+        };
+        if chunk.length < 12 || chunk.end() > bytes.len() {
+            return Err(PngError::InvalidChunk {
+                start_index: chunk.offset,
+                source: ChunkError::NotEnoughBytes(
+                    bytes.len(),
+                    u32::try_from(chunk.end()).unwrap_or(u32::MAX),
+                ),
+            });
+        }
+
+        if let Fix::RenameChunk { to, .. } = self {
+            bytes[chunk.offset + 4..chunk.offset + 8].copy_from_slice(&to.bytes());
+        }
+        let crc = Crc32::checksum(&bytes[chunk.offset + 4..chunk.end() - 4]);
+        bytes[chunk.end() - 4..chunk.end()].copy_from_slice(&crc.to_be_bytes());
+
+        Ok(())
+    }
+
+    fn json(&self) -> Value {
+        match self {
+            Fix::RecalculateCrc { chunk } => json!({ "id": self.id(), "chunk": chunk.json() }),
+            Fix::Truncate { offset } => json!({ "id": self.id(), "offset": *offset }),
+            Fix::RenameChunk { chunk, to } => json!({
+                "id": self.id(),
+                "chunk": chunk.json(),
+                "to": to.to_string(),
+            }),
+        }
+    }
+}
+
+// Apply fixes to the bytes of the file the diagnostics were made from, then read the result.
+// Truncating happens last, so it doesn't move the chunks the other fixes are for.
+pub fn apply_fixes(bytes: &[u8], fixes: &[Fix]) -> Result<Png, PngError> {
+    let mut bytes = bytes.to_vec();
+    let (truncates, others): (Vec<&Fix>, Vec<&Fix>) = fixes
+        .iter()
+        .partition(|fix| matches!(fix, Fix::Truncate { .. }));
+    for fix in others.into_iter().chain(truncates) {
+        fix.apply(&mut bytes)?;
+    }
+
+    Png::try_from(&bytes[..])
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    pub id: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>,
+    pub fix: Option<Fix>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostics {
+    pub file_size: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn fixes(&self) -> Vec<Fix> {
+        self.diagnostics.iter().filter_map(|d| d.fix).collect()
+    }
+
+    pub fn json(&self) -> Value {
+        let diagnostics: Vec<Value> = self
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                json!({
+                    "id": diagnostic.id,
+                    "severity": diagnostic.severity.name(),
+                    "message": diagnostic.message.clone(),
+                    "span": diagnostic.span.map(|span| span.json()),
+                    "fix": diagnostic.fix.map(|fix| fix.json()),
+                })
+            })
+            .collect();
+
+        json!({
+            "file_size": self.file_size,
+            "diagnostics": diagnostics,
+        })
+    }
+}
+
+// Ids for the problems with the standard chunks, and which chunk each is about
+fn standard_problem(error: &StandardChunkError) -> (&'static str, Option<&'static str>) {
+    match error {
+        StandardChunkError::Missing(chunk_type) => ("missing-chunk", Some(*chunk_type)),
+        StandardChunkError::WrongLength { chunk_type, .. } => ("wrong-length", Some(*chunk_type)),
+        StandardChunkError::UnknownColorType(_) => ("unknown-color-type", Some("IHDR")),
+        StandardChunkError::InvalidBitDepth { .. } => ("invalid-bit-depth", Some("IHDR")),
+        StandardChunkError::HistogramOddLength(_) => ("histogram-odd-length", Some("hIST")),
+        StandardChunkError::HistogramWithoutPalette => ("histogram-without-palette", Some("hIST")),
+        StandardChunkError::HistogramSizeMismatch(_, _) => {
+            ("histogram-size-mismatch", Some("hIST"))
+        }
+        StandardChunkError::SignificantBitsOutOfRange { .. } => {
+            ("significant-bits-out-of-range", Some("sBIT"))
+        }
+        StandardChunkError::InvalidColor(_) => ("invalid-color", Some("bKGD")),
+        StandardChunkError::ColorNotGrey(_, _) => ("color-not-grey", Some("bKGD")),
+        StandardChunkError::ColorNotInPalette(_) => ("color-not-in-palette", Some("bKGD")),
+        StandardChunkError::PaletteIndexOutOfRange { chunk_type, .. } => {
+            ("palette-index-out-of-range", Some(*chunk_type))
+        }
+        StandardChunkError::TransparencyNotAllowed(_) => ("transparency-not-allowed", Some("tRNS")),
+    }
+}
+
+impl Diagnosis {
+    fn chunk_span(&self, index: usize) -> Span {
+        Span {
+            offset: self.offsets[index],
+            length: self.png.chunks()[index].length() as usize + 12,
+        }
+    }
+
+    // Every lint of a chunk renames it to the same type, with all of its letters fixed
+    fn fixed_type(&self, index: usize) -> Option<ChunkType> {
+        self.findings
+            .iter()
+            .find(|finding| finding.index == index)
+            .map(|finding| finding.fixed_type)
+    }
+
+    fn diagnostic(&self, problem: &Problem) -> Diagnostic {
+        let message = problem.to_string();
+        match problem {
+            Problem::WrongCrc { index, .. } => Diagnostic {
+                id: "wrong-crc",
+                severity: Severity::Error,
+                message,
+                span: Some(self.chunk_span(*index)),
+                fix: Some(Fix::RecalculateCrc {
+                    chunk: self.chunk_span(*index),
+                }),
+            },
+            Problem::Trailer { offset, length } => Diagnostic {
+                id: "trailer",
+                severity: Severity::Warning,
+                message,
+                span: Some(Span {
+                    offset: *offset,
+                    length: *length,
+                }),
+                fix: Some(Fix::Truncate { offset: *offset }),
+            },
+            Problem::Lint { index, lint } => {
+                let (id, severity) = match lint {
+                    Lint::PrivateCritical(_) => ("private-critical", Severity::Error),
+                    Lint::UnregisteredPublic(_) => ("unregistered-public", Severity::Warning),
+                    Lint::UnsafeToCopyMessage(_) => ("unsafe-to-copy-message", Severity::Warning),
+                };
+                Diagnostic {
+                    id,
+                    severity,
+                    message,
+                    span: Some(self.chunk_span(*index)),
+                    fix: self.fixed_type(*index).map(|to| Fix::RenameChunk {
+                        chunk: self.chunk_span(*index),
+                        to,
+                    }),
+                }
+            }
+            Problem::Standard(error) => {
+                let (id, chunk_type) = standard_problem(error);
+                let span = chunk_type
+                    .and_then(|chunk_type| {
+                        self.png
+                            .chunks()
+                            .iter()
+                            .position(|c| c.chunk_type().to_string() == chunk_type)
+                    })
+                    .map(|index| self.chunk_span(index));
+                Diagnostic {
+                    id,
+                    severity: Severity::Error,
+                    message,
+                    span,
+                    fix: None,
+                }
+            }
+        }
+    }
+
+    pub fn diagnostics(&self) -> Diagnostics {
+        Diagnostics {
+            file_size: self.size,
+            diagnostics: self
+                .problems
+                .iter()
+                .map(|problem| self.diagnostic(problem))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::doctor;
+
+    fn broken_png() -> Vec<u8> {
+        let png = Png::from_chunks(vec![
+            Chunk::new(
+                ChunkType::try_from(*b"IHDR").unwrap(),
+                vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0],
+            ),
+            Chunk::from_strings("RuSt", "This is where your secret message will be!").unwrap(),
+            Chunk::from_strings("tEXt", "Comment\0hello").unwrap(),
+            Chunk::from_strings("IDAT", "data").unwrap(),
+            Chunk::from_strings("IEND", "").unwrap(),
+        ])
+        .unwrap();
+        let mut bytes = png.as_bytes();
+        // Corrupt the CRC of the tEXt chunk, and add a trailer
+        bytes[8 + 25 + 54 + 8 + 13] ^= 0xff;
+        bytes.extend_from_slice(b"trailing");
+        bytes
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let bytes = broken_png();
+        let diagnostics = doctor::diagnose(&bytes).unwrap().diagnostics();
+
+        assert_eq!(diagnostics.file_size, bytes.len());
+        let ids: Vec<&str> = diagnostics.diagnostics.iter().map(|d| d.id).collect();
+        assert_eq!(ids, vec!["wrong-crc", "trailer", "private-critical"]);
+
+        let wrong_crc = &diagnostics.diagnostics[0];
+        assert_eq!(wrong_crc.severity, Severity::Error);
+        assert_eq!(
+            wrong_crc.span,
+            Some(Span {
+                offset: 8 + 25 + 54,
+                length: 25
+            })
+        );
+        let trailer = &diagnostics.diagnostics[1];
+        assert_eq!(trailer.severity, Severity::Warning);
+        assert_eq!(
+            trailer.fix,
+            Some(Fix::Truncate {
+                offset: bytes.len() - 8
+            })
+        );
+    }
+
+    #[test]
+    fn test_apply_fixes() {
+        let bytes = broken_png();
+        let diagnostics = doctor::diagnose(&bytes).unwrap().diagnostics();
+
+        let fixed = apply_fixes(&bytes, &diagnostics.fixes()).unwrap();
+        assert!(doctor::diagnose(&fixed.as_bytes()).unwrap().is_healthy());
+
+        // Only fixing the CRC leaves the trailer, which `Png` won't read
+        let crc_only: Vec<Fix> = diagnostics
+            .fixes()
+            .into_iter()
+            .filter(|fix| fix.id() == "recalculate-crc")
+            .collect();
+        assert!(apply_fixes(&bytes, &crc_only).is_err());
+    }
+
+    #[test]
+    fn test_fix_outside_file() {
+        let fix = Fix::RecalculateCrc {
+            chunk: Span {
+                offset: 100,
+                length: 12,
+            },
+        };
+        assert!(fix.apply(&mut vec![0; 50]).is_err());
+    }
+}
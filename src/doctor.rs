@@ -0,0 +1,209 @@
+use crate::{
+    chunk::{ChunkDecoder, ChunkEvent},
+    chunk_type::ChunkType,
+    lint::{self, Finding, Lint},
+    png::{Png, PngError, PngResult},
+    standard::{self, StandardChunkError},
+    view::CrcCheck,
+};
+use std::io;
+use thiserror::Error;
+
+// Everything wrong with a file in one pass, so there is one command to run on a PNG which
+// other software won't open. Reading is more forgiving than `Png::try_from`, chunks with
+// the wrong CRC and bytes after the IEND chunk are reported rather than failing.
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Problem {
+    #[error("Chunk {index} `{chunk_type}` has the CRC {stored:#010x}, but its data gives {calculated:#010x}")]
+    WrongCrc {
+        index: usize,
+        chunk_type: ChunkType,
+        stored: u32,
+        calculated: u32,
+    },
+    #[error("Found {length} bytes after the IEND chunk, at offset {offset}")]
+    Trailer { offset: usize, length: usize },
+    #[error("Chunk {index} - {lint}")]
+    Lint { index: usize, lint: Lint },
+    #[error(transparent)]
+    Standard(#[from] StandardChunkError),
+}
+
+impl Problem {
+    // Whether it can be fixed without guessing what the file should contain.
+    // The standard chunks need someone to decide which of the conflicting values is right.
+    pub fn is_fixable(&self) -> bool {
+        !matches!(self, Problem::Standard(_))
+    }
+}
+
+pub struct Diagnosis {
+    pub problems: Vec<Problem>,
+    // The chunks which were read, with their CRCs calculated afresh and without any trailer
+    pub(crate) png: Png,
+    // Where each chunk starts in the file, for `diagnostics`
+    #[cfg_attr(not(feature = "diagnostics"), allow(dead_code))]
+    pub(crate) offsets: Vec<usize>,
+    #[cfg_attr(not(feature = "diagnostics"), allow(dead_code))]
+    pub(crate) size: usize,
+    pub(crate) findings: Vec<Finding>,
+}
+
+impl Diagnosis {
+    pub fn is_healthy(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn fixable(&self) -> usize {
+        self.problems.iter().filter(|p| p.is_fixable()).count()
+    }
+
+    // The file with every fixable problem fixed. Writing out the chunks which were read
+    // already corrects their CRCs and leaves out the trailer, what's left is the lints.
+    pub fn fix(&self) -> PngResult {
+        lint::fix(&self.png, &self.findings)
+    }
+}
+
+pub fn diagnose(bytes: &[u8]) -> Result<Diagnosis, PngError> {
+    if bytes.len() < Png::STANDARD_HEADER.len() {
+        return Err(PngError::Io {
+            source: io::ErrorKind::UnexpectedEof.into(),
+        });
+    }
+    if bytes[..8] != Png::STANDARD_HEADER {
+        return Err(PngError::NotAValidPNGHeader(Box::new(bytes[..8].to_vec())));
+    }
+
+    let mut problems = vec![];
+    let mut chunks = vec![];
+    let mut offsets = vec![];
+    let mut offset = 8;
+    for event in ChunkDecoder::with_crc_check(CrcCheck::Lazy).events(&bytes[8..]) {
+        let chunk = match event {
+            ChunkEvent::Chunk { chunk, .. } => chunk,
+            ChunkEvent::WrongCrc {
+                chunk,
+                stored,
+                calculated,
+                ..
+            } => {
+                problems.push(Problem::WrongCrc {
+                    index: chunks.len(),
+                    chunk_type: *chunk.chunk_type(),
+                    stored,
+                    calculated,
+                });
+                chunk
+            }
+            ChunkEvent::Error { error, .. } => {
+                return Err(PngError::InvalidChunk {
+                    start_index: offset,
+                    source: error,
+                })
+            }
+        };
+        offsets.push(offset);
+        offset += chunk.length() as usize + 12;
+
+        let is_end = chunk.chunk_type().to_string() == "IEND";
+        chunks.push(chunk);
+        if is_end {
+            break;
+        }
+    }
+    if offset < bytes.len() {
+        problems.push(Problem::Trailer {
+            offset,
+            length: bytes.len() - offset,
+        });
+    }
+
+    let png = Png::from_chunks(chunks)?;
+    let findings = lint::lint(&png);
+    problems.extend(findings.iter().flat_map(|finding| {
+        finding.lints.iter().map(move |&lint| Problem::Lint {
+            index: finding.index,
+            lint,
+        })
+    }));
+    problems.extend(standard::check(&png).into_iter().map(Problem::from));
+
+    Ok(Diagnosis {
+        problems,
+        png,
+        offsets,
+        size: bytes.len(),
+        findings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use std::convert::TryFrom;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(
+                ChunkType::try_from(*b"IHDR").unwrap(),
+                vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0],
+            ),
+            Chunk::from_strings("RuSt", "This is where your secret message will be!").unwrap(),
+            Chunk::from_strings("IDAT", "data").unwrap(),
+            Chunk::from_strings("IEND", "").unwrap(),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_diagnose_and_fix() {
+        let mut bytes = testing_png().as_bytes();
+        // Corrupt the CRC of the RuSt chunk, and add a trailer
+        let rust_crc = 8 + 25 + 8 + 42;
+        bytes[rust_crc] ^= 0xff;
+        bytes.extend_from_slice(b"trailing");
+
+        let diagnosis = diagnose(&bytes).unwrap();
+        assert_eq!(diagnosis.problems.len(), 3);
+        assert!(matches!(
+            diagnosis.problems[0],
+            Problem::WrongCrc { index: 1, .. }
+        ));
+        assert_eq!(
+            diagnosis.problems[1],
+            Problem::Trailer {
+                offset: bytes.len() - 8,
+                length: 8
+            }
+        );
+        assert!(matches!(
+            diagnosis.problems[2],
+            Problem::Lint {
+                index: 1,
+                lint: Lint::PrivateCritical(_)
+            }
+        ));
+        assert_eq!(diagnosis.fixable(), 3);
+
+        let fixed = diagnosis.fix().unwrap();
+        assert!(diagnose(&fixed.as_bytes()).unwrap().is_healthy());
+        assert_eq!(fixed.chunks()[1].chunk_type().to_string(), "ruSt");
+    }
+
+    #[test]
+    fn test_diagnose_healthy() {
+        let png = lint::fix(&testing_png(), &lint::lint(&testing_png())).unwrap();
+        assert!(diagnose(&png.as_bytes()).unwrap().is_healthy());
+    }
+
+    #[test]
+    fn test_diagnose_unreadable() {
+        let bytes = testing_png().as_bytes();
+        assert!(diagnose(&bytes[..bytes.len() - 20]).is_err());
+        assert!(diagnose(&bytes[1..]).is_err());
+    }
+}
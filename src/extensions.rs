@@ -0,0 +1,459 @@
+use crate::{chunk::Chunk, chunk_type::ChunkType};
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+};
+use thiserror::Error;
+
+// Typed views of the registered extension chunks used by scientific and mapping imagery,
+// as defined in `Extensions to the PNG 1.2 Specification, Version 1.5.0`
+
+#[derive(Error, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ExtensionChunkError {
+    #[error("`{chunk_type}` chunk should be {expected} bytes, but found `{found}` bytes")]
+    WrongLength {
+        chunk_type: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    #[error("`{0}` chunk is truncated")]
+    Truncated(&'static str),
+    #[error("Unknown `{chunk_type}` unit `{unit}`")]
+    UnknownUnit { chunk_type: &'static str, unit: u8 },
+    #[error("Unknown `pCAL` equation type `{0}`")]
+    UnknownEquation(u8),
+    #[error("`pCAL` equation {equation} needs {expected} parameters, but found `{found}`")]
+    WrongParameterCount {
+        equation: Equation,
+        expected: usize,
+        found: usize,
+    },
+    #[error("`{0}` is not a valid floating point value")]
+    InvalidFloat(String),
+    #[error("`sCAL` width and height must be positive, found `{0}` and `{1}`")]
+    ScaleNotPositive(f64, f64),
+}
+
+pub type ExtensionChunkResult<T> = Result<T, ExtensionChunkError>;
+
+// The unit of an oFFs offset
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OffsetUnit {
+    Pixel,
+    Micrometre,
+}
+
+// The oFFs chunk, the position of the image on a larger page or map
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ImageOffset {
+    pub x: i32,
+    pub y: i32,
+    pub unit: OffsetUnit,
+}
+
+impl ImageOffset {
+    const LENGTH: usize = 9;
+
+    pub fn to_chunk(&self) -> Chunk {
+        let unit = match self.unit {
+            OffsetUnit::Pixel => 0,
+            OffsetUnit::Micrometre => 1,
+        };
+        let data = self
+            .x
+            .to_be_bytes()
+            .iter()
+            .chain(self.y.to_be_bytes().iter())
+            .copied()
+            .chain(Some(unit))
+            .collect();
+
+        Chunk::new(ChunkType::from_known(*b"oFFs"), data)
+    }
+}
+
+impl TryFrom<&Chunk> for ImageOffset {
+    type Error = ExtensionChunkError;
+
+    fn try_from(chunk: &Chunk) -> ExtensionChunkResult<ImageOffset> {
+        let data = chunk.data();
+        if data.len() != ImageOffset::LENGTH {
+            return Err(ExtensionChunkError::WrongLength {
+                chunk_type: "oFFs",
+                expected: ImageOffset::LENGTH,
+                found: data.len(),
+            });
+        }
+
+        let unit = match data[8] {
+            0 => OffsetUnit::Pixel,
+            1 => OffsetUnit::Micrometre,
+            unit => {
+                return Err(ExtensionChunkError::UnknownUnit {
+                    chunk_type: "oFFs",
+                    unit,
+                })
+            }
+        };
+
+        Ok(ImageOffset {
+            x: i32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            y: i32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            unit,
+        })
+    }
+}
+
+impl Display for ImageOffset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let unit = match self.unit {
+            OffsetUnit::Pixel => "pixels",
+            OffsetUnit::Micrometre => "micrometres",
+        };
+        write!(f, "Image offset {}, {} {}", self.x, self.y, unit)
+    }
+}
+
+// The unit of an sCAL scale
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ScaleUnit {
+    Metre,
+    Radian,
+}
+
+// The sCAL chunk, the physical size of each pixel in the image subject.
+// Stored as text so it can hold values too large or small for pHYs.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PhysicalScale {
+    pub unit: ScaleUnit,
+    pub pixel_width: f64,
+    pub pixel_height: f64,
+}
+
+impl PhysicalScale {
+    pub fn to_chunk(&self) -> Chunk {
+        let unit = match self.unit {
+            ScaleUnit::Metre => 1,
+            ScaleUnit::Radian => 2,
+        };
+        let mut data = vec![unit];
+        data.extend(self.pixel_width.to_string().bytes());
+        data.push(0);
+        data.extend(self.pixel_height.to_string().bytes());
+
+        Chunk::new(ChunkType::from_known(*b"sCAL"), data)
+    }
+}
+
+impl TryFrom<&Chunk> for PhysicalScale {
+    type Error = ExtensionChunkError;
+
+    fn try_from(chunk: &Chunk) -> ExtensionChunkResult<PhysicalScale> {
+        let data = chunk.data();
+        let unit = match data.first() {
+            Some(1) => ScaleUnit::Metre,
+            Some(2) => ScaleUnit::Radian,
+            Some(unit) => {
+                return Err(ExtensionChunkError::UnknownUnit {
+                    chunk_type: "sCAL",
+                    unit: *unit,
+                })
+            }
+            None => return Err(ExtensionChunkError::Truncated("sCAL")),
+        };
+
+        let mut values = data[1..].splitn(2, |b| *b == 0);
+        let pixel_width = parse_float(values.next().unwrap_or(&[]))?;
+        let pixel_height = parse_float(
+            values
+                .next()
+                .ok_or(ExtensionChunkError::Truncated("sCAL"))?,
+        )?;
+        if pixel_width <= 0.0 || pixel_height <= 0.0 {
+            return Err(ExtensionChunkError::ScaleNotPositive(
+                pixel_width,
+                pixel_height,
+            ));
+        }
+
+        Ok(PhysicalScale {
+            unit,
+            pixel_width,
+            pixel_height,
+        })
+    }
+}
+
+impl Display for PhysicalScale {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let unit = match self.unit {
+            ScaleUnit::Metre => "metres",
+            ScaleUnit::Radian => "radians",
+        };
+        write!(
+            f,
+            "Each pixel is {} by {} {}",
+            self.pixel_width, self.pixel_height, unit
+        )
+    }
+}
+
+// How pCAL maps stored pixel values to physical values
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Equation {
+    Linear,
+    BaseEExponential,
+    ArbitraryBaseExponential,
+    Hyperbolic,
+}
+
+impl Equation {
+    fn from_byte(value: u8) -> ExtensionChunkResult<Equation> {
+        match value {
+            0 => Ok(Equation::Linear),
+            1 => Ok(Equation::BaseEExponential),
+            2 => Ok(Equation::ArbitraryBaseExponential),
+            3 => Ok(Equation::Hyperbolic),
+            v => Err(ExtensionChunkError::UnknownEquation(v)),
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Equation::Linear => 0,
+            Equation::BaseEExponential => 1,
+            Equation::ArbitraryBaseExponential => 2,
+            Equation::Hyperbolic => 3,
+        }
+    }
+
+    pub fn parameter_count(self) -> usize {
+        match self {
+            Equation::Linear => 2,
+            Equation::BaseEExponential => 3,
+            Equation::ArbitraryBaseExponential | Equation::Hyperbolic => 4,
+        }
+    }
+}
+
+impl Display for Equation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Equation::Linear => "linear",
+            Equation::BaseEExponential => "base-e exponential",
+            Equation::ArbitraryBaseExponential => "arbitrary-base exponential",
+            Equation::Hyperbolic => "hyperbolic",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+// The pCAL chunk, the calibration from stored pixel values to physical values,
+// eg elevation in metres for a height map.
+// The name and unit are Latin-1 text.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PixelCalibration {
+    pub name: String,
+    // The stored values original_zero and original_max map onto
+    pub original_zero: i32,
+    pub original_max: i32,
+    pub equation: Equation,
+    pub unit: String,
+    pub parameters: Vec<f64>,
+}
+
+impl PixelCalibration {
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = latin1_bytes(&self.name);
+        data.push(0);
+        data.extend(self.original_zero.to_be_bytes().iter());
+        data.extend(self.original_max.to_be_bytes().iter());
+        data.push(self.equation.to_byte());
+        // More parameters than fit in the count are never valid, so saturate and let readers reject it
+        data.push(u8::try_from(self.parameters.len()).unwrap_or(u8::MAX));
+        data.extend(latin1_bytes(&self.unit));
+        for parameter in self.parameters.iter() {
+            data.push(0);
+            data.extend(parameter.to_string().bytes());
+        }
+
+        Chunk::new(ChunkType::from_known(*b"pCAL"), data)
+    }
+}
+
+impl TryFrom<&Chunk> for PixelCalibration {
+    type Error = ExtensionChunkError;
+
+    fn try_from(chunk: &Chunk) -> ExtensionChunkResult<PixelCalibration> {
+        let data = chunk.data();
+        let name_end = data
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or(ExtensionChunkError::Truncated("pCAL"))?;
+        // After the name are the two 4 byte values, the equation type and the parameter count
+        let fixed = data
+            .get(name_end + 1..name_end + 11)
+            .ok_or(ExtensionChunkError::Truncated("pCAL"))?;
+
+        let equation = Equation::from_byte(fixed[8])?;
+        let mut fields = data[name_end + 11..].split(|b| *b == 0);
+        let unit = latin1_decode(fields.next().unwrap_or(&[]));
+        let parameters = fields
+            .map(parse_float)
+            .collect::<ExtensionChunkResult<Vec<f64>>>()?;
+        if parameters.len() != equation.parameter_count()
+            || parameters.len() != usize::from(fixed[9])
+        {
+            return Err(ExtensionChunkError::WrongParameterCount {
+                equation,
+                expected: equation.parameter_count(),
+                found: parameters.len(),
+            });
+        }
+
+        Ok(PixelCalibration {
+            name: latin1_decode(&data[..name_end]),
+            original_zero: i32::from_be_bytes([fixed[0], fixed[1], fixed[2], fixed[3]]),
+            original_max: i32::from_be_bytes([fixed[4], fixed[5], fixed[6], fixed[7]]),
+            equation,
+            unit,
+            parameters,
+        })
+    }
+}
+
+impl Display for PixelCalibration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let parameters: Vec<String> = self.parameters.iter().map(|p| p.to_string()).collect();
+        write!(
+            f,
+            "Calibration {}, {} to {} mapped to {} with {} equation, parameters {}",
+            self.name,
+            self.original_zero,
+            self.original_max,
+            self.unit,
+            self.equation,
+            parameters.join(", ")
+        )
+    }
+}
+
+// Describe the contents of an extension chunk, for the chunks which have a typed view
+pub fn describe(chunk: &Chunk) -> Option<ExtensionChunkResult<String>> {
+    let description = match chunk.chunk_type().to_string().as_str() {
+        "oFFs" => ImageOffset::try_from(chunk).map(|offset| offset.to_string()),
+        "sCAL" => PhysicalScale::try_from(chunk).map(|scale| scale.to_string()),
+        "pCAL" => PixelCalibration::try_from(chunk).map(|calibration| calibration.to_string()),
+        _ => return None,
+    };
+
+    Some(description)
+}
+
+fn parse_float(value: &[u8]) -> ExtensionChunkResult<f64> {
+    let text = String::from_utf8_lossy(value);
+    text.parse::<f64>()
+        .map_err(|_| ExtensionChunkError::InvalidFloat(text.to_string()))
+}
+
+// Latin-1 maps each byte to the code point of the same value, as in `text`, which isn't
+// always compiled in
+fn latin1_decode(value: &[u8]) -> String {
+    value.iter().map(|b| char::from(*b)).collect()
+}
+
+// Characters outside of Latin-1 are replaced, as the names are only descriptive
+fn latin1_bytes(value: &str) -> Vec<u8> {
+    value
+        .chars()
+        .map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?'))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    #[test]
+    fn test_image_offset_round_trip() {
+        let offset = ImageOffset {
+            x: -20,
+            y: 300,
+            unit: OffsetUnit::Micrometre,
+        };
+        let actual = ImageOffset::try_from(&offset.to_chunk()).unwrap();
+        assert_eq!(actual, offset);
+    }
+
+    #[test]
+    fn test_image_offset_unknown_unit() {
+        let data = vec![0, 0, 0, 1, 0, 0, 0, 1, 7];
+        assert!(ImageOffset::try_from(&chunk("oFFs", data)).is_err());
+    }
+
+    #[test]
+    fn test_physical_scale() {
+        let scale =
+            PhysicalScale::try_from(&chunk("sCAL", b"\x010.25\x001.5e-3".to_vec())).unwrap();
+        assert_eq!(scale.unit, ScaleUnit::Metre);
+        assert_eq!(scale.pixel_width, 0.25);
+        assert_eq!(scale.pixel_height, 0.0015);
+        assert_eq!(PhysicalScale::try_from(&scale.to_chunk()).unwrap(), scale);
+    }
+
+    #[test]
+    fn test_physical_scale_not_positive() {
+        assert!(PhysicalScale::try_from(&chunk("sCAL", b"\x01-1\x001".to_vec())).is_err());
+    }
+
+    #[test]
+    fn test_pixel_calibration_round_trip() {
+        let calibration = PixelCalibration {
+            name: String::from("Elevation"),
+            original_zero: 0,
+            original_max: 65535,
+            equation: Equation::Linear,
+            unit: String::from("m"),
+            parameters: vec![-100.0, 4000.5],
+        };
+        let actual = PixelCalibration::try_from(&calibration.to_chunk()).unwrap();
+        assert_eq!(actual, calibration);
+    }
+
+    #[test]
+    fn test_pixel_calibration_wrong_parameter_count() {
+        let mut calibration = PixelCalibration {
+            name: String::from("Depth"),
+            original_zero: 0,
+            original_max: 255,
+            equation: Equation::Hyperbolic,
+            unit: String::from("m"),
+            parameters: vec![1.0, 2.0],
+        };
+        assert!(PixelCalibration::try_from(&calibration.to_chunk()).is_err());
+
+        calibration.parameters = vec![1.0, 2.0, 3.0, 4.0];
+        assert!(PixelCalibration::try_from(&calibration.to_chunk()).is_ok());
+    }
+
+    #[test]
+    fn test_describe() {
+        let offset = ImageOffset {
+            x: 1,
+            y: 2,
+            unit: OffsetUnit::Pixel,
+        };
+        assert_eq!(
+            describe(&offset.to_chunk()).unwrap().unwrap(),
+            "Image offset 1, 2 pixels"
+        );
+        assert!(describe(&chunk("tEXt", vec![])).is_none());
+    }
+}
@@ -0,0 +1,596 @@
+use crate::{
+    apng::{AnimationControl, FrameControl},
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    png::Png,
+    standard::{ColorType, ImageHeader},
+    text::{TextChunk, TextKind},
+};
+use flate2::{write::ZlibEncoder, Compression};
+use serde_json::{json, Value};
+use std::io::Write;
+
+// A labelled corpus of small PNGs covering the format: every colour type and bit depth,
+// interlacing, animation, each kind of text chunk, and files broken in the ways real files
+// get broken. The files are generated rather than stored, with the same bytes on every run,
+// for testing pngme and any other software which reads PNGs.
+
+const SIZE: u32 = 8;
+
+const COLOR_TYPES: [(ColorType, &str); 5] = [
+    (ColorType::Grayscale, "grey"),
+    (ColorType::Truecolor, "rgb"),
+    (ColorType::Indexed, "indexed"),
+    (ColorType::GrayscaleAlpha, "grey-alpha"),
+    (ColorType::TruecolorAlpha, "rgba"),
+];
+
+// The Adam7 passes, as the column and row each starts at and the steps between pixels
+const ADAM7: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+pub struct Fixture {
+    // The file name, without the .png extension
+    pub name: String,
+    pub description: String,
+    // Whether a decoder should accept the file
+    pub valid: bool,
+    pub bytes: Vec<u8>,
+}
+
+impl Fixture {
+    fn new(name: String, description: String, valid: bool, bytes: Vec<u8>) -> Fixture {
+        Fixture {
+            name,
+            description,
+            valid,
+            bytes,
+        }
+    }
+
+    pub fn file_name(&self) -> String {
+        format!("{}.png", self.name)
+    }
+
+    pub fn json(&self) -> Value {
+        json!({
+            "file": self.file_name(),
+            "description": self.description.clone(),
+            "valid": self.valid,
+            "size": self.bytes.len(),
+        })
+    }
+}
+
+fn chunk(chunk_type: [u8; 4], data: Vec<u8>) -> Chunk {
+    Chunk::new(ChunkType::from_known(chunk_type), data)
+}
+
+fn header(color_type: ColorType, bit_depth: u8, width: u32, height: u32) -> ImageHeader {
+    ImageHeader {
+        width,
+        height,
+        bit_depth,
+        color_type,
+        interlaced: false,
+    }
+}
+
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    // Writing to a Vec can't fail
+    let _ = encoder.write_all(data);
+    encoder.finish().unwrap_or_default()
+}
+
+// Rows of pixels, each with a filter type byte of none in front. The pixels are a pattern
+// rather than a picture which wraps around, every value is a valid palette index as the
+// palettes are full size.
+#[allow(clippy::cast_possible_truncation)]
+fn scanlines(header: &ImageHeader, width: u32, height: u32, seed: u8) -> Vec<u8> {
+    let bits = header.color_type.channels() * header.bit_depth as usize;
+    let row_length = (width as usize * bits).div_ceil(8);
+
+    let mut data = Vec::with_capacity((row_length + 1) * height as usize);
+    for y in 0..height as usize {
+        data.push(0);
+        data.extend((0..row_length).map(|x| (x * 31 + y * 17) as u8 ^ seed));
+    }
+
+    data
+}
+
+// The compressed data for the IDAT chunk. Interlaced images store each pass as a smaller
+// image one after the other, leaving out passes with no pixels.
+fn image_data(header: &ImageHeader, seed: u8) -> Vec<u8> {
+    if !header.interlaced {
+        return compress(&scanlines(header, header.width, header.height, seed));
+    }
+
+    let mut data = vec![];
+    for &(x, y, x_step, y_step) in ADAM7.iter() {
+        let width = header.width.saturating_sub(x).div_ceil(x_step);
+        let height = header.height.saturating_sub(y).div_ceil(y_step);
+        if width > 0 && height > 0 {
+            data.extend(scanlines(header, width, height, seed));
+        }
+    }
+
+    compress(&data)
+}
+
+// The chunks of an image, with `extra` chunks between the header and the image data
+#[allow(clippy::cast_possible_truncation)]
+fn image(header: &ImageHeader, extra: Vec<Chunk>) -> Vec<Chunk> {
+    let mut chunks = vec![header.to_chunk()];
+    if header.color_type == ColorType::Indexed {
+        let entries = 1usize << header.bit_depth;
+        chunks.push(chunk(
+            *b"PLTE",
+            (0..entries * 3).map(|i| (i * 7) as u8).collect(),
+        ));
+    }
+    chunks.extend(extra);
+    chunks.push(chunk(*b"IDAT", image_data(header, 0)));
+    chunks.push(chunk(*b"IEND", vec![]));
+
+    chunks
+}
+
+// The fixtures are written as they are, broken or not
+fn to_bytes(chunks: Vec<Chunk>) -> Vec<u8> {
+    let mut bytes = Png::STANDARD_HEADER.to_vec();
+    for chunk in chunks.iter() {
+        bytes.extend(chunk.as_bytes());
+    }
+
+    bytes
+}
+
+// Where the chunk at the index starts in the file
+fn offset_of(chunks: &[Chunk], index: usize) -> usize {
+    Png::STANDARD_HEADER.len()
+        + chunks[..index]
+            .iter()
+            .map(|chunk| chunk.length() as usize + 12)
+            .sum::<usize>()
+}
+
+fn plain_images() -> Vec<Fixture> {
+    let mut fixtures = vec![];
+    for &(color_type, name) in COLOR_TYPES.iter() {
+        for &bit_depth in color_type.allowed_bit_depths() {
+            let header = header(color_type, bit_depth, SIZE, SIZE);
+            fixtures.push(Fixture::new(
+                format!("basic-{}-{}bit", name, bit_depth),
+                format!(
+                    "{}x{} {} image with {} bits per sample",
+                    SIZE, SIZE, color_type, bit_depth
+                ),
+                true,
+                to_bytes(image(&header, vec![])),
+            ));
+        }
+    }
+
+    fixtures
+}
+
+// Interlaced images are an odd size, so some passes are only partly filled
+fn interlaced_images() -> Vec<Fixture> {
+    let mut fixtures = vec![];
+    for &(color_type, name) in COLOR_TYPES.iter() {
+        for &bit_depth in color_type.allowed_bit_depths() {
+            let header = ImageHeader {
+                interlaced: true,
+                ..header(color_type, bit_depth, 13, 11)
+            };
+            fixtures.push(Fixture::new(
+                format!("interlaced-{}-{}bit", name, bit_depth),
+                format!(
+                    "13x11 Adam7 interlaced {} image with {} bits per sample",
+                    color_type, bit_depth
+                ),
+                true,
+                to_bytes(image(&header, vec![])),
+            ));
+        }
+    }
+
+    let header = ImageHeader {
+        interlaced: true,
+        ..header(ColorType::TruecolorAlpha, 8, 1, 1)
+    };
+    fixtures.push(Fixture::new(
+        String::from("interlaced-single-pixel"),
+        String::from("1x1 Adam7 interlaced image, where only the first pass has any pixels"),
+        true,
+        to_bytes(image(&header, vec![])),
+    ));
+
+    fixtures
+}
+
+fn animated_image() -> Fixture {
+    let header = header(ColorType::TruecolorAlpha, 8, SIZE, SIZE);
+    let frame = |sequence_number| FrameControl {
+        sequence_number,
+        width: SIZE,
+        height: SIZE,
+        x_offset: 0,
+        y_offset: 0,
+        delay_num: 1,
+        delay_den: 2,
+        dispose_op: 0,
+        blend_op: 0,
+    };
+    let control = AnimationControl {
+        num_frames: 2,
+        num_plays: 0,
+    };
+    let mut frame_data = 2u32.to_be_bytes().to_vec();
+    frame_data.extend(image_data(&header, 0xff));
+
+    Fixture::new(
+        String::from("animated"),
+        String::from("Animated PNG with two frames, the first of which is the default image"),
+        true,
+        to_bytes(vec![
+            header.to_chunk(),
+            control.to_chunk(),
+            frame(0).to_chunk(),
+            chunk(*b"IDAT", image_data(&header, 0)),
+            frame(1).to_chunk(),
+            chunk(*b"fdAT", frame_data),
+            chunk(*b"IEND", vec![]),
+        ]),
+    )
+}
+
+fn text_images() -> Vec<Fixture> {
+    let header = header(ColorType::Truecolor, 8, SIZE, SIZE);
+    let international = TextChunk {
+        language_tag: String::from("fr"),
+        translated_keyword: String::from("Titre"),
+        ..TextChunk::new(
+            TextKind::International,
+            "Title",
+            "Bonjour le monde, ça va ?",
+        )
+    };
+    let texts = vec![
+        (
+            "text-text",
+            "A tEXt chunk with Latin-1 text",
+            TextChunk::new(TextKind::Text, "Comment", "Café crème"),
+        ),
+        (
+            "text-compressed",
+            "A zTXt chunk with compressed Latin-1 text",
+            TextChunk::new(
+                TextKind::Compressed,
+                "Description",
+                &"compressible ".repeat(20),
+            ),
+        ),
+        (
+            "text-international",
+            "An iTXt chunk with UTF-8 text, a language tag and a translated keyword",
+            international.clone(),
+        ),
+        (
+            "text-international-compressed",
+            "An iTXt chunk with compressed UTF-8 text",
+            TextChunk {
+                compressed: true,
+                ..international
+            },
+        ),
+    ];
+
+    // The keywords and text above are all valid, test_text_fixtures checks none is left out
+    texts
+        .into_iter()
+        .filter_map(|(name, description, text)| {
+            let text_chunk = text.to_chunk().ok()?;
+            Some(Fixture::new(
+                name.to_string(),
+                description.to_string(),
+                true,
+                to_bytes(image(&header, vec![text_chunk])),
+            ))
+        })
+        .collect()
+}
+
+// Broken copies of a plain image, each broken in one way
+fn corrupt_images() -> Vec<Fixture> {
+    let chunks = image(&header(ColorType::TruecolorAlpha, 8, SIZE, SIZE), vec![]);
+    let bytes = to_bytes(chunks.clone());
+    let image_data = offset_of(&chunks, 1);
+    let image_data_end = offset_of(&chunks, 2);
+
+    let mut wrong_crc = bytes.clone();
+    wrong_crc[image_data_end - 1] ^= 0xff;
+
+    let mut trailer = bytes.clone();
+    trailer.extend_from_slice(b"trailing bytes");
+
+    let mut wrong_length = bytes.clone();
+    wrong_length[image_data..image_data + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+
+    // A transfer in text mode turns the CR LF in the signature into LF
+    let mut signature = bytes.clone();
+    signature.remove(4);
+
+    let corrupt = vec![
+        (
+            "corrupt-crc",
+            "The IDAT chunk's CRC doesn't match its data",
+            wrong_crc,
+        ),
+        (
+            "corrupt-truncated",
+            "The file ends part way through the IDAT chunk",
+            bytes[..(image_data + image_data_end) / 2].to_vec(),
+        ),
+        ("corrupt-trailer", "Bytes after the IEND chunk", trailer),
+        (
+            "corrupt-missing-iend",
+            "The file ends without an IEND chunk",
+            bytes[..image_data_end].to_vec(),
+        ),
+        (
+            "corrupt-chunk-length",
+            "The IDAT chunk's length is longer than the file",
+            wrong_length,
+        ),
+        (
+            "corrupt-signature",
+            "The signature's CR LF has been turned into LF",
+            signature,
+        ),
+    ];
+
+    corrupt
+        .into_iter()
+        .map(|(name, description, bytes)| {
+            Fixture::new(name.to_string(), description.to_string(), false, bytes)
+        })
+        .collect()
+}
+
+pub fn generate() -> Vec<Fixture> {
+    let mut fixtures = plain_images();
+    fixtures.extend(interlaced_images());
+    fixtures.push(animated_image());
+    fixtures.extend(text_images());
+    fixtures.extend(corrupt_images());
+
+    fixtures
+}
+
+// A description of every fixture, to write alongside them
+pub fn manifest(fixtures: &[Fixture]) -> Value {
+    Value::Array(fixtures.iter().map(|fixture| fixture.json()).collect())
+}
+
+// The tests read the fixtures with everything in the library which reads PNGs
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use super::*;
+    use crate::{
+        apng::{self, Animation},
+        carve,
+        chunk::{ChunkDecoder, ChunkReader},
+        dedupe, diagnostics, doctor, extensions, history,
+        inflate::{self, InflateLimits},
+        lint,
+        payload::Payload,
+        policy, provenance, size_diff, standard, text, trailer,
+        view::{CrcCheck, PngView},
+    };
+    use std::collections::HashSet;
+    use std::convert::TryFrom;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_fixtures_are_deterministic() {
+        let first = generate();
+        let second = generate();
+        assert_eq!(first.len(), second.len());
+        for (a, b) in first.iter().zip(second.iter()) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.bytes, b.bytes);
+        }
+
+        let names: HashSet<&str> = first.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names.len(), first.len());
+    }
+
+    #[test]
+    fn test_valid_fixtures() {
+        for fixture in generate().iter().filter(|f| f.valid) {
+            let png = Png::try_from(&fixture.bytes[..])
+                .unwrap_or_else(|e| panic!("{} failed to load: {}", fixture.name, e));
+            assert!(
+                doctor::diagnose(&fixture.bytes).unwrap().is_healthy(),
+                "{} has problems",
+                fixture.name
+            );
+            ImageHeader::from_png(&png).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_corrupt_fixtures() {
+        for fixture in generate().iter().filter(|f| !f.valid) {
+            let healthy = doctor::diagnose(&fixture.bytes)
+                .map(|diagnosis| diagnosis.is_healthy())
+                .unwrap_or(false);
+            assert!(!healthy, "{} wasn't found to be broken", fixture.name);
+        }
+    }
+
+    #[test]
+    fn test_fixture_contents() {
+        let fixtures = generate();
+        let find = |name: &str| {
+            let fixture = fixtures.iter().find(|f| f.name == name).unwrap();
+            Png::try_from(&fixture.bytes[..]).unwrap()
+        };
+
+        let animation = Animation::from_png(&find("animated")).unwrap();
+        assert_eq!(animation.frames.len(), 2);
+        assert!(animation.frames[0].is_default_image);
+
+        let header = ImageHeader::from_png(&find("interlaced-grey-1bit")).unwrap();
+        assert!(header.interlaced);
+
+        let png = find("text-international-compressed");
+        let texts = text::text_chunks(&png);
+        let text = texts[0].1.as_ref().unwrap();
+        assert_eq!(text.kind, TextKind::International);
+        assert!(text.compressed);
+        assert_eq!(text.language_tag, "fr");
+        assert_eq!(text.text, "Bonjour le monde, ça va ?");
+    }
+
+    #[test]
+    fn test_text_fixtures() {
+        let texts = text_images();
+        assert_eq!(texts.len(), 4);
+        for fixture in texts.iter() {
+            let png = Png::try_from(&fixture.bytes[..]).unwrap();
+            assert_eq!(text::text_chunks(&png).len(), 1, "{}", fixture.name);
+        }
+    }
+
+    // A repeatable stream of pseudo-random numbers (xorshift), so a failure can be reproduced
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self, below: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % below as u64) as usize
+        }
+    }
+
+    // Everything which reads the bytes of a file. Errors are expected, only panics fail.
+    fn read_untrusted_bytes(bytes: &[u8]) {
+        let _ = Png::try_from(bytes);
+        let _ = Png::from_reader(bytes);
+        let _ = Png::recover(bytes);
+        let _ = carve::carve(bytes).count();
+        let _ = trailer::find(bytes);
+        let _ = Png::scan_headers(&mut Cursor::new(bytes));
+        let _ = PngView::parse_with(bytes, CrcCheck::Lazy).map(|view| view.to_png());
+        if let Some(rest) = bytes.get(8..) {
+            let _ = ChunkDecoder::with_crc_check(CrcCheck::Lazy)
+                .events(rest)
+                .count();
+            let _ = ChunkReader::new(rest).count();
+        }
+        if let Ok(diagnosis) = doctor::diagnose(bytes) {
+            let _ = diagnosis.fix();
+            let _ = diagnostics::apply_fixes(bytes, &diagnosis.diagnostics().fixes());
+        }
+    }
+
+    // Everything which reads the contents of chunks, given chunks with valid CRCs
+    fn read_untrusted_chunks(png: &Png, original: &Png) {
+        let _ = ImageHeader::from_png(png);
+        let _ = standard::check(png);
+        let _ = Animation::from_png(png);
+        for index in 0..3 {
+            let _ = apng::extract_frame(png, index);
+        }
+        let _ = text::text_chunks(png);
+        let _ = lint::fix(png, &lint::lint(png));
+        let _ = dedupe::find_duplicates(png);
+        let _ = provenance::source(png);
+        let _ = size_diff::size_diff(original, png);
+        let _ = png.canonical_bytes();
+        for chunk in png.chunks() {
+            let _ = standard::describe(png, chunk);
+            let _ = extensions::describe(chunk);
+            let _ = history::versions(png, chunk.chunk_type());
+            let _ = Payload::try_from(chunk.data());
+            let _ = policy::exif_has_gps(chunk.data());
+            let _ = inflate::zlib(chunk.data(), &InflateLimits::DEFAULT);
+        }
+    }
+
+    #[test]
+    fn test_mutated_fixtures_never_panic() {
+        let mut random = Random(0x9e37_79b9_7f4a_7c15);
+        for fixture in generate() {
+            let bytes = &fixture.bytes;
+            for end in 0..bytes.len() {
+                read_untrusted_bytes(&bytes[..end]);
+            }
+            for _ in 0..200 {
+                let mut mutated = bytes.clone();
+                for _ in 0..1 + random.next(4) {
+                    let index = random.next(mutated.len());
+                    mutated[index] ^= 1 << random.next(8);
+                }
+                read_untrusted_bytes(&mutated);
+            }
+            // Lengths which are too large, or overflow when the rest of the chunk is added
+            let mut offset = 8;
+            while offset + 12 <= bytes.len() {
+                let length = u32::from_be_bytes([
+                    bytes[offset],
+                    bytes[offset + 1],
+                    bytes[offset + 2],
+                    bytes[offset + 3],
+                ]) as usize;
+                for huge in [u32::MAX, 1 << 31, (1 << 31) + 1, u32::MAX - 11] {
+                    let mut mutated = bytes.clone();
+                    mutated[offset..offset + 4].copy_from_slice(&huge.to_be_bytes());
+                    read_untrusted_bytes(&mutated);
+                }
+                offset += length + 12;
+            }
+
+            let original = match Png::try_from(&bytes[..]) {
+                Ok(png) => png,
+                Err(_) => continue,
+            };
+            for index in 0..original.chunks().len() {
+                let chunk = &original.chunks()[index];
+                let data = chunk.data();
+                let mut variants = vec![
+                    vec![],
+                    data[..data.len() / 2].to_vec(),
+                    [data, &[0xff; 8][..]].concat(),
+                ];
+                for _ in 0..20 {
+                    let mut mutated = data.to_vec();
+                    if !mutated.is_empty() {
+                        let index = random.next(mutated.len());
+                        mutated[index] = random.next(256) as u8;
+                    }
+                    variants.push(mutated);
+                }
+                for data in variants {
+                    let mut chunks = original.chunks().to_vec();
+                    chunks[index] = Chunk::new(*chunk.chunk_type(), data);
+                    let png = Png::from_chunks(chunks).unwrap();
+                    read_untrusted_chunks(&png, &original);
+                    read_untrusted_bytes(&png.as_bytes());
+                }
+            }
+        }
+    }
+}
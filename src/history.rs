@@ -0,0 +1,255 @@
+use crate::{
+    chunk::{Chunk, ChunkError, ChunkResult},
+    chunk_type::{ChunkType, ChunkTypeError},
+    png::{Png, PngError},
+};
+use std::{cmp::Reverse, convert::TryFrom};
+use thiserror::Error;
+
+// Earlier versions of a chunk, kept when it is replaced so the change can be rolled back.
+//
+// Each earlier version is stored in its own `veRs` chunk, placed after the chunk it came from.
+// The data is the chunk type of the versioned chunk, a 4 byte version number, then the chunk
+// data as it was. Versions count up from 1, the highest is the most recent.
+// `veRs` is ancillary, private and safe to copy, so it is ignored by anything but pngme.
+
+pub const HISTORY_TYPE: &str = "veRs";
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum HistoryError {
+    #[error("`{0}` is not a history chunk")]
+    NotAHistoryChunk(ChunkType),
+    #[error("History chunk is truncated, found `{0}` bytes")]
+    Truncated(usize),
+    #[error("History chunk holds an invalid chunk type")]
+    InvalidChunkType(#[from] ChunkTypeError),
+    #[error("There is no `{0}` chunk")]
+    ChunkNotFound(ChunkType),
+    #[error("Version `{1}` of `{0}` doesn't exist")]
+    VersionNotFound(ChunkType, u32),
+    #[error("Failed to rebuild the PNG")]
+    Png(#[from] PngError),
+    #[error("The earlier version doesn't fit in a chunk")]
+    Chunk(#[from] ChunkError),
+}
+
+pub type HistoryResult<T> = Result<T, HistoryError>;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Version {
+    pub chunk_type: ChunkType,
+    pub version: u32,
+    pub data: Vec<u8>,
+}
+
+impl Version {
+    // The version adds a header to the data, so data which filled a chunk no longer fits
+    pub fn to_chunk(&self) -> ChunkResult {
+        let data = self
+            .chunk_type
+            .bytes()
+            .iter()
+            .chain(self.version.to_be_bytes().iter())
+            .chain(self.data.iter())
+            .copied()
+            .collect();
+
+        Chunk::try_new(history_type(), data)
+    }
+}
+
+impl TryFrom<&Chunk> for Version {
+    type Error = HistoryError;
+
+    fn try_from(chunk: &Chunk) -> HistoryResult<Version> {
+        if chunk.chunk_type() != &history_type() {
+            return Err(HistoryError::NotAHistoryChunk(*chunk.chunk_type()));
+        }
+        let data = chunk.data();
+        if data.len() < 8 {
+            return Err(HistoryError::Truncated(data.len()));
+        }
+
+        Ok(Version {
+            chunk_type: ChunkType::try_from([data[0], data[1], data[2], data[3]])?,
+            version: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            data: data[8..].to_vec(),
+        })
+    }
+}
+
+// The earlier versions of a chunk type and where they are in the file, most recent first.
+// History chunks which can't be read are skipped.
+pub fn versions(png: &Png, chunk_type: &ChunkType) -> Vec<(usize, Version)> {
+    let mut versions: Vec<(usize, Version)> = png
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, chunk)| Version::try_from(chunk).ok().map(|v| (index, v)))
+        .filter(|(_, version)| &version.chunk_type == chunk_type)
+        .collect();
+    versions.sort_by_key(|(_, v)| Reverse(v.version));
+
+    versions
+}
+
+// Replace the first chunk of the same type, keeping what it held as the most recent version.
+// Only the `keep` most recent versions are kept. The chunk is appended if there isn't one.
+pub fn upsert_with_history(png: &Png, chunk: Chunk, keep: usize) -> HistoryResult<Png> {
+    let chunk_type = *chunk.chunk_type();
+    let existing = match png
+        .chunks()
+        .iter()
+        .position(|c| c.chunk_type() == &chunk_type)
+    {
+        Some(index) => index,
+        None => {
+            let mut chunks = png.chunks().to_vec();
+            chunks.insert(chunks.len() - 1, chunk);
+            return Ok(Png::from_chunks(chunks)?);
+        }
+    };
+
+    let earlier = versions(png, &chunk_type);
+    let previous = Version {
+        chunk_type,
+        version: earlier.first().map_or(1, |(_, v)| v.version + 1),
+        data: png.chunks()[existing].data().to_vec(),
+    };
+    // The new version is one of the ones kept, so the oldest go to make room for it
+    let dropped: Vec<usize> = earlier
+        .iter()
+        .skip(keep.saturating_sub(1))
+        .map(|(index, _)| *index)
+        .collect();
+
+    let mut chunks = vec![];
+    for (index, c) in png.chunks().iter().enumerate() {
+        if index == existing {
+            chunks.push(chunk.clone());
+            if keep > 0 {
+                chunks.push(previous.to_chunk()?);
+            }
+        } else if !dropped.contains(&index) {
+            chunks.push(c.clone());
+        }
+    }
+
+    Ok(Png::from_chunks(chunks)?)
+}
+
+// Put an earlier version back, the chunk it replaces becomes the most recent version.
+// The number of versions kept stays the same, so the oldest is dropped.
+pub fn restore(png: &Png, chunk_type: &ChunkType, version: u32) -> HistoryResult<Png> {
+    if png.chunk_by_type(chunk_type).is_none() {
+        return Err(HistoryError::ChunkNotFound(*chunk_type));
+    }
+    let earlier = versions(png, chunk_type);
+    let (index, restored) = earlier
+        .iter()
+        .find(|(_, v)| v.version == version)
+        .ok_or(HistoryError::VersionNotFound(*chunk_type, version))?;
+
+    // The restored version moves back into the chunk, so it isn't kept as a version as well
+    let chunks = png
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| i != index)
+        .map(|(_, c)| c.clone())
+        .collect();
+    let png = Png::from_chunks(chunks)?;
+
+    upsert_with_history(
+        &png,
+        Chunk::new(*chunk_type, restored.data.clone()),
+        earlier.len(),
+    )
+}
+
+fn history_type() -> ChunkType {
+    ChunkType::from_known(*b"veRs")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::from_strings(chunk_type, data).unwrap()
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("teSt", "one"),
+            chunk("IEND", ""),
+        ])
+        .unwrap()
+    }
+
+    fn messages(png: &Png) -> Vec<(u32, String)> {
+        versions(png, &ChunkType::from_str("teSt").unwrap())
+            .into_iter()
+            .map(|(_, v)| (v.version, String::from_utf8(v.data).unwrap()))
+            .collect()
+    }
+
+    fn current(png: &Png) -> String {
+        png.chunk_by_type(&ChunkType::from_str("teSt").unwrap())
+            .unwrap()
+            .data_as_string()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_version_round_trip() {
+        let version = Version {
+            chunk_type: ChunkType::from_str("teSt").unwrap(),
+            version: 7,
+            data: b"message".to_vec(),
+        };
+        assert_eq!(
+            Version::try_from(&version.to_chunk().unwrap()).unwrap(),
+            version
+        );
+        assert!(Version::try_from(&chunk("teSt", "message")).is_err());
+    }
+
+    #[test]
+    fn test_upsert_keeps_history() {
+        let png = upsert_with_history(&testing_png(), chunk("teSt", "two"), 2).unwrap();
+        let png = upsert_with_history(&png, chunk("teSt", "three"), 2).unwrap();
+        let png = upsert_with_history(&png, chunk("teSt", "four"), 2).unwrap();
+
+        assert_eq!(current(&png), "four");
+        assert_eq!(
+            messages(&png),
+            vec![(3, String::from("three")), (2, String::from("two"))]
+        );
+        assert_eq!(png.chunks().len(), 5);
+    }
+
+    #[test]
+    fn test_upsert_appends_when_missing() {
+        let png = upsert_with_history(&testing_png(), chunk("ruSt", "new"), 2).unwrap();
+        assert_eq!(png.chunks().len(), 4);
+        assert!(versions(&png, &ChunkType::from_str("ruSt").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_restore() {
+        let png = upsert_with_history(&testing_png(), chunk("teSt", "two"), 3).unwrap();
+        let png = upsert_with_history(&png, chunk("teSt", "three"), 3).unwrap();
+        let png = restore(&png, &ChunkType::from_str("teSt").unwrap(), 1).unwrap();
+
+        assert_eq!(current(&png), "one");
+        assert_eq!(
+            messages(&png),
+            vec![(3, String::from("three")), (2, String::from("two"))]
+        );
+        assert!(restore(&png, &ChunkType::from_str("teSt").unwrap(), 1).is_err());
+    }
+}
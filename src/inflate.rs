@@ -0,0 +1,147 @@
+use flate2::read::{GzDecoder, ZlibDecoder};
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use thiserror::Error;
+
+// Limits on inflating compressed data, eg a zTXt chunk or a compressed payload. A few
+// kilobytes of deflate can inflate to gigabytes, so a crafted file (a decompression bomb)
+// could otherwise use up all the memory. Inflating stops as soon as the output goes over
+// the limit, and it is refused with an error.
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum InflateError {
+    #[error("Compressed data inflates to more than the limit of {0} bytes")]
+    TooLarge(usize),
+    #[error(
+        "Compressed data inflates to more than {0} times its size, it may be a decompression bomb"
+    )]
+    TooCompressed(usize),
+    #[error("Failed to inflate the data")]
+    Io(#[from] io::Error),
+}
+
+pub type InflateResult<T> = Result<T, InflateError>;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub struct InflateLimits {
+    // The most bytes the output can be
+    pub max_size: usize,
+    // The most times larger than the compressed data the output can be
+    pub max_ratio: usize,
+}
+
+impl InflateLimits {
+    pub const DEFAULT: InflateLimits = InflateLimits {
+        max_size: 64 << 20,
+        max_ratio: 100,
+    };
+
+    // Output up to this size is allowed whatever the ratio, short repetitive text
+    // compresses far better than the ratio allows without being any danger
+    const RATIO_ALLOWANCE: usize = 1 << 20;
+
+    pub fn max_size(mut self, max_size: usize) -> InflateLimits {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn max_ratio(mut self, max_ratio: usize) -> InflateLimits {
+        self.max_ratio = max_ratio;
+        self
+    }
+}
+
+impl Default for InflateLimits {
+    fn default() -> InflateLimits {
+        InflateLimits::DEFAULT
+    }
+}
+
+// The limits used when a chunk is read, set once by the program, eg from its arguments
+static MAX_SIZE: AtomicUsize = AtomicUsize::new(InflateLimits::DEFAULT.max_size);
+static MAX_RATIO: AtomicUsize = AtomicUsize::new(InflateLimits::DEFAULT.max_ratio);
+
+pub fn set_limits(limits: InflateLimits) {
+    MAX_SIZE.store(limits.max_size, Ordering::Relaxed);
+    MAX_RATIO.store(limits.max_ratio, Ordering::Relaxed);
+}
+
+pub fn limits() -> InflateLimits {
+    InflateLimits {
+        max_size: MAX_SIZE.load(Ordering::Relaxed),
+        max_ratio: MAX_RATIO.load(Ordering::Relaxed),
+    }
+}
+
+// Read everything from a decoder of `compressed_size` bytes of compressed data.
+// One byte past the limit is read to tell whether the output goes over it.
+pub fn inflate<R: Read>(
+    decoder: R,
+    compressed_size: usize,
+    limits: &InflateLimits,
+) -> InflateResult<Vec<u8>> {
+    let ratio_limit = compressed_size
+        .saturating_mul(limits.max_ratio)
+        .max(InflateLimits::RATIO_ALLOWANCE);
+    let limit = limits.max_size.min(ratio_limit);
+
+    let mut data = vec![];
+    decoder.take(limit as u64 + 1).read_to_end(&mut data)?;
+    if data.len() > limit {
+        return Err(if limit == limits.max_size {
+            InflateError::TooLarge(limits.max_size)
+        } else {
+            InflateError::TooCompressed(limits.max_ratio)
+        });
+    }
+
+    Ok(data)
+}
+
+pub fn zlib(data: &[u8], limits: &InflateLimits) -> InflateResult<Vec<u8>> {
+    inflate(ZlibDecoder::new(data), data.len(), limits)
+}
+
+pub fn gzip(data: &[u8], limits: &InflateLimits) -> InflateResult<Vec<u8>> {
+    inflate(GzDecoder::new(data), data.len(), limits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text;
+
+    #[test]
+    fn test_inflate_within_limits() {
+        let data = b"Nothing to see here".repeat(100);
+        let compressed = text::compress(&data).unwrap();
+        assert_eq!(zlib(&compressed, &InflateLimits::DEFAULT).unwrap(), data);
+    }
+
+    #[test]
+    fn test_inflate_too_large() {
+        let limits = InflateLimits {
+            max_size: 1000,
+            max_ratio: 100,
+        };
+        let bomb = text::compress(&[0; 10_000]).unwrap();
+        assert!(matches!(
+            zlib(&bomb, &limits),
+            Err(InflateError::TooLarge(1000))
+        ));
+    }
+
+    #[test]
+    fn test_inflate_too_compressed() {
+        // 2MB claimed to come from 1KB of compressed data, 2000 times larger
+        let output = vec![0; 2 << 20];
+        let result = inflate(&output[..], 1024, &InflateLimits::DEFAULT);
+        assert!(matches!(result, Err(InflateError::TooCompressed(100))));
+
+        // Under the allowance the ratio doesn't matter
+        let output = vec![0; 1 << 20];
+        assert!(inflate(&output[..], 1, &InflateLimits::DEFAULT).is_ok());
+    }
+}
@@ -1,3 +1,79 @@
+// The library reads files from anywhere, so no input may make it panic, only return an
+// error. Tests unwrap freely. fixtures::tests::test_mutated_fixtures_never_panic feeds
+// broken copies of every fixture to everything which reads files, with `--features cli`.
+// Narrowing `as` casts are denied too, as they would silently truncate a length or offset.
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::panic,
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::cast_possible_truncation
+    )
+)]
+
+#[cfg(feature = "standard")]
+pub mod apng;
+pub mod cancel;
+#[cfg(feature = "trailer")]
+pub mod carve;
 pub mod chunk;
 pub mod chunk_type;
+#[cfg(feature = "standard")]
+pub mod constraints;
+pub mod crc;
+#[cfg(feature = "compare")]
+pub mod dedupe;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+#[cfg(feature = "doctor")]
+pub mod doctor;
+#[cfg(feature = "standard")]
+pub mod extensions;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+#[cfg(feature = "history")]
+pub mod history;
+#[cfg(feature = "compression")]
+pub mod inflate;
+#[cfg(feature = "doctor")]
+pub mod lint;
+#[cfg(feature = "payload")]
+pub mod payload;
+#[cfg(feature = "formats")]
+pub mod pipeline;
+#[cfg(feature = "image-check")]
+pub mod pixels;
 pub mod png;
+#[cfg(feature = "compression")]
+pub mod policy;
+#[cfg(feature = "crypto")]
+pub mod provenance;
+#[cfg(feature = "crypto")]
+pub mod redact;
+#[cfg(feature = "standard")]
+pub mod registry;
+#[cfg(feature = "crypto")]
+pub mod secret;
+#[cfg(feature = "selector")]
+pub mod selector;
+#[cfg(feature = "crypto")]
+pub mod shamir;
+#[cfg(feature = "compare")]
+pub mod size_diff;
+#[cfg(feature = "stamp")]
+pub mod stamp;
+#[cfg(feature = "standard")]
+pub mod standard;
+pub mod temporary;
+#[cfg(feature = "compression")]
+pub mod text;
+#[cfg(feature = "trailer")]
+pub mod trailer;
+pub mod view;
+#[cfg(feature = "xcheck")]
+pub mod xcheck;
+
+// The stable entry point for build scripts, see `stamp`
+#[cfg(feature = "stamp")]
+pub use stamp::{stamp, StampError, StampOptions, StampResult, Stamped};
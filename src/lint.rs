@@ -0,0 +1,150 @@
+use crate::{
+    chunk::Chunk, chunk_type::ChunkType, payload::Payload, png::Png, png::PngResult, registry,
+};
+use thiserror::Error;
+
+// Chunk types whose case bits say something other than what the chunk is.
+// The case of each letter is a promise to decoders and editors, eg an upper-case first
+// letter tells decoders to reject the file if they don't know the chunk.
+#[derive(Error, Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum Lint {
+    #[error(
+        "`{0}` is private but marked critical, decoders which don't know it must reject the file"
+    )]
+    PrivateCritical(ChunkType),
+    #[error("`{0}` is marked public but isn't a standard or registered chunk type")]
+    UnregisteredPublic(ChunkType),
+    #[error("`{0}` holds a pngme message but is marked unsafe to copy, editors will drop it")]
+    UnsafeToCopyMessage(ChunkType),
+}
+
+impl Lint {
+    // Which letter of the chunk type needs its case changing
+    fn letter(&self) -> usize {
+        match self {
+            Lint::PrivateCritical(_) => 0,
+            Lint::UnregisteredPublic(_) => 1,
+            Lint::UnsafeToCopyMessage(_) => 3,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub index: usize,
+    pub lints: Vec<Lint>,
+    // The chunk type with the case bits fixed
+    pub fixed_type: ChunkType,
+}
+
+fn lints(chunk: &Chunk) -> Vec<Lint> {
+    let chunk_type = *chunk.chunk_type();
+    let mut lints = vec![];
+
+    if chunk_type.is_critical() && !chunk_type.is_public() {
+        lints.push(Lint::PrivateCritical(chunk_type));
+    }
+    if !chunk_type.is_critical()
+        && chunk_type.is_public()
+        && registry::lookup(&chunk_type).is_none()
+    {
+        lints.push(Lint::UnregisteredPublic(chunk_type));
+    }
+    if !chunk_type.is_safe_to_copy() && Payload::has_header(chunk.data()) {
+        lints.push(Lint::UnsafeToCopyMessage(chunk_type));
+    }
+
+    lints
+}
+
+// Every letter the lints need changing is set to lower-case, which is always the fix:
+// ancillary, private and safe to copy. A letter in either case is still a letter, so the
+// type stays valid.
+fn fixed_type(chunk_type: &ChunkType, lints: &[Lint]) -> ChunkType {
+    let mut bytes = chunk_type.bytes();
+    for lint in lints {
+        bytes[lint.letter()] = bytes[lint.letter()].to_ascii_lowercase();
+    }
+
+    ChunkType::from_known(bytes)
+}
+
+pub fn lint(png: &Png) -> Vec<Finding> {
+    png.chunks()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, chunk)| {
+            let lints = lints(chunk);
+            if lints.is_empty() {
+                return None;
+            }
+            Some(Finding {
+                index,
+                fixed_type: fixed_type(chunk.chunk_type(), &lints),
+                lints,
+            })
+        })
+        .collect()
+}
+
+// Rename the chunks of the findings, which gives them a new CRC
+pub fn fix(png: &Png, findings: &[Finding]) -> PngResult {
+    let mut chunks = png.chunks().to_vec();
+    for finding in findings {
+        let data = chunks[finding.index].data().to_vec();
+        chunks[finding.index] = Chunk::new(finding.fixed_type, data);
+    }
+
+    Png::from_chunks(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::PayloadEncoding;
+    use std::str::FromStr;
+
+    fn testing_png(extra: Vec<Chunk>) -> Png {
+        let mut chunks = vec![Chunk::from_strings("IHDR", "header").unwrap()];
+        chunks.extend(extra);
+        chunks.push(Chunk::from_strings("IEND", "").unwrap());
+        Png::from_chunks(chunks).unwrap()
+    }
+
+    #[test]
+    fn test_lint_clean() {
+        let png = testing_png(vec![
+            Chunk::from_strings("tEXt", "Comment\0hello").unwrap(),
+            Chunk::from_strings("ruSt", "a message").unwrap(),
+        ]);
+        assert!(lint(&png).is_empty());
+    }
+
+    #[test]
+    fn test_lint_case_bits() {
+        let message = Payload::new(PayloadEncoding::Utf8, b"hello".to_vec()).as_bytes();
+        let png = testing_png(vec![
+            Chunk::from_strings("RuSt", "critical").unwrap(),
+            Chunk::from_strings("tEST", "public").unwrap(),
+            Chunk::new(ChunkType::from_str("RuST").unwrap(), message),
+        ]);
+        let findings = lint(&png);
+
+        assert_eq!(findings.len(), 3);
+        assert_eq!(findings[0].index, 1);
+        assert_eq!(findings[0].fixed_type.to_string(), "ruSt");
+        assert_eq!(findings[1].fixed_type.to_string(), "teST");
+        assert_eq!(findings[2].lints.len(), 2);
+        assert_eq!(findings[2].fixed_type.to_string(), "ruSt");
+
+        let fixed = fix(&png, &findings).unwrap();
+        assert!(lint(&fixed).is_empty());
+        let chunk = &fixed.chunks()[2];
+        assert_eq!(chunk.chunk_type().to_string(), "teST");
+        assert_eq!(
+            chunk.crc(),
+            Chunk::from_strings("teST", "public").unwrap().crc()
+        );
+    }
+}
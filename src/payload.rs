@@ -0,0 +1,450 @@
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+    mem,
+    str::FromStr,
+};
+use thiserror::Error;
+use zeroize::Zeroize;
+
+pub type PayloadResult = Result<Payload, PayloadError>;
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum PayloadError {
+    #[error("Payload header is truncated, found `{0}` bytes but expected at least `{1}`")]
+    TruncatedHeader(usize, usize),
+    #[error("Payload version `{0}` is not supported by this version of pngme")]
+    UnsupportedVersion(u8),
+    #[error("Unknown payload encoding `{0}`")]
+    UnknownEncoding(u8),
+    #[error("Unknown payload encoding name `{0}`, expected one of utf8, json, cbor, msgpack or protobuf")]
+    UnknownEncodingName(String),
+    #[error(
+        "Invalid MIME type `{0}`, expected the form type/subtype in at most 255 ASCII characters"
+    )]
+    InvalidMime(String),
+}
+
+// How the body of a payload has been encoded.
+// Plain messages are UTF-8, structured messages can be stored as JSON text
+// or in one of the compact binary formats.
+// Protobuf payloads are stored as given, a descriptor is needed to make sense of them.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum PayloadEncoding {
+    Utf8,
+    Json,
+    Cbor,
+    MessagePack,
+    Protobuf,
+}
+
+impl PayloadEncoding {
+    pub const ALL: [PayloadEncoding; 5] = [
+        PayloadEncoding::Utf8,
+        PayloadEncoding::Json,
+        PayloadEncoding::Cbor,
+        PayloadEncoding::MessagePack,
+        PayloadEncoding::Protobuf,
+    ];
+
+    pub fn is_structured(&self) -> bool {
+        *self != PayloadEncoding::Utf8
+    }
+
+    // The value stored in the payload header
+    pub fn to_byte(self) -> u8 {
+        match self {
+            PayloadEncoding::Utf8 => 0,
+            PayloadEncoding::Json => 1,
+            PayloadEncoding::Cbor => 2,
+            PayloadEncoding::MessagePack => 3,
+            PayloadEncoding::Protobuf => 4,
+        }
+    }
+
+    fn from_byte(value: u8) -> Result<PayloadEncoding, PayloadError> {
+        match value {
+            0 => Ok(PayloadEncoding::Utf8),
+            1 => Ok(PayloadEncoding::Json),
+            2 => Ok(PayloadEncoding::Cbor),
+            3 => Ok(PayloadEncoding::MessagePack),
+            4 => Ok(PayloadEncoding::Protobuf),
+            v => Err(PayloadError::UnknownEncoding(v)),
+        }
+    }
+}
+
+impl FromStr for PayloadEncoding {
+    type Err = PayloadError;
+
+    fn from_str(s: &str) -> Result<PayloadEncoding, PayloadError> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf8" | "utf-8" | "text" => Ok(PayloadEncoding::Utf8),
+            "json" => Ok(PayloadEncoding::Json),
+            "cbor" => Ok(PayloadEncoding::Cbor),
+            "msgpack" | "messagepack" => Ok(PayloadEncoding::MessagePack),
+            "protobuf" | "proto" => Ok(PayloadEncoding::Protobuf),
+            _ => Err(PayloadError::UnknownEncodingName(s.to_string())),
+        }
+    }
+}
+
+impl Display for PayloadEncoding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PayloadEncoding::Utf8 => "utf8",
+            PayloadEncoding::Json => "json",
+            PayloadEncoding::Cbor => "cbor",
+            PayloadEncoding::MessagePack => "msgpack",
+            PayloadEncoding::Protobuf => "protobuf",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+// The MIME type without parameters such as charset, in lower case
+fn mime_essence(mime: &str) -> String {
+    mime.split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase()
+}
+
+// Is the MIME type JSON, eg `application/json` or `application/ld+json`
+pub fn mime_is_json(mime: &str) -> bool {
+    let essence = mime_essence(mime);
+    essence == "application/json" || essence.ends_with("+json")
+}
+
+// Is the MIME type something which can be shown as text, the rest are binary
+pub fn mime_is_text(mime: &str) -> bool {
+    let essence = mime_essence(mime);
+    essence.starts_with("text/")
+        || mime_is_json(mime)
+        || essence == "application/xml"
+        || essence.ends_with("+xml")
+}
+
+// The data stored within a chunk by pngme.
+//
+// Version 1 payloads are the original format, the chunk data is the message
+// itself as a UTF-8 string with no header.
+// Version 2 payloads start with a small header describing the payload:
+//
+//   magic    - 4 bytes, `PNGm`
+//   version  - 1 byte, currently 2
+//   encoding - 1 byte, see `PayloadEncoding`
+//   flags    - 1 byte, the steps applied to the body after encoding, see `pipeline::Transform`
+//
+// followed by the encoded body.
+// Version 3 payloads have the same header followed by the MIME type of the message,
+// eg `application/json`, so a reader can tell what to do with it:
+//
+//   mime length - 1 byte
+//   mime        - that many bytes of ASCII
+//
+// Payloads without a MIME type are still written as version 2, so older releases can read them.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Payload {
+    version: u8,
+    encoding: PayloadEncoding,
+    flags: u8,
+    mime: Option<String>,
+    body: Vec<u8>,
+}
+
+impl Payload {
+    pub const MAGIC: [u8; 4] = *b"PNGm";
+    pub const LEGACY_VERSION: u8 = 1;
+    pub const HEADER_VERSION: u8 = 2;
+    pub const MIME_VERSION: u8 = 3;
+    pub const CURRENT_VERSION: u8 = 3;
+    pub const HEADER_LENGTH: usize = 7;
+
+    pub fn new(encoding: PayloadEncoding, body: Vec<u8>) -> Payload {
+        Payload {
+            version: Payload::HEADER_VERSION,
+            encoding,
+            flags: 0,
+            mime: None,
+            body,
+        }
+    }
+
+    pub fn legacy(message: String) -> Payload {
+        Payload {
+            version: Payload::LEGACY_VERSION,
+            encoding: PayloadEncoding::Utf8,
+            flags: 0,
+            mime: None,
+            body: message.into_bytes(),
+        }
+    }
+
+    // The same payload tagged with the MIME type of the message.
+    // A version 1 payload has no header to hold it, so becomes a UTF-8 payload with a header.
+    pub fn with_mime(mut self, mime: &str) -> PayloadResult {
+        let has_subtype = match mime.split_once('/') {
+            Some((kind, subtype)) => !kind.is_empty() && !subtype.is_empty(),
+            None => false,
+        };
+        let valid = mime.len() <= u8::MAX as usize
+            && mime.is_ascii()
+            && !mime.chars().any(|c| c.is_ascii_control());
+        if !has_subtype || !valid {
+            return Err(PayloadError::InvalidMime(mime.to_string()));
+        }
+
+        self.version = Payload::MIME_VERSION;
+        self.mime = Some(mime.to_string());
+        Ok(self)
+    }
+
+    // The same payload with its body replaced by the result of pipeline steps
+    #[cfg_attr(not(feature = "formats"), allow(dead_code))]
+    pub(crate) fn transformed(mut self, flags: u8, body: Vec<u8>) -> Payload {
+        self.flags = flags;
+        self.body.zeroize();
+        self.body = body;
+        self
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn encoding(&self) -> PayloadEncoding {
+        self.encoding
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn mime(&self) -> Option<&str> {
+        self.mime.as_deref()
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body[..]
+    }
+
+    pub fn into_body(mut self) -> Vec<u8> {
+        mem::take(&mut self.body)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        if self.version == Payload::LEGACY_VERSION {
+            return self.body.clone();
+        }
+
+        let mime = match &self.mime {
+            // `with_mime` only accepts types whose length fits in the byte
+            Some(mime) => [
+                &[u8::try_from(mime.len()).unwrap_or(u8::MAX)][..],
+                mime.as_bytes(),
+            ]
+            .concat(),
+            None => vec![],
+        };
+
+        Payload::MAGIC
+            .iter()
+            .copied()
+            .chain(
+                [self.version, self.encoding.to_byte(), self.flags]
+                    .iter()
+                    .copied(),
+            )
+            .chain(mime)
+            .chain(self.body.iter().copied())
+            .collect()
+    }
+
+    // Does the data start with the payload header magic
+    pub fn has_header(data: &[u8]) -> bool {
+        data.starts_with(&Payload::MAGIC)
+    }
+}
+
+// A message can be a secret, eg one about to be split into shares, so the body is wiped
+// rather than left behind in freed memory
+impl Drop for Payload {
+    fn drop(&mut self) {
+        self.body.zeroize();
+    }
+}
+
+impl TryFrom<&[u8]> for Payload {
+    type Error = PayloadError;
+
+    fn try_from(value: &[u8]) -> PayloadResult {
+        if !Payload::has_header(value) {
+            // Anything without the magic is a version 1 payload, i.e the raw message
+            return Ok(Payload {
+                version: Payload::LEGACY_VERSION,
+                encoding: PayloadEncoding::Utf8,
+                flags: 0,
+                mime: None,
+                body: value.to_vec(),
+            });
+        }
+
+        if value.len() < Payload::HEADER_LENGTH {
+            return Err(PayloadError::TruncatedHeader(
+                value.len(),
+                Payload::HEADER_LENGTH,
+            ));
+        }
+
+        let version = value[4];
+        if version != Payload::HEADER_VERSION && version != Payload::MIME_VERSION {
+            return Err(PayloadError::UnsupportedVersion(version));
+        }
+
+        let encoding = PayloadEncoding::from_byte(value[5])?;
+
+        let mut body = &value[Payload::HEADER_LENGTH..];
+        let mut mime = None;
+        if version == Payload::MIME_VERSION {
+            let length = *body.first().ok_or(PayloadError::TruncatedHeader(
+                value.len(),
+                Payload::HEADER_LENGTH + 1,
+            ))? as usize;
+            if body.len() < 1 + length {
+                return Err(PayloadError::TruncatedHeader(
+                    value.len(),
+                    Payload::HEADER_LENGTH + 1 + length,
+                ));
+            }
+            let text = String::from_utf8_lossy(&body[1..1 + length]).into_owned();
+            mime = Some(text);
+            body = &body[1 + length..];
+        }
+
+        Ok(Payload {
+            version,
+            encoding,
+            flags: value[6],
+            mime,
+            body: body.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_payload_round_trip() {
+        let payload = Payload::legacy(String::from("This is a plain message"));
+        let bytes = payload.as_bytes();
+        assert_eq!(&bytes[..], b"This is a plain message");
+
+        let actual = Payload::try_from(&bytes[..]).unwrap();
+        assert_eq!(actual.version(), Payload::LEGACY_VERSION);
+        assert_eq!(actual.encoding(), PayloadEncoding::Utf8);
+        assert_eq!(actual, payload);
+    }
+
+    #[test]
+    fn test_payload_with_header_round_trip() {
+        let payload = Payload::new(PayloadEncoding::Cbor, vec![0xa1, 0x61, 0x61, 0x01]);
+        let bytes = payload.as_bytes();
+        assert!(Payload::has_header(&bytes));
+
+        let actual = Payload::try_from(&bytes[..]).unwrap();
+        assert_eq!(actual.version(), Payload::HEADER_VERSION);
+        assert_eq!(actual.encoding(), PayloadEncoding::Cbor);
+        assert_eq!(actual.body(), &[0xa1, 0x61, 0x61, 0x01]);
+    }
+
+    #[test]
+    fn test_payload_with_mime_round_trip() {
+        let payload = Payload::legacy(String::from("{\"a\": 1}"))
+            .with_mime("application/json")
+            .unwrap();
+        let bytes = payload.as_bytes();
+        assert_eq!(&bytes[4..8], b"\x03\x00\x00\x10");
+        assert_eq!(&bytes[8..24], b"application/json");
+
+        let actual = Payload::try_from(&bytes[..]).unwrap();
+        assert_eq!(actual.version(), Payload::MIME_VERSION);
+        assert_eq!(actual.mime(), Some("application/json"));
+        assert_eq!(actual.body(), b"{\"a\": 1}");
+        assert_eq!(actual, payload);
+    }
+
+    #[test]
+    fn test_invalid_mime() {
+        let payload = Payload::new(PayloadEncoding::Utf8, vec![]);
+        assert!(payload.clone().with_mime("json").is_err());
+        assert!(payload.clone().with_mime("text/").is_err());
+        assert!(payload.with_mime("text/plain; charset=\u{e9}").is_err());
+    }
+
+    #[test]
+    fn test_mime_kinds() {
+        assert!(mime_is_json("application/json"));
+        assert!(mime_is_json("Application/LD+JSON; charset=utf-8"));
+        assert!(!mime_is_json("text/plain"));
+        assert!(mime_is_text("text/plain; charset=utf-8"));
+        assert!(mime_is_text("application/json"));
+        assert!(mime_is_text("image/svg+xml"));
+        assert!(!mime_is_text("image/png"));
+        assert!(!mime_is_text("application/octet-stream"));
+    }
+
+    #[test]
+    fn test_truncated_mime() {
+        let payload = Payload::try_from(&b"PNGm\x03\x00\x00\x10appl"[..]);
+        assert!(matches!(
+            payload,
+            Err(PayloadError::TruncatedHeader(12, 24))
+        ));
+    }
+
+    #[test]
+    fn test_encoding_bytes() {
+        for encoding in PayloadEncoding::ALL.iter() {
+            assert_eq!(
+                PayloadEncoding::from_byte(encoding.to_byte()).unwrap(),
+                *encoding
+            );
+        }
+    }
+
+    #[test]
+    fn test_truncated_header() {
+        let payload = Payload::try_from(&b"PNGm\x02"[..]);
+        assert!(payload.is_err());
+    }
+
+    #[test]
+    fn test_unknown_encoding() {
+        let payload = Payload::try_from(&b"PNGm\x02\x09\x00data"[..]);
+        assert!(payload.is_err());
+    }
+
+    #[test]
+    fn test_payload_encoding_from_str() {
+        assert_eq!(
+            PayloadEncoding::from_str("cbor").unwrap(),
+            PayloadEncoding::Cbor
+        );
+        assert_eq!(
+            PayloadEncoding::from_str("msgpack").unwrap(),
+            PayloadEncoding::MessagePack
+        );
+        assert_eq!(
+            PayloadEncoding::from_str("protobuf").unwrap(),
+            PayloadEncoding::Protobuf
+        );
+        assert!(PayloadEncoding::from_str("yaml").is_err());
+    }
+}
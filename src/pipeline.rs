@@ -0,0 +1,315 @@
+use crate::{
+    payload::{Payload, PayloadEncoding},
+    text,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::{error::Error, fmt::Debug, mem};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+// Transforms applied to the body of a payload after it has been encoded, eg compression.
+//
+// Each transform has a bit in the flags of the payload header, so decode knows which to undo.
+// Transforms always run in the same order whatever order they are asked for in, compression comes
+// before anything which makes the data look random, as random data doesn't compress.
+//
+// Adding a transform is a matter of implementing `Transform` and listing it in `transforms`,
+// encode and decode pick it up by name and flag. `Transform` is sealed, a transform from
+// outside the crate would have no flag other versions of pngme could undo.
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum PipelineError {
+    #[error("Payload has unknown flags `{0:#010b}`, it may have been written by a newer version of pngme")]
+    UnknownFlags(u8),
+    #[error("Unknown transform `{0}`, expected one of {}", names().join(", "))]
+    UnknownTransform(String),
+    #[error("Failed to {0} the payload")]
+    Apply(&'static str, #[source] Box<dyn Error + Send + Sync>),
+    #[error("Failed to undo {0} on the payload")]
+    Reverse(&'static str, #[source] Box<dyn Error + Send + Sync>),
+}
+
+pub type PipelineResult<T> = Result<T, PipelineError>;
+
+// Data with more bits of information per byte than this is likely already compressed
+// or encrypted, compressing it again wastes time for little or no gain
+pub const HIGH_ENTROPY: f64 = 7.5;
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for super::Compress {}
+    impl Sealed for super::Base64 {}
+}
+
+pub trait Transform: Debug + sealed::Sealed {
+    // The name used on the command line
+    fn name(&self) -> &'static str;
+
+    // The bit recorded in the payload header flags, different for every transform
+    fn flag(&self) -> u8;
+
+    // Where the transform runs when encoding, lowest first, decoding runs them in reverse
+    fn order(&self) -> u8;
+
+    fn apply(&self, body: &[u8]) -> PipelineResult<Vec<u8>>;
+
+    fn reverse(&self, body: &[u8]) -> PipelineResult<Vec<u8>>;
+}
+
+// zlib compression
+#[derive(Debug, Clone, Copy)]
+pub struct Compress;
+
+impl Transform for Compress {
+    fn name(&self) -> &'static str {
+        "compress"
+    }
+
+    fn flag(&self) -> u8 {
+        0b0000_0001
+    }
+
+    fn order(&self) -> u8 {
+        10
+    }
+
+    fn apply(&self, body: &[u8]) -> PipelineResult<Vec<u8>> {
+        text::compress(body).map_err(|e| PipelineError::Apply(self.name(), e.into()))
+    }
+
+    fn reverse(&self, body: &[u8]) -> PipelineResult<Vec<u8>> {
+        text::decompress(body).map_err(|e| PipelineError::Reverse(self.name(), e.into()))
+    }
+}
+
+// Standard base64, so the body is printable text. It runs last, after anything which
+// would otherwise produce binary data.
+#[derive(Debug, Clone, Copy)]
+pub struct Base64;
+
+impl Transform for Base64 {
+    fn name(&self) -> &'static str {
+        "base64"
+    }
+
+    fn flag(&self) -> u8 {
+        0b1000_0000
+    }
+
+    fn order(&self) -> u8 {
+        250
+    }
+
+    fn apply(&self, body: &[u8]) -> PipelineResult<Vec<u8>> {
+        Ok(STANDARD.encode(body).into_bytes())
+    }
+
+    fn reverse(&self, body: &[u8]) -> PipelineResult<Vec<u8>> {
+        STANDARD
+            .decode(body)
+            .map_err(|e| PipelineError::Reverse(self.name(), e.into()))
+    }
+}
+
+// Every transform pngme knows about
+pub fn transforms() -> Vec<Box<dyn Transform>> {
+    vec![Box::new(Compress), Box::new(Base64)]
+}
+
+pub fn names() -> Vec<&'static str> {
+    transforms().iter().map(|t| t.name()).collect()
+}
+
+// Find a transform by the name used on the command line
+pub fn transform(name: &str) -> PipelineResult<Box<dyn Transform>> {
+    transforms()
+        .into_iter()
+        .find(|t| t.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| PipelineError::UnknownTransform(name.to_string()))
+}
+
+#[derive(Debug, Default)]
+pub struct Pipeline {
+    transforms: Vec<Box<dyn Transform>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    // Add a transform, it runs in its place in the fixed order rather than last
+    pub fn with(mut self, transform: Box<dyn Transform>) -> Pipeline {
+        if self.flags() & transform.flag() == 0 {
+            self.transforms.push(transform);
+            self.transforms.sort_by_key(|t| t.order());
+        }
+        self
+    }
+
+    // The transforms recorded in a payload header's flags
+    pub fn from_flags(flags: u8) -> PipelineResult<Pipeline> {
+        let known = transforms().iter().fold(0, |known, t| known | t.flag());
+        if flags & !known != 0 {
+            return Err(PipelineError::UnknownFlags(flags));
+        }
+
+        Ok(transforms()
+            .into_iter()
+            .filter(|t| flags & t.flag() != 0)
+            .fold(Pipeline::new(), |pipeline, t| pipeline.with(t)))
+    }
+
+    pub fn transforms(&self) -> &[Box<dyn Transform>] {
+        &self.transforms
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.transforms.iter().fold(0, |flags, t| flags | t.flag())
+    }
+
+    // Run the transforms over the payload's body and record them in its header.
+    // Version 1 payloads have no header to record them in, so become version 2 payloads.
+    pub fn encode(&self, payload: Payload) -> PipelineResult<Payload> {
+        if self.is_empty() {
+            return Ok(payload);
+        }
+        let payload = if payload.version() == Payload::LEGACY_VERSION {
+            Payload::new(PayloadEncoding::Utf8, payload.into_body())
+        } else {
+            payload
+        };
+
+        // The steps in between are as sensitive as the message, so are wiped too
+        let mut body = Zeroizing::new(payload.body().to_vec());
+        for transform in self.transforms.iter() {
+            body = Zeroizing::new(transform.apply(&body)?);
+        }
+
+        Ok(payload.transformed(self.flags(), mem::take(&mut *body)))
+    }
+}
+
+// Undo the transforms recorded in the payload's header, giving the body as it was encoded
+pub fn decode(payload: Payload) -> PipelineResult<Payload> {
+    let pipeline = Pipeline::from_flags(payload.flags())?;
+    if pipeline.is_empty() {
+        return Ok(payload);
+    }
+
+    let mut body = Zeroizing::new(payload.body().to_vec());
+    for transform in pipeline.transforms.iter().rev() {
+        body = Zeroizing::new(transform.reverse(&body)?);
+    }
+
+    Ok(payload.transformed(0, mem::take(&mut *body)))
+}
+
+// Shannon entropy of the data, in bits per byte from 0 to 8
+pub fn entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+
+    let length = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / length;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_compress_round_trip() {
+        let payload = Payload::new(PayloadEncoding::Json, b"{\"build\":123}".to_vec());
+        let encoded = Pipeline::new()
+            .with(Box::new(Compress))
+            .encode(payload.clone())
+            .unwrap();
+        assert_eq!(encoded.flags(), Compress.flag());
+
+        let read = Payload::try_from(&encoded.as_bytes()[..]).unwrap();
+        assert_eq!(decode(read).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_fixed_order() {
+        let payload = Payload::new(PayloadEncoding::Utf8, b"aaaaaaaaaaaaaaaa".to_vec());
+        let encoded = Pipeline::new()
+            .with(Box::new(Base64))
+            .with(Box::new(Compress))
+            .with(Box::new(Base64))
+            .encode(payload.clone())
+            .unwrap();
+        assert_eq!(encoded.flags(), Compress.flag() | Base64.flag());
+
+        let compressed = text::compress(payload.body()).unwrap();
+        assert_eq!(encoded.body(), STANDARD.encode(compressed).as_bytes());
+        assert_eq!(decode(encoded).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_transform_by_name() {
+        assert_eq!(transform("BASE64").unwrap().name(), "base64");
+        assert!(transform("rot13").is_err());
+    }
+
+    #[test]
+    fn test_flags_are_unique() {
+        let flags: Vec<u8> = transforms().iter().map(|t| t.flag()).collect();
+        for (i, flag) in flags.iter().enumerate() {
+            assert_eq!(flag.count_ones(), 1);
+            assert!(!flags[i + 1..].contains(flag));
+        }
+    }
+
+    #[test]
+    fn test_legacy_payload_gets_a_header() {
+        let payload = Payload::legacy(String::from("message"));
+        let encoded = Pipeline::new()
+            .with(Box::new(Compress))
+            .encode(payload)
+            .unwrap();
+        assert_eq!(encoded.version(), Payload::HEADER_VERSION);
+        assert_eq!(decode(encoded).unwrap().body(), b"message");
+    }
+
+    #[test]
+    fn test_no_steps_leaves_payload() {
+        let payload = Payload::legacy(String::from("message"));
+        assert_eq!(Pipeline::new().encode(payload.clone()).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_unknown_flags() {
+        let read = Payload::try_from(&b"PNGm\x02\x00\x40data"[..]).unwrap();
+        assert!(decode(read).is_err());
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(b""), 0.0);
+        assert_eq!(entropy(b"aaaa"), 0.0);
+        assert_eq!(entropy(b"abab"), 1.0);
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(entropy(&all), 8.0);
+    }
+}
@@ -0,0 +1,104 @@
+use image::{DynamicImage, ImageError, ImageFormat};
+use std::convert::TryFrom;
+use thiserror::Error;
+
+// Checking two files show exactly the same pixels, to prove an edit to the chunks, eg
+// stripping metadata, didn't change the image. The images are decoded and compared as 16 bit
+// RGBA, so the same pixels stored with a different colour type, bit depth or interlacing are
+// still equal. Chunks which only change how pixels are rendered, eg gAMA, aren't compared.
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum PixelsError {
+    #[error("Failed to decode the image")]
+    Decode(#[from] ImageError),
+    #[error("The images are different sizes, {0}x{1} and {2}x{3}")]
+    DifferentSize(u32, u32, u32, u32),
+    #[error("{count} pixels are different, the first at {x},{y}")]
+    DifferentPixels { count: usize, x: u32, y: u32 },
+}
+
+pub type PixelsResult<T> = Result<T, PixelsError>;
+
+fn decode(bytes: &[u8]) -> PixelsResult<DynamicImage> {
+    Ok(image::load_from_memory_with_format(
+        bytes,
+        ImageFormat::Png,
+    )?)
+}
+
+// Succeeds when both PNG files decode to identical pixels
+pub fn assert_pixels_equal(a: &[u8], b: &[u8]) -> PixelsResult<()> {
+    let (a, b) = (decode(a)?, decode(b)?);
+    if (a.width(), a.height()) != (b.width(), b.height()) {
+        return Err(PixelsError::DifferentSize(
+            a.width(),
+            a.height(),
+            b.width(),
+            b.height(),
+        ));
+    }
+
+    let width = a.width() as usize;
+    let (a, b) = (a.to_rgba16().into_raw(), b.to_rgba16().into_raw());
+    let mut different = a
+        .chunks(4)
+        .zip(b.chunks(4))
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(index, _)| index);
+
+    match different.next() {
+        None => Ok(()),
+        Some(first) => Err(PixelsError::DifferentPixels {
+            count: 1 + different.count(),
+            x: u32::try_from(first % width).unwrap_or(u32::MAX),
+            y: u32::try_from(first / width).unwrap_or(u32::MAX),
+        }),
+    }
+}
+
+#[cfg(all(test, feature = "fixtures"))]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, fixtures, png::Png};
+    use std::convert::TryFrom;
+
+    fn fixture(name: &str) -> Vec<u8> {
+        fixtures::generate()
+            .into_iter()
+            .find(|fixture| fixture.name == name)
+            .unwrap()
+            .bytes
+    }
+
+    #[test]
+    fn test_metadata_doesnt_change_pixels() {
+        let bytes = fixture("basic-rgba-8bit");
+        let mut png = Png::try_from(&bytes[..]).unwrap();
+        png.insert_before_image_data(Chunk::from_strings("tEXt", "Comment\0hello").unwrap());
+
+        assert!(assert_pixels_equal(&bytes, &png.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_different_pixels() {
+        let result = assert_pixels_equal(&fixture("basic-rgba-8bit"), &fixture("basic-rgb-8bit"));
+        assert!(matches!(
+            result,
+            Err(PixelsError::DifferentPixels { x: 0, y: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_different_sizes() {
+        let result = assert_pixels_equal(
+            &fixture("basic-rgba-8bit"),
+            &fixture("interlaced-rgba-8bit"),
+        );
+        assert!(matches!(
+            result,
+            Err(PixelsError::DifferentSize(8, 8, 13, 11))
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{png::Png, text};
+use thiserror::Error;
+
+// Rules every PNG in a project has to follow, eg checked before each commit so a photo with
+// its location or a file without its licence never gets in.
+
+// The tag in an EXIF directory giving the offset of the GPS directory
+const GPS_INFO_TAG: u16 = 0x8825;
+// Length, chunk type and CRC
+const CHUNK_OVERHEAD: usize = 12;
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Policy {
+    // eXIf chunks mustn't say where the photo was taken
+    pub forbid_gps: bool,
+    // The most bytes the ancillary chunks of a file can take up altogether
+    pub max_metadata_bytes: Option<usize>,
+    // Keywords each file must have a text chunk for, eg License
+    pub required_keywords: Vec<String>,
+}
+
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub enum Violation {
+    #[error("Chunk {0} is an eXIf chunk with GPS coordinates")]
+    Gps(usize),
+    #[error("The metadata is {0} bytes, more than the limit of {1}")]
+    MetadataTooLarge(usize, usize),
+    #[error("There is no text chunk with the keyword {0:?}")]
+    MissingKeyword(String),
+}
+
+// Whether EXIF data has a GPS directory. EXIF is laid out like a TIFF file, "II" or "MM" for
+// little or big-endian, the number 42, then the offset of the first directory. A directory is
+// a 2 byte count of entries and 12 bytes per entry, each starting with its tag.
+pub fn exif_has_gps(exif: &[u8]) -> bool {
+    // Some writers keep the "Exif" header JPEG files have in front of it
+    let exif = exif.strip_prefix(b"Exif\0\0").unwrap_or(exif);
+    let little_endian = match exif.get(..2) {
+        Some(b"II") => true,
+        Some(b"MM") => false,
+        _ => return false,
+    };
+    let number = |offset: usize, size: usize| {
+        let bytes = exif.get(offset..offset.checked_add(size)?)?;
+        let fold = |n: usize, b: &u8| n << 8 | *b as usize;
+        Some(if little_endian {
+            bytes.iter().rev().fold(0, fold)
+        } else {
+            bytes.iter().fold(0, fold)
+        })
+    };
+
+    let directory = match number(4, 4) {
+        Some(directory) => directory,
+        None => return false,
+    };
+    let entries = number(directory, 2).unwrap_or(0);
+    (0..entries).any(|entry| number(directory + 2 + entry * 12, 2) == Some(GPS_INFO_TAG as usize))
+}
+
+pub fn check(png: &Png, policy: &Policy) -> Vec<Violation> {
+    let mut violations = vec![];
+
+    if policy.forbid_gps {
+        for (index, chunk) in png.chunks().iter().enumerate() {
+            if chunk.chunk_type().to_string() == "eXIf" && exif_has_gps(chunk.data()) {
+                violations.push(Violation::Gps(index));
+            }
+        }
+    }
+
+    if let Some(max_metadata_bytes) = policy.max_metadata_bytes {
+        let metadata_bytes: usize = png
+            .chunks()
+            .iter()
+            .filter(|chunk| !chunk.chunk_type().is_critical())
+            .map(|chunk| chunk.length() as usize + CHUNK_OVERHEAD)
+            .sum();
+        if metadata_bytes > max_metadata_bytes {
+            violations.push(Violation::MetadataTooLarge(
+                metadata_bytes,
+                max_metadata_bytes,
+            ));
+        }
+    }
+
+    for keyword in policy.required_keywords.iter() {
+        if text::find_keyword(png, keyword).is_empty() {
+            violations.push(Violation::MissingKeyword(keyword.clone()));
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    // Little-endian EXIF with one directory entry with the tag
+    fn exif(tag: u16) -> Vec<u8> {
+        let mut exif = b"II*\0\x08\0\0\0\x01\0".to_vec();
+        exif.extend_from_slice(&tag.to_le_bytes());
+        exif.extend_from_slice(&[4, 0, 1, 0, 0, 0, 26, 0, 0, 0]);
+        exif.extend_from_slice(&[0; 4]);
+        exif
+    }
+
+    fn png(chunks: Vec<Chunk>) -> Png {
+        let mut all = vec![Chunk::from_strings("IHDR", "header").unwrap()];
+        all.extend(chunks);
+        all.push(Chunk::from_strings("IEND", "").unwrap());
+        Png::from_chunks(all).unwrap()
+    }
+
+    #[test]
+    fn test_exif_has_gps() {
+        assert!(exif_has_gps(&exif(GPS_INFO_TAG)));
+        // Orientation
+        assert!(!exif_has_gps(&exif(0x0112)));
+
+        let big_endian = b"MM\0*\0\0\0\x08\0\x01\x88\x25\0\x04\0\0\0\x01\0\0\0\x1a";
+        assert!(exif_has_gps(big_endian));
+        let mut with_header = b"Exif\0\0".to_vec();
+        with_header.extend(exif(GPS_INFO_TAG));
+        assert!(exif_has_gps(&with_header));
+
+        // The directory is past the end
+        assert!(!exif_has_gps(b"II*\0\xff\0\0\0"));
+        assert!(!exif_has_gps(b""));
+    }
+
+    #[test]
+    fn test_check_policy() {
+        let exif = Chunk::new(ChunkType::from_str("eXIf").unwrap(), exif(GPS_INFO_TAG));
+        let comment = Chunk::from_strings("tEXt", "Comment\0hello").unwrap();
+        let png = png(vec![exif, comment]);
+        let policy = Policy {
+            forbid_gps: true,
+            max_metadata_bytes: Some(50),
+            required_keywords: vec![String::from("Comment"), String::from("License")],
+        };
+
+        assert_eq!(
+            check(&png, &policy),
+            vec![
+                Violation::Gps(1),
+                Violation::MetadataTooLarge(63, 50),
+                Violation::MissingKeyword(String::from("License")),
+            ]
+        );
+        assert!(check(&png, &Policy::default()).is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    png::{Png, PngError},
+};
+use sha2::{Digest, Sha256};
+use std::{convert::TryFrom, string::FromUtf8Error};
+use thiserror::Error;
+
+// The source asset a PNG was exported from, eg a PSD, so a shipped image can be traced back to
+// the file it came from in a large art repository.
+//
+// It is stored in a `srCe` chunk, the SHA-256 of the source, its size in bytes as 8 bytes
+// big-endian, then its path when it was stamped as UTF-8. The path is only a hint, files move,
+// the hash is what identifies the source. Git LFS and git-annex both name content by its
+// SHA-256 and size, so the source can be found from their pointer files without fetching it.
+// `srCe` is ancillary, private and safe to copy, so it survives editors which keep chunks.
+
+pub const SOURCE_TYPE: &str = "srCe";
+
+const HASH_LENGTH: usize = 32;
+const HEADER_LENGTH: usize = HASH_LENGTH + 8;
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum ProvenanceError {
+    #[error("`{0}` is not a source chunk")]
+    NotASourceChunk(ChunkType),
+    #[error("Source chunk is truncated, found `{0}` bytes")]
+    Truncated(usize),
+    #[error("Source chunk holds a path which isn't UTF-8")]
+    InvalidPath(#[from] FromUtf8Error),
+    #[error("Failed to rebuild the PNG")]
+    Png(#[from] PngError),
+}
+
+pub type ProvenanceResult<T> = Result<T, ProvenanceError>;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Source {
+    pub sha256: [u8; HASH_LENGTH],
+    pub size: u64,
+    pub path: String,
+}
+
+fn source_type() -> ChunkType {
+    ChunkType::from_known(*b"srCe")
+}
+
+impl Source {
+    pub fn new(content: &[u8], path: &str) -> Source {
+        let mut sha256 = [0; HASH_LENGTH];
+        sha256.copy_from_slice(&Sha256::digest(content));
+
+        Source {
+            sha256,
+            size: content.len() as u64,
+            path: path.to_string(),
+        }
+    }
+
+    pub fn sha256_hex(&self) -> String {
+        self.sha256.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    // Whether the content is the source
+    pub fn matches(&self, content: &[u8]) -> bool {
+        content.len() as u64 == self.size && Sha256::digest(content)[..] == self.sha256[..]
+    }
+
+    // Whether the content is a Git LFS pointer to the source, which is a few lines of text
+    // naming the content as `oid sha256:<hex>` with its `size`
+    pub fn matches_lfs_pointer(&self, content: &[u8]) -> bool {
+        let text = match std::str::from_utf8(content) {
+            Ok(text) if text.starts_with("version https://git-lfs.github.com/spec/") => text,
+            _ => return false,
+        };
+        let oid = format!("oid sha256:{}", self.sha256_hex());
+        let size = format!("size {}", self.size);
+
+        text.lines().any(|line| line == oid) && text.lines().any(|line| line == size)
+    }
+
+    // Whether a git-annex key names the source, eg the target of an annexed file's symlink
+    // ends in `SHA256E-s<size>--<hex>.psd`, or `SHA256-s<size>--<hex>` without the extension
+    pub fn matches_annex_key(&self, key: &str) -> bool {
+        let key = key.rsplit('/').next().unwrap_or(key);
+        let name = format!("-s{}--{}", self.size, self.sha256_hex());
+        ["SHA256E", "SHA256"].iter().any(|backend| {
+            let rest = key
+                .strip_prefix(backend)
+                .and_then(|rest| rest.strip_prefix(name.as_str()));
+            matches!(rest, Some(rest) if rest.is_empty() || rest.starts_with('.'))
+        })
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data = self
+            .sha256
+            .iter()
+            .chain(self.size.to_be_bytes().iter())
+            .chain(self.path.as_bytes().iter())
+            .copied()
+            .collect();
+
+        Chunk::new(source_type(), data)
+    }
+}
+
+impl TryFrom<&Chunk> for Source {
+    type Error = ProvenanceError;
+
+    fn try_from(chunk: &Chunk) -> ProvenanceResult<Source> {
+        if chunk.chunk_type() != &source_type() {
+            return Err(ProvenanceError::NotASourceChunk(*chunk.chunk_type()));
+        }
+        let data = chunk.data();
+        if data.len() < HEADER_LENGTH {
+            return Err(ProvenanceError::Truncated(data.len()));
+        }
+
+        let mut sha256 = [0; HASH_LENGTH];
+        sha256.copy_from_slice(&data[..HASH_LENGTH]);
+        let mut size = [0; 8];
+        size.copy_from_slice(&data[HASH_LENGTH..HEADER_LENGTH]);
+
+        Ok(Source {
+            sha256,
+            size: u64::from_be_bytes(size),
+            path: String::from_utf8(data[HEADER_LENGTH..].to_vec())?,
+        })
+    }
+}
+
+// The source the PNG was stamped with, if it was
+pub fn source(png: &Png) -> Option<ProvenanceResult<Source>> {
+    png.chunk_by_type(&source_type()).map(Source::try_from)
+}
+
+// Record the source in the PNG, replacing any source it was stamped with before. It goes
+// before the image data, so it is found without reading the whole file.
+pub fn stamp(png: &Png, source: &Source) -> ProvenanceResult<Png> {
+    let chunks = png
+        .chunks()
+        .iter()
+        .filter(|chunk| chunk.chunk_type() != &source_type())
+        .cloned()
+        .collect();
+    let mut png = Png::from_chunks(chunks)?;
+    png.insert_before_image_data(source.to_chunk());
+
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::from_strings("IHDR", "header").unwrap(),
+            Chunk::from_strings("IDAT", "data").unwrap(),
+            Chunk::from_strings("IEND", "").unwrap(),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_stamp_and_read_source() {
+        let source = Source::new(b"layers", "art/hero.psd");
+        let png = stamp(&png(), &source).unwrap();
+        assert_eq!(png.chunks()[1].chunk_type().to_string(), SOURCE_TYPE);
+        assert_eq!(super::source(&png).unwrap().unwrap(), source);
+
+        // Stamping again replaces the source
+        let newer = Source::new(b"more layers", "art/hero.psd");
+        let png = stamp(&png, &newer).unwrap();
+        assert_eq!(png.chunks().len(), 4);
+        assert_eq!(super::source(&png).unwrap().unwrap(), newer);
+    }
+
+    #[test]
+    fn test_no_source() {
+        assert!(source(&png()).is_none());
+    }
+
+    #[test]
+    fn test_truncated_source() {
+        let chunk = Chunk::new(source_type(), vec![0; 20]);
+        assert!(matches!(
+            Source::try_from(&chunk),
+            Err(ProvenanceError::Truncated(20))
+        ));
+    }
+
+    #[test]
+    fn test_matches() {
+        let source = Source::new(b"layers", "art/hero.psd");
+        assert!(source.matches(b"layers"));
+        assert!(!source.matches(b"other"));
+
+        let pointer = format!(
+            "version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize 6\n",
+            source.sha256_hex()
+        );
+        assert!(source.matches_lfs_pointer(pointer.as_bytes()));
+        assert!(!source.matches_lfs_pointer(&pointer.as_bytes()[..40]));
+
+        let key = format!(
+            ".git/annex/objects/ab/cd/SHA256E-s6--{}.psd",
+            source.sha256_hex()
+        );
+        assert!(source.matches_annex_key(&key));
+        assert!(source.matches_annex_key(&format!("SHA256-s6--{}", source.sha256_hex())));
+        assert!(!source.matches_annex_key(&format!("SHA256E-s7--{}.psd", source.sha256_hex())));
+    }
+}
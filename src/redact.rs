@@ -0,0 +1,155 @@
+use crate::{
+    chunk::{Chunk, ChunkError},
+    chunk_type::ChunkType,
+    history::{self, Version},
+    png::{Png, PngError},
+};
+use std::convert::TryFrom;
+use thiserror::Error;
+
+// Overwriting chunk data so a secret can't be recovered from the bytes on disk.
+//
+// Every chunk of the type is filled, along with the earlier versions of it kept by history
+// in `veRs` chunks. Chunks keep their length, so the file can be overwritten in place byte
+// for byte rather than written out again somewhere else on the disk.
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum RedactError {
+    #[error("Failed to generate random bytes")]
+    Random,
+    #[error("Failed to rebuild the PNG")]
+    Png(#[from] PngError),
+    #[error("The filled version doesn't fit in a chunk")]
+    Chunk(#[from] ChunkError),
+}
+
+pub type RedactResult<T> = Result<T, RedactError>;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum Fill {
+    Zeros,
+    Random,
+}
+
+impl Fill {
+    fn bytes(&self, length: usize) -> RedactResult<Vec<u8>> {
+        let mut bytes = vec![0; length];
+        if *self == Fill::Random {
+            getrandom::getrandom(&mut bytes).map_err(|_| RedactError::Random)?;
+        }
+
+        Ok(bytes)
+    }
+}
+
+// Where the chunks of the type, and the kept earlier versions of them, are in the file
+pub fn targets(png: &Png, chunk_type: &ChunkType) -> Vec<usize> {
+    let mut targets: Vec<usize> = png
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| chunk.chunk_type() == chunk_type)
+        .map(|(index, _)| index)
+        .collect();
+    targets.extend(history::versions(png, chunk_type).iter().map(|(i, _)| *i));
+    targets.sort_unstable();
+
+    targets
+}
+
+// The PNG with the data of every target filled, and how many chunks were filled.
+// Earlier versions keep their header so they are still recognised, and removed, as history.
+pub fn redact(png: &Png, chunk_type: &ChunkType, fill: Fill) -> RedactResult<(Png, usize)> {
+    let targets = targets(png, chunk_type);
+
+    let mut chunks = vec![];
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        if !targets.contains(&index) {
+            chunks.push(chunk.clone());
+        } else if chunk.chunk_type() == chunk_type {
+            chunks.push(Chunk::new(*chunk_type, fill.bytes(chunk.data().len())?));
+        } else if let Ok(version) = Version::try_from(chunk) {
+            let filled = Version {
+                data: fill.bytes(version.data.len())?,
+                ..version
+            };
+            chunks.push(filled.to_chunk()?);
+        }
+    }
+
+    Ok((Png::from_chunks(chunks)?, targets.len()))
+}
+
+// The PNG without the chunks of the type or their earlier versions
+pub fn remove(png: &Png, chunk_type: &ChunkType) -> RedactResult<Png> {
+    let targets = targets(png, chunk_type);
+    let chunks = png
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !targets.contains(index))
+        .map(|(_, chunk)| chunk.clone())
+        .collect();
+
+    Ok(Png::from_chunks(chunks)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::from_strings(chunk_type, data).unwrap()
+    }
+
+    fn testing_png() -> Png {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("ruSt", "old secret"),
+            chunk("teSt", "keep me"),
+            chunk("IEND", ""),
+        ])
+        .unwrap();
+        history::upsert_with_history(&png, chunk("ruSt", "new secret!"), 3).unwrap()
+    }
+
+    fn rust() -> ChunkType {
+        ChunkType::from_str("ruSt").unwrap()
+    }
+
+    #[test]
+    fn test_redact_zeros() {
+        let png = testing_png();
+        let (redacted, count) = redact(&png, &rust(), Fill::Zeros).unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(redacted.as_bytes().len(), png.as_bytes().len());
+        assert_eq!(redacted.chunk_by_type(&rust()).unwrap().data(), &[0; 11]);
+        let versions = history::versions(&redacted, &rust());
+        assert_eq!(versions[0].1.data, vec![0; 10]);
+        assert_eq!(
+            redacted
+                .chunk_by_type(&ChunkType::from_str("teSt").unwrap())
+                .unwrap()
+                .data(),
+            b"keep me"
+        );
+    }
+
+    #[test]
+    fn test_redact_random_keeps_length() {
+        let png = testing_png();
+        let (redacted, _) = redact(&png, &rust(), Fill::Random).unwrap();
+        assert_eq!(redacted.chunk_by_type(&rust()).unwrap().data().len(), 11);
+    }
+
+    #[test]
+    fn test_remove() {
+        let png = remove(&testing_png(), &rust()).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+        assert!(png.chunk_by_type(&rust()).is_none());
+    }
+}
@@ -0,0 +1,109 @@
+use crate::chunk_type::ChunkType;
+
+// A chunk type defined by the PNG specification or registered as a public extension
+#[derive(Debug, PartialEq, Eq)]
+pub struct KnownChunk {
+    pub chunk_type: &'static str,
+    pub name: &'static str,
+    // Where the chunk is defined, eg `PNG 1.2` or `Extensions 1.5`
+    pub defined_in: &'static str,
+}
+
+const fn known(
+    chunk_type: &'static str,
+    name: &'static str,
+    defined_in: &'static str,
+) -> KnownChunk {
+    KnownChunk {
+        chunk_type,
+        name,
+        defined_in,
+    }
+}
+
+pub const KNOWN_CHUNKS: [KnownChunk; 30] = [
+    known("IHDR", "Image header", "PNG 1.2"),
+    known("PLTE", "Palette", "PNG 1.2"),
+    known("IDAT", "Image data", "PNG 1.2"),
+    known("IEND", "Image trailer", "PNG 1.2"),
+    known("cHRM", "Primary chromaticities and white point", "PNG 1.2"),
+    known("gAMA", "Image gamma", "PNG 1.2"),
+    known("iCCP", "Embedded ICC profile", "PNG 1.2"),
+    known("sBIT", "Significant bits", "PNG 1.2"),
+    known("sRGB", "Standard RGB colour space", "PNG 1.2"),
+    known("bKGD", "Background colour", "PNG 1.2"),
+    known("hIST", "Image histogram", "PNG 1.2"),
+    known("tRNS", "Transparency", "PNG 1.2"),
+    known("pHYs", "Physical pixel dimensions", "PNG 1.2"),
+    known("sPLT", "Suggested palette", "PNG 1.2"),
+    known("tIME", "Image last-modification time", "PNG 1.2"),
+    known("iTXt", "International textual data", "PNG 1.2"),
+    known("tEXt", "Textual data", "PNG 1.2"),
+    known("zTXt", "Compressed textual data", "PNG 1.2"),
+    known("cICP", "Coding-independent code points", "PNG 3rd edition"),
+    known("mDCv", "Mastering display colour volume", "PNG 3rd edition"),
+    known("cLLi", "Content light level information", "PNG 3rd edition"),
+    known(
+        "eXIf",
+        "Exchangeable image file format profile",
+        "Extensions 1.5",
+    ),
+    known("acTL", "Animation control", "PNG 3rd edition"),
+    known("fcTL", "Frame control", "PNG 3rd edition"),
+    known("fdAT", "Frame data", "PNG 3rd edition"),
+    known("oFFs", "Image offset", "Extensions 1.5"),
+    known("pCAL", "Calibration of pixel values", "Extensions 1.5"),
+    known("sCAL", "Physical scale of image subject", "Extensions 1.5"),
+    known("sTER", "Indicator of stereo image", "Extensions 1.5"),
+    known("gIFg", "GIF graphic control extension", "Extensions 1.5"),
+];
+
+// Find the details of a standard or registered chunk type
+pub fn lookup(chunk_type: &ChunkType) -> Option<&'static KnownChunk> {
+    KNOWN_CHUNKS
+        .iter()
+        .find(|known| known.chunk_type.as_bytes() == chunk_type.bytes())
+}
+
+// Chunks which change how the image's colours are rendered.
+// Dropping any of them from an image can visibly shift its colours, even though they are ancillary.
+pub const COLOR_MANAGEMENT_CHUNKS: [&str; 7] =
+    ["iCCP", "sRGB", "gAMA", "cHRM", "cICP", "mDCv", "cLLi"];
+
+pub fn is_color_management(chunk_type: &ChunkType) -> bool {
+    COLOR_MANAGEMENT_CHUNKS
+        .iter()
+        .any(|name| name.as_bytes() == chunk_type.bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_lookup_known_chunk() {
+        let known = lookup(&ChunkType::from_str("tEXt").unwrap()).unwrap();
+        assert_eq!(known.name, "Textual data");
+    }
+
+    #[test]
+    fn test_lookup_unknown_chunk() {
+        assert!(lookup(&ChunkType::from_str("ruSt").unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_is_color_management() {
+        assert!(is_color_management(&ChunkType::from_str("iCCP").unwrap()));
+        assert!(is_color_management(&ChunkType::from_str("gAMA").unwrap()));
+        assert!(!is_color_management(&ChunkType::from_str("tEXt").unwrap()));
+    }
+
+    #[test]
+    fn test_known_chunks_are_valid() {
+        for known in KNOWN_CHUNKS.iter() {
+            let chunk_type = ChunkType::from_str(known.chunk_type).unwrap();
+            assert!(chunk_type.is_reserved_bit_valid(), "{}", known.chunk_type);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt::{self, Debug, Formatter};
+use zeroize::Zeroize;
+
+// Bytes which must not outlive their use, eg a message rebuilt from secret shares.
+// The bytes are wiped when the payload is dropped, and never shown by Debug, so they
+// don't linger in memory which is later reused, or end up in logs.
+pub struct SecretPayload {
+    bytes: Vec<u8>,
+}
+
+impl SecretPayload {
+    pub fn new(bytes: Vec<u8>) -> SecretPayload {
+        SecretPayload { bytes }
+    }
+
+    pub fn expose(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+impl From<Vec<u8>> for SecretPayload {
+    fn from(bytes: Vec<u8>) -> SecretPayload {
+        SecretPayload::new(bytes)
+    }
+}
+
+impl Drop for SecretPayload {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+    }
+}
+
+impl Debug for SecretPayload {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretPayload({} bytes)", self.bytes.len())
+    }
+}
+
+impl PartialEq for SecretPayload {
+    fn eq(&self, other: &SecretPayload) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Eq for SecretPayload {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_hides_bytes() {
+        let secret = SecretPayload::from(b"signing key".to_vec());
+        assert_eq!(format!("{:?}", secret), "SecretPayload(11 bytes)");
+        assert_eq!(secret.expose(), b"signing key");
+        assert_eq!(secret.len(), 11);
+    }
+}
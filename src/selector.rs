@@ -0,0 +1,445 @@
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+use thiserror::Error;
+
+// Picking out chunks by their type, so commands can work on a family of related chunks at once.
+//
+// A pattern is the 4 letters of a chunk type, where `?` matches any one letter
+// and `*` matches any number of letters, eg `ru??` matches `ruSt` and `ruBy`.
+// The case of each letter is part of the chunk type, `ruSt` and `rust` are different chunks
+// with different properties, matching ignoring case has to be asked for.
+//
+// A selector expression combines terms to pick chunks by more than their type, eg
+// `type=ru?? and len>1024` or `ancillary and not index=3`. `and` binds tighter than `or`,
+// parentheses group terms. Each term is written without spaces.
+//   type=PATTERN          the chunk type matches the pattern
+//   index=N               the chunk is the Nth in the file, counting from 0
+//   len<N, len>=N, ...    the length of the chunk data compared to N bytes
+//   critical, ancillary   the ancillary bit of the chunk type
+//   public, private       the private bit of the chunk type
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum SelectorError {
+    #[error("Invalid character `{0}` in chunk type pattern, must be an ASCII letter, `?` or `*`")]
+    InvalidCharacter(char),
+    #[error("Chunk type pattern `{0}` can never match, chunk types are exactly 4 letters")]
+    NeverMatches(String),
+    #[error("Unknown selector term `{0}`, expected one of type=, index=, len, critical, ancillary, public or private")]
+    UnknownTerm(String),
+    #[error("Invalid number `{0}` in selector term `{1}`")]
+    InvalidNumber(String, String),
+    #[error("Unexpected `{0}` in selector")]
+    UnexpectedToken(String),
+    #[error("Selector ended early, expected a term")]
+    UnexpectedEnd,
+}
+
+pub type SelectorResult<T> = Result<T, SelectorError>;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TypePattern {
+    pattern: Vec<u8>,
+    ignore_case: bool,
+}
+
+impl TypePattern {
+    // A pattern matching only this chunk type
+    pub fn exact(chunk_type: &ChunkType) -> TypePattern {
+        TypePattern {
+            pattern: chunk_type.bytes().to_vec(),
+            ignore_case: false,
+        }
+    }
+
+    pub fn ignore_case(self, ignore_case: bool) -> TypePattern {
+        TypePattern {
+            ignore_case,
+            ..self
+        }
+    }
+
+    // Whether the pattern can only ever match one chunk type
+    pub fn is_exact(&self) -> bool {
+        !self.ignore_case && !self.pattern.iter().any(|&b| b == b'?' || b == b'*')
+    }
+
+    // The chunk type itself when the pattern is exact
+    pub fn as_chunk_type(&self) -> Option<ChunkType> {
+        if !self.is_exact() {
+            return None;
+        }
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&self.pattern);
+        ChunkType::try_from(bytes).ok()
+    }
+
+    pub fn matches(&self, chunk_type: &ChunkType) -> bool {
+        glob_match(&self.pattern, &chunk_type.bytes(), self.ignore_case)
+    }
+
+    // Every chunk in the PNG whose type matches, in file order
+    pub fn matching_chunks<'a>(&self, png: &'a Png) -> Vec<&'a Chunk> {
+        png.chunks()
+            .iter()
+            .filter(|chunk| self.matches(chunk.chunk_type()))
+            .collect()
+    }
+
+    // The distinct chunk types in the PNG which match, in the order they first appear
+    pub fn matching_types(&self, png: &Png) -> Vec<ChunkType> {
+        png.chunk_types()
+            .into_iter()
+            .filter(|chunk_type| self.matches(chunk_type))
+            .collect()
+    }
+}
+
+impl FromStr for TypePattern {
+    type Err = SelectorError;
+
+    fn from_str(s: &str) -> SelectorResult<TypePattern> {
+        if let Some(c) = s
+            .chars()
+            .find(|&c| !(c.is_ascii_alphabetic() || c == '?' || c == '*'))
+        {
+            return Err(SelectorError::InvalidCharacter(c));
+        }
+        // Without a `*` the pattern has a fixed length, and with one it can't be too short
+        let fixed = s.chars().filter(|&c| c != '*').count();
+        if fixed > 4 || (fixed < 4 && !s.contains('*')) {
+            return Err(SelectorError::NeverMatches(s.to_string()));
+        }
+
+        Ok(TypePattern {
+            pattern: s.as_bytes().to_vec(),
+            ignore_case: false,
+        })
+    }
+}
+
+impl Display for TypePattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.pattern))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    fn compare(&self, left: usize, right: usize) -> bool {
+        match self {
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Equal => left == right,
+            Comparison::GreaterOrEqual => left >= right,
+            Comparison::Greater => left > right,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Selector {
+    Type(TypePattern),
+    Index(usize),
+    Length(Comparison, usize),
+    Critical,
+    Ancillary,
+    Public,
+    Private,
+    Not(Box<Selector>),
+    And(Box<Selector>, Box<Selector>),
+    Or(Box<Selector>, Box<Selector>),
+}
+
+impl Selector {
+    // Whether the chunk at this position in the file is selected
+    pub fn matches(&self, index: usize, chunk: &Chunk) -> bool {
+        match self {
+            Selector::Type(pattern) => pattern.matches(chunk.chunk_type()),
+            Selector::Index(i) => index == *i,
+            Selector::Length(comparison, length) => comparison.compare(chunk.data().len(), *length),
+            Selector::Critical => chunk.chunk_type().is_critical(),
+            Selector::Ancillary => !chunk.chunk_type().is_critical(),
+            Selector::Public => chunk.chunk_type().is_public(),
+            Selector::Private => !chunk.chunk_type().is_public(),
+            Selector::Not(selector) => !selector.matches(index, chunk),
+            Selector::And(left, right) => left.matches(index, chunk) && right.matches(index, chunk),
+            Selector::Or(left, right) => left.matches(index, chunk) || right.matches(index, chunk),
+        }
+    }
+
+    // The positions of every selected chunk in the PNG, in file order
+    pub fn select(&self, png: &Png) -> Vec<usize> {
+        png.chunks()
+            .iter()
+            .enumerate()
+            .filter(|(index, chunk)| self.matches(*index, chunk))
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+impl FromStr for Selector {
+    type Err = SelectorError;
+
+    fn from_str(s: &str) -> SelectorResult<Selector> {
+        let mut tokens = Tokens {
+            tokens: tokenize(s),
+            position: 0,
+        };
+        let selector = tokens.parse_or()?;
+        match tokens.next() {
+            Some(token) => Err(SelectorError::UnexpectedToken(token)),
+            None => Ok(selector),
+        }
+    }
+}
+
+// Split a selector into words and parentheses
+fn tokenize(s: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    for c in s.chars() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            if !c.is_whitespace() {
+                tokens.push(c.to_string());
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+struct Tokens {
+    tokens: Vec<String>,
+    position: usize,
+}
+
+impl Tokens {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(|t| t.as_str())
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> SelectorResult<Selector> {
+        let mut selector = self.parse_and()?;
+        while self.peek() == Some("or") {
+            self.next();
+            selector = Selector::Or(Box::new(selector), Box::new(self.parse_and()?));
+        }
+
+        Ok(selector)
+    }
+
+    fn parse_and(&mut self) -> SelectorResult<Selector> {
+        let mut selector = self.parse_term()?;
+        while self.peek() == Some("and") {
+            self.next();
+            selector = Selector::And(Box::new(selector), Box::new(self.parse_term()?));
+        }
+
+        Ok(selector)
+    }
+
+    fn parse_term(&mut self) -> SelectorResult<Selector> {
+        let token = self.next().ok_or(SelectorError::UnexpectedEnd)?;
+        match token.as_str() {
+            "not" => Ok(Selector::Not(Box::new(self.parse_term()?))),
+            "(" => {
+                let selector = self.parse_or()?;
+                match self.next() {
+                    Some(token) if token == ")" => Ok(selector),
+                    Some(token) => Err(SelectorError::UnexpectedToken(token)),
+                    None => Err(SelectorError::UnexpectedEnd),
+                }
+            }
+            ")" | "and" | "or" => Err(SelectorError::UnexpectedToken(token)),
+            _ => parse_atom(&token),
+        }
+    }
+}
+
+fn parse_atom(token: &str) -> SelectorResult<Selector> {
+    let number = |value: &str| {
+        value
+            .parse::<usize>()
+            .map_err(|_| SelectorError::InvalidNumber(value.to_string(), token.to_string()))
+    };
+
+    match token {
+        "critical" => return Ok(Selector::Critical),
+        "ancillary" => return Ok(Selector::Ancillary),
+        "public" => return Ok(Selector::Public),
+        "private" => return Ok(Selector::Private),
+        _ => {}
+    }
+    if let Some(pattern) = token.strip_prefix("type=") {
+        return Ok(Selector::Type(TypePattern::from_str(pattern)?));
+    }
+    if let Some(index) = token.strip_prefix("index=") {
+        return Ok(Selector::Index(number(index)?));
+    }
+    if let Some(rest) = token.strip_prefix("len") {
+        // Two character operators are checked first so `>=` isn't read as `>`
+        let operators = [
+            (">=", Comparison::GreaterOrEqual),
+            ("<=", Comparison::LessOrEqual),
+            (">", Comparison::Greater),
+            ("<", Comparison::Less),
+            ("=", Comparison::Equal),
+        ];
+        for (operator, comparison) in operators.iter() {
+            if let Some(length) = rest.strip_prefix(operator) {
+                return Ok(Selector::Length(*comparison, number(length)?));
+            }
+        }
+    }
+
+    Err(SelectorError::UnknownTerm(token.to_string()))
+}
+
+fn glob_match(pattern: &[u8], input: &[u8], ignore_case: bool) -> bool {
+    match pattern.split_first() {
+        None => input.is_empty(),
+        Some((b'*', rest)) => (0..=input.len()).any(|i| glob_match(rest, &input[i..], ignore_case)),
+        Some((&p, rest)) => match input.split_first() {
+            Some((&c, input)) => {
+                let same = if ignore_case {
+                    p.eq_ignore_ascii_case(&c)
+                } else {
+                    p == c
+                };
+                (p == b'?' || same) && glob_match(rest, input, ignore_case)
+            }
+            None => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk_type(s: &str) -> ChunkType {
+        ChunkType::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_exact() {
+        let pattern = TypePattern::exact(&chunk_type("ruSt"));
+        assert!(pattern.is_exact());
+        assert!(pattern.matches(&chunk_type("ruSt")));
+        assert!(!pattern.matches(&chunk_type("rust")));
+        assert_eq!(pattern.to_string(), "ruSt");
+        assert_eq!(pattern.as_chunk_type(), Some(chunk_type("ruSt")));
+    }
+
+    #[test]
+    fn test_wildcards() {
+        let pattern = TypePattern::from_str("ru??").unwrap();
+        assert!(!pattern.is_exact());
+        assert!(pattern.matches(&chunk_type("ruSt")));
+        assert!(pattern.matches(&chunk_type("ruBy")));
+        assert!(!pattern.matches(&chunk_type("RuSt")));
+        assert_eq!(pattern.as_chunk_type(), None);
+
+        let pattern = TypePattern::from_str("*Xt").unwrap();
+        assert!(pattern.matches(&chunk_type("tEXt")));
+        assert!(pattern.matches(&chunk_type("zTXt")));
+        assert!(!pattern.matches(&chunk_type("iTXT")));
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let pattern = TypePattern::from_str("rust").unwrap().ignore_case(true);
+        assert!(!pattern.is_exact());
+        assert!(pattern.matches(&chunk_type("ruSt")));
+        assert!(pattern.matches(&chunk_type("RUST")));
+        assert!(!pattern.matches(&chunk_type("ruby")));
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(chunk_type("IHDR"), vec![0; 13]),
+            Chunk::new(chunk_type("ruSt"), vec![0; 2000]),
+            Chunk::new(chunk_type("tEXt"), vec![0; 10]),
+            Chunk::new(chunk_type("ruBy"), vec![0; 10]),
+            Chunk::new(chunk_type("IEND"), vec![]),
+        ])
+        .unwrap()
+    }
+
+    fn select(selector: &str) -> Vec<usize> {
+        Selector::from_str(selector).unwrap().select(&testing_png())
+    }
+
+    #[test]
+    fn test_selector_terms() {
+        assert_eq!(select("type=ru??"), vec![1, 3]);
+        assert_eq!(select("index=2"), vec![2]);
+        assert_eq!(select("len>1024"), vec![1]);
+        assert_eq!(select("len<=10"), vec![2, 3, 4]);
+        assert_eq!(select("len=0"), vec![4]);
+        assert_eq!(select("critical"), vec![0, 4]);
+        assert_eq!(select("ancillary"), vec![1, 2, 3]);
+        assert_eq!(select("private"), vec![1, 3]);
+        assert_eq!(select("public"), vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn test_selector_combined() {
+        assert_eq!(select("private and len>1024"), vec![1]);
+        assert_eq!(select("index=0 or type=tEXt"), vec![0, 2]);
+        assert_eq!(select("ancillary and not type=tEXt"), vec![1, 3]);
+        // and binds tighter than or
+        assert_eq!(select("critical or private and len>100"), vec![0, 1, 4]);
+        assert_eq!(select("(critical or private) and len>100"), vec![1]);
+        assert_eq!(select("(index=1 or index=2) and public"), vec![2]);
+    }
+
+    #[test]
+    fn test_invalid_selectors() {
+        assert!(Selector::from_str("").is_err());
+        assert!(Selector::from_str("ancillary and").is_err());
+        assert!(Selector::from_str("(ancillary").is_err());
+        assert!(Selector::from_str("ancillary)").is_err());
+        assert!(Selector::from_str("size>10").is_err());
+        assert!(Selector::from_str("len>lots").is_err());
+        assert!(Selector::from_str("type=rusty").is_err());
+        assert!(Selector::from_str("critical ancillary").is_err());
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert!(TypePattern::from_str("ru?").is_err());
+        assert!(TypePattern::from_str("rusty").is_err());
+        assert!(TypePattern::from_str("ru*??t").is_err());
+        assert!(TypePattern::from_str("ru.t").is_err());
+        assert!(TypePattern::from_str("r*").is_ok());
+    }
+}
@@ -0,0 +1,364 @@
+use crate::secret::SecretPayload;
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+// Shamir's Secret Sharing, splitting a secret into shares so that any `threshold` of them
+// rebuild it, while fewer reveal nothing about it.
+//
+// Each byte of the secret is the constant term of a random polynomial of degree
+// `threshold - 1` over GF(256), and share `x` holds the value of every polynomial at `x`.
+// Interpolating at 0 with `threshold` shares gives back the secret.
+//
+// A share is stored as the threshold, the share's x coordinate, then one byte per secret byte.
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ShamirError {
+    #[error("Invalid sharing scheme `{0}`, expected the form 3-of-5")]
+    InvalidScheme(String),
+    #[error("A threshold of `{0}` isn't possible, it must be from 2 up to the number of shares")]
+    InvalidThreshold(u8),
+    #[error("Found `{found}` shares, but `{needed}` are needed to rebuild the secret")]
+    NotEnoughShares { found: usize, needed: usize },
+    #[error("Share is truncated, found `{0}` bytes")]
+    Truncated(usize),
+    #[error("Share has the x coordinate 0, which would hold the secret itself")]
+    ZeroIndex,
+    #[error("The shares are from different secrets, their thresholds or lengths don't match")]
+    Mismatched,
+    #[error("Failed to generate random numbers")]
+    Random,
+}
+
+pub type ShamirResult<T> = Result<T, ShamirError>;
+
+// How many shares to make, and how many are needed to rebuild the secret
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Scheme {
+    pub threshold: u8,
+    pub shares: u8,
+}
+
+impl FromStr for Scheme {
+    type Err = ShamirError;
+
+    fn from_str(s: &str) -> ShamirResult<Scheme> {
+        let invalid = || ShamirError::InvalidScheme(s.to_string());
+        let mut parts = s.splitn(2, "-of-");
+        let threshold = parts.next().and_then(|t| t.parse::<u8>().ok());
+        let shares = parts.next().and_then(|t| t.parse::<u8>().ok());
+        let (threshold, shares) = threshold.zip(shares).ok_or_else(invalid)?;
+        if threshold < 2 || threshold > shares {
+            return Err(ShamirError::InvalidThreshold(threshold));
+        }
+
+        Ok(Scheme { threshold, shares })
+    }
+}
+
+impl Display for Scheme {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-of-{}", self.threshold, self.shares)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Share {
+    pub threshold: u8,
+    // The x coordinate, never 0
+    pub index: u8,
+    pub data: Vec<u8>,
+}
+
+impl Share {
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [self.threshold, self.index]
+            .iter()
+            .chain(self.data.iter())
+            .copied()
+            .collect()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> ShamirResult<Share> {
+        if bytes.len() < 2 {
+            return Err(ShamirError::Truncated(bytes.len()));
+        }
+        if bytes[1] == 0 {
+            return Err(ShamirError::ZeroIndex);
+        }
+
+        Ok(Share {
+            threshold: bytes[0],
+            index: bytes[1],
+            data: bytes[2..].to_vec(),
+        })
+    }
+}
+
+// Split the secret into shares, using the operating system's random number generator
+pub fn split(secret: &[u8], scheme: Scheme) -> ShamirResult<Vec<Share>> {
+    split_with(secret, scheme, |bytes| {
+        getrandom::getrandom(bytes).map_err(|_| ShamirError::Random)
+    })
+}
+
+// Split the secret with coefficients derived from the secret and scheme instead of random numbers,
+// so the same secret always gives the same shares, for reproducible builds.
+// Anyone able to guess the secret can check a guess against the shares, so only use it for
+// secrets which can't be guessed, eg keys.
+pub fn split_deterministic(secret: &[u8], scheme: Scheme) -> ShamirResult<Vec<Share>> {
+    let mut seed = Sha256::new();
+    seed.update(b"pngme shamir ");
+    seed.update(scheme.to_string());
+    seed.update(secret);
+    let seed = Zeroizing::new(seed.finalize().to_vec());
+
+    // SHA-256 of the seed and a counter, as a stream of bytes
+    let mut counter: u64 = 0;
+    let mut block: Zeroizing<Vec<u8>> = Zeroizing::new(vec![]);
+    split_with(secret, scheme, |bytes| {
+        for byte in bytes.iter_mut() {
+            if block.is_empty() {
+                let mut hasher = Sha256::new();
+                hasher.update(&*seed);
+                hasher.update(counter.to_be_bytes());
+                *block = hasher.finalize().to_vec();
+                counter += 1;
+            }
+            *byte = block.pop().unwrap_or_default();
+        }
+        Ok(())
+    })
+}
+
+fn split_with<F>(secret: &[u8], scheme: Scheme, mut random: F) -> ShamirResult<Vec<Share>>
+where
+    F: FnMut(&mut [u8]) -> ShamirResult<()>,
+{
+    if scheme.threshold < 2 || scheme.threshold > scheme.shares {
+        return Err(ShamirError::InvalidThreshold(scheme.threshold));
+    }
+
+    let mut shares: Vec<Share> = (1..=scheme.shares)
+        .map(|index| Share {
+            threshold: scheme.threshold,
+            index,
+            data: Vec::with_capacity(secret.len()),
+        })
+        .collect();
+    // The coefficients give away the secret, so they are wiped once the shares are made
+    let mut coefficients = Zeroizing::new(vec![0; scheme.threshold as usize]);
+    for &byte in secret {
+        coefficients[0] = byte;
+        random(&mut coefficients[1..])?;
+        for share in shares.iter_mut() {
+            share.data.push(evaluate(&coefficients, share.index));
+        }
+    }
+
+    Ok(shares)
+}
+
+// Rebuild the secret from at least `threshold` shares, duplicate shares are ignored
+pub fn combine(shares: &[Share]) -> ShamirResult<SecretPayload> {
+    let first = shares.first().ok_or(ShamirError::NotEnoughShares {
+        found: 0,
+        needed: 2,
+    })?;
+    let mut unique: Vec<&Share> = vec![];
+    for share in shares {
+        if share.threshold != first.threshold || share.data.len() != first.data.len() {
+            return Err(ShamirError::Mismatched);
+        }
+        if share.index == 0 {
+            return Err(ShamirError::ZeroIndex);
+        }
+        if !unique.iter().any(|s| s.index == share.index) {
+            unique.push(share);
+        }
+    }
+    let needed = first.threshold as usize;
+    if unique.len() < needed {
+        return Err(ShamirError::NotEnoughShares {
+            found: unique.len(),
+            needed,
+        });
+    }
+    let unique = &unique[..needed];
+
+    // The Lagrange basis polynomials evaluated at 0, the same for every byte
+    let weights: Vec<u8> = unique
+        .iter()
+        .map(|share| {
+            unique
+                .iter()
+                .filter(|other| other.index != share.index)
+                .fold(1, |weight, other| {
+                    // In GF(256) subtraction is xor, so 0 - x is x
+                    mul(weight, mul(other.index, inverse(other.index ^ share.index)))
+                })
+        })
+        .collect();
+
+    let secret: Vec<u8> = (0..first.data.len())
+        .map(|i| {
+            unique
+                .iter()
+                .zip(weights.iter())
+                .fold(0, |secret, (share, &weight)| {
+                    secret ^ mul(share.data[i], weight)
+                })
+        })
+        .collect();
+
+    Ok(SecretPayload::from(secret))
+}
+
+// The polynomial at x, by Horner's method
+fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0, |result, &coefficient| mul(result, x) ^ coefficient)
+}
+
+// Multiplication in GF(256) with the AES polynomial x^8 + x^4 + x^3 + x + 1
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+
+    product
+}
+
+// a^254 is the inverse of a, as a^255 is 1 for every non zero a
+fn inverse(a: u8) -> u8 {
+    (0..254).fold(1, |result, _| mul(result, a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+
+    // Predictable coefficients so the tests don't depend on the random number generator
+    fn counting() -> impl FnMut(&mut [u8]) -> ShamirResult<()> {
+        let mut next = 1u8;
+        move |bytes| {
+            for byte in bytes.iter_mut() {
+                *byte = next;
+                next = next.wrapping_mul(7).wrapping_add(3);
+            }
+            Ok(())
+        }
+    }
+
+    fn scheme(threshold: u8, shares: u8) -> Scheme {
+        Scheme { threshold, shares }
+    }
+
+    #[test]
+    fn test_field() {
+        assert_eq!(mul(0x53, 0xca), 0x01);
+        for a in 1..=255u8 {
+            assert_eq!(mul(a, inverse(a)), 1);
+        }
+    }
+
+    #[test]
+    fn test_scheme_from_str() {
+        assert_eq!(Scheme::from_str("3-of-5").unwrap(), scheme(3, 5));
+        assert_eq!(scheme(3, 5).to_string(), "3-of-5");
+        assert!(Scheme::from_str("3 of 5").is_err());
+        assert!(Scheme::from_str("6-of-5").is_err());
+        assert!(Scheme::from_str("1-of-5").is_err());
+    }
+
+    #[test]
+    fn test_split_and_combine() {
+        let secret = b"signing key".to_vec();
+        let shares = split_with(&secret, scheme(3, 5), counting()).unwrap();
+        assert_eq!(shares.len(), 5);
+        assert!(shares.iter().all(|s| s.data != secret));
+
+        assert_eq!(combine(&shares).unwrap().expose(), &secret[..]);
+        assert_eq!(combine(&shares[2..]).unwrap().expose(), &secret[..]);
+        let picked = vec![shares[4].clone(), shares[0].clone(), shares[2].clone()];
+        assert_eq!(combine(&picked).unwrap().expose(), &secret[..]);
+    }
+
+    #[test]
+    fn test_deterministic_output() {
+        let encode = || {
+            let mut png = Png::from_chunks(vec![
+                Chunk::from_strings("IHDR", "header").unwrap(),
+                Chunk::from_strings("IEND", "").unwrap(),
+            ])
+            .unwrap();
+            for share in split_deterministic(b"signing key", scheme(3, 5)).unwrap() {
+                png.append_chunk(Chunk::new(
+                    ChunkType::from_str("keYs").unwrap(),
+                    share.as_bytes(),
+                ));
+            }
+            png.as_bytes()
+        };
+        assert_eq!(encode(), encode());
+
+        let shares = split_deterministic(b"signing key", scheme(3, 5)).unwrap();
+        assert_eq!(combine(&shares[1..4]).unwrap().expose(), b"signing key");
+        assert_ne!(
+            shares,
+            split_deterministic(b"signing key", scheme(2, 5)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_not_enough_shares() {
+        let shares = split_with(b"secret", scheme(3, 5), counting()).unwrap();
+        let duplicated = vec![shares[0].clone(), shares[1].clone(), shares[1].clone()];
+        assert_eq!(
+            combine(&duplicated),
+            Err(ShamirError::NotEnoughShares {
+                found: 2,
+                needed: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_mismatched_shares() {
+        let a = split_with(b"secret", scheme(2, 3), counting()).unwrap();
+        let b = split_with(b"longer secret", scheme(2, 3), counting()).unwrap();
+        assert_eq!(
+            combine(&[a[0].clone(), b[1].clone()]),
+            Err(ShamirError::Mismatched)
+        );
+    }
+
+    #[test]
+    fn test_share_bytes() {
+        let share = Share {
+            threshold: 3,
+            index: 2,
+            data: vec![9, 8, 7],
+        };
+        assert_eq!(Share::from_bytes(&share.as_bytes()).unwrap(), share);
+        assert_eq!(Share::from_bytes(&[3]), Err(ShamirError::Truncated(1)));
+        assert_eq!(Share::from_bytes(&[3, 0, 1]), Err(ShamirError::ZeroIndex));
+    }
+}
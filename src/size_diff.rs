@@ -0,0 +1,193 @@
+use crate::{chunk_type::ChunkType, png::Png};
+use std::fmt::{self, Display, Formatter};
+
+// Where the difference in size between two versions of a PNG comes from, chunk type by
+// chunk type, eg to see that an edit which should only have touched metadata re-encoded the
+// image data. Chunks are grouped by type, so a file whose IDAT is split differently shows
+// as one change to IDAT. Sizes include the 12 bytes of length, type and CRC around the data.
+
+// Length, chunk type and CRC
+const CHUNK_OVERHEAD: usize = 12;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct ChunkSizes {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SizeChange {
+    pub chunk_type: ChunkType,
+    // A count of 0 when the file has none of the type
+    pub old: ChunkSizes,
+    pub new: ChunkSizes,
+}
+
+impl SizeChange {
+    pub fn delta(&self) -> i64 {
+        self.new.bytes as i64 - self.old.bytes as i64
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.old != self.new
+    }
+}
+
+impl Display for SizeChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let chunks = |count: usize| {
+            if count == 1 {
+                String::new()
+            } else {
+                format!(" in {} chunks", count)
+            }
+        };
+        let (old, new) = (self.old, self.new);
+        match (old.count, new.count) {
+            (0, _) => write!(
+                f,
+                "added {}, {} bytes{}",
+                self.chunk_type,
+                new.bytes,
+                chunks(new.count)
+            ),
+            (_, 0) => write!(
+                f,
+                "removed {}, {} bytes{}",
+                self.chunk_type,
+                old.bytes,
+                chunks(old.count)
+            ),
+            _ => {
+                match self.delta() {
+                    0 => write!(f, "{} is unchanged at {} bytes", self.chunk_type, new.bytes)?,
+                    delta if delta > 0 => write!(
+                        f,
+                        "{} grew by {} bytes, {} to {}",
+                        self.chunk_type, delta, old.bytes, new.bytes
+                    )?,
+                    delta => write!(
+                        f,
+                        "{} shrank by {} bytes, {} to {}",
+                        self.chunk_type, -delta, old.bytes, new.bytes
+                    )?,
+                }
+                if old.count != new.count {
+                    write!(f, ", {} to {} chunks", old.count, new.count)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Every chunk type in either file, in the order they're first found, the old file's first
+pub fn size_diff(old: &Png, new: &Png) -> Vec<SizeChange> {
+    let mut changes: Vec<SizeChange> = vec![];
+    let files = [(old, false), (new, true)];
+    for (png, is_new) in files.iter() {
+        for chunk in png.chunks() {
+            let position = changes
+                .iter()
+                .position(|change| change.chunk_type == *chunk.chunk_type());
+            let position = position.unwrap_or_else(|| {
+                changes.push(SizeChange {
+                    chunk_type: *chunk.chunk_type(),
+                    old: ChunkSizes::default(),
+                    new: ChunkSizes::default(),
+                });
+                changes.len() - 1
+            });
+            let change = &mut changes[position];
+            let sizes = if *is_new {
+                &mut change.new
+            } else {
+                &mut change.old
+            };
+            sizes.count += 1;
+            sizes.bytes += chunk.length() as usize + CHUNK_OVERHEAD;
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+
+    fn png(chunks: &[(&str, &str)]) -> Png {
+        Png::from_chunks(
+            chunks
+                .iter()
+                .map(|(chunk_type, data)| Chunk::from_strings(chunk_type, data).unwrap())
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_size_diff() {
+        let old = png(&[
+            ("IHDR", "header"),
+            ("iCCP", "profile"),
+            ("IDAT", "data"),
+            ("IEND", ""),
+        ]);
+        let new = png(&[
+            ("IHDR", "header"),
+            ("tEXt", "Comment\0hello"),
+            ("IDAT", "more data"),
+            ("IDAT", "data"),
+            ("IEND", ""),
+        ]);
+        let changes = size_diff(&old, &new);
+
+        let names: Vec<String> = changes.iter().map(|c| c.chunk_type.to_string()).collect();
+        assert_eq!(names, ["IHDR", "iCCP", "IDAT", "IEND", "tEXt"]);
+        assert!(!changes[0].is_changed());
+        assert_eq!(changes[1].delta(), -19);
+        assert_eq!(changes[2].delta(), 21);
+        assert_eq!(changes[4].delta(), 25);
+
+        // The changes add up to the difference in file size
+        let total: i64 = changes.iter().map(|c| c.delta()).sum();
+        assert_eq!(
+            total,
+            new.as_bytes().len() as i64 - old.as_bytes().len() as i64
+        );
+    }
+
+    #[test]
+    fn test_describe_changes() {
+        let old = png(&[
+            ("IHDR", "header"),
+            ("iCCP", "profile"),
+            ("IDAT", "data"),
+            ("IEND", ""),
+        ]);
+        let new = png(&[
+            ("IHDR", "header"),
+            ("IDAT", "more data"),
+            ("IDAT", "data"),
+            ("tEXt", "Comment\0hello"),
+            ("IEND", ""),
+        ]);
+        let described: Vec<String> = size_diff(&old, &new)
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+
+        assert_eq!(
+            described,
+            [
+                "IHDR is unchanged at 18 bytes",
+                "removed iCCP, 19 bytes",
+                "IDAT grew by 21 bytes, 16 to 37, 1 to 2 chunks",
+                "IEND is unchanged at 12 bytes",
+                "added tEXt, 25 bytes",
+            ]
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use crate::{
+    png::{Png, PngError},
+    temporary,
+    text::{self, TextChunk, TextChunkError, TextKind},
+};
+use std::{
+    convert::TryFrom,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+// Recording a value in a PNG from a build script, eg the version or commit an asset was built
+// from, without knowing anything about chunks:
+//
+//     lib_pngme::stamp(Path::new("assets/logo.png"), "Version", "1.4.2", StampOptions::default())?;
+//
+// The value is stored as text under the key, replacing whatever the key held before. It's
+// stored in a tEXt chunk, or an iTXt chunk when it isn't Latin-1. Stamping is deterministic,
+// the same file, key and value always give the same bytes, and a file which already holds the
+// value isn't written at all, so its modification time doesn't set off another build. The file
+// is replaced in one step, a build stopped part way through never leaves a half written PNG.
+//
+// This is kept stable for build scripts to rely on. StampOptions can only be made with
+// `default()` and set with its methods, so new options can be added without breaking callers.
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum StampError {
+    #[error("Failed to read the PNG file {0:?}")]
+    Read(PathBuf, #[source] PngError),
+    #[error("Failed to stamp the key {0:?}")]
+    Text(String, #[source] TextChunkError),
+    #[error("Failed to write the PNG file {0:?}")]
+    Write(PathBuf, #[source] io::Error),
+}
+
+pub type StampResult<T> = Result<T, StampError>;
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct StampOptions {
+    output: Option<PathBuf>,
+    compress: bool,
+}
+
+impl StampOptions {
+    // Write the stamped PNG here rather than replacing the input
+    pub fn output<P: Into<PathBuf>>(mut self, output: P) -> StampOptions {
+        self.output = Some(output.into());
+        self
+    }
+
+    // Compress the value, worth it for long values such as a licence
+    pub fn compress(mut self, compress: bool) -> StampOptions {
+        self.compress = compress;
+        self
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum Stamped {
+    Added,
+    Replaced,
+    // The file already held the value, so it wasn't written
+    Unchanged,
+}
+
+fn text_for(key: &str, value: &str, compress: bool) -> TextChunk {
+    let latin1 = value.chars().all(|c| (c as u32) < 256);
+    match (latin1, compress) {
+        (true, false) => TextChunk::new(TextKind::Text, key, value),
+        (true, true) => TextChunk::new(TextKind::Compressed, key, value),
+        (false, _) => {
+            let mut text = TextChunk::new(TextKind::International, key, value);
+            text.compressed = compress;
+            text
+        }
+    }
+}
+
+// Store the value under the key in the PNG at `path`
+pub fn stamp(path: &Path, key: &str, value: &str, options: StampOptions) -> StampResult<Stamped> {
+    let original = fs::read(path).map_err(|e| StampError::Read(path.into(), e.into()))?;
+    let png = Png::try_from(&original[..]).map_err(|e| StampError::Read(path.into(), e))?;
+
+    let (stamped, replaced) = text::upsert(&png, &text_for(key, value, options.compress))
+        .map_err(|e| StampError::Text(key.to_string(), e))?;
+    let bytes = stamped.as_bytes();
+
+    let output = options.output.as_deref().unwrap_or(path);
+    if bytes == original && output == path {
+        return Ok(Stamped::Unchanged);
+    }
+    temporary::write_atomically(output, |file| file.write_all(&bytes))
+        .map_err(|e| StampError::Write(output.into(), e))?;
+
+    Ok(if replaced {
+        Stamped::Replaced
+    } else {
+        Stamped::Added
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use std::{env, process};
+
+    fn png_file(name: &str) -> PathBuf {
+        let png = Png::from_chunks(vec![
+            Chunk::from_strings("IHDR", "header").unwrap(),
+            Chunk::from_strings("IDAT", "data").unwrap(),
+            Chunk::from_strings("IEND", "").unwrap(),
+        ])
+        .unwrap();
+        let path = env::temp_dir().join(format!("pngme-stamp-{}-{}.png", name, process::id()));
+        fs::write(&path, png.as_bytes()).unwrap();
+        path
+    }
+
+    fn text(path: &Path, key: &str) -> Vec<TextChunk> {
+        let png = Png::from_file(path).unwrap();
+        text::find_keyword(&png, key)
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect()
+    }
+
+    #[test]
+    fn test_stamp_upserts() {
+        let path = png_file("upsert");
+        let stamp_version = |version| stamp(&path, "Version", version, StampOptions::default());
+
+        assert_eq!(stamp_version("1.0").unwrap(), Stamped::Added);
+        let first = fs::read(&path).unwrap();
+        assert_eq!(stamp_version("1.0").unwrap(), Stamped::Unchanged);
+        assert_eq!(stamp_version("1.1").unwrap(), Stamped::Replaced);
+        assert_eq!(text(&path, "Version")[0].text, "1.1");
+        assert_eq!(text(&path, "Version").len(), 1);
+
+        // The same value gives the same bytes
+        stamp_version("1.0").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), first);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_stamp_options() {
+        let path = png_file("options");
+        let output = path.with_extension("out.png");
+        let options = StampOptions::default().output(&output).compress(true);
+
+        assert_eq!(
+            stamp(&path, "Author", "Zoë", options).unwrap(),
+            Stamped::Added
+        );
+        assert!(text(&path, "Author").is_empty());
+        let stamped = &text(&output, "Author")[0];
+        assert_eq!(stamped.kind, TextKind::Compressed);
+        assert_eq!(stamped.text, "Zoë");
+
+        // Not Latin-1, so it needs iTXt
+        let options = StampOptions::default().output(&output);
+        stamp(&path, "Author", "佐藤", options).unwrap();
+        assert_eq!(text(&output, "Author")[0].kind, TextKind::International);
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&output).unwrap();
+    }
+
+    #[test]
+    fn test_stamp_invalid_key() {
+        let path = png_file("invalid");
+        let result = stamp(&path, "", "value", StampOptions::default());
+        assert!(matches!(result, Err(StampError::Text(..))));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
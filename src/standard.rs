@@ -0,0 +1,753 @@
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+use thiserror::Error;
+
+// Typed views of the standard chunks, for the chunks where the contents have to agree
+// with the rest of the image, eg a histogram needs an entry for every palette entry.
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StandardChunkError {
+    #[error("`{0}` chunk is missing")]
+    Missing(&'static str),
+    #[error("`{chunk_type}` chunk should be {expected} bytes, but found `{found}` bytes")]
+    WrongLength {
+        chunk_type: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Unknown colour type `{0}`")]
+    UnknownColorType(u8),
+    #[error("Bit depth `{bit_depth}` is not allowed for {color_type} images")]
+    InvalidBitDepth {
+        bit_depth: u8,
+        color_type: ColorType,
+    },
+    #[error("`hIST` chunk entries are 2 bytes each, but found `{0}` bytes")]
+    HistogramOddLength(usize),
+    #[error("`hIST` chunk is only allowed in images with a `PLTE` chunk")]
+    HistogramWithoutPalette,
+    #[error("`hIST` chunk has `{0}` entries, but the `PLTE` chunk has `{1}`")]
+    HistogramSizeMismatch(usize, usize),
+    #[error("`sBIT` value `{value}` should be between 1 and the sample depth of {sample_depth}")]
+    SignificantBitsOutOfRange { value: u8, sample_depth: u8 },
+    #[error("Invalid colour `{0}`, expected a hex colour such as #ffffff")]
+    InvalidColor(String),
+    #[error("Colour `{0}` isn't grey, but the image is {1}")]
+    ColorNotGrey(Rgb, ColorType),
+    #[error("Colour `{0}` isn't in the image's palette")]
+    ColorNotInPalette(Rgb),
+    #[error("`{chunk_type}` chunk refers to palette entry `{index}`, but the `PLTE` chunk has `{entries}` entries")]
+    PaletteIndexOutOfRange {
+        chunk_type: &'static str,
+        index: usize,
+        entries: usize,
+    },
+    #[error("`tRNS` chunk is not allowed in {0} images, they already have an alpha channel")]
+    TransparencyNotAllowed(ColorType),
+}
+
+pub type StandardChunkResult<T> = Result<T, StandardChunkError>;
+
+// How the pixels of the image are stored, from byte 9 of IHDR
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ColorType {
+    Grayscale,
+    Truecolor,
+    Indexed,
+    GrayscaleAlpha,
+    TruecolorAlpha,
+}
+
+impl ColorType {
+    fn from_byte(value: u8) -> StandardChunkResult<ColorType> {
+        match value {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Truecolor),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::TruecolorAlpha),
+            v => Err(StandardChunkError::UnknownColorType(v)),
+        }
+    }
+
+    pub fn to_byte(self) -> u8 {
+        match self {
+            ColorType::Grayscale => 0,
+            ColorType::Truecolor => 2,
+            ColorType::Indexed => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::TruecolorAlpha => 6,
+        }
+    }
+
+    // How many samples make up each pixel
+    pub fn channels(self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Truecolor => 3,
+            ColorType::TruecolorAlpha => 4,
+        }
+    }
+
+    pub(crate) fn allowed_bit_depths(self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            _ => &[8, 16],
+        }
+    }
+}
+
+impl Display for ColorType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorType::Grayscale => "greyscale",
+            ColorType::Truecolor => "truecolour",
+            ColorType::Indexed => "indexed colour",
+            ColorType::GrayscaleAlpha => "greyscale with alpha",
+            ColorType::TruecolorAlpha => "truecolour with alpha",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+// The IHDR chunk, the dimensions and pixel format of the image
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub interlaced: bool,
+}
+
+impl ImageHeader {
+    const LENGTH: usize = 13;
+
+    pub fn from_png(png: &Png) -> StandardChunkResult<ImageHeader> {
+        let chunk = find(png, *b"IHDR").ok_or(StandardChunkError::Missing("IHDR"))?;
+
+        ImageHeader::try_from(chunk)
+    }
+
+    // Compression, filter method and interlace method are the only ones the specification defines
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(ImageHeader::LENGTH);
+        data.extend_from_slice(&self.width.to_be_bytes());
+        data.extend_from_slice(&self.height.to_be_bytes());
+        data.extend_from_slice(&[
+            self.bit_depth,
+            self.color_type.to_byte(),
+            0,
+            0,
+            self.interlaced as u8,
+        ]);
+
+        Chunk::new(ChunkType::from_known(*b"IHDR"), data)
+    }
+
+    // The bit depth of each palette entry for indexed images, otherwise of each pixel channel
+    pub fn sample_depth(&self) -> u8 {
+        if self.color_type == ColorType::Indexed {
+            8
+        } else {
+            self.bit_depth
+        }
+    }
+}
+
+impl TryFrom<&Chunk> for ImageHeader {
+    type Error = StandardChunkError;
+
+    fn try_from(chunk: &Chunk) -> StandardChunkResult<ImageHeader> {
+        let data = expect_length(chunk, "IHDR", ImageHeader::LENGTH)?;
+
+        let bit_depth = data[8];
+        let color_type = ColorType::from_byte(data[9])?;
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(StandardChunkError::InvalidBitDepth {
+                bit_depth,
+                color_type,
+            });
+        }
+
+        Ok(ImageHeader {
+            width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            bit_depth,
+            color_type,
+            interlaced: data[12] == 1,
+        })
+    }
+}
+
+// The hIST chunk, how often each palette entry is used
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Histogram {
+    pub frequencies: Vec<u16>,
+}
+
+impl Histogram {
+    pub fn validate(&self, palette_entries: Option<usize>) -> StandardChunkResult<()> {
+        match palette_entries {
+            None => Err(StandardChunkError::HistogramWithoutPalette),
+            Some(entries) if entries != self.frequencies.len() => Err(
+                StandardChunkError::HistogramSizeMismatch(self.frequencies.len(), entries),
+            ),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+impl TryFrom<&Chunk> for Histogram {
+    type Error = StandardChunkError;
+
+    fn try_from(chunk: &Chunk) -> StandardChunkResult<Histogram> {
+        let data = chunk.data();
+        if !data.len().is_multiple_of(2) {
+            return Err(StandardChunkError::HistogramOddLength(data.len()));
+        }
+
+        Ok(Histogram {
+            frequencies: data
+                .chunks(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect(),
+        })
+    }
+}
+
+impl Display for Histogram {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let used = self.frequencies.iter().filter(|f| **f > 0).count();
+        write!(
+            f,
+            "Histogram of {} palette entries, {} used",
+            self.frequencies.len(),
+            used
+        )
+    }
+}
+
+// The sBIT chunk, how many bits of each channel are significant.
+// There is one value per channel, or per palette colour channel for indexed images.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SignificantBits {
+    pub bits: Vec<u8>,
+}
+
+impl SignificantBits {
+    pub fn from_chunk(chunk: &Chunk, header: &ImageHeader) -> StandardChunkResult<SignificantBits> {
+        let channels = match header.color_type {
+            ColorType::Grayscale => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Truecolor | ColorType::Indexed => 3,
+            ColorType::TruecolorAlpha => 4,
+        };
+        let data = expect_length(chunk, "sBIT", channels)?;
+
+        let sample_depth = header.sample_depth();
+        if let Some(value) = data.iter().find(|v| **v == 0 || **v > sample_depth) {
+            return Err(StandardChunkError::SignificantBitsOutOfRange {
+                value: *value,
+                sample_depth,
+            });
+        }
+
+        Ok(SignificantBits {
+            bits: data.to_vec(),
+        })
+    }
+}
+
+impl Display for SignificantBits {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let bits: Vec<String> = self.bits.iter().map(|b| b.to_string()).collect();
+        write!(f, "Significant bits per channel: {}", bits.join(", "))
+    }
+}
+
+// An 8 bit per channel colour, as given on the command line
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Rgb {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl FromStr for Rgb {
+    type Err = StandardChunkError;
+
+    fn from_str(s: &str) -> StandardChunkResult<Rgb> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(StandardChunkError::InvalidColor(s.to_string()));
+        }
+
+        let value = u32::from_str_radix(hex, 16)
+            .map_err(|_| StandardChunkError::InvalidColor(s.to_string()))?;
+        let [_, red, green, blue] = value.to_be_bytes();
+        Ok(Rgb { red, green, blue })
+    }
+}
+
+impl Display for Rgb {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+}
+
+// Scale an 8 bit channel to the bit depth of the image, eg 255 is 65535 in a 16 bit image
+fn scale_to_depth(value: u8, bit_depth: u8) -> u16 {
+    let max = (1u32 << bit_depth) - 1;
+    u16::try_from((u32::from(value) * max + 127) / 255).unwrap_or(u16::MAX)
+}
+
+// The bKGD chunk, the colour to show the image against.
+// The form depends on the colour type, a grey level, a colour, or a palette entry.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Background {
+    Grey(u16),
+    Color(u16, u16, u16),
+    PaletteIndex(u8),
+}
+
+impl Background {
+    // Work out the background for the image which shows as the given colour
+    pub fn for_color(
+        color: Rgb,
+        header: &ImageHeader,
+        palette: Option<&[u8]>,
+    ) -> StandardChunkResult<Background> {
+        match header.color_type {
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                if color.red != color.green || color.green != color.blue {
+                    return Err(StandardChunkError::ColorNotGrey(color, header.color_type));
+                }
+                Ok(Background::Grey(scale_to_depth(
+                    color.red,
+                    header.bit_depth,
+                )))
+            }
+            ColorType::Truecolor | ColorType::TruecolorAlpha => Ok(Background::Color(
+                scale_to_depth(color.red, header.bit_depth),
+                scale_to_depth(color.green, header.bit_depth),
+                scale_to_depth(color.blue, header.bit_depth),
+            )),
+            ColorType::Indexed => palette
+                .unwrap_or(&[])
+                .chunks(3)
+                .position(|entry| entry == [color.red, color.green, color.blue])
+                .and_then(|index| u8::try_from(index).ok())
+                .map(Background::PaletteIndex)
+                .ok_or(StandardChunkError::ColorNotInPalette(color)),
+        }
+    }
+
+    pub fn from_chunk(
+        chunk: &Chunk,
+        header: &ImageHeader,
+        palette_entries: Option<usize>,
+    ) -> StandardChunkResult<Background> {
+        match header.color_type {
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                let data = expect_length(chunk, "bKGD", 2)?;
+                Ok(Background::Grey(u16::from_be_bytes([data[0], data[1]])))
+            }
+            ColorType::Truecolor | ColorType::TruecolorAlpha => {
+                let data = expect_length(chunk, "bKGD", 6)?;
+                Ok(Background::Color(
+                    u16::from_be_bytes([data[0], data[1]]),
+                    u16::from_be_bytes([data[2], data[3]]),
+                    u16::from_be_bytes([data[4], data[5]]),
+                ))
+            }
+            ColorType::Indexed => {
+                let data = expect_length(chunk, "bKGD", 1)?;
+                let entries = palette_entries.unwrap_or(0);
+                if usize::from(data[0]) >= entries {
+                    return Err(StandardChunkError::PaletteIndexOutOfRange {
+                        chunk_type: "bKGD",
+                        index: usize::from(data[0]),
+                        entries,
+                    });
+                }
+                Ok(Background::PaletteIndex(data[0]))
+            }
+        }
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data = match self {
+            Background::Grey(grey) => grey.to_be_bytes().to_vec(),
+            Background::Color(red, green, blue) => [red, green, blue]
+                .iter()
+                .flat_map(|channel| channel.to_be_bytes().to_vec())
+                .collect(),
+            Background::PaletteIndex(index) => vec![*index],
+        };
+
+        Chunk::new(ChunkType::from_known(*b"bKGD"), data)
+    }
+}
+
+impl Display for Background {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Background::Grey(grey) => write!(f, "Background grey level {}", grey),
+            Background::Color(red, green, blue) => {
+                write!(f, "Background colour {}, {}, {}", red, green, blue)
+            }
+            Background::PaletteIndex(index) => write!(f, "Background palette entry {}", index),
+        }
+    }
+}
+
+// The tRNS chunk, either a single colour to treat as transparent,
+// or an alpha value for each of the first palette entries
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Transparency {
+    Grey(u16),
+    Color(u16, u16, u16),
+    PaletteAlpha(Vec<u8>),
+}
+
+impl Transparency {
+    pub fn from_chunk(
+        chunk: &Chunk,
+        header: &ImageHeader,
+        palette_entries: Option<usize>,
+    ) -> StandardChunkResult<Transparency> {
+        match header.color_type {
+            ColorType::Grayscale => {
+                let data = expect_length(chunk, "tRNS", 2)?;
+                Ok(Transparency::Grey(u16::from_be_bytes([data[0], data[1]])))
+            }
+            ColorType::Truecolor => {
+                let data = expect_length(chunk, "tRNS", 6)?;
+                Ok(Transparency::Color(
+                    u16::from_be_bytes([data[0], data[1]]),
+                    u16::from_be_bytes([data[2], data[3]]),
+                    u16::from_be_bytes([data[4], data[5]]),
+                ))
+            }
+            ColorType::Indexed => {
+                let data = chunk.data();
+                let entries = palette_entries.unwrap_or(0);
+                if data.len() > entries {
+                    return Err(StandardChunkError::PaletteIndexOutOfRange {
+                        chunk_type: "tRNS",
+                        index: data.len() - 1,
+                        entries,
+                    });
+                }
+                Ok(Transparency::PaletteAlpha(data.to_vec()))
+            }
+            color_type => Err(StandardChunkError::TransparencyNotAllowed(color_type)),
+        }
+    }
+}
+
+impl Display for Transparency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Transparency::Grey(grey) => write!(f, "Transparent grey level {}", grey),
+            Transparency::Color(red, green, blue) => {
+                write!(f, "Transparent colour {}, {}, {}", red, green, blue)
+            }
+            Transparency::PaletteAlpha(alpha) => {
+                write!(f, "Alpha for the first {} palette entries", alpha.len())
+            }
+        }
+    }
+}
+
+// The palette colours, 3 bytes per entry
+pub fn palette(png: &Png) -> Option<&[u8]> {
+    find(png, *b"PLTE").map(|plte| plte.data())
+}
+
+// The number of entries in the PLTE chunk, if there is one
+pub fn palette_entries(png: &Png) -> Option<usize> {
+    find(png, *b"PLTE").map(|plte| plte.data().len() / 3)
+}
+
+// Describe the contents of a standard chunk, for the chunks which have a typed view
+pub fn describe(png: &Png, chunk: &Chunk) -> Option<StandardChunkResult<String>> {
+    let description = match chunk.chunk_type().to_string().as_str() {
+        "IHDR" => ImageHeader::try_from(chunk).map(|header| {
+            format!(
+                "{}x{}, {} bit {}{}",
+                header.width,
+                header.height,
+                header.bit_depth,
+                header.color_type,
+                if header.interlaced {
+                    ", interlaced"
+                } else {
+                    ""
+                }
+            )
+        }),
+        "hIST" => Histogram::try_from(chunk).map(|histogram| histogram.to_string()),
+        "sBIT" => ImageHeader::from_png(png)
+            .and_then(|header| SignificantBits::from_chunk(chunk, &header))
+            .map(|bits| bits.to_string()),
+        "bKGD" => ImageHeader::from_png(png)
+            .and_then(|header| Background::from_chunk(chunk, &header, palette_entries(png)))
+            .map(|background| background.to_string()),
+        "tRNS" => ImageHeader::from_png(png)
+            .and_then(|header| Transparency::from_chunk(chunk, &header, palette_entries(png)))
+            .map(|transparency| transparency.to_string()),
+        _ => return None,
+    };
+
+    Some(description)
+}
+
+// Check the standard chunks agree with each other, returning every problem found
+pub fn check(png: &Png) -> Vec<StandardChunkError> {
+    let mut problems = vec![];
+
+    let header = match ImageHeader::from_png(png) {
+        Ok(header) => header,
+        Err(e) => return vec![e],
+    };
+
+    if let Some(chunk) = find(png, *b"hIST") {
+        if let Err(e) = Histogram::try_from(chunk)
+            .and_then(|histogram| histogram.validate(palette_entries(png)))
+        {
+            problems.push(e);
+        }
+    }
+    if let Some(chunk) = find(png, *b"sBIT") {
+        if let Err(e) = SignificantBits::from_chunk(chunk, &header) {
+            problems.push(e);
+        }
+    }
+    if let Some(chunk) = find(png, *b"bKGD") {
+        if let Err(e) = Background::from_chunk(chunk, &header, palette_entries(png)) {
+            problems.push(e);
+        }
+    }
+    if let Some(chunk) = find(png, *b"tRNS") {
+        if let Err(e) = Transparency::from_chunk(chunk, &header, palette_entries(png)) {
+            problems.push(e);
+        }
+    }
+
+    problems
+}
+
+fn find(png: &Png, chunk_type: [u8; 4]) -> Option<&Chunk> {
+    png.chunk_by_type(&ChunkType::from_known(chunk_type))
+}
+
+fn expect_length<'a>(
+    chunk: &'a Chunk,
+    chunk_type: &'static str,
+    expected: usize,
+) -> StandardChunkResult<&'a [u8]> {
+    let data = chunk.data();
+    if data.len() != expected {
+        return Err(StandardChunkError::WrongLength {
+            chunk_type,
+            expected,
+            found: data.len(),
+        });
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ihdr(bit_depth: u8, color_type: u8) -> Chunk {
+        let data = vec![0, 0, 0, 2, 0, 0, 0, 3, bit_depth, color_type, 0, 0, 0];
+        Chunk::new(ChunkType::from_str("IHDR").unwrap(), data)
+    }
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    fn testing_png(mut chunks: Vec<Chunk>) -> Png {
+        chunks.push(chunk("IEND", vec![]));
+        Png::from_chunks(chunks).unwrap()
+    }
+
+    #[test]
+    fn test_image_header() {
+        let header = ImageHeader::try_from(&ihdr(8, 6)).unwrap();
+        assert_eq!(header.width, 2);
+        assert_eq!(header.height, 3);
+        assert_eq!(header.color_type, ColorType::TruecolorAlpha);
+        assert!(!header.interlaced);
+    }
+
+    #[test]
+    fn test_image_header_to_chunk() {
+        let chunk = ihdr(8, 6);
+        let header = ImageHeader::try_from(&chunk).unwrap();
+        assert_eq!(header.to_chunk().as_bytes(), chunk.as_bytes());
+    }
+
+    #[test]
+    fn test_image_header_invalid_bit_depth() {
+        assert!(ImageHeader::try_from(&ihdr(16, 3)).is_err());
+        assert!(ImageHeader::try_from(&ihdr(8, 5)).is_err());
+    }
+
+    #[test]
+    fn test_histogram_matches_palette() {
+        let png = testing_png(vec![
+            ihdr(8, 3),
+            chunk("PLTE", vec![0; 6]),
+            chunk("hIST", vec![0, 1, 0, 0]),
+        ]);
+        assert!(check(&png).is_empty());
+
+        let histogram = Histogram::try_from(&png.chunks()[2]).unwrap();
+        assert_eq!(histogram.frequencies, vec![1, 0]);
+    }
+
+    #[test]
+    fn test_histogram_size_mismatch() {
+        let png = testing_png(vec![
+            ihdr(8, 3),
+            chunk("PLTE", vec![0; 9]),
+            chunk("hIST", vec![0, 1, 0, 0]),
+        ]);
+        assert_eq!(
+            check(&png),
+            vec![StandardChunkError::HistogramSizeMismatch(2, 3)]
+        );
+    }
+
+    #[test]
+    fn test_histogram_without_palette() {
+        let png = testing_png(vec![ihdr(8, 2), chunk("hIST", vec![0, 1])]);
+        assert_eq!(
+            check(&png),
+            vec![StandardChunkError::HistogramWithoutPalette]
+        );
+    }
+
+    #[test]
+    fn test_significant_bits() {
+        let header = ImageHeader::try_from(&ihdr(16, 4)).unwrap();
+        let bits = SignificantBits::from_chunk(&chunk("sBIT", vec![12, 16]), &header).unwrap();
+        assert_eq!(bits.bits, vec![12, 16]);
+    }
+
+    #[test]
+    fn test_significant_bits_wrong_channels() {
+        let header = ImageHeader::try_from(&ihdr(8, 2)).unwrap();
+        assert!(SignificantBits::from_chunk(&chunk("sBIT", vec![8]), &header).is_err());
+    }
+
+    #[test]
+    fn test_significant_bits_above_bit_depth() {
+        let png = testing_png(vec![ihdr(4, 0), chunk("sBIT", vec![5])]);
+        assert_eq!(
+            check(&png),
+            vec![StandardChunkError::SignificantBitsOutOfRange {
+                value: 5,
+                sample_depth: 4
+            }]
+        );
+    }
+
+    #[test]
+    fn test_rgb_from_str() {
+        let color = Rgb::from_str("#ff8000").unwrap();
+        assert_eq!((color.red, color.green, color.blue), (255, 128, 0));
+        assert_eq!(color.to_string(), "#ff8000");
+        assert!(Rgb::from_str("#fff").is_err());
+        assert!(Rgb::from_str("#gggggg").is_err());
+    }
+
+    #[test]
+    fn test_background_for_truecolour() {
+        let header = ImageHeader::try_from(&ihdr(16, 2)).unwrap();
+        let background =
+            Background::for_color(Rgb::from_str("#ff0000").unwrap(), &header, None).unwrap();
+        assert_eq!(background, Background::Color(65535, 0, 0));
+        assert_eq!(background.to_chunk().data(), &[255, 255, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_background_for_greyscale() {
+        let header = ImageHeader::try_from(&ihdr(4, 0)).unwrap();
+        let white = Rgb::from_str("#ffffff").unwrap();
+        assert_eq!(
+            Background::for_color(white, &header, None).unwrap(),
+            Background::Grey(15)
+        );
+
+        let red = Rgb::from_str("#ff0000").unwrap();
+        assert!(Background::for_color(red, &header, None).is_err());
+    }
+
+    #[test]
+    fn test_background_for_indexed() {
+        let header = ImageHeader::try_from(&ihdr(8, 3)).unwrap();
+        let palette = [0, 0, 0, 255, 255, 255];
+        let white = Rgb::from_str("#ffffff").unwrap();
+        assert_eq!(
+            Background::for_color(white, &header, Some(&palette)).unwrap(),
+            Background::PaletteIndex(1)
+        );
+
+        let red = Rgb::from_str("#ff0000").unwrap();
+        assert!(Background::for_color(red, &header, Some(&palette)).is_err());
+    }
+
+    #[test]
+    fn test_background_palette_index_out_of_range() {
+        let png = testing_png(vec![
+            ihdr(8, 3),
+            chunk("PLTE", vec![0; 6]),
+            chunk("bKGD", vec![2]),
+        ]);
+        assert_eq!(
+            check(&png),
+            vec![StandardChunkError::PaletteIndexOutOfRange {
+                chunk_type: "bKGD",
+                index: 2,
+                entries: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn test_transparency_not_allowed_with_alpha() {
+        let png = testing_png(vec![ihdr(8, 6), chunk("tRNS", vec![0; 6])]);
+        assert_eq!(
+            check(&png),
+            vec![StandardChunkError::TransparencyNotAllowed(
+                ColorType::TruecolorAlpha
+            )]
+        );
+    }
+
+    #[test]
+    fn test_transparency_for_indexed() {
+        let png = testing_png(vec![
+            ihdr(8, 3),
+            chunk("PLTE", vec![0; 6]),
+            chunk("tRNS", vec![0, 128]),
+        ]);
+        assert!(check(&png).is_empty());
+    }
+}
@@ -0,0 +1,188 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+// Files are replaced in one step by writing a temporary file beside them and renaming it over
+// them, so a stopped write never leaves half a file. The temporary file for `logo.png` is
+// `.logo.png.pngme-tmp`.
+//
+// A write in progress is remembered, so a Ctrl-C handler can remove its temporary file with
+// `remove_in_progress` before exiting. A process killed outright still leaves it behind,
+// `is_temporary` recognises those so they can be cleaned up later.
+
+pub const SUFFIX: &str = ".pngme-tmp";
+
+static IN_PROGRESS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+// A panic while holding the lock doesn't make the list wrong
+fn in_progress() -> MutexGuard<'static, Vec<PathBuf>> {
+    IN_PROGRESS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Forgets the temporary file once the write is over, however it ended
+struct InProgress(PathBuf);
+
+impl InProgress {
+    fn start(path: PathBuf) -> InProgress {
+        in_progress().push(path.clone());
+        InProgress(path)
+    }
+}
+
+impl Drop for InProgress {
+    fn drop(&mut self) {
+        in_progress().retain(|path| path != &self.0);
+    }
+}
+
+pub fn temporary_path(output: &Path) -> io::Result<PathBuf> {
+    let name = output
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Output isn't a file"))?;
+    let mut temporary_name = OsString::from(".");
+    temporary_name.push(name);
+    temporary_name.push(SUFFIX);
+
+    Ok(output.with_file_name(temporary_name))
+}
+
+pub fn is_temporary(path: &Path) -> bool {
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+    name.starts_with('.') && name.ends_with(SUFFIX) && name.len() > 1 + SUFFIX.len()
+}
+
+// The file a write to `output` should replace. A symlink is written through to its target, so
+// the link is kept rather than replaced by a plain file. A dangling link is replaced.
+fn resolve(output: &Path) -> PathBuf {
+    match fs::symlink_metadata(output) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            fs::canonicalize(output).unwrap_or_else(|_| output.to_path_buf())
+        }
+        _ => output.to_path_buf(),
+    }
+}
+
+// Write to a temporary file beside the output, then rename it over the output. An existing
+// output keeps its permissions, eg a private 0600 file isn't made readable by everyone and an
+// executable stays executable. Hard links to the output still end up pointing at the old file.
+pub fn write_atomically<F>(output: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut File) -> io::Result<()>,
+{
+    let output = resolve(output);
+    let permissions = fs::metadata(&output)
+        .ok()
+        .map(|metadata| metadata.permissions());
+    let temporary = temporary_path(&output)?;
+    let _in_progress = InProgress::start(temporary.clone());
+
+    let written = File::create(&temporary)
+        .and_then(|mut file| {
+            if let Some(permissions) = permissions {
+                file.set_permissions(permissions)?;
+            }
+            write(&mut file).and_then(|_| file.sync_all())
+        })
+        .and_then(|_| fs::rename(&temporary, &output));
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+
+    written
+}
+
+// Remove the temporary files of the writes in progress, eg before exiting on Ctrl-C.
+// Returns how many were removed.
+pub fn remove_in_progress() -> usize {
+    in_progress()
+        .drain(..)
+        .filter(|path| fs::remove_file(path).is_ok())
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::Write;
+    use std::process;
+
+    #[test]
+    fn test_temporary_path() {
+        let temporary = temporary_path(Path::new("assets/logo.png")).unwrap();
+        assert_eq!(temporary, Path::new("assets/.logo.png.pngme-tmp"));
+        assert!(is_temporary(&temporary));
+        assert!(!is_temporary(Path::new("assets/logo.png")));
+        assert!(!is_temporary(Path::new(".pngme-tmp")));
+    }
+
+    #[test]
+    fn test_write_atomically() {
+        let output = env::temp_dir().join(format!("pngme-atomic-{}.png", process::id()));
+        let temporary = temporary_path(&output).unwrap();
+
+        write_atomically(&output, |file| {
+            assert!(in_progress().contains(&temporary));
+            file.write_all(b"written")
+        })
+        .unwrap();
+        assert_eq!(fs::read(&output).unwrap(), b"written");
+        assert!(!temporary.exists());
+        assert!(!in_progress().contains(&temporary));
+
+        // A failed write leaves the output as it was
+        let failed = write_atomically(&output, |file| {
+            file.write_all(b"half")?;
+            Err(io::Error::from(io::ErrorKind::Interrupted))
+        });
+        assert!(failed.is_err());
+        assert_eq!(fs::read(&output).unwrap(), b"written");
+        assert!(!temporary.exists());
+
+        fs::remove_file(&output).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomically_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let output = env::temp_dir().join(format!("pngme-private-{}.png", process::id()));
+        fs::write(&output, b"private").unwrap();
+        fs::set_permissions(&output, fs::Permissions::from_mode(0o600)).unwrap();
+
+        write_atomically(&output, |file| file.write_all(b"still private")).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), b"still private");
+        let mode = fs::metadata(&output).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        fs::remove_file(&output).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomically_through_symlink() {
+        use std::os::unix::fs::symlink;
+
+        let target = env::temp_dir().join(format!("pngme-target-{}.png", process::id()));
+        let link = env::temp_dir().join(format!("pngme-link-{}.png", process::id()));
+        fs::write(&target, b"target").unwrap();
+        let _ = fs::remove_file(&link);
+        symlink(&target, &link).unwrap();
+
+        write_atomically(&link, |file| file.write_all(b"through the link")).unwrap();
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read(&target).unwrap(), b"through the link");
+
+        fs::remove_file(&link).unwrap();
+        fs::remove_file(&target).unwrap();
+    }
+}
@@ -0,0 +1,434 @@
+use crate::{
+    chunk::{Chunk, ChunkError},
+    chunk_type::ChunkType,
+    inflate::{self, InflateResult},
+    png::{Png, PngError},
+};
+use flate2::{write::ZlibEncoder, Compression};
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+    io::{self, Write},
+    str::FromStr,
+};
+use thiserror::Error;
+
+// The three chunk types which hold text, each a keyword followed by the text:
+//
+//   tEXt - Latin-1 text
+//   zTXt - Latin-1 text, zlib compressed
+//   iTXt - UTF-8 text, optionally zlib compressed, with a language tag and translated keyword
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum TextChunkError {
+    #[error("`{0}` is not a text chunk type, expected one of tEXt, zTXt or iTXt")]
+    NotATextChunk(String),
+    #[error("`{0}` chunk is missing the null separator after the keyword")]
+    MissingSeparator(&'static str),
+    #[error("`{0}` chunk is truncated")]
+    Truncated(&'static str),
+    #[error("Unknown compression method `{0}`, only zlib (0) is supported")]
+    UnknownCompressionMethod(u8),
+    #[error("`iTXt` text is not valid UTF-8")]
+    NotUtf8,
+    #[error("`{0}` can't be stored in a `{1}` chunk, which only holds Latin-1 text")]
+    NotLatin1(char, &'static str),
+    #[error("Keyword `{0}` must be between 1 and 79 characters")]
+    KeywordLength(String),
+    #[error(
+        "Keyword `{0}` contains `{1:?}`, keywords can only contain printable Latin-1 characters"
+    )]
+    KeywordCharacter(String, char),
+    #[error("Keyword `{0}` can't start or end with a space, or contain consecutive spaces")]
+    KeywordSpaces(String),
+    #[error("Failed to compress the text")]
+    Compression(#[from] io::Error),
+    #[error(transparent)]
+    Inflate(#[from] inflate::InflateError),
+    #[error("Failed to rebuild the PNG")]
+    Png(#[from] PngError),
+    #[error("The text doesn't fit in a chunk")]
+    Chunk(#[from] ChunkError),
+}
+
+pub type TextChunkResult<T> = Result<T, TextChunkError>;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TextKind {
+    Text,
+    Compressed,
+    International,
+}
+
+impl TextKind {
+    fn name(self) -> &'static str {
+        match self {
+            TextKind::Text => "tEXt",
+            TextKind::Compressed => "zTXt",
+            TextKind::International => "iTXt",
+        }
+    }
+
+    pub fn chunk_type(self) -> ChunkType {
+        ChunkType::from_known(match self {
+            TextKind::Text => *b"tEXt",
+            TextKind::Compressed => *b"zTXt",
+            TextKind::International => *b"iTXt",
+        })
+    }
+}
+
+impl FromStr for TextKind {
+    type Err = TextChunkError;
+
+    fn from_str(s: &str) -> TextChunkResult<TextKind> {
+        match s {
+            "tEXt" => Ok(TextKind::Text),
+            "zTXt" => Ok(TextKind::Compressed),
+            "iTXt" => Ok(TextKind::International),
+            _ => Err(TextChunkError::NotATextChunk(s.to_string())),
+        }
+    }
+}
+
+impl Display for TextKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// A keyword and its text, from any of the text chunk types
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TextChunk {
+    pub kind: TextKind,
+    pub keyword: String,
+    pub text: String,
+    // Only stored in iTXt chunks, empty otherwise
+    pub language_tag: String,
+    pub translated_keyword: String,
+    // Whether an iTXt chunk's text is compressed, zTXt is always compressed
+    pub compressed: bool,
+}
+
+impl TextChunk {
+    pub fn new(kind: TextKind, keyword: &str, text: &str) -> TextChunk {
+        TextChunk {
+            kind,
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+            language_tag: String::new(),
+            translated_keyword: String::new(),
+            compressed: kind == TextKind::Compressed,
+        }
+    }
+
+    // The same keyword and text stored in another kind of text chunk.
+    // The language tag and translated keyword are dropped unless converting to iTXt.
+    pub fn convert(&self, kind: TextKind, compressed: bool) -> TextChunk {
+        let international = kind == TextKind::International;
+        TextChunk {
+            kind,
+            keyword: self.keyword.clone(),
+            text: self.text.clone(),
+            language_tag: if international {
+                self.language_tag.clone()
+            } else {
+                String::new()
+            },
+            translated_keyword: if international {
+                self.translated_keyword.clone()
+            } else {
+                String::new()
+            },
+            compressed: kind == TextKind::Compressed || (international && compressed),
+        }
+    }
+
+    pub fn to_chunk(&self) -> TextChunkResult<Chunk> {
+        validate_keyword(&self.keyword)?;
+        let name = self.kind.name();
+        let mut data = latin1_encode(&self.keyword, name)?;
+        data.push(0);
+
+        match self.kind {
+            TextKind::Text => data.extend(latin1_encode(&self.text, name)?),
+            TextKind::Compressed => {
+                data.push(0);
+                data.extend(compress(&latin1_encode(&self.text, name)?)?);
+            }
+            TextKind::International => {
+                data.push(self.compressed as u8);
+                data.push(0);
+                data.extend(self.language_tag.bytes());
+                data.push(0);
+                data.extend(self.translated_keyword.bytes());
+                data.push(0);
+                if self.compressed {
+                    data.extend(compress(self.text.as_bytes())?);
+                } else {
+                    data.extend(self.text.bytes());
+                }
+            }
+        }
+
+        Ok(Chunk::try_new(self.kind.chunk_type(), data)?)
+    }
+}
+
+impl TryFrom<&Chunk> for TextChunk {
+    type Error = TextChunkError;
+
+    fn try_from(chunk: &Chunk) -> TextChunkResult<TextChunk> {
+        let kind = TextKind::from_str(&chunk.chunk_type().to_string())?;
+        let name = kind.name();
+
+        let (keyword, rest) =
+            split_null(chunk.data()).ok_or(TextChunkError::MissingSeparator(name))?;
+        let mut text_chunk = TextChunk::new(kind, &latin1_decode(keyword), "");
+
+        match kind {
+            TextKind::Text => text_chunk.text = latin1_decode(rest),
+            TextKind::Compressed => {
+                let (method, compressed) =
+                    rest.split_first().ok_or(TextChunkError::Truncated(name))?;
+                if *method != 0 {
+                    return Err(TextChunkError::UnknownCompressionMethod(*method));
+                }
+                text_chunk.text = latin1_decode(&decompress(compressed)?);
+            }
+            TextKind::International => {
+                if rest.len() < 2 {
+                    return Err(TextChunkError::Truncated(name));
+                }
+                let compressed = rest[0] == 1;
+                if compressed && rest[1] != 0 {
+                    return Err(TextChunkError::UnknownCompressionMethod(rest[1]));
+                }
+                let (language_tag, rest) =
+                    split_null(&rest[2..]).ok_or(TextChunkError::Truncated(name))?;
+                let (translated_keyword, text) =
+                    split_null(rest).ok_or(TextChunkError::Truncated(name))?;
+                let text = if compressed {
+                    decompress(text)?
+                } else {
+                    text.to_vec()
+                };
+
+                text_chunk.language_tag = latin1_decode(language_tag);
+                text_chunk.translated_keyword = String::from_utf8(translated_keyword.to_vec())
+                    .map_err(|_| TextChunkError::NotUtf8)?;
+                text_chunk.text = String::from_utf8(text).map_err(|_| TextChunkError::NotUtf8)?;
+                text_chunk.compressed = compressed;
+            }
+        }
+
+        Ok(text_chunk)
+    }
+}
+
+// Keywords are 1 to 79 printable Latin-1 characters, with single spaces only between words
+pub fn validate_keyword(keyword: &str) -> TextChunkResult<()> {
+    let length = keyword.chars().count();
+    if length == 0 || length > 79 {
+        return Err(TextChunkError::KeywordLength(keyword.to_string()));
+    }
+
+    let printable = |c: char| (' '..='~').contains(&c) || ('\u{a1}'..='\u{ff}').contains(&c);
+    if let Some(c) = keyword.chars().find(|c| !printable(*c)) {
+        return Err(TextChunkError::KeywordCharacter(keyword.to_string(), c));
+    }
+    if keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+        return Err(TextChunkError::KeywordSpaces(keyword.to_string()));
+    }
+
+    Ok(())
+}
+
+// Every text chunk in the PNG along with its index, in the order they appear
+pub fn text_chunks(png: &Png) -> Vec<(usize, TextChunkResult<TextChunk>)> {
+    png.chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| TextKind::from_str(&chunk.chunk_type().to_string()).is_ok())
+        .map(|(index, chunk)| (index, TextChunk::try_from(chunk)))
+        .collect()
+}
+
+// The text chunks with the keyword, keywords are case sensitive
+pub fn find_keyword(png: &Png, keyword: &str) -> Vec<(usize, TextChunk)> {
+    text_chunks(png)
+        .into_iter()
+        .filter_map(|(index, text)| text.ok().map(|text| (index, text)))
+        .filter(|(_, text)| text.keyword == keyword)
+        .collect()
+}
+
+// Set the text for its keyword. The first chunk with the keyword is replaced in place and any
+// others are dropped, otherwise the text is added before IEND. Returns whether text was replaced.
+pub fn upsert(png: &Png, text: &TextChunk) -> TextChunkResult<(Png, bool)> {
+    let chunk = text.to_chunk()?;
+    let existing: Vec<usize> = find_keyword(png, &text.keyword)
+        .iter()
+        .map(|(index, _)| *index)
+        .collect();
+
+    let mut chunks = vec![];
+    for (index, c) in png.chunks().iter().enumerate() {
+        if existing.first() == Some(&index) {
+            chunks.push(chunk.clone());
+        } else if !existing.contains(&index) {
+            chunks.push(c.clone());
+        }
+    }
+    let mut png = Png::from_chunks(chunks)?;
+    if existing.is_empty() {
+        png.append_chunk(chunk);
+    }
+
+    Ok((png, !existing.is_empty()))
+}
+
+// Latin-1 maps each byte directly to the unicode code point of the same value
+pub(crate) fn latin1_decode(value: &[u8]) -> String {
+    value.iter().map(|b| char::from(*b)).collect()
+}
+
+fn latin1_encode(value: &str, chunk_type: &'static str) -> TextChunkResult<Vec<u8>> {
+    value
+        .chars()
+        .map(|c| u8::try_from(u32::from(c)).map_err(|_| TextChunkError::NotLatin1(c, chunk_type)))
+        .collect()
+}
+
+fn split_null(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    data.iter()
+        .position(|b| *b == 0)
+        .map(|i| (&data[..i], &data[i + 1..]))
+}
+
+pub(crate) fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+// Limited by the inflate limits, as text chunks come from files which may not be trusted
+pub(crate) fn decompress(data: &[u8]) -> InflateResult<Vec<u8>> {
+    inflate::zlib(data, &inflate::limits())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_round_trip() {
+        let text = TextChunk::new(TextKind::Text, "Comment", "Café");
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(chunk.data(), b"Comment\0Caf\xe9");
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let text = TextChunk::new(TextKind::Compressed, "Comment", "Café");
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(&chunk.data()[..9], b"Comment\0\0");
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+
+        let international = text.convert(TextKind::International, true);
+        let chunk = international.to_chunk().unwrap();
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), international);
+    }
+
+    #[test]
+    fn test_international_fields() {
+        let mut data = b"Title\0\0\0en\0Titre\0".to_vec();
+        data.extend("Été".as_bytes());
+        let chunk = Chunk::new(ChunkType::from_str("iTXt").unwrap(), data);
+
+        let text = TextChunk::try_from(&chunk).unwrap();
+        assert_eq!(text.keyword, "Title");
+        assert_eq!(text.language_tag, "en");
+        assert_eq!(text.translated_keyword, "Titre");
+        assert_eq!(text.text, "Été");
+        assert!(!text.compressed);
+    }
+
+    #[test]
+    fn test_convert_to_text_drops_international_fields() {
+        let mut text = TextChunk::new(TextKind::International, "Title", "Été");
+        text.language_tag = String::from("fr");
+
+        let converted = text.convert(TextKind::Text, false);
+        assert_eq!(converted.kind, TextKind::Text);
+        assert_eq!(converted.text, "Été");
+        assert!(converted.language_tag.is_empty());
+        assert!(converted.to_chunk().is_ok());
+    }
+
+    #[test]
+    fn test_convert_to_latin1_fails_for_other_characters() {
+        let text = TextChunk::new(TextKind::International, "Title", "東京");
+        assert!(text.convert(TextKind::Text, false).to_chunk().is_err());
+    }
+
+    #[test]
+    fn test_validate_keyword() {
+        assert!(validate_keyword("Comment").is_ok());
+        assert!(validate_keyword("Creation Time").is_ok());
+        assert!(validate_keyword("Légende").is_ok());
+        assert!(validate_keyword("").is_err());
+        assert!(validate_keyword(&"a".repeat(80)).is_err());
+        assert!(validate_keyword(" Comment").is_err());
+        assert!(validate_keyword("Comment ").is_err());
+        assert!(validate_keyword("Two  spaces").is_err());
+        assert!(validate_keyword("Tab\tbed").is_err());
+        assert!(validate_keyword("東京").is_err());
+    }
+
+    #[test]
+    fn test_find_keyword() {
+        let header = Chunk::from_strings("IHDR", "header").unwrap();
+        let comment = TextChunk::new(TextKind::Text, "Comment", "first");
+        let title = TextChunk::new(TextKind::International, "Title", "title");
+        let png = Png::from_chunks(vec![
+            header,
+            comment.to_chunk().unwrap(),
+            title.to_chunk().unwrap(),
+            Chunk::from_strings("IEND", "").unwrap(),
+        ])
+        .unwrap();
+
+        assert_eq!(text_chunks(&png).len(), 2);
+        assert_eq!(find_keyword(&png, "Title"), vec![(2, title)]);
+        assert!(find_keyword(&png, "title").is_empty());
+    }
+
+    #[test]
+    fn test_not_a_text_chunk() {
+        let chunk = Chunk::from_strings("ruSt", "Comment\0text").unwrap();
+        assert!(TextChunk::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_missing_separator() {
+        let chunk = Chunk::from_strings("tEXt", "no separator").unwrap();
+        assert!(TextChunk::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_decompression_bomb() {
+        // Zeros compress far beyond the allowed ratio, and inflate past the size limit
+        let bomb = compress(&vec![0; inflate::InflateLimits::DEFAULT.max_size + 1]).unwrap();
+        let mut data = b"Comment\0\0".to_vec();
+        data.extend(bomb);
+        let chunk = Chunk::new(ChunkType::from_str("zTXt").unwrap(), data);
+
+        assert!(matches!(
+            TextChunk::try_from(&chunk),
+            Err(TextChunkError::Inflate(_))
+        ));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::crc::Crc32;
+use std::convert::TryFrom;
+use thiserror::Error;
+
+// A payload appended to the end of any file, eg an executable or a PDF, which the file's own
+// readers ignore. The payload is followed by a footer, so it is found by reading back from
+// the end of the file:
+//
+//   payload, then its length, 8 bytes big-endian, then its CRC-32, 4 bytes big-endian,
+//   then MAGIC
+//
+// A file has at most one trailer, appending another replaces it.
+
+pub const MAGIC: [u8; 8] = *b"pngmeTRL";
+pub const FOOTER_LENGTH: usize = 8 + 4 + MAGIC.len();
+
+pub type TrailerResult<T> = Result<T, TrailerError>;
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum TrailerError {
+    #[error("No pngme trailer at the end of the file")]
+    NotFound,
+    #[error("The trailer is for a payload of `{0}` bytes, but only `{1}` bytes come before it")]
+    Truncated(u64, usize),
+    #[error("The trailer's payload has CRC `{0}`, but the footer says `{1}`")]
+    WrongCrc(u32, u32),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Trailer<'a> {
+    // The file without the trailer
+    pub host: &'a [u8],
+    pub payload: &'a [u8],
+}
+
+// The payload followed by its footer, to be appended to a file
+pub fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(payload.len() + FOOTER_LENGTH);
+    bytes.extend_from_slice(payload);
+    bytes.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    bytes.extend_from_slice(&Crc32::checksum(payload).to_be_bytes());
+    bytes.extend_from_slice(&MAGIC);
+    bytes
+}
+
+// Whether the bytes end with a footer, even one whose payload is damaged
+pub fn has_trailer(bytes: &[u8]) -> bool {
+    bytes.ends_with(&MAGIC) && bytes.len() >= FOOTER_LENGTH
+}
+
+// Split the file into the host and the payload appended to it
+pub fn find(bytes: &[u8]) -> TrailerResult<Trailer<'_>> {
+    if !has_trailer(bytes) {
+        return Err(TrailerError::NotFound);
+    }
+
+    let footer = bytes.len() - FOOTER_LENGTH;
+    let mut length = [0; 8];
+    length.copy_from_slice(&bytes[footer..footer + 8]);
+    let length = u64::from_be_bytes(length);
+    let mut crc = [0; 4];
+    crc.copy_from_slice(&bytes[footer + 8..footer + 12]);
+    let crc = u32::from_be_bytes(crc);
+
+    let start = usize::try_from(length)
+        .ok()
+        .and_then(|length| footer.checked_sub(length))
+        .ok_or(TrailerError::Truncated(length, footer))?;
+    let payload = &bytes[start..footer];
+    let calculated = Crc32::checksum(payload);
+    if calculated != crc {
+        return Err(TrailerError::WrongCrc(calculated, crc));
+    }
+
+    Ok(Trailer {
+        host: &bytes[..start],
+        payload,
+    })
+}
+
+// The file with the payload appended, replacing any trailer it already has
+pub fn append(bytes: &[u8], payload: &[u8]) -> TrailerResult<Vec<u8>> {
+    let host = match find(bytes) {
+        Ok(trailer) => trailer.host,
+        Err(TrailerError::NotFound) => bytes,
+        Err(e) => return Err(e),
+    };
+
+    let mut appended = host.to_vec();
+    appended.extend(frame(payload));
+    Ok(appended)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_and_find() {
+        let host = b"%PDF-1.7 not really a pdf";
+        let bytes = append(host, b"the payload").unwrap();
+        assert_eq!(bytes.len(), host.len() + 11 + FOOTER_LENGTH);
+
+        let trailer = find(&bytes).unwrap();
+        assert_eq!(trailer.host, host);
+        assert_eq!(trailer.payload, b"the payload");
+    }
+
+    #[test]
+    fn test_append_replaces_trailer() {
+        let bytes = append(b"host", b"first").unwrap();
+        let bytes = append(&bytes, b"second").unwrap();
+
+        let trailer = find(&bytes).unwrap();
+        assert_eq!(trailer.host, b"host");
+        assert_eq!(trailer.payload, b"second");
+    }
+
+    #[test]
+    fn test_empty_payload_and_host() {
+        let bytes = frame(b"");
+        let trailer = find(&bytes).unwrap();
+        assert!(trailer.host.is_empty());
+        assert!(trailer.payload.is_empty());
+    }
+
+    #[test]
+    fn test_find_errors() {
+        assert!(matches!(find(b"no trailer"), Err(TrailerError::NotFound)));
+        assert!(matches!(find(&MAGIC), Err(TrailerError::NotFound)));
+
+        let mut bytes = append(b"host", b"payload").unwrap();
+        bytes[5] ^= 1;
+        assert!(matches!(find(&bytes), Err(TrailerError::WrongCrc(_, _))));
+
+        let bytes = frame(b"payload");
+        assert!(matches!(
+            find(&bytes[3..]),
+            Err(TrailerError::Truncated(7, 4))
+        ));
+
+        let mut bytes = frame(b"");
+        bytes[..8].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert!(matches!(find(&bytes), Err(TrailerError::Truncated(_, 0))));
+    }
+
+    // Appending to an executable and removing the payload again leaves it executable
+    #[cfg(unix)]
+    #[test]
+    fn test_executable_host_keeps_mode() {
+        use crate::temporary;
+        use std::env;
+        use std::fs;
+        use std::io::Write;
+        use std::os::unix::fs::PermissionsExt;
+        use std::process;
+
+        let path = env::temp_dir().join(format!("pngme-host-{}", process::id()));
+        fs::write(&path, b"#!/bin/sh\nexit 0\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        let mode = |path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        let appended = append(&fs::read(&path).unwrap(), b"the payload").unwrap();
+        temporary::write_atomically(&path, |file| file.write_all(&appended)).unwrap();
+        assert_eq!(mode(&path), 0o755);
+
+        let bytes = fs::read(&path).unwrap();
+        let found = find(&bytes).unwrap();
+        temporary::write_atomically(&path, |file| file.write_all(found.host)).unwrap();
+        assert_eq!(mode(&path), 0o755);
+        assert_eq!(fs::read(&path).unwrap(), b"#!/bin/sh\nexit 0\n");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
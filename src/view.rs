@@ -0,0 +1,313 @@
+use crate::{
+    chunk::{self, Chunk, ChunkError},
+    chunk_type::ChunkType,
+    png::{Png, PngError, PngResult},
+};
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+// Parsing without copying the chunk data, for scanning many files quickly.
+//
+// `Png` copies the data of every chunk into its own Vec, which dominates the time taken
+// to scan lots of small files. A `PngView` instead borrows each chunk's data from the
+// file's bytes, so parsing makes one allocation for the list of chunks. `PngBuffer`
+// holds those bytes and is reused from file to file, so reading a file only allocates
+// when it is bigger than every file before it.
+//
+// Checking CRCs means reading every byte of the file, so they can be left until a chunk's
+// data is needed with `CrcCheck::Lazy`. Listing the chunks of a large file then only reads
+// the chunk headers.
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CrcCheck {
+    // Check every chunk's CRC while parsing
+    Eager,
+    // Check a chunk's CRC when its data is read with `checked_data`, or by `verify`
+    Lazy,
+}
+
+// A chunk whose data is borrowed from the bytes of the file
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkView<'a> {
+    chunk_type: ChunkType,
+    data: &'a [u8],
+    crc: u32,
+    // Where the chunk starts in the file
+    offset: usize,
+    // Whether the CRC was checked while parsing
+    checked: bool,
+}
+
+impl<'a> ChunkView<'a> {
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
+
+    // The data, without checking the CRC if parsing left it until later
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    // The data, once the CRC has been checked
+    pub fn checked_data(&self) -> Result<&'a [u8], ChunkError> {
+        self.verify()?;
+        Ok(self.data)
+    }
+
+    pub fn crc(&self) -> u32 {
+        self.crc
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn verify(&self) -> Result<(), ChunkError> {
+        if self.checked {
+            return Ok(());
+        }
+        let calculated_crc = chunk::calculate_chunk_crc(&self.chunk_type.bytes(), self.data);
+        if calculated_crc != self.crc {
+            return Err(ChunkError::InvalidCRCValue(self.crc, calculated_crc));
+        }
+
+        Ok(())
+    }
+
+    // Copy the chunk out of the file's bytes
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(self.chunk_type, self.data.to_vec())
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for ChunkView<'a> {
+    type Error = ChunkError;
+
+    // The same checks as `Chunk::try_from`, without copying the data
+    fn try_from(value: &'a [u8]) -> Result<ChunkView<'a>, ChunkError> {
+        ChunkView::parse(value, 0, CrcCheck::Eager)
+    }
+}
+
+impl<'a> ChunkView<'a> {
+    pub(crate) fn parse(
+        value: &'a [u8],
+        offset: usize,
+        crc_check: CrcCheck,
+    ) -> Result<ChunkView<'a>, ChunkError> {
+        if value.len() < 12 {
+            return Err(ChunkError::NotEnoughBytes(value.len(), 12));
+        }
+        let length = u32::from_be_bytes([value[0], value[1], value[2], value[3]]);
+        if length as usize > Chunk::MAX_LENGTH {
+            return Err(ChunkError::LengthTooLarge(
+                length as usize,
+                Chunk::MAX_LENGTH,
+            ));
+        }
+        let end = 8 + length as usize;
+        if value.len() < end + 4 {
+            return Err(ChunkError::NotEnoughBytes(value.len(), length));
+        }
+        if value.len() > end + 4 {
+            return Err(ChunkError::RemainingBytes(value.len() - end - 4));
+        }
+
+        let chunk_type = ChunkType::try_from([value[4], value[5], value[6], value[7]])?;
+        let crc = u32::from_be_bytes([value[end], value[end + 1], value[end + 2], value[end + 3]]);
+        let view = ChunkView {
+            chunk_type,
+            data: &value[8..end],
+            crc,
+            offset,
+            checked: false,
+        };
+        if crc_check == CrcCheck::Lazy {
+            return Ok(view);
+        }
+        view.verify()?;
+
+        Ok(ChunkView {
+            checked: true,
+            ..view
+        })
+    }
+}
+
+// A PNG whose chunks borrow their data from the bytes of the file
+#[derive(Debug)]
+pub struct PngView<'a> {
+    chunks: Vec<ChunkView<'a>>,
+}
+
+impl<'a> PngView<'a> {
+    // Parse with the same checks as `Png::try_from`
+    pub fn parse(bytes: &'a [u8]) -> Result<PngView<'a>, PngError> {
+        PngView::parse_with(bytes, CrcCheck::Eager)
+    }
+
+    pub fn parse_with(bytes: &'a [u8], crc_check: CrcCheck) -> Result<PngView<'a>, PngError> {
+        if bytes.len() < Png::STANDARD_HEADER.len() {
+            return Err(PngError::Io {
+                source: io::ErrorKind::UnexpectedEof.into(),
+            });
+        }
+        if bytes[..8] != Png::STANDARD_HEADER {
+            return Err(PngError::NotAValidPNGHeader(Box::new(bytes[..8].to_vec())));
+        }
+
+        let mut offset = 8;
+        let mut chunks = vec![];
+        while offset < bytes.len() {
+            let invalid = |source| PngError::InvalidChunk {
+                start_index: offset,
+                source,
+            };
+            // The length comes first, `ChunkView::parse` checks the rest of the chunk is there
+            let rest = &bytes[offset..];
+            let length = match rest {
+                [a, b, c, d, ..] => u32::from_be_bytes([*a, *b, *c, *d]) as usize,
+                _ => 0,
+            };
+            let chunk_bytes = &rest[..rest.len().min(length.saturating_add(12))];
+            chunks.push(ChunkView::parse(chunk_bytes, offset, crc_check).map_err(invalid)?);
+            offset += chunk_bytes.len();
+        }
+
+        if chunks.len() < 2 {
+            return Err(PngError::NeedAtLeastTwoChunks(chunks.len()));
+        }
+        if chunks[0].chunk_type().to_string() != "IHDR" {
+            return Err(PngError::IHDRChunkShouldBeFirst(*chunks[0].chunk_type()));
+        }
+        let last = chunks[chunks.len() - 1].chunk_type();
+        if last.to_string() != "IEND" {
+            return Err(PngError::IENDChunkShouldLast(*last));
+        }
+
+        Ok(PngView { chunks })
+    }
+
+    pub fn chunks(&self) -> &[ChunkView<'a>] {
+        &self.chunks
+    }
+
+    // Check the CRC of every chunk which wasn't checked while parsing
+    pub fn verify(&self) -> Result<(), PngError> {
+        for chunk in self.chunks.iter() {
+            chunk.verify().map_err(|source| PngError::InvalidChunk {
+                start_index: chunk.offset(),
+                source,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    pub fn chunk_by_type(&self, chunk_type: &ChunkType) -> Option<&ChunkView<'a>> {
+        self.chunks.iter().find(|c| c.chunk_type() == chunk_type)
+    }
+
+    // Copy every chunk out of the file's bytes
+    pub fn to_png(&self) -> PngResult {
+        Png::from_chunks(self.chunks.iter().map(|c| c.to_chunk()).collect())
+    }
+}
+
+// The bytes of one file at a time, reused between files so reading doesn't allocate each time
+#[derive(Debug, Default)]
+pub struct PngBuffer {
+    bytes: Vec<u8>,
+}
+
+impl PngBuffer {
+    pub fn new() -> PngBuffer {
+        PngBuffer::default()
+    }
+
+    // Read the file in place of the previous one and parse it
+    pub fn read(&mut self, path: &Path) -> Result<PngView<'_>, PngError> {
+        self.read_with(path, CrcCheck::Eager)
+    }
+
+    pub fn read_with(&mut self, path: &Path, crc_check: CrcCheck) -> Result<PngView<'_>, PngError> {
+        self.read_from(File::open(path)?, crc_check)
+    }
+
+    // Read from anything, eg a download, in place of the previous file and parse it
+    pub fn read_from<R: Read>(
+        &mut self,
+        mut reader: R,
+        crc_check: CrcCheck,
+    ) -> Result<PngView<'_>, PngError> {
+        self.bytes.clear();
+        reader.read_to_end(&mut self.bytes)?;
+
+        PngView::parse_with(&self.bytes, crc_check)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::from_strings("IHDR", "I am the first chunk").unwrap(),
+            Chunk::from_strings("ruSt", "This is where your secret message will be!").unwrap(),
+            Chunk::from_strings("IEND", "").unwrap(),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_view_matches_png() {
+        let bytes = testing_png().as_bytes();
+        let view = PngView::parse(&bytes).unwrap();
+
+        assert_eq!(view.chunks().len(), 3);
+        let rust = ChunkType::try_from(*b"ruSt").unwrap();
+        let chunk = view.chunk_by_type(&rust).unwrap();
+        assert_eq!(chunk.data(), b"This is where your secret message will be!");
+        assert_eq!(chunk.crc(), chunk.to_chunk().crc());
+        assert_eq!(view.to_png().unwrap().as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_view_rejects_bad_crc() {
+        let mut bytes = testing_png().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        assert!(PngView::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_lazy_crc_check() {
+        let mut bytes = testing_png().as_bytes();
+        // Corrupt the data of the ruSt chunk, leaving its CRC as it was
+        let rust_data = 8 + 12 + 20 + 8;
+        bytes[rust_data] ^= 0xff;
+
+        let view = PngView::parse_with(&bytes, CrcCheck::Lazy).unwrap();
+        assert_eq!(view.chunks()[1].offset(), 8 + 12 + 20);
+        assert!(view.chunks()[0].checked_data().is_ok());
+        assert!(view.chunks()[1].checked_data().is_err());
+        match view.verify() {
+            Err(PngError::InvalidChunk { start_index, .. }) => assert_eq!(start_index, 40),
+            _ => panic!("expected the corrupt chunk to fail verification"),
+        }
+        assert!(PngView::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_view_rejects_bad_header() {
+        let mut bytes = testing_png().as_bytes();
+        bytes[0] = 0;
+        assert!(PngView::parse(&bytes).is_err());
+        assert!(PngView::parse(&bytes[..4]).is_err());
+    }
+}
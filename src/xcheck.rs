@@ -0,0 +1,336 @@
+use crate::{
+    apng::AnimationControl,
+    png::Png,
+    standard::{self, ImageHeader},
+    text,
+};
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+use std::fmt::{self, Debug, Display, Formatter};
+
+// Reading the same file with this crate and with the `png` crate, and reporting where they
+// disagree. Either could be wrong, but a disagreement is worth a closer look, eg a chunk
+// boundary in a different place means one of the parsers has misread a length.
+//
+// Text chunks are only compared up to the image data, the `png` crate reads the rest
+// while decoding the image, which isn't done here.
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Disagreement {
+    pub what: &'static str,
+    pub pngme: String,
+    pub png: String,
+}
+
+impl Disagreement {
+    pub fn json(&self) -> Value {
+        json!({ "what": self.what, "pngme": self.pngme.clone(), "png": self.png.clone() })
+    }
+}
+
+impl Display for Disagreement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: pngme read {}, the png crate read {}",
+            self.what, self.pngme, self.png
+        )
+    }
+}
+
+// What each parser found in the file
+#[derive(Debug, PartialEq, Eq)]
+struct Reading {
+    // The type and length of every chunk, in order
+    chunks: Vec<(String, u32)>,
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+    palette_entries: Option<usize>,
+    // The number of frames and plays
+    animation: Option<(u32, u32)>,
+    texts: usize,
+}
+
+fn read_pngme(bytes: &[u8]) -> Result<Reading, String> {
+    let png = Png::try_from(bytes).map_err(|e| e.to_string())?;
+    let header = ImageHeader::from_png(&png).map_err(|e| e.to_string())?;
+
+    let chunks: Vec<(String, u32)> = png
+        .chunks()
+        .iter()
+        .map(|chunk| (chunk.chunk_type().to_string(), chunk.length()))
+        .collect();
+    let image_data = chunks
+        .iter()
+        .position(|(chunk_type, _)| chunk_type == "IDAT")
+        .unwrap_or(chunks.len());
+    let animation = png
+        .chunks()
+        .iter()
+        .find(|chunk| chunk.chunk_type().to_string() == "acTL")
+        .map(|chunk| AnimationControl::try_from(chunk).map_err(|e| e.to_string()))
+        .transpose()?
+        .map(|control| (control.num_frames, control.num_plays));
+    let texts = text::text_chunks(&png)
+        .iter()
+        .filter(|(index, _)| *index < image_data)
+        .count();
+
+    Ok(Reading {
+        width: header.width,
+        height: header.height,
+        bit_depth: header.bit_depth,
+        color_type: header.color_type.to_byte(),
+        interlaced: header.interlaced,
+        palette_entries: standard::palette_entries(&png),
+        animation,
+        texts,
+        chunks,
+    })
+}
+
+// The chunk boundaries come from the streaming decoder, which reports each chunk as it
+// starts, the rest from the metadata read before the image data
+fn read_png_crate(bytes: &[u8]) -> Result<Reading, String> {
+    use ::png::{Decoded, Decoder, StreamingDecoder};
+
+    let mut chunks = vec![];
+    let mut decoder = StreamingDecoder::new();
+    let mut image_data = vec![];
+    let mut remaining = bytes;
+    while !remaining.is_empty() {
+        let (consumed, decoded) = decoder
+            .update(remaining, &mut image_data)
+            .map_err(|e| e.to_string())?;
+        remaining = &remaining[consumed..];
+        image_data.clear();
+        match decoded {
+            Decoded::ChunkBegin(length, chunk_type) => {
+                chunks.push((String::from_utf8_lossy(&chunk_type.0).into_owned(), length))
+            }
+            Decoded::ImageEnd => break,
+            Decoded::Nothing if consumed == 0 => {
+                return Err(String::from("The decoder stopped part way through"))
+            }
+            _ => {}
+        }
+    }
+
+    let reader = Decoder::new(bytes).read_info().map_err(|e| e.to_string())?;
+    let info = reader.info();
+
+    Ok(Reading {
+        chunks,
+        width: info.width,
+        height: info.height,
+        bit_depth: info.bit_depth as u8,
+        color_type: info.color_type as u8,
+        interlaced: info.interlaced,
+        palette_entries: info.palette.as_ref().map(|palette| palette.len() / 3),
+        animation: info
+            .animation_control
+            .map(|control| (control.num_frames, control.num_plays)),
+        texts: info.uncompressed_latin1_text.len()
+            + info.compressed_latin1_text.len()
+            + info.utf8_text.len(),
+    })
+}
+
+fn compare<T: Debug + PartialEq>(
+    what: &'static str,
+    pngme: &T,
+    png: &T,
+    disagreements: &mut Vec<Disagreement>,
+) {
+    if pngme != png {
+        disagreements.push(Disagreement {
+            what,
+            pngme: format!("{:?}", pngme),
+            png: format!("{:?}", png),
+        });
+    }
+}
+
+// Chunk boundaries are compared one chunk at a time, so the report says where they first differ
+fn compare_chunks(
+    pngme: &[(String, u32)],
+    png: &[(String, u32)],
+    disagreements: &mut Vec<Disagreement>,
+) {
+    let describe = |chunk: Option<&(String, u32)>| match chunk {
+        Some((chunk_type, length)) => format!("`{}` of {} bytes", chunk_type, length),
+        None => String::from("no chunk"),
+    };
+
+    let first_difference =
+        (0..pngme.len().max(png.len())).find(|&index| pngme.get(index) != png.get(index));
+    if let Some(index) = first_difference {
+        disagreements.push(Disagreement {
+            what: "chunk",
+            pngme: format!("{} at chunk {}", describe(pngme.get(index)), index),
+            png: describe(png.get(index)),
+        });
+    }
+}
+
+// Every disagreement between the parsers, empty when they agree. When both parsers reject
+// the file they agree, whatever their reasons.
+pub fn check(bytes: &[u8]) -> Vec<Disagreement> {
+    let mut disagreements = vec![];
+    let (pngme, png) = match (read_pngme(bytes), read_png_crate(bytes)) {
+        (Ok(pngme), Ok(png)) => (pngme, png),
+        (Err(_), Err(_)) => return disagreements,
+        (pngme, png) => {
+            let outcome = |reading: Result<Reading, String>| match reading {
+                Ok(_) => String::from("a valid file"),
+                Err(e) => format!("an invalid file, {}", e),
+            };
+            disagreements.push(Disagreement {
+                what: "validity",
+                pngme: outcome(pngme),
+                png: outcome(png),
+            });
+            return disagreements;
+        }
+    };
+
+    compare_chunks(&pngme.chunks, &png.chunks, &mut disagreements);
+    compare("width", &pngme.width, &png.width, &mut disagreements);
+    compare("height", &pngme.height, &png.height, &mut disagreements);
+    compare(
+        "bit depth",
+        &pngme.bit_depth,
+        &png.bit_depth,
+        &mut disagreements,
+    );
+    compare(
+        "colour type",
+        &pngme.color_type,
+        &png.color_type,
+        &mut disagreements,
+    );
+    compare(
+        "interlacing",
+        &pngme.interlaced,
+        &png.interlaced,
+        &mut disagreements,
+    );
+    compare(
+        "palette entries",
+        &pngme.palette_entries,
+        &png.palette_entries,
+        &mut disagreements,
+    );
+    compare(
+        "animation",
+        &pngme.animation,
+        &png.animation,
+        &mut disagreements,
+    );
+    compare(
+        "text chunks before the image data",
+        &pngme.texts,
+        &png.texts,
+        &mut disagreements,
+    );
+
+    disagreements
+}
+
+#[cfg(all(test, feature = "fixtures"))]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, chunk_type::ChunkType, fixtures};
+    use std::str::FromStr;
+
+    // A small xorshift generator, so the random files are the same on every run
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, limit: usize) -> usize {
+            (self.next() % limit as u64) as usize
+        }
+    }
+
+    // A valid file with private ancillary chunks of random types and lengths added after the
+    // header, which both parsers should read the same way
+    fn random_file(random: &mut Random, base: &[u8]) -> Vec<u8> {
+        let png = Png::try_from(base).unwrap();
+        let mut chunks = png.chunks().to_vec();
+        for _ in 0..random.below(5) {
+            // Only the third letter, the reserved bit, has to be upper-case
+            let name: String = (0..4)
+                .map(|i| {
+                    let letter = b'a' + random.below(26) as u8;
+                    (if i == 2 {
+                        letter.to_ascii_uppercase()
+                    } else {
+                        letter
+                    }) as char
+                })
+                .collect();
+            let data = (0..random.below(300))
+                .map(|_| random.next() as u8)
+                .collect();
+            let index = 1 + random.below(chunks.len() - 1);
+            chunks.insert(index, Chunk::new(ChunkType::from_str(&name).unwrap(), data));
+        }
+
+        Png::from_chunks(chunks).unwrap().as_bytes()
+    }
+
+    #[test]
+    fn test_fixtures_agree() {
+        for fixture in fixtures::generate().into_iter().filter(|f| f.valid) {
+            let disagreements = check(&fixture.bytes);
+            assert!(
+                disagreements.is_empty(),
+                "{}: {:?}",
+                fixture.name,
+                disagreements
+            );
+        }
+    }
+
+    #[test]
+    fn test_random_files_agree() {
+        let valid: Vec<_> = fixtures::generate()
+            .into_iter()
+            .filter(|fixture| fixture.valid)
+            .collect();
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        for _ in 0..200 {
+            let base = &valid[random.below(valid.len())];
+            let bytes = random_file(&mut random, &base.bytes);
+            let disagreements = check(&bytes);
+            assert!(
+                disagreements.is_empty(),
+                "{} with random chunks: {:?}",
+                base.name,
+                disagreements
+            );
+        }
+    }
+
+    #[test]
+    fn test_disagreement_reported() {
+        // The png crate stops reading at the IEND chunk, pngme rejects what comes after it
+        let fixture = fixtures::generate()
+            .into_iter()
+            .find(|fixture| fixture.name == "corrupt-trailer")
+            .unwrap();
+        let disagreements = check(&fixture.bytes);
+        assert_eq!(disagreements.len(), 1);
+        assert_eq!(disagreements[0].what, "validity");
+    }
+}